@@ -1,41 +1,41 @@
-use rand::prelude::*;
-use rand_distr::{Distribution, Normal};
-use std::f64::consts::PI;
+#[path = "../../../src/core/live/line_checksum.rs"]
+#[allow(dead_code)] // Only appends checksums, verifying them is the receiver's side
+mod line_checksum;
+#[path = "../../../src/core/live/motion.rs"]
+mod motion;
+
+use line_checksum::append_checksum;
+use motion::{MotionGen, MotionProfile};
 use std::io::Write;
 use std::net::TcpStream;
 use std::thread::sleep;
 use std::time::{Duration, Instant};
 
-#[derive(Clone, Copy)]
-struct Vec6([f64; 6]);
+/// Timestamps of the `t` column.
+///
+/// By default `t` is the sample index, so it says nothing about when a sample was sent.
+/// With `--exact-timestamps` it's `i * period` in nanoseconds, computed from the index alone:
+/// sends are still paced in real time, but a late wakeup doesn't shift any timestamp, so two runs
+/// emit the same timestamps. A clock-based timestamp (time elapsed since start when the sample is
+/// sent) would carry the scheduling jitter of every send into the data instead.
+struct Timestamps {
+    exact: bool,
+    period_ns: u64,
+}
 
-impl Vec6 {
-    fn map1(self, f: impl Fn(f64) -> f64) -> Vec6 {
-        let mut out = [0.0; 6];
-        for i in 0..6 {
-            out[i] = f(self.0[i]);
-        }
-        Vec6(out)
+impl Timestamps {
+    fn new(exact: bool, period: f64) -> Self {
+        Self { exact, period_ns: (period * 1e9).round() as u64 }
     }
-}
 
-fn advance_vector(
-    x: &mut Vec6,
-    v: &mut Vec6,
-    aabs: Vec6,
-    rho: f64,
-    dt: f64,
-    rng: &mut impl Rng,
-) {
-    let sigma = aabs.map1(|a| a * (1.0 - rho * rho).sqrt());
-    let mut eps = [0.0; 6];
-    for i in 0..6 {
-        let normal = Normal::new(0.0, sigma.0[i].max(1e-12)).unwrap();
-        eps[i] = normal.sample(rng);
+    /// `tscale` header value matching `at`.
+    fn tscale(&self) -> &'static str {
+        if self.exact { "0.000000001" } else { "1.0" }
     }
-    for i in 0..6 {
-        v.0[i] = rho * v.0[i] + eps[i];
-        x.0[i] = x.0[i] + v.0[i] * dt;
+
+    /// `t` of sample `i`.
+    fn at(&self, i: u64) -> u64 {
+        if self.exact { i * self.period_ns } else { i }
     }
 }
 
@@ -46,6 +46,17 @@ fn main() -> std::io::Result<()> {
     let args: Vec<String> = std::env::args().collect();
     let use_degrees = args.iter().any(|a| a.eq_ignore_ascii_case("deg"));
     let use_radians = args.iter().any(|a| a.eq_ignore_ascii_case("rad"));
+    let exact_timestamps = args.iter().any(|a| a == "--exact-timestamps");
+    // Trailing CRC-32 on every record, for links that may corrupt lines
+    let checksum = args.iter().any(|a| a == "--checksum");
+    let profile = match args.iter().position(|a| a == "--profile") {
+        Some(i) => match args.get(i + 1).map(|p| p.parse::<MotionProfile>()) {
+            Some(Ok(profile)) => profile,
+            Some(Err(e)) => { eprintln!("{e}"); std::process::exit(2); }
+            None => { eprintln!("--profile needs a value: still, random-walk, pan or shake"); std::process::exit(2); }
+        },
+        None => MotionProfile::default(),
+    };
 
     // Default = radians
     let mode = if use_degrees {
@@ -54,7 +65,7 @@ fn main() -> std::io::Result<()> {
         "rad"
     };
 
-    println!("IMU OUTPUT MODE: {}", mode);
+    println!("IMU OUTPUT MODE: {}, motion: {}", mode, profile);
 
     // -------------------------
     // Core config
@@ -64,17 +75,9 @@ fn main() -> std::io::Result<()> {
     let period_hz: f64 = 5.0;
     let period = 1.0 / period_hz;
     let dt_sim = 0.01;
+    let timestamps = Timestamps::new(exact_timestamps, period);
 
-    let rho = 0.92;
-
-    let gscale = 0.001_221_730_47_f64;
-    let ascale = 0.000_488_281_25_f64;
-
-    let mut rng = StdRng::from_entropy();
-
-    let aabs = Vec6([11.333, 5.133, 17.133, 53.066, 15.266, 69.8]);
-    let mut v = Vec6(aabs.0);
-    let mut x = Vec6([17.0, 14.0, 19.0, -42.0, -5.0, 99.0]);
+    let mut motion = MotionGen::new(profile, rand::random());
 
     // -------------------------------------
     // Connect to stabilization server
@@ -111,10 +114,11 @@ fn main() -> std::io::Result<()> {
     lens_info,wide
     frame_readout_time,15.23
     frame_readout_direction,0
-    tscale,1.0
+    tscale,{}
     gscale,1.0
     ascale,1.0
-    t,gx,gy,gz,ax,ay,az"
+    t,gx,gy,gz,ax,ay,az",
+    timestamps.tscale()
     );
     stream.write_all(header.as_bytes())?;
 
@@ -126,28 +130,25 @@ fn main() -> std::io::Result<()> {
     let step = Duration::from_secs_f64(period);
 
     loop {
-        advance_vector(&mut x, &mut v, aabs, rho, dt_sim, &mut rng);
+        let (gyro, accel) = motion.step(dt_sim);
 
         // -------- Gyro formatting --------
-        let (gx, gy, gz) = if mode == "deg" {
+        let [gx, gy, gz] = if mode == "deg" {
             // degrees/sec
-            (x.0[0], x.0[1], x.0[2])
+            gyro
         } else {
             // radians/sec
-            (
-                x.0[0] * (PI / 180.0),
-                x.0[1] * (PI / 180.0),
-                x.0[2] * (PI / 180.0),
-            )
+            gyro.map(f64::to_radians)
         };
 
-        // -------- Accel (whatever original units you want) --------
-        let msg = format!(
-            "{i},{gx:.6},{gy:.6},{gz:.6},{:.3},{:.3},{:.3}\n",
-            x.0[3], x.0[4], x.0[5]
+        // -------- Accel, m/s² --------
+        let record = format!(
+            "{},{gx:.6},{gy:.6},{gz:.6},{:.3},{:.3},{:.3}",
+            timestamps.at(i), accel[0], accel[1], accel[2]
         );
+        let msg = if checksum { append_checksum(&record) } else { record };
 
-        stream.write_all(msg.as_bytes())?;
+        stream.write_all(format!("{msg}\n").as_bytes())?;
 
         i += 1;
         next_t += step;
@@ -157,3 +158,22 @@ fn main() -> std::io::Result<()> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::prelude::*;
+
+    #[test]
+    fn exact_timestamps_ignore_send_jitter() {
+        let period = 1.0 / 5.0;
+        let timestamps = Timestamps::new(true, period);
+        let mut rng = StdRng::seed_from_u64(1);
+        for i in 0..20u64 {
+            // Sends wake up late by a random amount
+            sleep(Duration::from_micros(rng.gen_range(0..2000)));
+            assert_eq!(timestamps.at(i), i * 200_000_000);
+        }
+        assert_eq!(Timestamps::new(false, period).at(7), 7);
+    }
+}
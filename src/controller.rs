@@ -2323,7 +2323,11 @@ impl Controller {
 
         core::run_threaded(move || {
             progress((0, total));
-            for (fname_base, frame, dist, undist) in gyroflow_core::stmap::generate_stmaps(&stab, per_frame) {
+            for maps in gyroflow_core::stmap::generate_stmaps(&stab, per_frame) {
+                let (fname_base, frame, dist, undist) = match maps {
+                    Ok(maps) => maps,
+                    Err(e) => return err(e.to_string()),
+                };
                 if let Err(e) = filesystem::write(&filesystem::get_file_url(&folder_url, &format!("{fname_base}-undistort-{frame}.exr"), true), &undist) {
                     return err(e.to_string());
                 }
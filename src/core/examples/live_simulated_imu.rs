@@ -0,0 +1,100 @@
+// Stabilizes a synthetic video against a simulated IMU stream, the whole live path without a
+// camera, a socket or the LiveGyroGen generator:
+//
+//   cargo run --example live_simulated_imu --features testing [still|random-walk|pan|shake]
+
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use gyroflow_core::gpu::{BufferDescription, BufferSource, Buffers};
+use gyroflow_core::lens_profile::Dimensions;
+use gyroflow_core::live::{simulate_imu_stream, ComputeFallback, ImuSimConfig, LivePipeline, MotionProfile};
+use gyroflow_core::stabilization::pixel_formats::RGBA8;
+use gyroflow_core::StabilizationManager;
+
+const SIZE: (usize, usize) = (640, 360);
+const FPS: f64 = 30.0;
+const FRAMES: usize = 60;
+
+/// Checkerboard with a gradient, so any rotation changes most pixels.
+fn synthetic_frame(w: usize, h: usize, k: usize) -> Vec<u8> {
+    let mut buf = vec![255u8; w * h * 4];
+    for (i, px) in buf.chunks_exact_mut(4).enumerate() {
+        let (x, y) = (i % w, i / w);
+        px[0] = if ((x / 32) + (y / 32)) % 2 == 0 { 200 } else { 40 };
+        px[1] = (x * 255 / w) as u8;
+        px[2] = ((y * 255 / h) as u8).wrapping_add(k as u8);
+    }
+    buf
+}
+
+fn main() {
+    let profile = std::env::args().nth(1).map_or(Ok(MotionProfile::Shake), |p| p.parse()).unwrap_or_else(|e| panic!("{e}"));
+    let (w, h) = SIZE;
+
+    let stab = Arc::new(StabilizationManager::default());
+    stab.init_from_stream_data(FPS, SIZE);
+    {
+        // Plain pinhole-like fisheye lens, so the stabilization is the only thing moving the pixels
+        let f = w as f64 * 0.8;
+        let mut lens = stab.lens.write();
+        lens.calib_dimension = Dimensions { w, h };
+        lens.fisheye_params.camera_matrix = vec![[f, 0.0, w as f64 / 2.0], [0.0, f, h as f64 / 2.0], [0.0, 0.0, 1.0]];
+        lens.fisheye_params.distortion_coeffs = vec![0.0; 4];
+    }
+    stab.gyro.read().enable_live(3.0, 1.0, 0.0, FPS);
+    stab.set_render_params(SIZE, SIZE);
+    let pipeline = LivePipeline::builder(stab.clone()).compute_fallback(ComputeFallback::Cpu).start().expect("live pipeline");
+
+    // The whole stream plus the smoothing look-ahead, generated as fast as the pipeline takes it
+    let post_ms = stab.gyro.read().live_smoothing_window().unwrap_or_default().post_ms;
+    let video_secs = FRAMES as f64 / FPS;
+    let imu = simulate_imu_stream(ImuSimConfig {
+        profile,
+        rate_hz: 500.0,
+        duration: Some(Duration::from_secs_f64(video_secs + post_ms / 1000.0 + 0.1)),
+        realtime: false,
+        seed: 1,
+    });
+    for sample in imu.iter() {
+        pipeline.push_imu(sample, sample.ts_sensor_us).expect("IMU channel closed");
+    }
+
+    // Wait until the smoothed orientation covers the last frame
+    let last_us = (video_secs * 1_000_000.0) as i64;
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while Instant::now() < deadline {
+        let reached = stab.gyro.read().live.read().as_ref()
+            .and_then(|st| st.quat_buffer_store_smoothed.get_latest_buffer())
+            .is_some_and(|b| b.last_us >= last_us);
+        if reached { break; }
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    println!("Stabilizing {FRAMES} frames of {w}x{h} against a simulated `{profile}` IMU");
+    let out_size = stab.live_output_buffer_size();
+    for k in 0..FRAMES {
+        let ts_us = (k as f64 / FPS * 1_000_000.0).round() as i64;
+        stab.live_on_new_frame(k, ts_us as f64 / 1000.0, 1);
+        let mut input = synthetic_frame(w, h, k);
+        let original = input.clone();
+        let mut output = vec![0u8; out_size.0 * out_size.1 * 4];
+        let mut buffers = Buffers {
+            input:  BufferDescription { size: (w, h, w * 4), rect: None, rotation: None, data: BufferSource::Cpu { buffer: &mut input }, texture_copy: false },
+            output: BufferDescription { size: (out_size.0, out_size.1, out_size.0 * 4), rect: None, rotation: None, data: BufferSource::Cpu { buffer: &mut output }, texture_copy: false },
+        };
+        match stab.process_pixels::<RGBA8>(ts_us, None, &mut buffers) {
+            Ok(info) if out_size == SIZE => {
+                let diff = output.iter().zip(&original).map(|(a, b)| a.abs_diff(*b) as f64).sum::<f64>() / output.len() as f64;
+                println!("frame {k:3}: fov {:.3}, mean pixel change {diff:.1} ({})", info.fov, info.backend);
+            }
+            Ok(info) => println!("frame {k:3}: fov {:.3} ({})", info.fov, info.backend),
+            Err(e) => println!("frame {k:3}: failed: {e:?}"),
+        }
+    }
+
+    let stats = pipeline.ingest_stats();
+    println!("{} IMU samples in {} batches", stats.samples, stats.batches);
+    pipeline.stop();
+}
@@ -71,6 +71,35 @@ impl Lowpass {
         }
         Ok(())
     }
+    /// `filter_gyro_forward_backward` of the gyro only, for a window whose newest samples matter most
+    /// (live integration). Zero phase, so the filtered motion doesn't lag the video. Both ends are padded
+    /// with the signal mirrored around the end sample, so the filter has settled when it reaches the
+    /// first and last samples instead of pulling them towards zero.
+    pub fn filter_gyro_zero_phase(freq: f64, sample_rate: f64, data: &mut [TimeIMU]) -> Result<(), biquad::Errors> {
+        if data.len() < 2 { return Ok(()); }
+        let mut forward = Self::new(freq, sample_rate)?;
+        let mut backward = Self::new(freq, sample_rate)?;
+        // About three periods of the cutoff
+        let pad = ((3.0 * sample_rate / freq).ceil() as usize).clamp(1, data.len() - 1);
+        let gyro = |x: &TimeIMU| x.gyro.unwrap_or_default();
+        let mirror = |end: [f64; 3], g: [f64; 3]| [2.0 * end[0] - g[0], 2.0 * end[1] - g[1], 2.0 * end[2] - g[2]];
+        let (first, last) = (gyro(&data[0]), gyro(&data[data.len() - 1]));
+
+        let mut signal = Vec::with_capacity(data.len() + 2 * pad);
+        signal.extend(data[1..=pad].iter().rev().map(|x| mirror(first, gyro(x))));
+        signal.extend(data.iter().map(gyro));
+        signal.extend(data[data.len() - 1 - pad..data.len() - 1].iter().rev().map(|x| mirror(last, gyro(x))));
+        for g in signal.iter_mut() {
+            for (i, v) in g.iter_mut().enumerate() { *v = forward.run(i, *v); }
+        }
+        for g in signal.iter_mut().rev() {
+            for (i, v) in g.iter_mut().enumerate() { *v = backward.run(i, *v); }
+        }
+        for (x, g) in data.iter_mut().zip(&signal[pad..]) {
+            if let Some(gyro) = x.gyro.as_mut() { *gyro = *g; }
+        }
+        Ok(())
+    }
     pub fn filter_quats_forward_backward(freq: f64, sample_rate: f64, data: &mut TimeQuat) -> Result<(), biquad::Errors> {
         let mut forward = Self::new(freq, sample_rate)?;
         let mut backward = Self::new(freq, sample_rate)?;
@@ -159,3 +188,55 @@ impl Median {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::PI;
+    use crate::gyro_source::{ LiveImuSample, Quat64 };
+    use crate::live::live_manager;
+
+    #[test]
+    fn gyro_lowpass_reduces_orientation_jitter() {
+        // 2 s at 1 kHz of a 1 Hz sway around Z, plus broadband sensor noise on all axes
+        let sway = |i: i64| 0.5 * (2.0 * PI * i as f64 / 1000.0).sin();
+        let mut rng = 1u64;
+        let mut noise = move || {
+            rng = rng.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            ((rng >> 11) as f64 / (1u64 << 53) as f64 - 0.5) * 0.6
+        };
+        let noisy: Vec<[f64; 3]> = (0..2000).map(|i| [noise(), noise(), sway(i) + noise()]).collect();
+        let clean: Vec<[f64; 3]> = (0..2000).map(|i| [0.0, 0.0, sway(i)]).collect();
+
+        let orientation = |gyro: &[[f64; 3]], cutoff_hz: Option<f64>| -> Vec<Quat64> {
+            let stab = live_manager();
+            stab.gyro.write().integration_method = 3; // gyro only
+            stab.set_live_gyro_lpf_cutoff_hz(cutoff_hz);
+            let batch: Vec<_> = gyro.iter().enumerate().map(|(i, &g)| (LiveImuSample { ts_sensor_us: i as i64 * 1000, gyro: g, accel: None }, i as i64 * 1000)).collect();
+            stab.gyro.read().push_live_imu_batch(&batch);
+            stab.integrate_live_data();
+            let gyro = stab.gyro.read();
+            let live = gyro.live.read();
+            let buf = live.as_ref().unwrap().quat_buffer_store_org.get_latest_buffer().unwrap();
+            buf.quats.values().copied().collect()
+        };
+        // RMS change of the rotation from one sample to the next
+        let jitter = |quats: &[Quat64]| {
+            let steps: Vec<_> = quats.windows(2).map(|q| (q[0].inverse() * q[1]).scaled_axis()).collect();
+            let sq: f64 = steps.windows(2).map(|d| (d[1] - d[0]).norm_squared()).sum();
+            (sq / (steps.len() - 1) as f64).sqrt()
+        };
+
+        let (raw, filtered) = (jitter(&orientation(&noisy, None)), jitter(&orientation(&noisy, Some(20.0))));
+        assert!(filtered < raw * 0.2, "jitter {filtered} with the filter, {raw} without");
+
+        // Zero phase: on a clean signal the filter doesn't shift the orientation, up to the newest sample
+        let (unfiltered, filtered) = (orientation(&clean, None), orientation(&clean, Some(20.0)));
+        assert_eq!(unfiltered.len(), filtered.len());
+        let max_deg = unfiltered.iter().zip(&filtered).map(|(a, b)| a.angle_to(b).to_degrees()).fold(0.0, f64::max);
+        assert!(max_deg < 0.05, "filtered orientation off by {max_deg} deg");
+
+        // Above half the IMU rate the samples go through unchanged
+        assert_eq!(orientation(&clean, Some(600.0)), unfiltered);
+    }
+}
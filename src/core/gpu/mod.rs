@@ -4,6 +4,7 @@
 #[cfg(feature = "use-opencl")]
 pub mod opencl;
 pub mod wgpu;
+pub mod wgpu_stmap;
 
 pub mod wgpu_interop;
 #[cfg(not(any(target_os = "macos", target_os = "ios")))] pub mod wgpu_interop_vulkan;
@@ -12,6 +13,7 @@ pub mod wgpu_interop;
 #[cfg(any(target_os = "windows", target_os = "linux"))]  pub mod wgpu_interop_cuda;
 
 pub mod drawing;
+pub mod shader_cache;
 use std::hash::Hasher;
 
 #[derive(Debug, Default)]
@@ -22,11 +24,35 @@ pub struct BufferDescription<'a> {
     pub data: BufferSource<'a>,
     pub texture_copy: bool
 }
+/// Input and output of `process_pixels`.
+///
+/// CPU buffers must not overlap: the kernel samples anywhere in the input while it writes the output,
+/// so in-place processing would read pixels that were already overwritten. `process_pixels` rejects
+/// overlapping buffers with `GyroflowCoreError::BuffersOverlap`, copy the input first to work in place.
 pub struct Buffers<'a> {
     pub input: BufferDescription<'a>,
     pub output: BufferDescription<'a>
 }
 
+impl Buffers<'_> {
+    /// `Err(BuffersOverlap)` if the input and output are CPU buffers sharing memory.
+    pub fn check_disjoint(&self) -> Result<(), crate::GyroflowCoreError> {
+        match (&self.input.data, &self.output.data) {
+            (BufferSource::Cpu { buffer: input }, BufferSource::Cpu { buffer: output }) => check_disjoint(input, output),
+            _ => Ok(())
+        }
+    }
+}
+
+/// `Err(BuffersOverlap)` if the two slices share memory, see `Buffers`.
+pub fn check_disjoint(input: &[u8], output: &[u8]) -> Result<(), crate::GyroflowCoreError> {
+    let (a, b) = (input.as_ptr_range(), output.as_ptr_range());
+    if !input.is_empty() && !output.is_empty() && a.start < b.end && b.start < a.end {
+        return Err(crate::GyroflowCoreError::BuffersOverlap);
+    }
+    Ok(())
+}
+
 #[derive(Debug, Default)]
 pub enum BufferSource<'a> {
     #[default]
@@ -191,3 +217,20 @@ pub fn initialize_contexts() -> Option<(String, String)> {
 
     None
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::GyroflowCoreError;
+
+    #[test]
+    fn overlapping_buffers_are_rejected() {
+        let frame = vec![0u8; 64 * 3];
+        // In place, and output starting halfway into the input
+        assert!(matches!(check_disjoint(&frame, &frame), Err(GyroflowCoreError::BuffersOverlap)));
+        assert!(matches!(check_disjoint(&frame[..96], &frame[48..]), Err(GyroflowCoreError::BuffersOverlap)));
+        // Adjacent halves of one allocation don't share a byte
+        assert!(check_disjoint(&frame[..96], &frame[96..]).is_ok());
+        assert!(check_disjoint(&frame, &vec![0u8; 64 * 3]).is_ok());
+    }
+}
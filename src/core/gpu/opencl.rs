@@ -178,37 +178,47 @@ impl OclWrapper {
     pub fn new(params: &KernelParams, ocl_names: (&str, &str, &str, &str), distortion_model: DistortionModel, digital_lens: Option<DistortionModel>, buffers: &Buffers, drawing_len: usize) -> ocl::Result<Self> {
         if params.height < 4 || params.output_height < 4 || params.stride < 1 { return Err(ocl::BufferCmdError::AlreadyMapped.into()); }
 
-        let mut kernel = include_str!("opencl_undistort.cl").to_string();
-        // let mut kernel = std::fs::read_to_string("D:/programowanie/projekty/Rust/gyroflow/src/core/gpu/opencl_undistort.cl").unwrap();
-
-        let mut lens_model_functions = distortion_model.opencl_functions().to_string();
-        let default_digital_lens = "float2 digital_undistort_point(float2 uv, __global KernelParams *p) { return uv; }
-                                        float2 digital_distort_point(float2 uv, __global KernelParams *p) { return uv; }";
-        lens_model_functions.push_str(digital_lens.as_ref().map(|x| x.opencl_functions()).unwrap_or(default_digital_lens));
-
-        let mut extensions = String::new();
-        if ocl_names.1 == "convert_half4" {
-            extensions.push_str(r#"
-                #pragma OPENCL EXTENSION cl_khr_fp16 : enable
-                half4 convert_half4(float4 v) { half4 out = 0.0; vstore_half4_rte(v, 0, (half *)&out); return out; }
-                float4 convert_half4_to_float4(half4 v) { return vload_half4(0, (half*)&v); }
-            "#);
-        }
+        let key = super::shader_cache::ShaderKey {
+            backend: "opencl",
+            model_id: distortion_model.id(),
+            digital_lens_id: digital_lens.as_ref().map(|x| x.id()).unwrap_or_default(),
+            flags: params.flags & !4,
+            variant: format!("{ocl_names:?}|{}|{}", params.bytes_per_pixel, params.interpolation),
+        };
+        let kernel = super::shader_cache::get_or_build(key, || {
+            let mut kernel = include_str!("opencl_undistort.cl").to_string();
+            // let mut kernel = std::fs::read_to_string("D:/programowanie/projekty/Rust/gyroflow/src/core/gpu/opencl_undistort.cl").unwrap();
+
+            let mut lens_model_functions = distortion_model.opencl_functions().to_string();
+            let default_digital_lens = "float2 digital_undistort_point(float2 uv, __global KernelParams *p) { return uv; }
+                                            float2 digital_distort_point(float2 uv, __global KernelParams *p) { return uv; }";
+            lens_model_functions.push_str(digital_lens.as_ref().map(|x| x.opencl_functions()).unwrap_or(default_digital_lens));
+
+            let mut extensions = String::new();
+            if ocl_names.1 == "convert_half4" {
+                extensions.push_str(r#"
+                    #pragma OPENCL EXTENSION cl_khr_fp16 : enable
+                    half4 convert_half4(float4 v) { half4 out = 0.0; vstore_half4_rte(v, 0, (half *)&out); return out; }
+                    float4 convert_half4_to_float4(half4 v) { return vload_half4(0, (half*)&v); }
+                "#);
+            }
 
-        kernel = kernel.replace("LENS_MODEL_FUNCTIONS;", &lens_model_functions)
-                       .replace("EXTENSIONS;", &extensions)
-                       .replace("DATA_CONVERTF", ocl_names.3)
-                       .replace("DATA_TYPEF", ocl_names.2)
-                       .replace("DATA_CONVERT", ocl_names.1)
-                       .replace("DATA_TYPE", ocl_names.0)
-                       .replace("PIXEL_BYTES", &format!("{}", params.bytes_per_pixel))
-                       .replace("INTERPOLATION", &format!("{}", params.interpolation));
-
-        for i in 0..31 {
-            let v = 1 << i;
-            if v == 4 { continue; } // Fill with background can be different per frame
-            kernel = kernel.replace(&format!("(params->flags & {v})"), if (params.flags & v) == v { "true" } else { "false" });
-        }
+            kernel = kernel.replace("LENS_MODEL_FUNCTIONS;", &lens_model_functions)
+                           .replace("EXTENSIONS;", &extensions)
+                           .replace("DATA_CONVERTF", ocl_names.3)
+                           .replace("DATA_TYPEF", ocl_names.2)
+                           .replace("DATA_CONVERT", ocl_names.1)
+                           .replace("DATA_TYPE", ocl_names.0)
+                           .replace("PIXEL_BYTES", &format!("{}", params.bytes_per_pixel))
+                           .replace("INTERPOLATION", &format!("{}", params.interpolation));
+
+            for i in 0..31 {
+                let v = 1 << i;
+                if v == 4 { continue; } // Fill with background can be different per frame
+                kernel = kernel.replace(&format!("(params->flags & {v})"), if (params.flags & v) == v { "true" } else { "false" });
+            }
+            kernel
+        });
 
         {
             let ctx = CONTEXT.read();
@@ -280,7 +290,7 @@ impl OclWrapper {
             let (dest_buffer, image_dst) = resolve_texture(&buffers.output, false, &mut ocl_queue, out_desc, image_src.as_ref())?;
 
             let program = Program::builder()
-                .src(&kernel)
+                .src(&*kernel)
                 .devices(ctx.device)
                 .build(&ctx.context)?;
 
@@ -0,0 +1,137 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright © 2021-2022 Adrian <adrian.eddy at gmail>
+
+// Cache of assembled kernel sources, so toggling between lens configurations doesn't redo
+// the string concatenation and replacements every time a backend is rebuilt.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{ Duration, Instant };
+use parking_lot::Mutex;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ShaderKey {
+    pub backend: &'static str,
+    pub model_id: &'static str,
+    pub digital_lens_id: &'static str,
+    /// Kernel flags baked into the source (0 if the backend reads them at runtime)
+    pub flags: i32,
+    /// Everything else the source depends on (pixel type, texture vs buffer input, ...)
+    pub variant: String,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ShaderCacheStats {
+    pub hits: usize,
+    pub misses: usize,
+    /// Sum of the original build time of every cache hit
+    pub time_saved: Duration,
+}
+
+struct Entry {
+    source: Arc<str>,
+    build_time: Duration,
+}
+
+/// Sources by key, with hit/miss stats. The renderers share the global one (`get_or_build`).
+#[derive(Default)]
+pub struct ShaderCache {
+    entries: HashMap<ShaderKey, Entry>,
+    stats: ShaderCacheStats,
+}
+
+impl ShaderCache {
+    fn get(&mut self, key: &ShaderKey) -> Option<Arc<str>> {
+        let e = self.entries.get(key)?;
+        let (source, build_time) = (e.source.clone(), e.build_time);
+        self.stats.hits += 1;
+        self.stats.time_saved += build_time;
+        log::debug!("Shader cache hit for {key:?}, saved {:.3} ms", build_time.as_secs_f64() * 1000.0);
+        Some(source)
+    }
+
+    fn insert(&mut self, key: ShaderKey, source: Arc<str>, build_time: Duration) {
+        self.stats.misses += 1;
+        self.entries.insert(key, Entry { source, build_time });
+    }
+
+    /// Return the cached source for `key`, or assemble it with `build` and store it.
+    pub fn get_or_build(&mut self, key: ShaderKey, build: impl FnOnce() -> String) -> Arc<str> {
+        if let Some(source) = self.get(&key) {
+            return source;
+        }
+        let (source, build_time) = timed_build(build);
+        self.insert(key, source.clone(), build_time);
+        source
+    }
+
+    pub fn stats(&self) -> ShaderCacheStats { self.stats }
+
+    /// Drop all cached sources and reset the stats.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.stats = ShaderCacheStats::default();
+    }
+}
+
+fn timed_build(build: impl FnOnce() -> String) -> (Arc<str>, Duration) {
+    let start = Instant::now();
+    let source: Arc<str> = build().into();
+    (source, start.elapsed())
+}
+
+lazy_static::lazy_static! {
+    static ref CACHE: Mutex<ShaderCache> = Mutex::new(ShaderCache::default());
+}
+
+/// `ShaderCache::get_or_build` on the global cache.
+pub fn get_or_build(key: ShaderKey, build: impl FnOnce() -> String) -> Arc<str> {
+    if let Some(source) = CACHE.lock().get(&key) {
+        return source;
+    }
+    // Build outside of the lock
+    let (source, build_time) = timed_build(build);
+    CACHE.lock().insert(key, source.clone(), build_time);
+    source
+}
+
+pub fn stats() -> ShaderCacheStats {
+    CACHE.lock().stats()
+}
+
+/// Drop all cached sources of the global cache and reset its stats.
+pub fn clear() {
+    CACHE.lock().clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repeated_toggles_reuse_source() {
+        // A cache of its own, the global one is shared with the other tests
+        let mut cache = ShaderCache::default();
+        let key = |model_id| ShaderKey { backend: "test", model_id, digital_lens_id: "", flags: 64, variant: "u8".into() };
+        let mut builds = 0;
+        for _ in 0..5 {
+            // Toggle between two lens models, like switching lens correction on and off
+            for model in ["opencv_fisheye", "insta360"] {
+                let src = cache.get_or_build(key(model), || {
+                    builds += 1;
+                    std::thread::sleep(Duration::from_millis(2));
+                    format!("kernel for {model}")
+                });
+                assert_eq!(&*src, format!("kernel for {model}"));
+            }
+        }
+        assert_eq!(builds, 2);
+
+        let s = cache.stats();
+        assert_eq!((s.hits, s.misses), (8, 2));
+        assert!(s.time_saved >= Duration::from_millis(16));
+
+        cache.clear();
+        assert_eq!(cache.stats().hits, 0);
+    }
+}
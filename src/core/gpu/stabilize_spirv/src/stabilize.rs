@@ -80,9 +80,9 @@ pub fn undistort(uv: Vec2, params: &KernelParams, matrices: &MatricesType, coeff
     ///////////////////////////////////////////////////////////////////
     // Add lens distortion back
     if params.lens_correction_amount < 1.0 {
-        let factor = (1.0 - params.lens_correction_amount).max(0.001); // FIXME: this is close but wrong
+        // Same focal length as the output camera, see `Stabilization::add_lens_distortion`
         let out_c = vec2(params.output_width as f32 / 2.0, params.output_height as f32 / 2.0);
-        let out_f = params.f / params.fov / factor;
+        let out_f = params.f / params.fov;
         let mut new_out_pos = out_pos;
 
         if (flags & 2) == 2 { // Has digial lens
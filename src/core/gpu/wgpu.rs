@@ -143,6 +143,25 @@ impl WgpuWrapper {
         Some((name, list_name))
     }
 
+    /// Plain device on the selected adapter, for compute passes that don't go through the undistortion kernel.
+    pub fn request_device() -> Result<(wgpu::Device, wgpu::Queue), WgpuError> {
+        if ADAPTERS.read().get(ADAPTER.load(SeqCst)).is_none() { Self::initialize_context(); }
+        let lock = ADAPTERS.read();
+        let adapter = lock.get(ADAPTER.load(SeqCst)).ok_or(WgpuError::NoAvailableAdapter)?;
+        let adapter_limits = adapter.limits();
+        pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor {
+            label: None,
+            required_features: wgpu::Features::empty(),
+            required_limits: wgpu::Limits {
+                max_buffer_size: adapter_limits.max_buffer_size,
+                max_storage_buffer_binding_size: adapter_limits.max_storage_buffer_binding_size,
+                ..wgpu::Limits::default()
+            },
+            memory_hints: wgpu::MemoryHints::Performance,
+            trace: wgpu::Trace::Off
+        })).map_err(WgpuError::RequestDevice)
+    }
+
     pub fn new(params: &KernelParams, wgpu_format: (wgpu::TextureFormat, &str, bool), distortion_model: DistortionModel, digital_lens: Option<DistortionModel>, buffers: &Buffers, mut drawing_len: usize) -> Result<Self, WgpuError> {
         let max_matrix_count = 14 * if (params.flags & 16) == 16 { params.width } else { params.height } as usize;
 
@@ -247,16 +266,6 @@ impl WgpuWrapper {
                 log::error!("Uncaptured device error: {e:?}");
             }));
 
-            let mut kernel = include_str!("wgpu_undistort.wgsl").to_string();
-            //let mut kernel = std::fs::read_to_string("D:/programowanie/projekty/Rust/gyroflow/src/core/gpu/wgpu_undistort.wgsl").unwrap();
-
-            let mut lens_model_functions = distortion_model.wgsl_functions().to_string();
-            let default_digital_lens = "fn digital_undistort_point(uv: vec2<f32>) -> vec2<f32> { return uv; }
-                                        fn digital_distort_point  (uv: vec2<f32>) -> vec2<f32> { return uv; }";
-            lens_model_functions.push_str(digital_lens.as_ref().map(|x| x.wgsl_functions()).unwrap_or(default_digital_lens));
-            kernel = kernel.replace("LENS_MODEL_FUNCTIONS;", &lens_model_functions);
-            kernel = kernel.replace("SCALAR", wgpu_format.1);
-
             if !drawing_enabled {
                 drawing_len = 16;
             }
@@ -266,19 +275,40 @@ impl WgpuWrapper {
             let out_texture = init_texture(&device, backend, &buffers.output, wgpu_format.0, false);
 
             let uses_textures = in_texture.wgpu_texture.is_some();
-            if uses_textures {
-                while let Some(pos) = kernel.find("{buffer_input}") {
-                    kernel.replace_range(pos..kernel.find("{/buffer_input}").unwrap() + 15, "");
-                }
-            } else {
-                while let Some(pos) = kernel.find("{texture_input}") {
-                    kernel.replace_range(pos..kernel.find("{/texture_input}").unwrap() + 16, "");
+
+            let key = super::shader_cache::ShaderKey {
+                backend: "wgpu",
+                model_id: distortion_model.id(),
+                digital_lens_id: digital_lens.as_ref().map(|x| x.id()).unwrap_or_default(),
+                flags: 0, // read from the params at runtime
+                variant: format!("{}|{uses_textures}", wgpu_format.1),
+            };
+            let kernel = super::shader_cache::get_or_build(key, || {
+                let mut kernel = include_str!("wgpu_undistort.wgsl").to_string();
+                //let mut kernel = std::fs::read_to_string("D:/programowanie/projekty/Rust/gyroflow/src/core/gpu/wgpu_undistort.wgsl").unwrap();
+
+                let mut lens_model_functions = distortion_model.wgsl_functions().to_string();
+                let default_digital_lens = "fn digital_undistort_point(uv: vec2<f32>) -> vec2<f32> { return uv; }
+                                            fn digital_distort_point  (uv: vec2<f32>) -> vec2<f32> { return uv; }";
+                lens_model_functions.push_str(digital_lens.as_ref().map(|x| x.wgsl_functions()).unwrap_or(default_digital_lens));
+                kernel = kernel.replace("LENS_MODEL_FUNCTIONS;", &lens_model_functions);
+                kernel = kernel.replace("SCALAR", wgpu_format.1);
+
+                if uses_textures {
+                    while let Some(pos) = kernel.find("{buffer_input}") {
+                        kernel.replace_range(pos..kernel.find("{/buffer_input}").unwrap() + 15, "");
+                    }
+                } else {
+                    while let Some(pos) = kernel.find("{texture_input}") {
+                        kernel.replace_range(pos..kernel.find("{/texture_input}").unwrap() + 16, "");
+                    }
                 }
-            }
+                kernel
+            });
             // log::info!("Using kernel: {kernel}");
 
             let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-                source: wgpu::ShaderSource::Wgsl(Cow::Owned(kernel)),
+                source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(&kernel)),
                 label: None
             });
 
@@ -0,0 +1,160 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::borrow::Cow;
+use wgpu::BufferUsages;
+use super::wgpu::{ WgpuError, WgpuWrapper };
+use crate::live::texture_pool::{ TexturePool, TexturePoolStats, TextureKey, DEFAULT_FRAMES_IN_FLIGHT };
+
+#[repr(C)]
+#[derive(Clone, Copy, Default, bytemuck::Pod, bytemuck::Zeroable)]
+struct StmapParams {
+    in_width: u32,
+    in_height: u32,
+    out_width: u32,
+    out_height: u32,
+}
+
+/// Buffers of one frame and the bind group over them, pooled together so the bind group is only
+/// created with the buffers.
+struct FrameBuffers {
+    input: wgpu::Buffer,
+    coords: wgpu::Buffer,
+    output: wgpu::Buffer,
+    /// CPU readback copy of `output`.
+    staging: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+}
+
+/// Applies STMaps (absolute input pixel coordinates per output pixel) to RGBA8 frames on the GPU.
+///
+/// Uses a device on the same adapter as the undistortion kernel. The per-frame buffers come from
+/// a `TexturePool` keyed by the input and output size, so they are recycled across frames and only
+/// allocated while warming up or when the resolution changes.
+pub struct WgpuStmap {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    pipeline: wgpu::ComputePipeline,
+    params: wgpu::Buffer,
+    frames: TexturePool<FrameBuffers, (TextureKey, TextureKey)>,
+}
+
+impl WgpuStmap {
+    pub fn new() -> Result<Self, WgpuError> {
+        Self::with_frames_in_flight(DEFAULT_FRAMES_IN_FLIGHT)
+    }
+
+    /// `frames_in_flight` sizes the buffer pools, see `TexturePool`.
+    pub fn with_frames_in_flight(frames_in_flight: usize) -> Result<Self, WgpuError> {
+        let (device, queue) = WgpuWrapper::request_device()?;
+        device.on_uncaptured_error(Box::new(|e| {
+            log::error!("Uncaptured device error (stmap): {e:?}");
+        }));
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("wgpu_stmap.wgsl"))),
+            label: Some("stmap")
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            module: &shader,
+            entry_point: Some("remap_compute"),
+            label: Some("stmap"),
+            layout: None,
+            compilation_options: Default::default(),
+            cache: Default::default()
+        });
+        let params = device.create_buffer(&wgpu::BufferDescriptor { size: std::mem::size_of::<StmapParams>() as u64, usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST, label: None, mapped_at_creation: false });
+
+        Ok(Self {
+            device, queue, pipeline, params,
+            frames: TexturePool::new(frames_in_flight),
+        })
+    }
+
+    /// Hits and misses of the frame buffer pool.
+    pub fn pool_stats(&self) -> TexturePoolStats { self.frames.stats() }
+
+    fn buffer(device: &wgpu::Device, size: u64, usage: BufferUsages) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor { size, usage, label: None, mapped_at_creation: false })
+    }
+
+    fn frame_buffers(device: &wgpu::Device, pipeline: &wgpu::ComputePipeline, params: &wgpu::Buffer, in_bytes: u64, out_bytes: u64) -> FrameBuffers {
+        let input   = Self::buffer(device, in_bytes, BufferUsages::STORAGE | BufferUsages::COPY_DST);
+        let coords  = Self::buffer(device, out_bytes * 2, BufferUsages::STORAGE | BufferUsages::COPY_DST);
+        let output  = Self::buffer(device, out_bytes, BufferUsages::STORAGE | BufferUsages::COPY_SRC);
+        let staging = Self::buffer(device, out_bytes, BufferUsages::MAP_READ | BufferUsages::COPY_DST);
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &pipeline.get_bind_group_layout(0),
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: params.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 1, resource: coords.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 2, resource: input.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 3, resource: output.as_entire_binding() },
+            ],
+        });
+        FrameBuffers { input, coords, output, staging, bind_group }
+    }
+
+    /// Remap tightly packed RGBA8 `input` into `output` through `coords` (x, y pairs, one per output pixel).
+    pub fn apply(&mut self, input: &[u8], in_size: (usize, usize), coords: &[f32], output: &mut [u8], out_size: (usize, usize)) -> bool {
+        let out_px = out_size.0 * out_size.1;
+        if input.len() < in_size.0 * in_size.1 * 4 || coords.len() < out_px * 2 || output.len() < out_px * 4 || in_size.0 == 0 || in_size.1 == 0 {
+            log::error!("wgpu stmap: buffer size mismatch");
+            return false;
+        }
+
+        let key = (TextureKey::new(in_size, wgpu::TextureFormat::Rgba8Unorm), TextureKey::new(out_size, wgpu::TextureFormat::Rgba8Unorm));
+        let (in_bytes, out_bytes) = ((in_size.0 * in_size.1 * 4) as u64, (out_px * 4) as u64);
+        let (device, pipeline, params) = (&self.device, &self.pipeline, &self.params);
+        let bufs = self.frames.acquire(key, || {
+            log::debug!("wgpu stmap: allocating buffers for {}x{} -> {}x{}", in_size.0, in_size.1, out_size.0, out_size.1);
+            Self::frame_buffers(device, pipeline, params, in_bytes, out_bytes)
+        });
+
+        let p = StmapParams { in_width: in_size.0 as u32, in_height: in_size.1 as u32, out_width: out_size.0 as u32, out_height: out_size.1 as u32 };
+        self.queue.write_buffer(&self.params, 0, bytemuck::bytes_of(&p));
+        self.queue.write_buffer(&bufs.input, 0, &input[..in_size.0 * in_size.1 * 4]);
+        self.queue.write_buffer(&bufs.coords, 0, bytemuck::cast_slice(&coords[..out_px * 2]));
+        let ok = self.dispatch(&bufs, output, out_size);
+
+        self.frames.release(key, bufs);
+        ok
+    }
+
+    fn dispatch(&self, bufs: &FrameBuffers, output: &mut [u8], out_size: (usize, usize)) -> bool {
+        let out_px = out_size.0 * out_size.1;
+        let (device, queue, pipeline) = (&self.device, &self.queue, &self.pipeline);
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        {
+            let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: None, timestamp_writes: None });
+            cpass.set_pipeline(pipeline);
+            cpass.set_bind_group(0, &bufs.bind_group, &[]);
+            cpass.dispatch_workgroups((out_size.0 as u32).div_ceil(8), (out_size.1 as u32).div_ceil(8), 1);
+        }
+        encoder.copy_buffer_to_buffer(&bufs.output, 0, &bufs.staging, 0, (out_px * 4) as u64);
+        queue.submit(Some(encoder.finish()));
+
+        let buffer_slice = bufs.staging.slice(..);
+        let (sender, receiver) = futures_intrusive::channel::shared::oneshot_channel();
+        buffer_slice.map_async(wgpu::MapMode::Read, move |v| sender.send(v).unwrap());
+        let _ = device.poll(wgpu::PollType::Wait);
+
+        if let Some(Ok(())) = pollster::block_on(receiver.receive()) {
+            let data = buffer_slice.get_mapped_range();
+            output[..out_px * 4].copy_from_slice(&data);
+            drop(data);
+            bufs.staging.unmap();
+            true
+        } else {
+            log::error!("wgpu stmap: failed to read back the output");
+            false
+        }
+    }
+}
+
+impl Drop for WgpuStmap {
+    fn drop(&mut self) {
+        self.frames.clear();
+        let _ = self.device.poll(wgpu::PollType::Wait);
+    }
+}
@@ -0,0 +1,39 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+// Applies a per-pixel STMap (absolute input pixel coordinates) to an RGBA8 image with bilinear sampling.
+// Same sampling as the CPU path: coordinates are clamped to the input, result rounded to nearest.
+
+struct Params {
+    in_width: u32,
+    in_height: u32,
+    out_width: u32,
+    out_height: u32,
+}
+
+@group(0) @binding(0) var<uniform> params: Params;
+@group(0) @binding(1) var<storage, read> coords: array<vec2<f32>>;
+@group(0) @binding(2) var<storage, read> input: array<u32>;
+@group(0) @binding(3) var<storage, read_write> output: array<u32>;
+
+fn fetch(x: u32, y: u32) -> vec4<f32> {
+    return unpack4x8unorm(input[y * params.in_width + x]) * 255.0;
+}
+
+@compute @workgroup_size(8, 8)
+fn remap_compute(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (id.x >= params.out_width || id.y >= params.out_height) { return; }
+    let i = id.y * params.out_width + id.x;
+
+    let max_xy = vec2<f32>(f32(params.in_width - 1u), f32(params.in_height - 1u));
+    let uv = clamp(coords[i], vec2<f32>(0.0), max_xy);
+    let p0 = floor(uv);
+    let t = uv - p0;
+    let x0 = u32(p0.x);
+    let y0 = u32(p0.y);
+    let x1 = min(x0 + 1u, params.in_width - 1u);
+    let y1 = min(y0 + 1u, params.in_height - 1u);
+
+    let top    = mix(fetch(x0, y0), fetch(x1, y0), t.x);
+    let bottom = mix(fetch(x0, y1), fetch(x1, y1), t.x);
+    output[i] = pack4x8unorm(round(mix(top, bottom, t.y)) / 255.0);
+}
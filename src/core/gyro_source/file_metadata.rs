@@ -107,6 +107,9 @@ impl ReadOnlyFileMetadata {
     pub fn set_raw_imu(&mut self, v: Vec<TimeIMU>) {
         self.0.write().raw_imu = v;
     }
+    pub fn set_digital_zoom(&mut self, v: Option<f64>) {
+        self.0.write().digital_zoom = v;
+    }
 }
 impl serde::Serialize for ReadOnlyFileMetadata {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: serde::Serializer {
@@ -6,10 +6,11 @@ use crossbeam_channel::{unbounded, Sender, Receiver};
 use super::FileMetadata;
 use super::TimeQuat;
 use super::Quat64;
+use super::TimeVec;
 use std::fmt;
-use std::sync::atomic::{AtomicBool, Ordering, AtomicU64};
+use std::sync::atomic::{AtomicBool, Ordering, AtomicU64, AtomicI64};
 use std::collections::BTreeMap;
-use nalgebra::{Quaternion as NQuat, UnitQuaternion as NUnitQuat}; // adjust if you already import nalgebra elsewhere
+use nalgebra::{Quaternion as NQuat, UnitQuaternion as NUnitQuat, Vector3}; // adjust if you already import nalgebra elsewhere
 use std::path::Path;
 use crate::gyro_source::csv_quats;
 
@@ -20,7 +21,7 @@ pub struct LiveImuSample {
     pub accel: Option<[f64;3]>,
 }
 
-#[derive(Default)]
+#[derive(Default, Clone, Copy, Debug, PartialEq)]
 pub struct LiveClockSync {
     // Linear mapping sensor_time -> video_time: video = a*sensor + b (all µs)
     pub a: f64,  // scale
@@ -29,6 +30,11 @@ pub struct LiveClockSync {
 
 impl LiveClockSync {
     pub fn new(a: f64, b: f64) -> Self { Self { a, b } }
+
+    #[inline]
+    pub fn to_video_us(&self, ts_sensor_us: i64) -> i64 {
+        (self.a * ts_sensor_us as f64 + self.b).round() as i64
+    }
 }
 
 impl fmt::Display for LiveClockSync {
@@ -37,10 +43,143 @@ impl fmt::Display for LiveClockSync {
     }
 }
 
+/// Pairs kept by `ClockSyncFit`, the Theil-Sen fit is quadratic in this.
+pub const CLOCK_SYNC_MAX_PAIRS: usize = 64;
+/// Pairs older than this (sensor clock) are dropped from the fit.
+pub const CLOCK_SYNC_WINDOW_US: i64 = 30_000_000;
+/// Residual up to which a pair counts as an inlier of the fit.
+pub const CLOCK_SYNC_INLIER_TOL_US: f64 = 2_000.0;
+
+/// Robust `LiveClockSync` estimate from (sensor, video) timestamp pairs over a sliding window.
+///
+/// Uses a Theil-Sen fit: `a` is the median of the pairwise slopes and `b` the median offset,
+/// so a few grossly wrong pairs (network stalls, clock glitches) don't move the mapping.
+#[derive(Clone, Debug, Default)]
+pub struct ClockSyncFit {
+    pairs: VecDeque<(i64, i64)>,
+    inlier_ratio: f64,
+    inliers: usize,
+    residual_us: f64,
+}
+
+/// Clock mapping in effect and how well the pairs behind it agree, see `ClockSyncFit`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ClockSyncState {
+    /// Scale of `video = a * sensor + b`.
+    pub a: f64,
+    /// Offset of `video = a * sensor + b`, µs.
+    pub b: f64,
+    /// Median distance of the windowed pairs from the fit, µs. 0 before the first fit.
+    pub residual: f64,
+    /// Pairs within `CLOCK_SYNC_INLIER_TOL_US` of the fit.
+    pub inliers: usize,
+}
+
+impl ClockSyncState {
+    /// The identity mapping, before any pair was fitted.
+    pub fn identity() -> Self { Self { a: 1.0, b: 0.0, residual: 0.0, inliers: 0 } }
+}
+
+fn median(v: &mut [f64]) -> Option<f64> {
+    if v.is_empty() { return None; }
+    v.sort_by(f64::total_cmp);
+    let m = v.len() / 2;
+    Some(if v.len() % 2 == 0 { (v[m - 1] + v[m]) / 2.0 } else { v[m] })
+}
+
+impl ClockSyncFit {
+    pub fn new() -> Self { Self::default() }
+
+    /// Add a pair and refit. Returns the new mapping, `None` until the pairs span some time.
+    pub fn update_from_pair(&mut self, sensor_us: i64, video_us: i64) -> Option<LiveClockSync> {
+        self.pairs.push_back((sensor_us, video_us));
+        while self.pairs.len() > CLOCK_SYNC_MAX_PAIRS || self.pairs.front().is_some_and(|p| sensor_us - p.0 > CLOCK_SYNC_WINDOW_US) {
+            self.pairs.pop_front();
+        }
+        self.fit()
+    }
+
+    /// Fraction of the windowed pairs within `CLOCK_SYNC_INLIER_TOL_US` of the current fit (0 before the first fit).
+    pub fn inlier_ratio(&self) -> f64 { self.inlier_ratio }
+
+    /// Pairs within `CLOCK_SYNC_INLIER_TOL_US` of the current fit.
+    pub fn inliers(&self) -> usize { self.inliers }
+
+    /// Median distance of the pairs from the current fit, µs.
+    pub fn residual_us(&self) -> f64 { self.residual_us }
+
+    pub fn len(&self) -> usize { self.pairs.len() }
+    pub fn is_empty(&self) -> bool { self.pairs.is_empty() }
+
+    pub fn clear(&mut self) {
+        self.pairs.clear();
+        self.inlier_ratio = 0.0;
+        self.inliers = 0;
+        self.residual_us = 0.0;
+    }
+
+    fn fit(&mut self) -> Option<LiveClockSync> {
+        // Relative to the first pair, keeps the products small
+        let (s0, v0) = *self.pairs.front()?;
+        let rel: Vec<(f64, f64)> = self.pairs.iter().map(|&(s, v)| ((s - s0) as f64, (v - v0) as f64)).collect();
+
+        let mut slopes = Vec::with_capacity(rel.len() * (rel.len() - 1) / 2);
+        for (i, &(si, vi)) in rel.iter().enumerate() {
+            for &(sj, vj) in &rel[i + 1..] {
+                if sj != si { slopes.push((vj - vi) / (sj - si)); }
+            }
+        }
+        let a = median(&mut slopes)?;
+        let mut offsets: Vec<f64> = rel.iter().map(|&(s, v)| v - a * s).collect();
+        let b_rel = median(&mut offsets)?;
+
+        let mut residuals: Vec<f64> = rel.iter().map(|&(s, v)| (v - a * s - b_rel).abs()).collect();
+        self.inliers = residuals.iter().filter(|&&r| r <= CLOCK_SYNC_INLIER_TOL_US).count();
+        self.inlier_ratio = self.inliers as f64 / rel.len() as f64;
+        self.residual_us = median(&mut residuals).unwrap_or_default();
+
+        // video = a * (sensor - s0) + v0 + b_rel
+        Some(LiveClockSync::new(a, v0 as f64 + b_rel - a * s0 as f64))
+    }
+}
+
+impl LiveImuSample {
+    /// Accelerometer reading, zero for samples without one.
+    /// Only for consumers that take a zero vector as "no accelerometer", see `held_accels` otherwise.
+    pub fn accel_or_zero(&self) -> [f64; 3] {
+        self.accel.unwrap_or_default()
+    }
+}
+
+/// Accelerometer readings for orientation filters, one per sample.
+///
+/// Loggers may send `accel` only every few samples. A zero vector would be taken as a (bogus)
+/// gravity direction by the complementary filters, so samples without a reading hold the previous
+/// one, and the ones before the first reading take that one. Gyro-only streams stay at zero,
+/// which the integrators already treat as missing.
+pub fn held_accels(samples: &[LiveImuSample]) -> Vec<[f64; 3]> {
+    let mut held = samples.iter().find_map(|s| s.accel);
+    samples.iter().map(|s| {
+        if s.accel.is_some() { held = s.accel; }
+        held.unwrap_or_else(|| s.accel_or_zero())
+    }).collect()
+}
+
 #[derive(Default)]
 pub struct ImuRing {
     pub buf: VecDeque<LiveImuSample>,
     pub keep_us: i64, // e.g. 3_000_000
+    /// Sample cap on top of `keep_us`, 0 for none, see `LiveMemoryLimits`.
+    pub max_samples: usize,
+}
+
+/// Memory caps of the live state, 0 for unbounded. Usually derived from a `live::memory::MemoryBudget`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LiveMemoryLimits {
+    /// Samples kept by the IMU ring.
+    pub imu_samples: usize,
+    /// Bytes of each quaternion buffer store.
+    pub quat_store_bytes: usize,
 }
 
 
@@ -66,27 +205,32 @@ impl fmt::Display for LiveImuSample {
 
 
 impl ImuRing {
-    pub fn new(keep_us: i64) -> Self { Self { buf: VecDeque::new(), keep_us } }
+    pub fn new(keep_us: i64) -> Self { Self { buf: VecDeque::new(), keep_us, max_samples: 0 } }
+    /// Samples are stored with their raw sensor timestamps, the clock sync is only
+    /// applied when reading, so a sync update also applies to already buffered samples.
     pub fn push(&mut self, s: LiveImuSample, now_video_us: i64, sync: &LiveClockSync) {
-        // convert to video clock immediately
-        let vts = (sync.a * s.ts_sensor_us as f64 + sync.b).round() as i64;
-        let sample = LiveImuSample { ts_sensor_us: vts, ..s }; // reuse field for video ts
-        self.buf.push_back(sample);
+        self.buf.push_back(s);
         // evict old
         while let Some(front) = self.buf.front() {
-            if now_video_us - front.ts_sensor_us > self.keep_us { self.buf.pop_front(); } else { break; }
+            if now_video_us - sync.to_video_us(front.ts_sensor_us) > self.keep_us { self.buf.pop_front(); } else { break; }
+        }
+        if self.max_samples > 0 && self.buf.len() > self.max_samples {
+            let excess = self.buf.len() - self.max_samples;
+            self.buf.drain(..excess);
         }
     }
-    pub fn window(&self, start_us: i64, end_us: i64) -> impl Iterator<Item=&LiveImuSample> {
-        self.buf.iter().filter(move |s| s.ts_sensor_us >= start_us && s.ts_sensor_us <= end_us)
+
+    pub fn bytes(&self) -> usize { self.buf.len() * std::mem::size_of::<LiveImuSample>() }
+    /// Samples within `[start_us, end_us]` (video clock), with `ts_sensor_us` converted to the video clock.
+    pub fn window<'a>(&'a self, start_us: i64, end_us: i64, sync: &'a LiveClockSync) -> impl Iterator<Item=LiveImuSample> + 'a {
+        self.buf.iter()
+            .map(move |s| LiveImuSample { ts_sensor_us: sync.to_video_us(s.ts_sensor_us), ..*s }) // reuse field for video ts
+            .filter(move |s| s.ts_sensor_us >= start_us && s.ts_sensor_us <= end_us)
     }
-    pub fn snapshot(&self) -> Vec<LiveImuSample> {
-        self.buf.iter().copied().collect()
+    /// All buffered samples converted to the video clock.
+    pub fn snapshot(&self, sync: &LiveClockSync) -> Vec<LiveImuSample> {
+        self.window(i64::MIN, i64::MAX, sync).collect()
     }
-
-
-
-
 }
 
 #[derive(Debug, Clone, Default)]
@@ -108,6 +252,9 @@ impl QuatBuffer {
     #[inline]
     pub fn mid_us(&self) -> i64 { (self.first_us + self.last_us) / 2 }
 
+    /// Payload size of the quaternions, tree overhead not counted.
+    pub fn bytes(&self) -> usize { self.quats.len() * std::mem::size_of::<(i64, Quat64)>() }
+
     #[inline]
     pub fn span_us(&self) -> i64 { (self.last_us - self.first_us).max(0) }
 
@@ -129,6 +276,16 @@ impl QuatBuffer {
         (target_us as f64 - self.mid_us() as f64).abs() <= tol
     }
 
+    /// How well this buffer serves `target_us` under `window`, 0..1: the share of the required
+    /// padding it has on the thinner side, scaled by `OFF_CENTER_CONFIDENCE` when the target is off-center.
+    /// 0 outside the buffer, where `quat_at_ms` can only hold the edge orientation.
+    pub fn coverage(&self, target_us: i64, window: &SmoothingWindow) -> f64 {
+        if self.quats.is_empty() || target_us < self.first_us || target_us > self.last_us { return 0.0; }
+        let share = |have_us: i64, need_us: i64| if need_us > 0 { (have_us as f64 / need_us as f64).min(1.0) } else { 1.0 };
+        let padded = share(target_us - self.first_us, window.pre_us()).min(share(self.last_us - target_us, window.post_us()));
+        if self.is_centered_for(target_us, window.center_ratio) { padded } else { padded * OFF_CENTER_CONFIDENCE }
+    }
+
     /// Simple SLERP lookup (same logic you already use elsewhere).
     pub fn quat_at_ms(&self, t_ms: f64) -> Option<Quat64> {
         if self.quats.is_empty() { return None; }
@@ -174,6 +331,14 @@ impl QuatBuffer {
         (last_ts - first_ts) as f64 / 1000.0
     }
 
+    /// Buffer holding every sample, in any order. `None` for an empty slice.
+    pub fn from_csv_samples(samples: &[crate::gyro_source::csv_quats::CsvQuatSample]) -> Option<Self> {
+        let map: TimeQuat = samples.iter()
+            .map(|s| (s.t_us, NUnitQuat::new_normalize(NQuat::new(s.qw, s.qx, s.qy, s.qz))))
+            .collect();
+        QuatBuffer::from_btreemap(&map)
+    }
+
     pub fn from_csv_samples_range(
         samples: &[crate::gyro_source::csv_quats::CsvQuatSample],
         start_us: i64,
@@ -204,10 +369,65 @@ impl QuatBuffer {
     }
 }
 
+/// Coverage of a target that is covered but not centered in its buffer, see `QuatBuffer::coverage`.
+pub const OFF_CENTER_CONFIDENCE: f64 = 0.8;
+
+/// Which live quaternion store is the target orientation of the rendered frame.
+/// `Org` gives no correction, i.e. shows the raw shake, for comparing against the smoothed result.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QuatKind {
+    /// `quat_buffer_store_org`, the camera orientation.
+    Org,
+    /// `quat_buffer_store_smoothed`, the stabilized orientation.
+    #[default]
+    Smoothed,
+}
+
+/// How much IMU data a live quaternion lookup needs around the target time.
+///
+/// `post_ms` is the look-ahead: a frame can only be rendered once the buffers reach `t + post_ms`,
+/// so it is the latency the smoothing adds. Less look-ahead means lower latency but less future
+/// motion to smooth against. `center_ratio` is how far from the middle of a buffer the target may be,
+/// as a fraction of half its span (see `QuatBuffer::is_centered_for`).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SmoothingWindow {
+    pub pre_ms: f64,
+    pub post_ms: f64,
+    pub center_ratio: f64,
+}
+
+impl Default for SmoothingWindow {
+    fn default() -> Self {
+        Self { pre_ms: 0.0, post_ms: 500.0, center_ratio: 0.25 }
+    }
+}
+
+impl SmoothingWindow {
+    #[inline]
+    pub fn pre_us(&self) -> i64 { (self.pre_ms.max(0.0) * 1000.0) as i64 }
+    #[inline]
+    pub fn post_us(&self) -> i64 { (self.post_ms.max(0.0) * 1000.0) as i64 }
+
+    /// IMU history the ring has to keep so a buffer can cover the whole window.
+    pub fn required_retention_us(&self) -> i64 { self.pre_us() + self.post_us() }
+}
+
+/// Buffer used by the previous lookup, and the one before the last switch.
+#[derive(Debug, Default)]
+struct BufferSwitch {
+    current: Option<Arc<QuatBuffer>>,
+    previous: Option<Arc<QuatBuffer>>,
+    switch_us: i64,
+}
+
 #[derive(Debug, Default)]
 pub struct QuatBufferStore {
     dq: RwLock<VecDeque<Arc<QuatBuffer>>>,
     version: AtomicU64,
+    blend_window_us: AtomicI64,
+    switch: Mutex<BufferSwitch>,
+    max_bytes: AtomicU64,
 }
 
 impl QuatBufferStore {
@@ -215,36 +435,104 @@ impl QuatBufferStore {
         Self {
             dq: RwLock::new(VecDeque::new()),
             version: AtomicU64::new(0),
+            blend_window_us: AtomicI64::new(0),
+            switch: Mutex::new(BufferSwitch::default()),
+            max_bytes: AtomicU64::new(0),
+        }
+    }
+
+    /// Cap the published buffers at `bytes` (0 for no cap): `publish` drops the oldest buffers
+    /// beyond it, always keeping the newest one.
+    pub fn set_max_bytes(&self, bytes: usize) {
+        self.max_bytes.store(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// Payload size of the published buffers, see `QuatBuffer::bytes`.
+    pub fn bytes(&self) -> usize { self.dq.read().iter().map(|b| b.bytes()).sum() }
+
+    /// When the buffer selected by `get_quat_at_time` changes, blend from the outgoing buffer
+    /// to the incoming one over `window_ms` instead of jumping. `0` disables blending.
+    pub fn set_blend_window_ms(&self, window_ms: f64) {
+        self.blend_window_us.store((window_ms.max(0.0) * 1000.0).round() as i64, Ordering::Relaxed);
+    }
+
+    fn blend_across_switch(&self, buf: &Arc<QuatBuffer>, t_ms: f64, q: Quat64) -> Quat64 {
+        let window_us = self.blend_window_us.load(Ordering::Relaxed);
+        let t_us = (t_ms * 1000.0).round() as i64;
+        let mut sw = self.switch.lock();
+
+        match &sw.current {
+            Some(cur) if Arc::ptr_eq(cur, buf) => {}
+            Some(cur) if window_us > 0 => {
+                sw.previous = Some(cur.clone());
+                sw.switch_us = t_us;
+                sw.current = Some(buf.clone());
+            }
+            _ => sw.current = Some(buf.clone()),
+        }
+        if window_us <= 0 {
+            sw.previous = None;
+            return q;
         }
+
+        let dt = t_us - sw.switch_us;
+        let Some(prev) = sw.previous.as_ref() else { return q; };
+        // Past the window, or far before the switch (seek): the incoming buffer alone
+        if dt >= window_us || dt < -window_us || t_us < prev.first_us || t_us > prev.last_us {
+            sw.previous = None;
+            return q;
+        }
+        let Some(q_prev) = prev.quat_at_ms(t_ms) else { return q; };
+        q_prev.slerp(&q, (dt as f64 / window_us as f64).clamp(0.0, 1.0))
+    }
+
+    /// Store holding one buffer with all of `samples`, e.g. to replay a recorded orientation track
+    /// through the live render path without IMU integration.
+    pub fn from_csv_samples(samples: &[crate::gyro_source::csv_quats::CsvQuatSample]) -> Self {
+        let store = Self::new();
+        store.publish_csv_samples(samples);
+        store
+    }
+
+    /// Publish all of `samples` as one buffer, returns the new version (`None` for no samples).
+    pub fn publish_csv_samples(&self, samples: &[crate::gyro_source::csv_quats::CsvQuatSample]) -> Option<u64> {
+        let buf = QuatBuffer::from_csv_samples(samples)?;
+        Some(self.publish(buf).1)
     }
 
-    /// Publish a new buffer (no capacity-based deletion here).
+    /// Publish a new buffer, evicting the oldest ones beyond `set_max_bytes`.
     pub fn publish(&self, buf: QuatBuffer) -> (Arc<QuatBuffer>, u64) {
         let arc = Arc::new(buf);
         {
             let mut w: parking_lot::lock_api::RwLockWriteGuard<'_, parking_lot::RawRwLock, VecDeque<Arc<QuatBuffer>>> = self.dq.write();
             w.push_back(arc.clone());
+            let max_bytes = self.max_bytes.load(Ordering::Relaxed) as usize;
+            if max_bytes > 0 {
+                let mut total: usize = w.iter().map(|b| b.bytes()).sum();
+                while total > max_bytes && w.len() > 1 {
+                    total -= w.pop_front().map_or(0, |b| b.bytes());
+                }
+            }
         }
         let ver = self.version.fetch_add(1, Ordering::SeqCst) + 1;
         (arc, ver)
     }
 
-    /// Select the **newest** buffer where `t_ms` is (a) covered with padding and (b) roughly centered.
+    /// Select the **newest** buffer where `t_ms` is (a) covered with the window's padding and (b) roughly centered.
     /// Then prune any **older** buffers that also center the same `t_ms`.
     ///
     /// If none are centered, optionally fall back to newest *covering* buffer (if `fallback_ok`).
     pub fn select_centered_and_prune(
         &self,
         t_ms: f64,
-        pre_ms: f64,
-        post_ms: f64,
-        center_ratio: f64,
+        window: &SmoothingWindow,
         fallback_ok: bool,
     ) -> Option<(Arc<QuatBuffer>, u64)>
     {
         let t_us    = (t_ms * 1000.0) as i64;
-        let pre_us  = (pre_ms * 1000.0) as i64;
-        let post_us = (post_ms * 1000.0) as i64;
+        let pre_us  = window.pre_us();
+        let post_us = window.post_us();
+        let center_ratio = window.center_ratio;
 
         // 1) Read-pass: find best candidate index (newest-first).
         let (cand_idx, fallback_idx) = {
@@ -300,34 +588,62 @@ impl QuatBufferStore {
     pub fn get_quat_at_time(
     &self,
     t_ms: f64,
-    pre_ms: f64,
-    post_ms: f64,
-    center_ratio: f64,
+    window: &SmoothingWindow,
 ) -> Option<Quat64> {
     let (buf, _ver) = self
-        .select_centered_and_prune(t_ms, pre_ms, post_ms, center_ratio, true)?;
-    buf.quat_at_ms(t_ms)
+        .select_centered_and_prune(t_ms, window, true)?;
+    let q = buf.quat_at_ms(t_ms)?;
+    Some(self.blend_across_switch(&buf, t_ms, q))
 }
 /// Get a buffer that covers a window around `t_ms`, without pruning the store.
     pub fn get_buffer_for_time(
         &self,
         t_ms: f64,
-        pre_ms: f64,
-        post_ms: f64,
-        center_ratio: f64,
+        window: &SmoothingWindow,
     ) -> Option<Arc<QuatBuffer>> {
         let (buf, _ver) =
-            self.select_centered_and_prune(t_ms, pre_ms, post_ms, center_ratio, false)?;
+            self.select_centered_and_prune(t_ms, window, false)?;
         Some(buf)
     }
 
 
 
+    /// Best `QuatBuffer::coverage` of `t_ms` among the published buffers, 0 when none has it.
+    pub fn coverage_at(&self, t_ms: f64, window: &SmoothingWindow) -> f64 {
+        let t_us = (t_ms * 1000.0) as i64;
+        self.dq.read().iter().map(|b| b.coverage(t_us, window)).fold(0.0, f64::max)
+    }
+
     pub fn get_latest_buffer(&self) -> Option<Arc<QuatBuffer>> {
         let r = self.dq.read();
         r.back().cloned()
     }
 
+    /// Merge all published buffers into one track and clear the store, e.g. to export the
+    /// orientation of a whole session. Where buffers overlap, the newest one wins over its whole span,
+    /// so samples of different integrations are never interleaved.
+    pub fn drain_all(&self) -> TimeQuat {
+        let buffers = std::mem::take(&mut *self.dq.write());
+        self.clear();
+        let mut track = TimeQuat::new();
+        for buf in buffers {
+            // Oldest first: each buffer replaces what older ones had in its span
+            let stale: Vec<i64> = track.range(buf.first_us..=buf.last_us).map(|(t, _)| *t).collect();
+            for t in stale {
+                track.remove(&t);
+            }
+            track.extend(buf.quats.iter().map(|(t, q)| (*t, *q)));
+        }
+        track
+    }
+
+    /// Drop all published buffers, e.g. when their timestamps became stale.
+    pub fn clear(&self) {
+        self.dq.write().clear();
+        *self.switch.lock() = BufferSwitch::default();
+        self.version.fetch_add(1, Ordering::SeqCst);
+    }
+
     /// Load quaternions from a CSV (org or stab depending on `stabbed`) and publish them
     /// as sliding windows: each window is 3 seconds long, next window starts 1 second later.
     ///
@@ -401,13 +717,141 @@ impl QuatBufferStore {
 
 
 
+/// Time constant of the low-pass filter separating gravity from motion in the accelerometer.
+pub const GRAVITY_LPF_TAU_US: f64 = 200_000.0;
+
+/// Per-sample gravity direction (unit vector, camera/IMU frame) for a window of live IMU data.
+#[derive(Debug, Clone, Default)]
+pub struct GravityBuffer {
+    pub vecs: TimeVec,
+    pub first_us: i64,
+    pub last_us:  i64,
+}
+
+impl GravityBuffer {
+    /// Gravity from low-passed accelerometer readings. The accelerometer measures the reaction
+    /// to gravity, so the gravity direction is the opposite of the filtered accel vector.
+    pub fn from_samples(samples: &[LiveImuSample]) -> Option<Self> {
+        let mut vecs = TimeVec::new();
+        let mut filtered: Option<(i64, Vector3<f64>)> = None;
+        for s in samples {
+            let Some(a) = s.accel else { continue; };
+            let a = Vector3::new(a[0], a[1], a[2]);
+            if a.norm_squared() < 1e-12 { continue; }
+            let lp = match filtered {
+                Some((t0, prev)) => {
+                    let dt = (s.ts_sensor_us - t0).max(0) as f64;
+                    let alpha = dt / (GRAVITY_LPF_TAU_US + dt);
+                    prev + (a - prev) * alpha
+                }
+                None => a,
+            };
+            filtered = Some((s.ts_sensor_us, lp));
+            vecs.insert(s.ts_sensor_us, -lp.normalize());
+        }
+        let first_us = *vecs.keys().next()?;
+        let last_us  = *vecs.keys().next_back()?;
+        Some(Self { vecs, first_us, last_us })
+    }
+
+    pub fn gravity_at_ms(&self, t_ms: f64) -> Option<Vector3<f64>> {
+        let t_us = (t_ms * 1000.0).round() as i64;
+        crate::smoothing::horizon::HorizonLock::interpolate_gravity_vector(&self.vecs, t_us.clamp(self.first_us, self.last_us))
+            .map(|v| v.normalize())
+    }
+}
+
+/// Latest live gravity buffer, published alongside the quaternion buffers.
+#[derive(Debug, Default)]
+pub struct GravityStore {
+    latest: RwLock<Option<Arc<GravityBuffer>>>,
+}
+
+impl GravityStore {
+    pub fn new() -> Self { Self::default() }
+
+    pub fn publish(&self, buf: GravityBuffer) -> Arc<GravityBuffer> {
+        let arc = Arc::new(buf);
+        *self.latest.write() = Some(arc.clone());
+        arc
+    }
+
+    pub fn get_latest_buffer(&self) -> Option<Arc<GravityBuffer>> {
+        self.latest.read().clone()
+    }
+
+    pub fn get_gravity_at_time(&self, t_ms: f64) -> Option<Vector3<f64>> {
+        self.latest.read().as_ref()?.gravity_at_ms(t_ms)
+    }
+
+    pub fn clear(&self) {
+        *self.latest.write() = None;
+    }
+}
+
+/// Per-frame orientation from an external tracker, used instead of the IMU quaternions while set.
+///
+/// The quaternion of a frame is the orientation it is rendered with (the smoothed one), the camera
+/// orientation is taken as identity, so the correction is exactly the supplied rotation.
+/// Frames are looked up directly in the track: unlike the published quaternion buffers, there's no
+/// smoothing window that needs orientation from frames that haven't arrived yet.
+#[derive(Debug, Default)]
+pub struct ExternalOrientationStore {
+    track: Mutex<TimeQuat>,
+    latest: RwLock<Option<Arc<QuatBuffer>>>,
+}
+
+impl ExternalOrientationStore {
+    /// Add the orientation of the frame at `ts_us` and drop the ones more than `keep_us` older than the newest.
+    pub fn push(&self, ts_us: i64, q: Quat64, keep_us: i64) -> Arc<QuatBuffer> {
+        let mut track = self.track.lock();
+        track.insert(ts_us, q);
+        let newest = *track.keys().next_back().unwrap();
+        *track = track.split_off(&(newest - keep_us));
+        let arc = Arc::new(QuatBuffer::from_btreemap(&track).unwrap());
+        *self.latest.write() = Some(arc.clone());
+        arc
+    }
+
+    pub fn is_active(&self) -> bool { self.latest.read().is_some() }
+
+    pub fn get_latest_buffer(&self) -> Option<Arc<QuatBuffer>> {
+        self.latest.read().clone()
+    }
+
+    /// Interpolated between neighbouring frames, clamped to the ends of the track.
+    pub fn get_quat_at_time(&self, t_ms: f64) -> Option<Quat64> {
+        self.latest.read().as_ref()?.quat_at_ms(t_ms)
+    }
+
+    /// Whether `t_ms` lies within the track, i.e. isn't clamped to one of its ends.
+    pub fn covers(&self, t_ms: f64) -> bool {
+        let t_us = (t_ms * 1000.0).round() as i64;
+        self.latest.read().as_ref().is_some_and(|b| b.first_us <= t_us && t_us <= b.last_us)
+    }
+
+    /// Go back to the IMU orientation.
+    pub fn clear(&self) {
+        self.track.lock().clear();
+        *self.latest.write() = None;
+    }
+}
+
 pub struct LiveState {
     pub header: String,
     pub ring: Mutex<ImuRing>,
-    pub sync: LiveClockSync,
+    pub sync: RwLock<LiveClockSync>,
+    pub sync_fit: Mutex<ClockSyncFit>,
     pub quat_buffer_store_org: QuatBufferStore,
     pub quat_buffer_store_smoothed: QuatBufferStore,
+    pub gravity_store: GravityStore,
+    pub external_orientation: ExternalOrientationStore,
+    pub window: RwLock<SmoothingWindow>,
     pub enabled: AtomicBool,
+    /// An IMU sample was pushed, see `live::NoImu` for the output before that.
+    pub imu_received: AtomicBool,
+    /// Samples pushed since the last integration started, see `GyroSource::live_samples_since_integrate`.
+    pub samples_since_integrate: AtomicU64,
 }
 
 impl Default for LiveState {
@@ -416,16 +860,28 @@ impl Default for LiveState {
              header: String::new(),
              // default keep_us=3s; enable_live will override when constructing
              ring: Mutex::new(ImuRing::new(3_000_000)),
-             sync: LiveClockSync::default(),
+             sync: RwLock::new(LiveClockSync::default()),
+             sync_fit: Mutex::new(ClockSyncFit::new()),
              quat_buffer_store_org: QuatBufferStore::new(),
              quat_buffer_store_smoothed: QuatBufferStore::new(),
+             gravity_store: GravityStore::new(),
+             external_orientation: ExternalOrientationStore::default(),
+             window: RwLock::new(SmoothingWindow::default()),
              enabled: AtomicBool::new(false),
+             imu_received: AtomicBool::new(false),
+             samples_since_integrate: AtomicU64::new(0),
          }
      }
 
 }
 
 impl LiveState {
+    pub fn set_memory_limits(&self, limits: LiveMemoryLimits) {
+        self.ring.lock().max_samples = limits.imu_samples;
+        self.quat_buffer_store_org.set_max_bytes(limits.quat_store_bytes);
+        self.quat_buffer_store_smoothed.set_max_bytes(limits.quat_store_bytes);
+    }
+
     pub fn enable_live(&self, keep_secs: f64) {
         let keep_us = (keep_secs * 1_000_000.0).round() as i64;
         let mut ring = self.ring.lock();
@@ -441,6 +897,32 @@ impl LiveState {
         self.enabled.load(Ordering::Relaxed)
     }
 
+    /// Use `window` for every quaternion lookup. If the IMU ring keeps less history than the window
+    /// needs, retention is extended (with a warning) so buffers can still cover it.
+    pub fn set_window(&self, window: SmoothingWindow) {
+        let mut ring = self.ring.lock();
+        let required = window.required_retention_us();
+        if window.post_us() > ring.keep_us || required > ring.keep_us {
+            log::warn!("live: smoothing window {window:?} needs {} ms of IMU history, only {} ms kept; extending retention",
+                required / 1000, ring.keep_us / 1000);
+            ring.keep_us = required;
+        }
+        *self.window.write() = window;
+    }
+
+    /// Replace the sensor → video clock mapping.
+    /// Returns the largest timestamp shift of the buffered samples, in µs.
+    pub fn set_sync(&self, sync: LiveClockSync) -> i64 {
+        let ring = self.ring.lock();
+        let mut cur = self.sync.write();
+        let shift = [ring.buf.front(), ring.buf.back()].into_iter().flatten()
+            .map(|s| (sync.to_video_us(s.ts_sensor_us) - cur.to_video_us(s.ts_sensor_us)).abs())
+            .max()
+            .unwrap_or(0);
+        *cur = sync;
+        shift
+    }
+
     pub fn load_quats_from_csv_sliding_windows<P: AsRef<Path>>(
         &self,
         path: P,
@@ -453,3 +935,204 @@ impl LiveState {
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+    use std::time::{Duration, Instant};
+    use crate::gyro_source::GyroSource;
+    use crate::live::{live_manager, LivePipeline, ManualClock};
+
+    fn live_gyro() -> GyroSource {
+        let gyro = GyroSource::new();
+        gyro.enable_live(3.0, 1.0, 0.0, 30.0);
+        gyro
+    }
+
+    #[test]
+    fn robust_clock_sync_ignores_outlier_pairs() {
+        // video = 1.0001 * sensor + 5 ms, pairs every 0.5 s with ±100 µs jitter, every 10th pair off by up to 80 ms
+        let (a, b) = (1.0001, 5_000.0);
+        let pairs: Vec<(i64, i64)> = (0..60_i64).map(|i| {
+            let sensor = 1_000_000_000 + i * 500_000;
+            let jitter = ((i * 37) % 21 - 10) as f64 * 10.0;
+            let outlier = if i % 10 == 3 { if i % 20 == 3 { 80_000.0 } else { -45_000.0 } } else { 0.0 };
+            (sensor, (a * sensor as f64 + b + jitter + outlier).round() as i64)
+        }).collect();
+
+        let mut fit = ClockSyncFit::new();
+        let mut sync = None;
+        for &(s, v) in &pairs {
+            sync = fit.update_from_pair(s, v).or(sync);
+        }
+        let sync = sync.unwrap();
+        assert!((sync.a - a).abs() < 1e-5, "scale {} drifted from {a}", sync.a);
+        for &(s, _) in &pairs {
+            let expected = (a * s as f64 + b).round() as i64;
+            assert!((sync.to_video_us(s) - expected).abs() < 300, "{s}: {} vs {expected}", sync.to_video_us(s));
+        }
+        let ratio = fit.inlier_ratio();
+        assert!(ratio > 0.85 && ratio < 0.95, "inlier ratio {ratio}");
+
+        // Through the gyro source, the fitted mapping becomes the live sync
+        let mut gyro = live_gyro();
+        let mut ratio = None;
+        for &(s, v) in &pairs {
+            ratio = gyro.update_live_clock_sync_from_pair(s, v).or(ratio);
+        }
+        assert_eq!(ratio, gyro.live_clock_sync_inlier_ratio());
+        let live_sync = *gyro.live.read().as_ref().unwrap().sync.read();
+        assert_eq!(live_sync, sync);
+    }
+
+    #[test]
+    fn quat_store_from_csv_samples() {
+        use csv_quats::CsvQuatSample;
+
+        // Yaw ramp of 0.1 rad per 10 ms, written out of order and slightly denormalized like a float export
+        let sample = |t_us: i64, angle: f64| {
+            let (s, c) = (angle / 2.0).sin_cos();
+            CsvQuatSample { t_us, qw: c * 1.001, qx: 0.0, qy: 0.0, qz: s * 1.001 }
+        };
+        let samples = [sample(10_000, 0.1), sample(0, 0.0), sample(30_000, 0.3), sample(20_000, 0.2)];
+
+        let store = QuatBufferStore::from_csv_samples(&samples);
+        let buf = store.get_latest_buffer().expect("nothing published");
+        assert_eq!((buf.first_us, buf.last_us, buf.quats.len()), (0, 30_000, 4));
+
+        for (t_ms, angle) in [(0.0, 0.0), (10.0, 0.1), (15.0, 0.15), (30.0, 0.3)] {
+            let q = buf.quat_at_ms(t_ms).unwrap();
+            assert!((q.angle() - angle).abs() < 1e-7, "t={t_ms}: angle {} != {angle}", q.angle());
+        }
+
+        assert!(QuatBufferStore::from_csv_samples(&[]).get_latest_buffer().is_none());
+    }
+
+    #[test]
+    fn drain_all_merges_overlapping_buffers() {
+        // Buffer `id` tags its quaternions with a rotation of `id` rad, every 10 ms over its span
+        let buffer = |id: f64, from_ms: i64, to_ms: i64, offset_us: i64| {
+            let map: BTreeMap<i64, Quat64> = (from_ms / 10..=to_ms / 10)
+                .map(|i| (i * 10_000 + offset_us, Quat64::from_scaled_axis(Vector3::new(0.0, 0.0, id))))
+                .collect();
+            QuatBuffer::from_btreemap(&map).unwrap()
+        };
+        let store = QuatBufferStore::new();
+        store.publish(buffer(0.1, 0, 3000, 0));
+        store.publish(buffer(0.2, 1000, 4000, 0));
+        // Integrated on a shifted grid, its samples must not interleave with the older ones
+        store.publish(buffer(0.3, 2000, 5000, 5_000));
+
+        let track = store.drain_all();
+        assert!(store.get_latest_buffer().is_none());
+        assert!(store.drain_all().is_empty());
+
+        let id_at = |t: i64| (track[&t].angle() * 10.0).round() as i64;
+        assert!(track.keys().zip(track.keys().skip(1)).all(|(a, b)| a < b));
+        assert_eq!(id_at(0), 1);
+        assert_eq!(id_at(1_000_000), 2);
+        assert_eq!(id_at(2_005_000), 3);
+        assert!(!track.contains_key(&2_010_000) && !track.contains_key(&3_000_000), "older samples left inside the newest span");
+        assert!(track.range(2_005_000..).all(|(t, _)| id_at(*t) == 3));
+        assert_eq!(*track.keys().next_back().unwrap(), 5_005_000);
+        // 0..1000 from the first buffer, 1000..2000 from the second, the rest from the third
+        assert_eq!(track.len(), 100 + 101 + 301);
+    }
+
+    #[test]
+    fn clock_sync_reset_restarts_the_fit() {
+        let mut gyro = live_gyro();
+        // video = 1.0002 * sensor + 40 ms, pairs every 0.5 s
+        let pairs = |a: f64, b: f64| (0..20_i64).map(move |i| {
+            let sensor = 2_000_000_000 + i * 500_000;
+            (sensor, (a * sensor as f64 + b).round() as i64)
+        });
+        for (s, v) in pairs(1.0002, 40_000.0) {
+            gyro.update_live_clock_sync_from_pair(s, v);
+        }
+        let state = gyro.live_clock_sync_state().unwrap();
+        assert!((state.a - 1.0002).abs() < 1e-6, "scale {}", state.a);
+        assert_eq!(state.inliers, 20);
+        assert!(state.residual < 1.0, "residual {} µs", state.residual);
+
+        gyro.reset_live_clock_sync();
+        let reset = gyro.live_clock_sync_state().unwrap();
+        assert_eq!(reset, ClockSyncState::identity());
+        assert_eq!((reset.a, reset.b), (1.0, 0.0));
+
+        // The pairs of the old mapping are gone, the new ones alone make the fit
+        for (s, v) in pairs(0.9999, -15_000.0) {
+            gyro.update_live_clock_sync_from_pair(s, v);
+        }
+        let state = gyro.live_clock_sync_state().unwrap();
+        assert!((state.a - 0.9999).abs() < 1e-6, "scale {}", state.a);
+        assert_eq!(state.inliers, 20);
+        let expected = 0.9999 * 2_000_000_000.0 - 15_000.0;
+        assert!((state.a * 2_000_000_000.0 + state.b - expected).abs() < 2.0);
+    }
+
+    #[test]
+    fn integration_is_skipped_while_the_ring_is_unchanged() {
+        let stab = live_manager();
+        let batch = |from: i64, to: i64| (from..to).map(|i| (LiveImuSample { ts_sensor_us: i * 5_000, gyro: [0.0, 0.0, 0.5], accel: Some([0.0, 0.0, 1.0]) }, i * 5_000)).collect::<Vec<_>>();
+        assert!(!stab.integrate_live_data_if_changed());
+
+        stab.gyro.read().push_live_imu_batch(&batch(0, 100));
+        assert_eq!(stab.gyro.read().live_samples_since_integrate(), 100);
+        assert!(stab.integrate_live_data_if_changed());
+        assert_eq!(stab.gyro.read().live_samples_since_integrate(), 0);
+        assert!(!stab.integrate_live_data_if_changed(), "integrated an unchanged ring");
+
+        stab.gyro.read().push_live_imu_batch(&batch(100, 101));
+        assert!(stab.integrate_live_data_if_changed());
+
+        // The pipeline counts the idle periods it skipped
+        let clock = Arc::new(ManualClock::new(0));
+        stab.use_live_clock(clock.clone());
+        let pipeline = LivePipeline::new(stab.clone(), Some(Duration::from_millis(10)));
+        let deadline = Instant::now() + Duration::from_secs(5);
+        for idle in 1..=3 {
+            clock.advance(Duration::from_millis(10));
+            while pipeline.ingest_stats().idle_integrations < idle {
+                assert!(Instant::now() < deadline, "idle period never skipped");
+                thread::sleep(Duration::from_millis(5));
+            }
+        }
+        assert_eq!(pipeline.ingest_stats().samples, 0);
+    }
+
+    #[test]
+    fn center_ratio_changes_the_selected_buffer() {
+        let stab = live_manager();
+        let buffer = |from_ms: i64, to_ms: i64, yaw: f64| {
+            let quats: TimeQuat = (from_ms..=to_ms).step_by(5).map(|t| (t * 1000, Quat64::from_euler_angles(0.0, 0.0, yaw))).collect();
+            QuatBuffer::from_btreemap(&quats).unwrap()
+        };
+        {
+            let gyro = stab.gyro.read();
+            let live = gyro.live.read();
+            let store = &live.as_ref().unwrap().quat_buffer_store_org;
+            store.publish(buffer(0, 1000, 0.1));
+            store.publish(buffer(500, 1500, 0.2));
+        }
+        let selected_first_us = |t_ms: f64| {
+            let gyro = stab.gyro.read();
+            let window = gyro.live_smoothing_window().unwrap();
+            let live = gyro.live.read();
+            live.as_ref().unwrap().quat_buffer_store_org.get_buffer_for_time(t_ms, &window).map(|b| b.first_us)
+        };
+
+        stab.set_live_buffer_padding(0.0, 100.0);
+        // 600 ms is 100 ms off the middle of the older buffer and 400 ms off the newer one
+        stab.set_live_center_ratio(0.25);
+        assert_eq!(selected_first_us(600.0), Some(0));
+        stab.set_live_center_ratio(1.0);
+        assert_eq!(selected_first_us(600.0), Some(500_000));
+
+        let window = stab.gyro.read().live_smoothing_window().unwrap();
+        assert_eq!(window, SmoothingWindow { pre_ms: 0.0, post_ms: 100.0, center_ratio: 1.0 });
+        // Too much look-ahead for either buffer
+        stab.set_live_buffer_padding(0.0, 1000.0);
+        assert_eq!(selected_first_us(600.0), None);
+    }
+}
@@ -27,6 +27,13 @@ use crate::filesystem;
 pub use live::LiveImuSample;
 pub use live::QuatBuffer;
 pub use live::QuatBufferStore;
+pub use live::GravityBuffer;
+pub use live::ExternalOrientationStore;
+pub use live::SmoothingWindow;
+pub use live::QuatKind;
+pub use live::{ClockSyncFit, ClockSyncState};
+pub use live::OFF_CENTER_CONFIDENCE;
+pub use live::LiveMemoryLimits;
 
 use super::imu_integration::*;
 use super::smoothing::SmoothingAlgorithm;
@@ -34,6 +41,8 @@ use crate::StabilizationParams;
 use std::path::Path;
 
 const DEG2RAD: f64 = std::f64::consts::PI / 180.0;
+/// Clock sync changes that move buffered live IMU samples by more than this (µs) rebuild the quaternion buffers.
+pub const LIVE_SYNC_BACKFILL_US: i64 = 1_000;
 
 pub type Quat64 = UnitQuaternion<f64>;
 pub type TimeIMU = telemetry_parser::util::IMUData;
@@ -102,6 +111,14 @@ pub struct GyroSource {
     #[serde(skip, default)]
     pub live: std::sync::Arc<parking_lot::RwLock<Option<live::LiveState>>>,
 
+    /// Applied to every live session, see `set_live_memory_limits`.
+    #[serde(skip, default)]
+    live_memory_limits: live::LiveMemoryLimits,
+
+    /// Low-pass cutoff of the live gyro samples before integration, see `set_live_gyro_lpf_cutoff_hz`.
+    #[serde(skip, default)]
+    live_gyro_lpf_cutoff_hz: Option<f64>,
+
 }
 
 impl GyroSource {
@@ -155,17 +172,151 @@ impl GyroSource {
         *st = Some(live::LiveState {
             header: make_header(video_fps),              // use actual video FPS
             ring: parking_lot::Mutex::new(live::ImuRing::new((keep_seconds * 1_000_000.0) as i64)),
-            sync: live::LiveClockSync { a, b },
+            sync: RwLock::new(live::LiveClockSync { a, b }),
+            sync_fit: parking_lot::Mutex::new(live::ClockSyncFit::new()),
             quat_buffer_store_org: live::QuatBufferStore::new(),
             quat_buffer_store_smoothed: live::QuatBufferStore::new(),
+            gravity_store: live::GravityStore::new(),
+            external_orientation: Default::default(),
+            window: RwLock::new(live::SmoothingWindow::default()),
             enabled: std::sync::atomic::AtomicBool::new(true),
+            imu_received: std::sync::atomic::AtomicBool::new(false),
+            samples_since_integrate: std::sync::atomic::AtomicU64::new(0),
         });
+        if let Some(st) = st.as_ref() {
+            st.set_memory_limits(self.live_memory_limits);
+        }
+    }
+
+    /// Cap the memory of the live IMU ring and quaternion stores, for this and later live sessions.
+    pub fn set_live_memory_limits(&mut self, limits: live::LiveMemoryLimits) {
+        self.live_memory_limits = limits;
+        if let Some(st) = self.live.read().as_ref() {
+            st.set_memory_limits(limits);
+        }
+    }
+
+    /// Low-pass filter the live gyro samples at `cutoff_hz` before they are integrated (`None` disables it),
+    /// for sensor noise that would otherwise show as jitter of the orientation. Zero phase, see
+    /// `filtering::Lowpass::filter_gyro_zero_phase`. Applies from the next integration.
+    pub fn set_live_gyro_lpf_cutoff_hz(&mut self, cutoff_hz: Option<f64>) {
+        self.live_gyro_lpf_cutoff_hz = cutoff_hz.filter(|hz| *hz > 0.0);
+    }
+
+    pub fn live_gyro_lpf_cutoff_hz(&self) -> Option<f64> { self.live_gyro_lpf_cutoff_hz }
+
+    /// Bytes held by the live IMU ring and by both quaternion stores, `None` when live is off.
+    pub fn live_memory_usage(&self) -> Option<(usize, usize)> {
+        let live = self.live.read();
+        let st = live.as_ref()?;
+        Some((st.ring.lock().bytes(), st.quat_buffer_store_org.bytes() + st.quat_buffer_store_smoothed.bytes()))
     }
 
     pub fn disable_live(&self) {
         *self.live.write() = None;
     }
 
+    /// Padding/centering used by all live quaternion lookups, see `SmoothingWindow`.
+    pub fn set_live_smoothing_window(&self, window: SmoothingWindow) {
+        if let Some(st) = self.live.read().as_ref() {
+            st.set_window(window);
+        }
+    }
+
+    pub fn live_smoothing_window(&self) -> Option<SmoothingWindow> {
+        self.live.read().as_ref().map(|st| *st.window.read())
+    }
+
+    /// Blend over `window_ms` when lookups switch to a newer quaternion buffer, see `QuatBufferStore::set_blend_window_ms`.
+    /// Applies to the current live session.
+    pub fn set_live_buffer_blend(&self, window_ms: f64) {
+        if let Some(st) = self.live.read().as_ref() {
+            st.quat_buffer_store_org.set_blend_window_ms(window_ms);
+            st.quat_buffer_store_smoothed.set_blend_window_ms(window_ms);
+        }
+    }
+
+    /// Update the live sensor → video clock mapping. Buffered IMU samples are kept in the
+    /// sensor clock, so they follow the new mapping; if it moves them by more than
+    /// `LIVE_SYNC_BACKFILL_US`, the published buffers are stale and get rebuilt right away.
+    pub fn set_live_clock_sync(&mut self, a: f64, b: f64) {
+        let shift = match self.live.read().as_ref() {
+            Some(st) => st.set_sync(live::LiveClockSync::new(a, b)),
+            None => return,
+        };
+        if shift > LIVE_SYNC_BACKFILL_US {
+            log::debug!("Live clock sync moved buffered samples by {shift} µs, rebuilding quaternion buffers");
+            if let Some(st) = self.live.read().as_ref() {
+                st.quat_buffer_store_org.clear();
+                st.quat_buffer_store_smoothed.clear();
+                st.gravity_store.clear();
+            }
+            self.integrate_live_data();
+        }
+    }
+
+    /// Feed one (sensor, video) timestamp pair to the robust clock fit and apply the result,
+    /// see `ClockSyncFit`. Returns the fit's inlier ratio, `None` when live is off or the fit isn't ready.
+    pub fn update_live_clock_sync_from_pair(&mut self, sensor_us: i64, video_us: i64) -> Option<f64> {
+        let (sync, ratio) = {
+            let live = self.live.read();
+            let mut fit = live.as_ref()?.sync_fit.lock();
+            let sync = fit.update_from_pair(sensor_us, video_us)?;
+            (sync, fit.inlier_ratio())
+        };
+        if ratio < 0.5 {
+            log::warn!("Live clock sync: only {:.0}% of the timestamp pairs agree with {sync}", ratio * 100.0);
+        }
+        self.set_live_clock_sync(sync.a, sync.b);
+        Some(ratio)
+    }
+
+    /// Confidence in the live orientation of a frame, 0..1, `None` when live is off.
+    ///
+    /// The IMU coverage of the frame in the original and smoothed quaternion buffers (see
+    /// `QuatBufferStore::coverage_at`), times the inlier ratio of the clock fit once it has pairs to fit.
+    /// Low for frames rendered from extrapolated or thinly padded orientation.
+    /// With an external orientation, 1 inside its track and 0 outside.
+    pub fn live_confidence_at_timestamp(&self, timestamp_ms: f64) -> Option<f64> {
+        let corrected_ms = timestamp_ms - self.offset_at_video_timestamp(timestamp_ms);
+        let live = self.live.read();
+        let st = live.as_ref()?;
+        if st.external_orientation.is_active() {
+            return Some(if st.external_orientation.covers(corrected_ms) { 1.0 } else { 0.0 });
+        }
+        let window = *st.window.read();
+        let coverage = st.quat_buffer_store_org.coverage_at(corrected_ms, &window)
+            .min(st.quat_buffer_store_smoothed.coverage_at(corrected_ms, &window));
+        let fit = st.sync_fit.lock();
+        let sync = if fit.len() >= 2 { fit.inlier_ratio() } else { 1.0 };
+        Some(coverage * sync)
+    }
+
+    /// Live sensor → video clock mapping and the state of its fit, `None` when live is off.
+    pub fn live_clock_sync_state(&self) -> Option<live::ClockSyncState> {
+        let live = self.live.read();
+        let st = live.as_ref()?;
+        let sync = *st.sync.read();
+        let fit = st.sync_fit.lock();
+        Some(live::ClockSyncState { a: sync.a, b: sync.b, residual: fit.residual_us(), inliers: fit.inliers() })
+    }
+
+    /// Forget the pairs of the clock fit and go back to the identity mapping, e.g. after a scene cut
+    /// or a camera swap. Later pairs fit the mapping from scratch.
+    pub fn reset_live_clock_sync(&mut self) {
+        match self.live.read().as_ref() {
+            Some(st) => st.sync_fit.lock().clear(),
+            None => return,
+        }
+        log::info!("Live clock sync reset to the identity mapping");
+        self.set_live_clock_sync(1.0, 0.0);
+    }
+
+    /// Inlier ratio of the robust live clock fit, how much the current mapping can be trusted.
+    pub fn live_clock_sync_inlier_ratio(&self) -> Option<f64> {
+        self.live.read().as_ref().map(|st| st.sync_fit.lock().inlier_ratio())
+    }
+
     pub fn load_quats_from_file<P: AsRef<Path>>(&self,
         path: P){
         println!("[DEBUG] Loading live quats from file: {:?}", path.as_ref());
@@ -191,11 +342,32 @@ impl GyroSource {
             let new_sample = self.transform_live_sample(sample);
 
             // Now push the transformed IMU into the ring
-            st.ring.lock().push(new_sample, now_video_us, &st.sync);
+            st.ring.lock().push(new_sample, now_video_us, &st.sync.read());
+            st.samples_since_integrate.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        }
+    }
+
+    /// Push a burst of `(sample, now_video_us)` pairs taking the live and ring locks only once.
+    pub fn push_live_imu_batch(&self, batch: &[(live::LiveImuSample, i64)]) {
+        if batch.is_empty() { return; }
+        if let Some(st) = self.live.read().as_ref() {
+            let sync = st.sync.read();
+            let mut ring = st.ring.lock();
+            for &(sample, now_video_us) in batch {
+                ring.push(self.transform_live_sample(sample), now_video_us, &sync);
+            }
+            st.imu_received.store(true, std::sync::atomic::Ordering::Relaxed);
+            st.samples_since_integrate.fetch_add(batch.len() as u64, std::sync::atomic::Ordering::Relaxed);
         }
     }
 
     pub fn integrate_live_data(&mut self) {
+        self.integrate_live_data_with(None);
+    }
+
+    /// `integrate_live_data` with the smoothed orientation from `smoother` (horizon lock, then the algorithm),
+    /// as offline `recompute_smoothness` does. `None` blends neighbouring samples only.
+    pub fn integrate_live_data_with(&mut self, smoother: Option<(&dyn SmoothingAlgorithm, &super::smoothing::horizon::HorizonLock, &crate::ComputeParams)>) {
     // 0) Live enabled?
     let live_opt = self.live.read();
     if live_opt.is_none() {
@@ -203,25 +375,30 @@ impl GyroSource {
     }
     // 1) Copy IMU window out of ring (no heavy work under lock)
     let live_state = live_opt.as_ref().unwrap();
+    // Reset before the snapshot: samples pushed meanwhile count towards the next integration
+    live_state.samples_since_integrate.store(0, std::sync::atomic::Ordering::Relaxed);
     let samples = {
         let ring = live_state.ring.lock();
-        ring.snapshot()
+        ring.snapshot(&live_state.sync.read())
     }; // lock released
 
      
     if samples.is_empty() {
-        log::warn!("No IMU samples available for live integration");
+        // Before the first sample this is the `NoImu` case, not worth a warning every integration
+        if live_state.imu_received.load(std::sync::atomic::Ordering::Relaxed) {
+            log::warn!("No IMU samples available for live integration");
+        }
         return;
     }
     //println!("Integrating {} live IMU samples", samples.len());
 
     // 2) Convert to TimeIMU (telemetry_parser::IMUData)
     let mut imu_data_vec: Vec<TimeIMU> = Vec::with_capacity(samples.len());
-    for s in &samples {
+    for (s, accel) in samples.iter().zip(live::held_accels(&samples)) {
         let mut imu_point = TimeIMU::default();
         imu_point.timestamp_ms = s.ts_sensor_us as f64 / 1000.0;
         imu_point.gyro  = Some(s.gyro);   // if s.gyro is [f32;3], cast to f64 here
-        imu_point.accl  = s.accel;        // same note as above
+        imu_point.accl  = Some(accel);    // gaps hold the previous reading
         imu_data_vec.push(imu_point);
     }
 
@@ -230,6 +407,12 @@ impl GyroSource {
     println!("Live IMU data timestamps: {:.3} ms to {:.3} ms", start_ms, end_ms);
     let duration_ms = end_ms - start_ms;
     //println!("Live IMU data duration: {:.3} ms", duration_ms);
+    if let Some(cutoff_hz) = self.live_gyro_lpf_cutoff_hz.filter(|_| duration_ms > 0.0) {
+        let sample_rate = (imu_data_vec.len() - 1) as f64 / (duration_ms / 1000.0);
+        if let Err(e) = super::filtering::Lowpass::filter_gyro_zero_phase(cutoff_hz, sample_rate, &mut imu_data_vec) {
+            crate::live::LOG_THROTTLE.warn("gyro_source: live lpf", format_args!("Live gyro low-pass at {cutoff_hz} Hz failed ({sample_rate:.0} Hz samples): {e:?}"));
+        }
+    }
     // 3) Integrate → quats (sorted by timestamp)
     let quat_map: TimeQuat = match self.integration_method {
         
@@ -245,17 +428,24 @@ impl GyroSource {
         }
     };
     //println!("Integrated {} quaternions from live IMU data", quat_map.len());
-    // 4) Build a tiny smoothed map (example: blend last with previous)
-    let mut smoothed_quat_map = BTreeMap::new();
-    let mut prev_opt: Option<(&i64, &Quat64)> = None;
-
-    for (ts, q) in &quat_map {
-        if let Some((_, prev_q)) = prev_opt {
-            let blended = prev_q.slerp(&q, 0.5);
-            smoothed_quat_map.insert(*ts, blended);
+    // 4) Smoothed map: the selected algorithm, or a tiny blend of last with previous
+    let smoothed_quat_map = if let Some((alg, horizon_lock, compute_params)) = smoother {
+        let mut locked = quat_map.clone();
+        horizon_lock.lock(&mut locked, &quat_map, &self.file_metadata.read().gravity_vectors, self.use_gravity_vectors, self.integration_method, compute_params);
+        alg.smooth(&locked, duration_ms, compute_params)
+    } else {
+        let mut smoothed_quat_map = BTreeMap::new();
+        let mut prev_opt: Option<(&i64, &Quat64)> = None;
+
+        for (ts, q) in &quat_map {
+            if let Some((_, prev_q)) = prev_opt {
+                let blended = prev_q.slerp(&q, 0.5);
+                smoothed_quat_map.insert(*ts, blended);
+            }
+            prev_opt = Some((ts, q));
         }
-        prev_opt = Some((ts, q));
-    }
+        smoothed_quat_map
+    };
     //println!("Smoothed {} quaternions for live data", smoothed_quat_map.len());
 
     // 5) Convert both to QuatBuffer (use your associated function)
@@ -266,6 +456,9 @@ impl GyroSource {
     if let Some(st) = self.live.read().as_ref() {
         st.quat_buffer_store_org.publish(buf_org.unwrap());
         st.quat_buffer_store_smoothed.publish(buf_smoothed.unwrap());
+        if let Some(grav) = live::GravityBuffer::from_samples(&samples) {
+            st.gravity_store.publish(grav);
+        }
         //println!("published live quat buffers");
     }
     //println!("Finished integrating live IMU data");
@@ -725,6 +918,16 @@ impl GyroSource {
         }
     }
 
+    /// IMU samples pushed since the last `integrate_live_data`, 0 when live is off.
+    pub fn live_samples_since_integrate(&self) -> u64 {
+        self.live.read().as_ref().map_or(0, |st| st.samples_since_integrate.load(std::sync::atomic::Ordering::Relaxed))
+    }
+
+    /// Whether an IMU sample reached the live ring since `enable_live`.
+    pub fn live_imu_received(&self) -> bool {
+        self.live.read().as_ref().is_some_and(|st| st.imu_received.load(std::sync::atomic::Ordering::Relaxed))
+    }
+
     pub fn is_live_enabled(&self) -> bool {
         let guard = self.live.read();
         if let Some(ref state) = *guard {
@@ -1069,14 +1272,14 @@ impl GyroSource {
 
     // Try live path first (if enabled)
     if let Some(st) = self.live.read().as_ref() {
-        // tuning knobs
-        const PRE_MS: f64 = 0.0;
-        const POST_MS: f64 = 500.0;
-        const CENTER_RATIO: f64 = 0.25;
-
+        // An external tracker gives the whole correction, see `ExternalOrientationStore`
+        if st.external_orientation.is_active() {
+            return Quat64::identity();
+        }
+        let window = *st.window.read();
         if let Some(q) = st
             .quat_buffer_store_org
-            .get_quat_at_time(corrected_ms, PRE_MS, POST_MS, CENTER_RATIO)
+            .get_quat_at_time(corrected_ms, &window)
         {
             return q;
         }
@@ -1086,17 +1289,23 @@ impl GyroSource {
     self.quat_at_timestamp(&self.quaternions, timestamp_ms)
 }
 
+/// Live gravity direction (unit vector, IMU frame) at a video timestamp, `None` without accelerometer data.
+pub fn live_gravity_at_timestamp(&self, timestamp_ms: f64) -> Option<Vector3<f64>> {
+    let corrected_ms = timestamp_ms - self.offset_at_video_timestamp(timestamp_ms);
+    self.live.read().as_ref()?.gravity_store.get_gravity_at_time(corrected_ms)
+}
+
 pub fn smoothed_quat_at_timestamp(&self, timestamp_ms: f64) -> Quat64 {
     let corrected_ms = timestamp_ms - self.offset_at_video_timestamp(timestamp_ms);
 
     if let Some(st) = self.live.read().as_ref() {
-        const PRE_MS: f64 = 0.0;
-        const POST_MS: f64 = 500.0;
-        const CENTER_RATIO: f64 = 0.25;
-
+        if let Some(q) = st.external_orientation.get_quat_at_time(corrected_ms) {
+            return q;
+        }
+        let window = *st.window.read();
         if let Some(q) = st
             .quat_buffer_store_smoothed
-            .get_quat_at_time(corrected_ms, PRE_MS, POST_MS, CENTER_RATIO)
+            .get_quat_at_time(corrected_ms, &window)
         {
             return q;
         }
@@ -320,6 +320,29 @@ impl LensProfile {
             mat
         }
     }
+    /// `input_horizontal_stretch` and `input_vertical_stretch`, 1.0 when unset.
+    pub fn input_stretch(&self) -> (f64, f64) {
+        (if self.input_horizontal_stretch > 0.01 { self.input_horizontal_stretch } else { 1.0 },
+         if self.input_vertical_stretch   > 0.01 { self.input_vertical_stretch   } else { 1.0 })
+    }
+    /// Ratios from the calibration resolution to `size`, input stretch included.
+    pub fn calib_scale(&self, size: (usize, usize)) -> (f64, f64) {
+        let (calib_width, calib_height) = if self.calib_dimension.w > 0 && self.calib_dimension.h > 0 {
+            (self.calib_dimension.w as f64, self.calib_dimension.h as f64)
+        } else {
+            (size.0.max(1) as f64, size.1.max(1) as f64)
+        };
+        let (stretch_x, stretch_y) = self.input_stretch();
+        ((size.0 as f64 / calib_width) * stretch_x, (size.1 as f64 / calib_height) * stretch_y)
+    }
+    /// Scale a camera matrix in calibration pixels (`get_camera_matrix`) to a video of `size`.
+    pub fn scale_camera_matrix(&self, mat: &mut nalgebra::Matrix3<f64>, size: (usize, usize)) {
+        let (ratiox, ratioy) = self.calib_scale(size);
+        mat[(0, 0)] *= ratiox;
+        mat[(1, 1)] *= ratioy;
+        mat[(0, 2)] *= ratiox;
+        mat[(1, 2)] *= ratioy;
+    }
     pub fn get_distortion_coeffs(&self) -> [f64; 12] {
         let mut ret = [0.0; 12];
         for (i, x) in self.fisheye_params.distortion_coeffs.iter().enumerate() {
@@ -26,6 +26,7 @@ pub mod util;
 pub mod stabilization_params;
 
 pub mod stmap_live;
+pub mod live;
 
 use std::sync::{ Arc, atomic::{ AtomicU64, AtomicBool, Ordering::SeqCst } };
 use std::collections::BTreeMap;
@@ -114,6 +115,21 @@ pub struct StabilizationManager {
     pub params: Arc<RwLock<StabilizationParams>>,
 
     pub sync_data: Arc<RwLock<SyncData>>,
+
+    pub live_crop_stats: Arc<live::crop::LiveCropStats>,
+    pub live_rotation_guard: Arc<live::RotationSpikeGuard>,
+    pub live_latency_stats: Arc<live::latency::LatencySlaStats>,
+    pub live_confidence_stats: Arc<live::LiveConfidenceStats>,
+    pub live_memory_gauges: Arc<live::memory::MemoryGauges>,
+    pub live_session_stats: Arc<live::LiveSessionStats>,
+    pub live_render_timing: Arc<live::RenderTimingStats>,
+    pub live_e2e_latency: Arc<live::EndToEndLatencyStats>,
+    pub live_latest_stmap: Arc<live::LatestStmap>,
+    pub live_latest_frame: Arc<live::LatestFramePublisher>,
+    pub live_param_log: Arc<RwLock<Option<live::param_log::ParamLog>>>,
+    pub live_clock: Arc<RwLock<live::SharedClock>>,
+    pub live_lens_presets: Arc<live::LensPresets>,
+    pub live_latency_profile: Arc<live::LatencyProfileCell>,
 }
 
 impl Default for StabilizationManager {
@@ -152,6 +168,21 @@ impl Default for StabilizationManager {
             camera_id: Arc::new(RwLock::new(None)),
 
             sync_data: Arc::new(RwLock::new(SyncData::default())),
+
+            live_crop_stats: Arc::new(live::crop::LiveCropStats::default()),
+            live_rotation_guard: Arc::new(live::RotationSpikeGuard::default()),
+            live_latency_stats: Arc::new(live::latency::LatencySlaStats::default()),
+            live_confidence_stats: Arc::new(live::LiveConfidenceStats::default()),
+            live_memory_gauges: Arc::new(live::memory::MemoryGauges::default()),
+            live_session_stats: Arc::new(live::LiveSessionStats::default()),
+            live_render_timing: Arc::new(live::RenderTimingStats::default()),
+            live_e2e_latency: Arc::new(live::EndToEndLatencyStats::default()),
+            live_latest_stmap: Arc::new(live::LatestStmap::default()),
+            live_latest_frame: Arc::new(live::LatestFramePublisher::default()),
+            live_param_log: Arc::new(RwLock::new(None)),
+            live_clock: Arc::new(RwLock::new(Arc::new(live::MonotonicClock::new()))),
+            live_lens_presets: Arc::new(live::LensPresets::default()),
+            live_latency_profile: Arc::new(live::LatencyProfileCell::default()),
         }
     }
 }
@@ -350,6 +381,73 @@ impl StabilizationManager {
 
 
 
+    /// Resolve a lens profile sent by a live source: either a name/id looked up in the lens DB,
+    /// or a full profile embedded as a JSON object. Returns `false` if nothing was applied.
+    fn load_live_lens_profile(&self, lens: &serde_json::Value) -> bool {
+        let mut l = self.lens.write();
+        if let Some(lens_str) = lens.as_str() {
+            // Lens by name → resolve from lens DB
+            let mut db = self.lens_profile_db.read();
+            if !db.loaded {
+                drop(db);
+                {
+                    let mut db = self.lens_profile_db.write();
+                    db.load_all();
+                }
+                db = self.lens_profile_db.read();
+            }
+            if let Some(found) = db.find(lens_str) {
+                *l = found.clone();
+                return true;
+            }
+        } else if lens.is_object() {
+            // Lens embedded as JSON object in metadata
+            l.load_from_json_value(lens);
+            // In file mode we do: l.path_to_file = filesystem::url_to_path(url);
+            // For live, we usually don't have a file path, so we skip that.
+            let db = self.lens_profile_db.read();
+            l.resolve_interpolations(&db);
+            return true;
+        }
+        false
+    }
+
+    /// Check that the current lens profile can drive the live undistortion.
+    /// Insta360 needs all 6 coefficients (k1, k2, k3, p1, p2, xi), a shorter list would silently
+    /// read zeros in the kernel and produce a wrong mapping.
+    fn validate_live_lens(&self) -> Result<(), GyroflowCoreError> {
+        let lens = self.lens.read();
+        let model = stabilization::distortion_models::DistortionModel::from_name(lens.distortion_model.as_deref().unwrap_or("opencv_fisheye"));
+        if model.id() == "insta360" {
+            let n = lens.fisheye_params.distortion_coeffs.len();
+            if n < 6 {
+                log::error!("live: insta360 lens '{}' has {n} distortion coefficients, expected 6 (k1, k2, k3, p1, p2, xi)", lens.name);
+                return Err(GyroflowCoreError::InvalidData);
+            }
+        }
+        if lens.fisheye_params.camera_matrix.len() != 3 {
+            log::error!("live: lens '{}' has no valid camera matrix", lens.name);
+            return Err(GyroflowCoreError::InvalidData);
+        }
+        Self::check_live_lens_resolution(&lens, self.params.read().size);
+        Ok(())
+    }
+
+    /// The camera matrix is in pixels of the calibration resolution, `FrameTransform` scales it to the
+    /// stream (`LensProfile::scale_camera_matrix`, as offline). That only holds for the same sensor area,
+    /// so a different aspect ratio (another sensor mode or crop) is warned about.
+    fn check_live_lens_resolution(lens: &LensProfile, size: (usize, usize)) {
+        let (calib_w, calib_h) = (lens.calib_dimension.w, lens.calib_dimension.h);
+        if calib_w == 0 || calib_h == 0 || size.0 == 0 || size.1 == 0 || (calib_w, calib_h) == size { return; }
+        let (sx, sy) = (size.0 as f64 / calib_w as f64, size.1 as f64 / calib_h as f64);
+        if (sx / sy - 1.0).abs() > 0.01 {
+            log::warn!("live: lens '{}' is calibrated at {calib_w}x{calib_h}, the stream is {}x{} with a different aspect ratio; scaling it by {sx:.3}x{sy:.3} likely gives a wrong correction",
+                lens.name, size.0, size.1);
+        } else {
+            log::info!("live: lens '{}' calibrated at {calib_w}x{calib_h}, scaled by {sx:.3} to {}x{}", lens.name, size.0, size.1);
+        }
+    }
+
     pub fn start_single_stream(&self, 
         metadata: FileMetadata,
         keep_secs: f64,   // e.g., 3.0
@@ -360,9 +458,20 @@ impl StabilizationManager {
         p: &Path,
         load_path: bool
     )  -> Result<(), GyroflowCoreError> {
-        // Initialize the gyro source
         println!("[DEBUG] [start_single_stream]");
-        let fps = self.params.read().fps;
+        //  Apply metadata to the stabilizer params
+        let fps = {
+            let mut params = self.params.write();
+            params.frame_readout_time = metadata.frame_readout_time.unwrap_or_default();
+            params.frame_readout_direction = metadata.frame_readout_direction;
+            params.fps = metadata.frame_rate.unwrap_or(params.fps);
+            params.size = size;
+            params.output_size = output_size;
+            //no need for frame count
+            params.frame_count = 0;
+            params.fps
+        };
+        // Initialize the gyro source
         {
             let mut gyro = self.gyro.write();
             gyro.clear();
@@ -376,22 +485,12 @@ impl StabilizationManager {
             
         }
 
-        //  Apply metadata to the stabilizer params
-        let mut params = self.params.write();
-        params.frame_readout_time = metadata.frame_readout_time.unwrap_or_default();
-        params.frame_readout_direction = metadata.frame_readout_direction;
-        params.fps = metadata.frame_rate.unwrap_or(params.fps);
-        params.size = size;
-        params.output_size = output_size;
-        //no need for frame count
-        params.frame_count = 0;
-
         // Apply lens and camera info
-       /*  if let Some(lens_json) = &self.gyro.read().file_metadata.read().lens_profile {
-            let mut lens = self.lens.write();
-            lens.load_from_json_value(&lens_json);
-            lens.resolve_interpolations(&self.lens_profile_db.read());
-        }else{*/
+        let lens_from_stream = metadata.lens_profile.as_ref().map(|l| self.load_live_lens_profile(l)).unwrap_or_default();
+        if lens_from_stream {
+            let profile = self.lens.read().choose_for(size.0, size.1, fps);
+            self.lens.write().clone_from(&profile);
+        } else {
             let mut db = LensProfileDatabase::default();
             db.load_all();
             db.prepare_list_for_ui();
@@ -418,9 +517,14 @@ impl StabilizationManager {
             //println!("camera matrix after live load: {:?}", self.lens.read().get_camera_matrix(size, false));
             //println!("lens: {}", self.lens.read().get_json().unwrap())
             //self.lens.write().resolve_interpolations(&db);
-            
+            }
+        }
 
+        // Model override from the stream header, e.g. `distortion_model,insta360`
+        if let Some(model) = metadata.additional_data.get("distortion_model").and_then(|v| v.as_str()) {
+            self.lens.write().distortion_model = Some(model.to_string());
         }
+        self.validate_live_lens()?;
 
         // Reset internal states
         //self.invalidate_smoothing();
@@ -429,6 +533,86 @@ impl StabilizationManager {
         Ok(())
     }
 
+    /// Apply a header re-sent during a live stream (reconnect, lens change). Unlike `start_single_stream`
+    /// this keeps the IMU ring and the published buffers; only the lens, readout and frame rate change.
+    pub fn update_live_metadata(&self, metadata: FileMetadata) -> Result<(), GyroflowCoreError> {
+        {
+            let mut gyro = self.gyro.write();
+            gyro.imu_transforms.imu_orientation = metadata.imu_orientation.clone();
+            gyro.file_metadata = ReadOnlyFileMetadata::from(metadata.clone());
+        }
+
+        let (size, fps) = {
+            let mut params = self.params.write();
+            if let Some(t) = metadata.frame_readout_time {
+                params.frame_readout_time = t;
+            }
+            params.frame_readout_direction = metadata.frame_readout_direction;
+            if let Some(fps) = metadata.frame_rate {
+                if (fps - params.fps).abs() > 0.001 {
+                    log::info!("live: frame rate changed {} -> {fps}", params.fps);
+                }
+                params.fps = fps;
+            }
+            (params.size, params.fps)
+        };
+
+        if let Some(lens) = metadata.lens_profile.as_ref() {
+            if self.load_live_lens_profile(lens) {
+                let profile = self.lens.read().choose_for(size.0, size.1, fps);
+                self.lens.write().clone_from(&profile);
+                log::info!("live: lens profile changed to '{}'", self.lens.read().get_display_name());
+            }
+        }
+        if let Some(model) = metadata.additional_data.get("distortion_model").and_then(|v| v.as_str()) {
+            self.lens.write().distortion_model = Some(model.to_string());
+        }
+        self.validate_live_lens()?;
+
+        self.recompute_undistortion();
+        Ok(())
+    }
+
+    /// Replace the lens of a running stream, e.g. from `live::LensProfileWatcher`. The variant
+    /// matching the stream is picked as for header lenses; a profile failing the live lens checks
+    /// is rejected and the previous one kept.
+    pub fn reload_live_lens_profile(&self, lens: LensProfile) -> Result<(), GyroflowCoreError> {
+        let (size, fps) = {
+            let params = self.params.read();
+            (params.size, params.fps)
+        };
+        let mut lens = lens.choose_for(size.0, size.1, fps);
+        lens.resolve_interpolations(&self.lens_profile_db.read());
+        let previous = std::mem::replace(&mut *self.lens.write(), lens);
+        if let Err(e) = self.validate_live_lens() {
+            *self.lens.write() = previous;
+            return Err(e);
+        }
+        self.live_lens_presets.set_active(None);
+        log::info!("live: lens profile changed to '{}'", self.lens.read().get_display_name());
+        self.recompute_undistortion();
+        Ok(())
+    }
+
+    /// Live: register `profile` as lens preset `name` for `activate_live_lens_preset`, replacing one of the same name.
+    pub fn add_live_lens_preset(&self, name: &str, profile: LensProfile) {
+        self.live_lens_presets.insert(name, profile);
+    }
+
+    /// Live: switch the running stream to lens preset `name`. The distortion model, coefficients and
+    /// camera matrix change in one swap, as in `reload_live_lens_profile`, and the radial distortion
+    /// limit is recomputed for the preset's model, so the next frame renders entirely with the new lens.
+    pub fn activate_live_lens_preset(&self, name: &str) -> Result<(), GyroflowCoreError> {
+        let Some(mut lens) = self.live_lens_presets.get(name) else {
+            log::error!("live: no lens preset '{name}', have: {}", self.live_lens_presets.names().join(", "));
+            return Err(GyroflowCoreError::InvalidData);
+        };
+        lens.init();
+        self.reload_live_lens_profile(lens)?;
+        self.live_lens_presets.set_active(Some(name));
+        Ok(())
+    }
+
     pub fn live_on_new_frame(&self, frame_idx: usize, now_ms: f64, recompute_period: usize) {
         // keep params timeline in sync
         {
@@ -437,6 +621,20 @@ impl StabilizationManager {
             p.duration_ms = now_ms.max(p.duration_ms);
         }
 
+        let ts_us = (now_ms * 1000.0).round() as i64;
+        live::trace::trace_frame(&self.gyro.read(), frame_idx, ts_us);
+        self.live_latest_stmap.rendered(frame_idx, ts_us);
+
+        // The rotation guard follows the rendered frames, whatever else asks for transforms meanwhile
+        let (max_rotation_delta_deg, orientation) = {
+            let p = self.params.read();
+            (p.live_max_rotation_delta_deg, p.live_preview_orientation)
+        };
+        if let Some(max_delta_deg) = max_rotation_delta_deg {
+            let quat = stabilization::FrameTransform::preview_quat(orientation, &self.gyro.read(), now_ms);
+            self.live_rotation_guard.limit(now_ms, quat, max_delta_deg);
+        }
+
         // update IMU transforms if GUI changed
         // self.recompute_gyro();  // only if needed
 
@@ -461,29 +659,7 @@ impl StabilizationManager {
     // except we don't have a real URL or a file on disk.
         // --- 1) Lens profile ---
         if let Some(ref lens) = md.lens_profile {
-            let mut l = self.lens.write();
-            if let Some(lens_str) = lens.as_str() {
-                // Lens by name → resolve from lens DB
-                let mut db = self.lens_profile_db.read();
-                if !db.loaded {
-                    drop(db);
-                    {
-                        let mut db = self.lens_profile_db.write();
-                        db.load_all();
-                    }
-                    db = self.lens_profile_db.read();
-                }
-                if let Some(found) = db.find(lens_str) {
-                    *l = found.clone();
-                }
-            } else if lens.is_object() {
-                // Lens embedded as JSON object in metadata
-                l.load_from_json_value(lens);
-                // In file mode we do: l.path_to_file = filesystem::url_to_path(url);
-                // For live, we usually don't have a file path, so we skip that.
-                let db = self.lens_profile_db.read();
-                l.resolve_interpolations(&db);
-            }
+            self.load_live_lens_profile(lens);
         }
         println!("step 2 live load gyro info");
         // --- 2) FPS override from metadata, if it doesn't match current params ---
@@ -1053,6 +1229,9 @@ impl StabilizationManager {
                     }
                 }
             }
+            if p.live_overlay != live::LiveOverlay::None {
+                p.live_overlay.draw(drawing, p.output_size, y_inverted);
+            }
             if !p.zooming_debug_points.is_empty() {
                 if let Some((_, points)) = p.zooming_debug_points.range(timestamp_us - 1000..).next() {
                     for i in 0..points.len() {
@@ -1081,6 +1260,7 @@ impl StabilizationManager {
     pub fn process_pixels<T: PixelType>(&self, mut timestamp_us: i64, frame: Option<usize>, buffers: &mut Buffers) -> Result<stabilization::ProcessedInfo, GyroflowCoreError> {
         if let gpu::BufferSource::Cpu { buffer } = &buffers.input.data  { if buffer.is_empty() { return Err(GyroflowCoreError::InputBufferEmpty); } }
         if let gpu::BufferSource::Cpu { buffer } = &buffers.output.data { if buffer.is_empty() { return Err(GyroflowCoreError::OutputBufferEmpty); } }
+        buffers.check_disjoint()?;
 
         let (offset, fps) = {
             let params = self.params.read();
@@ -1121,7 +1301,12 @@ impl StabilizationManager {
         }
 
         if let Some(undist) = self.stabilization.try_read_for(std::time::Duration::from_millis(30000)) {
-            undist.process_pixels::<T>(timestamp_us, frame, buffers, None)
+            let mut info = undist.process_pixels::<T>(timestamp_us, frame, buffers, None)?;
+            info.confidence = self.gyro.read().live_confidence_at_timestamp(timestamp_us as f64 / 1000.0);
+            if let Some(c) = info.confidence {
+                self.live_confidence_stats.record(c);
+            }
+            Ok(info)
         } else {
             Err(GyroflowCoreError::Unknown)
         }
@@ -1158,7 +1343,7 @@ impl StabilizationManager {
     pub fn set_fov                   (&self, v: f64)  { self.params.write().fov                    = v; }
     pub fn set_fov_overview          (&self, v: bool) { self.params.write().fov_overview           = v; }
     pub fn set_show_safe_area        (&self, v: bool) { self.params.write().show_safe_area         = v; }
-    pub fn set_lens_correction_amount(&self, v: f64)  { self.params.write().lens_correction_amount = v; self.invalidate_zooming(); }
+    pub fn set_lens_correction_amount(&self, v: f64)  { { let mut p = self.params.write(); p.lens_correction_amount = v; p.live_lens_correction_ramp = None; } self.invalidate_zooming(); }
     pub fn set_frame_offset          (&self, v: i32)  { self.params.write().frame_offset           = v; }
     pub fn set_light_refraction_coefficient(&self, v: f64) { self.params.write().light_refraction_coefficient = v; self.invalidate_zooming(); }
     pub fn set_background_color      (&self, bg: Vector4<f32>) { self.params.write().background = bg; }
@@ -1378,13 +1563,366 @@ impl StabilizationManager {
         self.params.write().framebuffer_inverted = false;
         self.params.write().fov_overview = false;
         self.params.write().show_safe_area = false;
-        self.stabilization.write().kernel_flags.set(KernelParamsFlags::DRAWING_ENABLED, false);
+        let live_overlay = self.params.read().live_overlay;
+        self.stabilization.write().kernel_flags.set(KernelParamsFlags::DRAWING_ENABLED, live_overlay != live::LiveOverlay::None);
         self.set_size(size.0, size.1);
         self.set_output_size(output_size.0, output_size.1);
+        let live_output_size = self.params.read().live_output_size;
+        if let Some((w, h)) = live_output_size {
+            self.set_live_output_size(w, h);
+        }
+
+        self.recompute_undistortion();
+    }
+
+    /// Live: size of the full stabilized output (kernel `output_width`/`output_height`).
+    /// Unlike `set_output_size` it's not fitted to the input aspect ratio and it survives `set_render_params`.
+    pub fn set_live_output_size(&self, width: usize, height: usize) {
+        if width == 0 || height == 0 {
+            log::warn!("live: ignoring output size {width}x{height}");
+            return;
+        }
+        self.log_live_param("output_size", serde_json::json!([width, height]));
+        {
+            let mut params = self.params.write();
+            params.live_output_size = Some((width, height));
+            params.output_size = (width, height);
+        }
+        self.init_size();
+        self.clamp_live_output_rect();
+    }
+
+    /// Live: only write the `(x, y, w, h)` region of the stabilized output to the render buffer,
+    /// e.g. a centered 9:16 column out of a 16:9 frame. Use `set_zooming_center_x/y` to pan the crop within the output.
+    /// The render buffer size becomes `(w, h)`, see `live_output_buffer_size`.
+    pub fn set_live_output_rect(&self, x: usize, y: usize, w: usize, h: usize) {
+        if w == 0 || h == 0 {
+            log::warn!("live: ignoring empty output rect");
+            return;
+        }
+        self.log_live_param("output_rect", serde_json::json!([x, y, w, h]));
+        {
+            let mut stab = self.stabilization.write();
+            stab.output_crop = Some((x, y, w, h));
+            stab.clear_stab_data();
+        }
+        self.clamp_live_output_rect();
+    }
+
+    pub fn clear_live_output_rect(&self) {
+        self.log_live_param("output_rect", serde_json::Value::Null);
+        let mut stab = self.stabilization.write();
+        stab.output_crop = None;
+        stab.clear_stab_data();
+    }
+
+    fn clamp_live_output_rect(&self) {
+        let (ow, oh) = self.params.read().output_size;
+        if ow == 0 || oh == 0 { return; }
+        let mut stab = self.stabilization.write();
+        if let Some((x, y, w, h)) = stab.output_crop {
+            let w = w.min(ow);
+            let h = h.min(oh);
+            let clamped = (x.min(ow - w), y.min(oh - h), w, h);
+            if clamped != (x, y, w, h) {
+                log::warn!("live: output rect {:?} doesn't fit in {ow}x{oh}, clamped to {clamped:?}", (x, y, w, h));
+                stab.output_crop = Some(clamped);
+                stab.clear_stab_data();
+            }
+        }
+    }
+
+    /// Live: make the alpha channel of RGBA output a validity mask. Out-of-FOV pixels are filled with the
+    /// background color, so a transparent solid background leaves alpha 0 exactly where there is no real image.
+    /// Input alpha must be opaque for this to hold. Turning it off restores the background it replaced.
+    pub fn set_live_alpha_mask(&self, enabled: bool) {
+        self.log_live_param("alpha_mask", serde_json::json!(enabled));
+        {
+            let mut params = self.params.write();
+            if enabled {
+                if params.live_mask_saved_background.is_none() {
+                    params.live_mask_saved_background = Some((params.background[3], params.background_mode));
+                }
+                params.background[3] = 0.0;
+                params.background_mode = stabilization_params::BackgroundMode::SolidColor;
+            } else if let Some((alpha, mode)) = params.live_mask_saved_background.take() {
+                params.background[3] = alpha;
+                params.background_mode = mode;
+            }
+        }
+        self.undistortion_invalidated.store(true, SeqCst);
+    }
+
+    /// Live: cap how far the output may zoom into the input, in % of the input size (`None` disables the cap).
+    /// The fov is clamped to the cap, and frames whose correction would show borders at that fov
+    /// get a weaker correction instead. See `live_crop_stats` for how often that happens.
+    pub fn set_live_max_crop(&self, max_crop_pct: Option<f64>) {
+        self.log_live_param("max_crop", serde_json::json!(max_crop_pct));
+        self.params.write().live_max_crop = max_crop_pct.map(|x| x.clamp(0.0, 99.0));
+        self.live_crop_stats.reset();
+        self.recompute_undistortion();
+    }
+
+    /// Live: cross-fade between quaternion buffers over `window_ms` when a newer buffer takes over,
+    /// so buffer switches don't show up as a jump in the output. `0` disables it.
+    pub fn set_live_buffer_blend(&self, window_ms: f64) {
+        self.log_live_param("buffer_blend", serde_json::json!(window_ms));
+        self.gyro.read().set_live_buffer_blend(window_ms);
+    }
+
+    /// Live: padding and centering required around a frame's timestamp when picking a quaternion buffer.
+    /// A smaller `post_ms` lowers latency at the cost of look-ahead for smoothing. Applies to the current live session.
+    pub fn set_live_smoothing_window(&self, window: gyro_source::SmoothingWindow) {
+        self.log_live_param("smoothing_window", serde_json::json!({ "pre_ms": window.pre_ms, "post_ms": window.post_ms, "center_ratio": window.center_ratio }));
+        self.gyro.read().set_live_smoothing_window(window);
+    }
+
+    /// Live: how far from the middle of a quaternion buffer a frame may be, as a fraction of half the buffer's span.
+    /// Tighter picks buffers with IMU data on both sides of the frame, smoother but the frame waits longer for one;
+    /// looser takes the newest buffer covering the frame, for lower latency. Keeps the padding of `set_live_smoothing_window`
+    /// and applies from the next lookup on.
+    pub fn set_live_center_ratio(&self, center_ratio: f64) {
+        let Some(window) = self.gyro.read().live_smoothing_window() else { return };
+        self.set_live_smoothing_window(gyro_source::SmoothingWindow { center_ratio: center_ratio.max(0.0), ..window });
+    }
+
+    /// Live: IMU data a quaternion buffer needs before (`pre_ms`) and after (`post_ms`) a frame to be picked for it.
+    /// Keeps the center ratio of `set_live_smoothing_window` and applies from the next lookup on.
+    pub fn set_live_buffer_padding(&self, pre_ms: f64, post_ms: f64) {
+        let Some(window) = self.gyro.read().live_smoothing_window() else { return };
+        self.set_live_smoothing_window(gyro_source::SmoothingWindow { pre_ms: pre_ms.max(0.0), post_ms: post_ms.max(0.0), ..window });
+    }
+
+    /// Live: low-pass cutoff (Hz) of the gyro samples before integration, `None` disables it. Cleans the sensor
+    /// noise out of the input rather than smoothing the orientation; zero phase, so sync isn't affected.
+    /// A cutoff at or above half the IMU rate leaves the samples as they are.
+    pub fn set_live_gyro_lpf_cutoff_hz(&self, cutoff_hz: Option<f64>) {
+        self.log_live_param("gyro_lpf_cutoff_hz", serde_json::json!(cutoff_hz));
+        self.gyro.write().set_live_gyro_lpf_cutoff_hz(cutoff_hz);
+    }
+
+    /// Live: smoothing algorithm that fills the smoothed quaternion store, by name (see `Smoothing::get_names`,
+    /// e.g. "Default", "Plain 3D", "Fixed camera", "No smoothing"). Its parameters and the horizon lock are the
+    /// ones of `smoothing`. Takes effect on the next integration, older buffers are replaced as new ones arrive.
+    pub fn set_live_smoothing_algorithm(&self, name: &str) {
+        self.log_live_param("smoothing_algorithm", serde_json::json!(name));
+        let mut smoothing = self.smoothing.write();
+        let Some(id) = smoothing.get_names().iter().position(|n| n.eq_ignore_ascii_case(name)) else {
+            log::warn!("live: unknown smoothing algorithm {name:?}, available: {:?}", smoothing.get_names());
+            return;
+        };
+        smoothing.set_current(id);
+        let name = smoothing.current().get_name();
+        drop(smoothing);
+        self.params.write().live_smoothing_algorithm = Some(name);
+    }
+
+    /// Live: orientation of the frame at `ts_us` (video clock) from an external tracker, see `ExternalOrientationStore`.
+    /// From the first call on it wins over the IMU quaternions, until `clear_live_external_orientation`.
+    pub fn set_live_external_orientation(&self, ts_us: i64, q: Quat64) {
+        let gyro = self.gyro.read();
+        let live = gyro.live.read();
+        let Some(st) = live.as_ref() else {
+            log::warn!("live: external orientation pushed before live was enabled, ignored");
+            return;
+        };
+        if !st.external_orientation.is_active() {
+            log::info!("live: using the external orientation instead of the IMU");
+        }
+        let keep_us = st.ring.lock().keep_us;
+        st.external_orientation.push(ts_us, q, keep_us);
+    }
+
+    /// Live: back to the IMU orientation after `set_live_external_orientation`.
+    pub fn clear_live_external_orientation(&self) {
+        if let Some(st) = self.gyro.read().live.read().as_ref() {
+            st.external_orientation.clear();
+        }
+    }
+
+    /// Integrate the buffered live IMU samples into the quaternion stores, smoothing them with the
+    /// algorithm of `set_live_smoothing_algorithm` if one was selected.
+    pub fn integrate_live_data(&self) {
+        if self.params.read().live_smoothing_algorithm.is_none() {
+            self.gyro.write().integrate_live_data();
+            return;
+        }
+        // Built before the gyro lock, `from_manager` reads it
+        let compute_params = ComputeParams::from_manager(self);
+        let smoothing = self.smoothing.read();
+        self.gyro.write().integrate_live_data_with(Some((smoothing.current().as_ref(), &smoothing.horizon_lock, &compute_params)));
+    }
+
+    /// `integrate_live_data` unless no IMU sample arrived since the last integration, so an idle
+    /// stream doesn't take the gyro write lock for nothing. Returns whether it integrated.
+    pub fn integrate_live_data_if_changed(&self) -> bool {
+        if self.gyro.read().live_samples_since_integrate() == 0 {
+            return false;
+        }
+        self.integrate_live_data();
+        true
+    }
+
+    /// Live: framing guide (grid, crosshair, safe area) drawn on the stabilized output by the kernel.
+    pub fn set_live_overlay(&self, overlay: live::LiveOverlay) {
+        self.log_live_param("overlay", serde_json::json!(overlay));
+        self.params.write().live_overlay = overlay;
+        let mut stab = self.stabilization.write();
+        stab.live_overlay = overlay != live::LiveOverlay::None;
+        stab.set_drawing_enabled(stab.live_overlay);
+    }
+
+    /// Live: pixel interpolation of the undistortion kernel. Bilinear (the default) is the fastest,
+    /// bicubic / Lanczos4 / EWA are sharper but cost more GPU time per frame.
+    pub fn set_live_interpolation(&self, interpolation: stabilization::Interpolation) {
+        self.log_live_param("interpolation", serde_json::json!(interpolation));
+        self.stabilization.write().interpolation = interpolation;
+    }
 
+    /// Live: level the output to the mount instead of the camera body. `roll`, `pitch` and `yaw` (degrees)
+    /// are a fixed rotation composed into the stabilized orientation, unlike horizon lock it doesn't follow
+    /// the motion. All zero disables it.
+    pub fn set_live_mount_correction(&self, roll: f64, pitch: f64, yaw: f64) {
+        self.log_live_param("mount_correction", serde_json::json!([roll, pitch, yaw]));
+        let correction = (roll != 0.0 || pitch != 0.0 || yaw != 0.0).then_some((roll, pitch, yaw));
+        self.params.write().live_mount_correction = correction;
         self.recompute_undistortion();
     }
 
+    /// Live: render the frames to the camera orientation (`QuatKind::Org`, the raw shake) or to the
+    /// smoothed one (the default), to compare both while tuning. Takes effect from the next frame.
+    pub fn set_live_preview_orientation(&self, kind: gyro_source::QuatKind) {
+        self.log_live_param("preview_orientation", serde_json::json!(kind));
+        self.params.write().live_preview_orientation = kind;
+        self.recompute_undistortion();
+    }
+
+    /// Live: limit how far the applied rotation may move between consecutive frames, in degrees (`None` disables it).
+    /// A frame beyond it keeps the previous frame's rotation, so a corrupt IMU sample can't throw the output
+    /// around for a frame. See `live_rotation_guard` for the spikes caught.
+    pub fn set_live_max_rotation_delta(&self, max_rotation_delta_deg: Option<f64>) {
+        self.log_live_param("max_rotation_delta_deg", serde_json::json!(max_rotation_delta_deg));
+        self.params.write().live_max_rotation_delta_deg = max_rotation_delta_deg.map(|x| x.max(0.0));
+        self.live_rotation_guard.reset();
+        self.recompute_undistortion();
+    }
+
+    /// Live: output while video flows but no IMU sample arrived yet, see `live::NoImu`.
+    pub fn set_live_no_imu_behavior(&self, behavior: live::NoImu) {
+        self.log_live_param("no_imu_behavior", serde_json::json!(behavior));
+        self.params.write().live_no_imu = behavior;
+    }
+
+    /// Live: wait strategy of the IMU consumer, the STMap worker and the render loop, see `live::LatencyProfile`.
+    pub fn set_live_latency_profile(&self, profile: live::LatencyProfile) {
+        self.log_live_param("latency_profile", serde_json::json!(profile));
+        self.params.write().live_latency_profile = profile;
+        self.live_latency_profile.set(profile);
+    }
+
+    /// Live: the `NoImu` behavior in effect, `None` once IMU samples arrived.
+    pub fn live_no_imu_action(&self) -> Option<live::NoImu> {
+        if self.gyro.read().live_imu_received() { return None; }
+        Some(self.params.read().live_no_imu)
+    }
+
+    /// Live: lens correction amount, 1.0 is the full correction and 0.0 the uncorrected lens geometry (at the same fov).
+    /// With `ramp_ms` > 0 the amount in effect moves linearly to `amount` over that much stream time, starting at
+    /// the newest frame, so the correction can be faded in or out without a jump.
+    pub fn set_live_lens_correction_amount(&self, amount: f64, ramp_ms: f64) {
+        self.log_live_param("lens_correction_amount", serde_json::json!({ "amount": amount, "ramp_ms": ramp_ms }));
+        let amount = amount.clamp(0.0, 1.0);
+        {
+            let mut params = self.params.write();
+            let start_ms = params.duration_ms;
+            let from = params.live_lens_correction_ramp.map(|r| r.amount_at(start_ms)).unwrap_or(params.lens_correction_amount);
+            params.lens_correction_amount = amount;
+            params.live_lens_correction_ramp = (ramp_ms > 0.0 && from != amount).then_some(live::LensCorrectionRamp { from, to: amount, start_ms, duration_ms: ramp_ms });
+        }
+        self.recompute_undistortion();
+    }
+
+    /// Live: digital zoom (crop factor) of the camera, same as the header's `digital_zoom`; 1.0 for none.
+    /// Applied as a longer focal length of the lens profile, i.e. the frame is a center crop of the
+    /// calibrated sensor area, so the undistortion of the zoomed frame still matches the lens.
+    pub fn set_live_digital_zoom(&self, zoom: f64) {
+        self.log_live_param("digital_zoom", serde_json::json!(zoom));
+        let zoom = (zoom > 0.0 && (zoom - 1.0).abs() > 1e-6).then_some(zoom);
+        let changed = {
+            let mut gyro = self.gyro.write();
+            let changed = gyro.file_metadata.read().digital_zoom != zoom;
+            gyro.file_metadata.set_digital_zoom(zoom);
+            changed
+        };
+        if changed {
+            self.recompute_undistortion();
+        }
+    }
+
+    pub fn live_digital_zoom(&self) -> f64 {
+        self.gyro.read().file_metadata.read().digital_zoom.filter(|z| *z > 0.0).unwrap_or(1.0)
+    }
+
+    /// Live: optical flow method used for the frames detected from now on. Frames of the previous
+    /// method that are still waiting for their pose are dropped, the estimated ones are kept.
+    pub fn set_live_of_method(&self, method: synchronization::OfMethod) {
+        self.log_live_param("of_method", serde_json::json!(method));
+        let mut params = self.params.write();
+        if params.of_method == method.index() { return; }
+        params.of_method = method.index();
+        drop(params);
+        let dropped = self.pose_estimator.discard_pending();
+        log::debug!("Optical flow method switched to {method:?}, dropped {dropped} pending frames");
+    }
+
+    /// Live: part of the frame the optical flow features are taken from, for the frames estimated from now on.
+    pub fn set_live_of_roi(&self, roi: synchronization::FeatureRoi) {
+        self.log_live_param("of_roi", serde_json::json!(roi));
+        *self.pose_estimator.feature_roi.write() = roi;
+    }
+
+    /// Live: optical flow method to detect the next frame with.
+    pub fn live_of_method(&self) -> synchronization::OfMethod {
+        synchronization::OfMethod::from_index(self.params.read().of_method).unwrap_or_default()
+    }
+
+    /// Live: append every `set_live_*` change to a JSONL sidecar at `path`, for auditing or replaying a session
+    /// (see `live::param_log::ParamLog::load` / `apply`). Replaces a log that is already running.
+    pub fn start_live_param_log(&self, path: &Path) -> std::io::Result<()> {
+        let log = live::param_log::ParamLog::create(path)?;
+        *self.live_param_log.write() = Some(log);
+        Ok(())
+    }
+
+    /// Stop the parameter log, flushing pending events.
+    pub fn stop_live_param_log(&self) {
+        self.live_param_log.write().take();
+    }
+
+    /// Live: time source of the pipeline, the render loop and the IMU server, a `live::MonotonicClock` by default.
+    /// Set it before they start, they take it once.
+    pub fn use_live_clock(&self, clock: live::SharedClock) {
+        *self.live_clock.write() = clock;
+    }
+
+    pub fn live_clock(&self) -> live::SharedClock {
+        self.live_clock.read().clone()
+    }
+
+    fn log_live_param(&self, name: &str, value: serde_json::Value) {
+        if let Some(log) = self.live_param_log.read().as_ref() {
+            log.record(self.params.read().duration_ms, name, value);
+        }
+    }
+
+    /// Size of the buffer the live render loop has to allocate for the stabilized output.
+    pub fn live_output_buffer_size(&self) -> (usize, usize) {
+        if let Some((_, _, w, h)) = self.stabilization.read().output_crop {
+            return (w, h);
+        }
+        self.params.read().output_size
+    }
+
     pub fn clear(&self) {
         self.params.write().clear();
         self.invalidate_ongoing_computations();
@@ -2255,6 +2793,9 @@ pub enum GyroflowCoreError {
     #[error("Output buffer is empty")]
     OutputBufferEmpty,
 
+    #[error("Input and output buffers overlap, in-place processing is not supported")]
+    BuffersOverlap,
+
     #[error("Failed to find cached wgpu in process_pixels. Key: {0}")]
     NoCachedWgpuInstance(String),
 
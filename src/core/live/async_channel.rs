@@ -0,0 +1,97 @@
+// live/async_channel.rs
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::thread;
+
+use crossbeam_channel::{Receiver, Sender};
+use futures::channel::mpsc;
+use futures::{Sink, SinkExt, Stream, StreamExt};
+
+/// Async side of a crossbeam receiver, e.g. the stabilized frames of the render loop.
+///
+/// Crossbeam has no wakers, so a bridge thread blocks on `recv` and forwards every item into a
+/// single-slot futures channel. It waits while that slot is full, so a slow consumer slows the
+/// producer down the same way a blocking `recv` loop would. The stream ends once all crossbeam
+/// senders are gone and the queued items are consumed.
+pub struct AsyncReceiver<T> {
+    rx: mpsc::Receiver<T>,
+}
+
+impl<T: Send + 'static> AsyncReceiver<T> {
+    pub fn new(rx: Receiver<T>) -> Self {
+        let (mut tx, async_rx) = mpsc::channel(0);
+        thread::Builder::new()
+            .name("live_async_recv".into())
+            .spawn(move || {
+                for item in rx.iter() {
+                    // The stream was dropped
+                    if futures::executor::block_on(tx.send(item)).is_err() { break; }
+                }
+            })
+            .expect("spawn live async receiver");
+        Self { rx: async_rx }
+    }
+
+    /// Next item, `None` once the channel is closed.
+    pub async fn recv(&mut self) -> Option<T> { self.rx.next().await }
+}
+
+impl<T> Stream for AsyncReceiver<T> {
+    type Item = T;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        Pin::new(&mut self.rx).poll_next(cx)
+    }
+}
+
+/// Async side of a crossbeam sender, e.g. the IMU channel of `LivePipeline`.
+///
+/// A bridge thread does the (possibly blocking) crossbeam `send`, so awaiting `send` only waits
+/// while a bounded channel is full and never blocks the executor. Items keep their order.
+pub struct AsyncSender<T> {
+    tx: mpsc::Sender<T>,
+}
+
+impl<T> Clone for AsyncSender<T> {
+    fn clone(&self) -> Self { Self { tx: self.tx.clone() } }
+}
+
+/// The crossbeam receiver is gone, the item wasn't delivered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Disconnected;
+
+impl<T: Send + 'static> AsyncSender<T> {
+    pub fn new(tx: Sender<T>) -> Self {
+        let (async_tx, mut rx) = mpsc::channel::<T>(0);
+        thread::Builder::new()
+            .name("live_async_send".into())
+            .spawn(move || {
+                while let Some(item) = futures::executor::block_on(rx.next()) {
+                    if tx.send(item).is_err() { break; }
+                }
+            })
+            .expect("spawn live async sender");
+        Self { tx: async_tx }
+    }
+
+    pub async fn send(&mut self, item: T) -> Result<(), Disconnected> {
+        self.tx.send(item).await.map_err(|_| Disconnected)
+    }
+}
+
+impl<T> Sink<T> for AsyncSender<T> {
+    type Error = Disconnected;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Disconnected>> {
+        self.tx.poll_ready(cx).map_err(|_| Disconnected)
+    }
+    fn start_send(mut self: Pin<&mut Self>, item: T) -> Result<(), Disconnected> {
+        self.tx.start_send(item).map_err(|_| Disconnected)
+    }
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Disconnected>> {
+        Pin::new(&mut self.tx).poll_flush(cx).map_err(|_| Disconnected)
+    }
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Disconnected>> {
+        Pin::new(&mut self.tx).poll_close(cx).map_err(|_| Disconnected)
+    }
+}
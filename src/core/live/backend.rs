@@ -0,0 +1,86 @@
+// live/backend.rs
+use log::{info, warn};
+
+/// What to do when no GPU backend is usable.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ComputeFallback {
+    /// Render on the CPU (slow, but the output isn't black)
+    #[default]
+    Cpu,
+    /// Refuse to start with `LiveError::NoComputeBackend`
+    Fail,
+}
+
+/// Result of probing one backend: `Ok(device name)` or `Err(reason)`.
+pub type ProbeResult = (&'static str, Result<String, String>);
+
+#[derive(Debug, Default, Clone)]
+pub struct BackendProbe {
+    pub attempted: Vec<ProbeResult>,
+}
+
+impl BackendProbe {
+    /// First backend that initialized, as "backend: device".
+    pub fn selected(&self) -> Option<String> {
+        self.attempted.iter().find_map(|(name, res)| res.as_ref().ok().map(|dev| format!("{name}: {dev}")))
+    }
+
+    /// Human readable list of what was tried, for logs and errors.
+    pub fn attempted_names(&self) -> Vec<String> {
+        self.attempted.iter().map(|(name, res)| match res {
+            Ok(dev) => format!("{name} ({dev})"),
+            Err(e)  => format!("{name} ({e})"),
+        }).collect()
+    }
+}
+
+fn panic_message(e: Box<dyn std::any::Any + Send>) -> String {
+    if let Some(s) = e.downcast_ref::<&str>() {
+        s.to_string()
+    } else if let Some(s) = e.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "panic".into()
+    }
+}
+
+/// One-time capability probe, same order as `gpu::initialize_contexts` (OpenCL, then wgpu),
+/// but keeps going so every attempted backend can be reported.
+pub fn probe_compute_backends() -> BackendProbe {
+    let mut probe = BackendProbe::default();
+
+    #[cfg(feature = "use-opencl")]
+    {
+        let res = if !std::env::var("NO_OPENCL").unwrap_or_default().is_empty() {
+            Err("disabled by NO_OPENCL".to_string())
+        } else {
+            match std::panic::catch_unwind(|| crate::gpu::opencl::OclWrapper::initialize_context(None)) {
+                Ok(Ok((name, _list))) => Ok(name),
+                Ok(Err(e)) => Err(format!("{e:?}")),
+                Err(e) => Err(panic_message(e)),
+            }
+        };
+        probe.attempted.push(("OpenCL", res));
+    }
+    #[cfg(not(feature = "use-opencl"))]
+    probe.attempted.push(("OpenCL", Err("not compiled in".to_string())));
+
+    let res = if !std::env::var("NO_WGPU").unwrap_or_default().is_empty() {
+        Err("disabled by NO_WGPU".to_string())
+    } else {
+        match std::panic::catch_unwind(crate::gpu::wgpu::WgpuWrapper::initialize_context) {
+            Ok(Some((name, _list))) => Ok(name),
+            Ok(None) => Err("no adapter".to_string()),
+            Err(e) => Err(panic_message(e)),
+        }
+    };
+    probe.attempted.push(("wgpu", res));
+
+    for (name, res) in &probe.attempted {
+        match res {
+            Ok(dev) => info!("live: compute backend {name}: {dev}"),
+            Err(e)  => warn!("live: compute backend {name} unavailable: {e}"),
+        }
+    }
+    probe
+}
@@ -0,0 +1,123 @@
+// live/clock.rs
+use std::sync::Arc;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::time::{Duration, Instant};
+
+/// Source of the current time (µs) for the live pipeline: the integration trigger, the IMU wait,
+/// frame latency, the ingest stamps of frames and the video-clock stamps of IMU samples. Injected through
+/// `StabilizationManager::use_live_clock`, so a test can drive all of them with a `ManualClock`.
+pub trait Clock: Send + Sync {
+    fn now_us(&self) -> i64;
+
+    /// Called by the stream reader with the timestamp (µs) of every frame it reads.
+    fn on_frame(&self, _pts_us: i64) {}
+}
+
+/// How the live clock is shared between the pipeline threads.
+pub type SharedClock = Arc<dyn Clock>;
+
+/// Wall clock that never goes backwards, µs since it was created. The default.
+#[derive(Debug, Clone, Copy)]
+pub struct MonotonicClock {
+    epoch: Instant,
+}
+
+impl MonotonicClock {
+    pub fn new() -> Self { Self { epoch: Instant::now() } }
+}
+
+impl Default for MonotonicClock {
+    fn default() -> Self { Self::new() }
+}
+
+impl Clock for MonotonicClock {
+    fn now_us(&self) -> i64 { self.epoch.elapsed().as_micros() as i64 }
+}
+
+/// The stream's own clock: the PTS (µs) of the most recent frame, set by whoever reads the frames.
+/// Stands still while no frame comes, and only moves forward.
+#[derive(Debug, Default)]
+pub struct StreamPtsClock {
+    pts_us: AtomicI64,
+}
+
+impl Clock for StreamPtsClock {
+    fn now_us(&self) -> i64 { self.pts_us.load(Ordering::Relaxed) }
+
+    fn on_frame(&self, pts_us: i64) { self.pts_us.fetch_max(pts_us, Ordering::Relaxed); }
+}
+
+/// Clock that only moves when told to, for deterministic tests and offline replays.
+#[derive(Debug, Default)]
+pub struct ManualClock {
+    now_us: AtomicI64,
+}
+
+impl ManualClock {
+    pub fn new(now_us: i64) -> Self { Self { now_us: AtomicI64::new(now_us) } }
+
+    pub fn set(&self, now_us: i64) { self.now_us.store(now_us, Ordering::Relaxed); }
+
+    pub fn advance(&self, by: Duration) { self.now_us.fetch_add(by.as_micros() as i64, Ordering::Relaxed); }
+}
+
+impl Clock for ManualClock {
+    fn now_us(&self) -> i64 { self.now_us.load(Ordering::Relaxed) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+    use crate::gyro_source::LiveImuSample;
+    use crate::live::{live_manager, LiveError, LivePipeline, StreamClock, DEFAULT_NO_IMU_TIMEOUT};
+
+    #[test]
+    fn manual_clock_drives_the_pipeline() {
+        let stab = live_manager();
+        let clock = Arc::new(ManualClock::new(0));
+        stab.use_live_clock(clock.clone());
+        let pipeline = Arc::new(LivePipeline::new(stab.clone(), Some(Duration::from_millis(10))));
+
+        // The IMU wait times out on the live clock, not the wall clock
+        let waiter = {
+            let pipeline = pipeline.clone();
+            thread::spawn(move || pipeline.wait_for_imu())
+        };
+        thread::sleep(Duration::from_millis(50));
+        assert!(!waiter.is_finished(), "the IMU wait timed out while the clock stood still");
+        clock.advance(DEFAULT_NO_IMU_TIMEOUT);
+        assert!(matches!(waiter.join().unwrap(), Ok(false) | Err(LiveError::NoImu(_))));
+
+        for i in 0..200_i64 {
+            let ts = i * 5_000;
+            pipeline.push_imu(LiveImuSample { ts_sensor_us: ts, gyro: [0.0, 0.0, 0.5], accel: Some([0.0, 0.0, 1.0]) }, ts).unwrap();
+        }
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while pipeline.ingest_stats().samples < 200 {
+            assert!(Instant::now() < deadline, "samples never ingested");
+            thread::sleep(Duration::from_millis(5));
+        }
+
+        // Ingested but not integrated until the clock moves by the integration period
+        let published = || stab.gyro.read().live.read().as_ref().and_then(|st| st.quat_buffer_store_org.get_latest_buffer()).map(|b| b.last_us);
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(published(), None, "integrated while the clock stood still");
+        clock.advance(Duration::from_millis(10));
+        while !published().is_some_and(|last_us| last_us >= 995_000) {
+            assert!(Instant::now() < deadline, "quaternions never published");
+            thread::sleep(Duration::from_millis(5));
+        }
+
+        let mut latency = StreamClock::new(stab.live_clock());
+        assert_eq!(latency.latency_ms(0), 0.0);
+        clock.advance(Duration::from_millis(50));
+        assert_eq!(latency.latency_ms(33_000), 17.0);
+
+        // The stream clock follows the frames the reader hands it, never backwards
+        let pts = StreamPtsClock::default();
+        pts.on_frame(40_000);
+        pts.on_frame(20_000);
+        assert_eq!(pts.now_us(), 40_000);
+    }
+}
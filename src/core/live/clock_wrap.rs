@@ -0,0 +1,48 @@
+// live/clock_wrap.rs
+use log::info;
+
+/// Unwraps a sensor clock that counts modulo a fixed period (e.g. a 32-bit µs counter wraps every ~71.6 min).
+///
+/// A backwards jump of more than half a period is taken as a wrap and adds one period to the epoch offset,
+/// so the timestamps handed on stay monotonic. A sample jumping forward by more than half a period is a
+/// late one from before the last wrap and gets the previous epoch. Smaller jumps (jitter, reordering)
+/// are passed through.
+#[derive(Debug, Clone)]
+pub struct ClockUnwrapper {
+    period_us: i64,
+    epoch_us: i64,
+    last_raw_us: Option<i64>,
+    wraps: u64,
+}
+
+impl ClockUnwrapper {
+    pub fn new(period_us: i64) -> Self {
+        Self { period_us: period_us.max(1), epoch_us: 0, last_raw_us: None, wraps: 0 }
+    }
+
+    /// Clock of `bits` bits (the `clock_bits` header key), `us_per_tick` being the microseconds per count.
+    pub fn from_bits(bits: u32, us_per_tick: f64) -> Self {
+        Self::new(((1u64 << bits.clamp(1, 62)) as f64 * us_per_tick).round() as i64)
+    }
+
+    pub fn period_us(&self) -> i64 { self.period_us }
+
+    /// Wraps seen so far.
+    pub fn wraps(&self) -> u64 { self.wraps }
+
+    /// Timestamp on the continuous timeline for a raw one in µs.
+    pub fn unwrap(&mut self, raw_us: i64) -> i64 {
+        let half = self.period_us / 2;
+        if let Some(last) = self.last_raw_us {
+            if last - raw_us > half {
+                self.epoch_us += self.period_us;
+                self.wraps += 1;
+                info!("live: sensor clock wrapped ({} -> {raw_us} us), epoch offset now {} us", last, self.epoch_us);
+            } else if raw_us - last > half && self.wraps > 0 {
+                return raw_us + self.epoch_us - self.period_us;
+            }
+        }
+        self.last_raw_us = Some(raw_us);
+        raw_us + self.epoch_us
+    }
+}
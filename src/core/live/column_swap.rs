@@ -0,0 +1,114 @@
+// live/column_swap.rs
+use log::{debug, warn};
+
+use crate::gyro_source::LiveImuSample;
+
+/// Samples with an accelerometer reading looked at per decision.
+const WINDOW_SAMPLES: usize = 200;
+/// Windows tried before giving up, e.g. when the camera keeps moving during warm-up.
+const MAX_WINDOWS: usize = 10;
+/// Magnitudes accepted as gravity: 1 g ± 20%, in g or in m/s².
+const GRAVITY_RANGES: [(f64, f64); 2] = [(0.8, 1.2), (0.8 * 9.81, 1.2 * 9.81)];
+/// Mean magnitude under which a triple reads as a gyro at rest (rad/s or deg/s).
+const STILL_MAGNITUDE: f64 = 0.2;
+/// Standard deviation of the magnitude, relative to the mean, under which gravity counts as steady.
+const STEADY_RATIO: f64 = 0.1;
+
+/// Outcome of `ColumnSwapDetector`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColumnOrder {
+    /// Gyro and accel columns are where the header says.
+    Expected,
+    /// The logger wrote the accelerometer into the gyro columns and vice versa.
+    Swapped,
+    /// No still window during warm-up, the columns are used as they are.
+    Undecided,
+}
+
+/// Checks during warm-up whether the gyro and accel columns of a stream are swapped.
+///
+/// A camera at rest reads about 1 g on the accelerometer and about zero on the gyro.
+/// If a still window shows the opposite (a steady 1 g in the "gyro" triple, zero in the "accel" one),
+/// the columns are swapped. Windows with motion are skipped; samples without an accelerometer
+/// reading can't be checked and are kept as they are.
+#[derive(Debug, Default)]
+pub struct ColumnSwapDetector {
+    gyro: Vec<f64>,
+    accel: Vec<f64>,
+    windows: usize,
+    seen: usize,
+    order: Option<ColumnOrder>,
+}
+
+impl ColumnSwapDetector {
+    pub fn new() -> Self { Self::default() }
+
+    /// `None` while still warming up.
+    pub fn order(&self) -> Option<ColumnOrder> { self.order }
+
+    /// Look at one sample, returns the order once it's decided.
+    pub fn observe(&mut self, sample: &LiveImuSample) -> Option<ColumnOrder> {
+        if self.order.is_some() { return self.order; }
+        self.seen += 1;
+        let Some(accel) = sample.accel else {
+            // Gyro-only streams never fill a window
+            if self.seen >= WINDOW_SAMPLES * MAX_WINDOWS { return self.decide(ColumnOrder::Undecided); }
+            return None;
+        };
+        self.gyro.push(magnitude(&sample.gyro));
+        self.accel.push(magnitude(&accel));
+        if self.gyro.len() < WINDOW_SAMPLES { return None; }
+
+        let (gyro, accel) = (stats(&self.gyro), stats(&self.accel));
+        self.gyro.clear();
+        self.accel.clear();
+        self.windows += 1;
+        let order = if is_gravity(accel) && gyro.0 < STILL_MAGNITUDE {
+            ColumnOrder::Expected
+        } else if is_gravity(gyro) && accel.0 < STILL_MAGNITUDE {
+            ColumnOrder::Swapped
+        } else if self.windows >= MAX_WINDOWS {
+            ColumnOrder::Undecided
+        } else {
+            return None;
+        };
+        if order == ColumnOrder::Swapped {
+            warn!("live: the gyro columns read {:.2} at rest and the accel columns {:.2}, they look swapped; swapping them back", gyro.0, accel.0);
+        }
+        self.decide(order)
+    }
+
+    fn decide(&mut self, order: ColumnOrder) -> Option<ColumnOrder> {
+        match order {
+            ColumnOrder::Swapped => {}
+            ColumnOrder::Undecided => debug!("live: no still window in {} samples, can't tell whether the gyro and accel columns are swapped", self.seen),
+            ColumnOrder::Expected => debug!("live: gyro and accel columns are in the expected order"),
+        }
+        self.order = Some(order);
+        self.order
+    }
+
+    /// `sample` with the columns put back in order, once they are known to be swapped.
+    pub fn correct(&self, mut sample: LiveImuSample) -> LiveImuSample {
+        if self.order == Some(ColumnOrder::Swapped) {
+            if let Some(accel) = sample.accel {
+                sample.accel = Some(sample.gyro);
+                sample.gyro = accel;
+            }
+        }
+        sample
+    }
+}
+
+fn magnitude(v: &[f64; 3]) -> f64 { (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt() }
+
+/// Mean and standard deviation.
+fn stats(v: &[f64]) -> (f64, f64) {
+    let mean = v.iter().sum::<f64>() / v.len() as f64;
+    let var = v.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / v.len() as f64;
+    (mean, var.sqrt())
+}
+
+fn is_gravity((mean, std): (f64, f64)) -> bool {
+    GRAVITY_RANGES.iter().any(|(lo, hi)| (*lo..=*hi).contains(&mean)) && std < mean * STEADY_RATIO
+}
@@ -0,0 +1,92 @@
+// live/crop.rs
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use nalgebra::{Matrix3, Vector3};
+
+use crate::gyro_source::Quat64;
+
+/// Binary search steps when looking for the strongest correction that fits the crop cap.
+const STRENGTH_SEARCH_STEPS: usize = 12;
+
+/// Smallest `fov` allowed by a crop cap given in percent of the input frame.
+///
+/// `fov` < 1 zooms in, so cropping away `max_crop_pct` % of the width/height means `fov >= 1 - pct / 100`.
+pub fn min_fov_for_crop(max_crop_pct: f64) -> f64 {
+    (1.0 - max_crop_pct.clamp(0.0, 99.0) / 100.0).max(0.01)
+}
+
+/// Counters of the live crop guard, shared by every `ComputeParams` clone of a manager.
+#[derive(Debug, Default)]
+pub struct LiveCropStats {
+    frames: AtomicU64,
+    cap_hits: AtomicU64,
+    last_strength: AtomicU64, // f64 bits
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct LiveCropSnapshot {
+    /// Frames checked against the cap.
+    pub frames: u64,
+    /// Frames where the stabilization strength had to be reduced to stay within the cap.
+    pub cap_hits: u64,
+    /// Strength (0..1) applied to the last frame.
+    pub last_strength: f64,
+}
+
+impl LiveCropStats {
+    pub fn record(&self, strength: f64) {
+        self.frames.fetch_add(1, Ordering::Relaxed);
+        if strength < 1.0 {
+            self.cap_hits.fetch_add(1, Ordering::Relaxed);
+        }
+        self.last_strength.store(strength.to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> LiveCropSnapshot {
+        LiveCropSnapshot {
+            frames: self.frames.load(Ordering::Relaxed),
+            cap_hits: self.cap_hits.load(Ordering::Relaxed),
+            last_strength: f64::from_bits(self.last_strength.load(Ordering::Relaxed)),
+        }
+    }
+
+    pub fn reset(&self) {
+        self.frames.store(0, Ordering::Relaxed);
+        self.cap_hits.store(0, Ordering::Relaxed);
+        self.last_strength.store(0, Ordering::Relaxed);
+    }
+}
+
+/// Whether every corner of the output, seen through the camera rotation `r`, lands inside the input frame.
+///
+/// Pinhole approximation of the kernel mapping: output pixel → `(new_k * r)⁻¹` → input ray → `k`.
+pub fn output_fits_input(r: &Matrix3<f64>, new_k: &Matrix3<f64>, k: &Matrix3<f64>, input_size: (usize, usize), output_size: (usize, usize)) -> bool {
+    let Some(inv) = (new_k * r).try_inverse() else { return false; };
+    let (ow, oh) = (output_size.0 as f64, output_size.1 as f64);
+    let (iw, ih) = (input_size.0 as f64, input_size.1 as f64);
+    [(0.0, 0.0), (ow, 0.0), (0.0, oh), (ow, oh)].iter().all(|&(x, y)| {
+        let ray = inv * Vector3::new(x, y, 1.0);
+        if ray.z <= 0.0 { return false; }
+        let p = k * (ray / ray.z);
+        p.x >= -0.5 && p.x <= iw + 0.5 && p.y >= -0.5 && p.y <= ih + 0.5
+    })
+}
+
+/// Strongest blend from `neutral` (no correction) towards `target` that still passes `fits`.
+///
+/// Returns the limited quaternion and the strength used (1.0 when `target` already fits).
+pub fn limit_correction<F: Fn(&Quat64) -> bool>(neutral: &Quat64, target: &Quat64, fits: F) -> (Quat64, f64) {
+    if fits(target) {
+        return (*target, 1.0);
+    }
+    if !fits(neutral) {
+        // Even the uncorrected frame has borders (zoomed out), the cap can't help here
+        return (*target, 1.0);
+    }
+    let (mut lo, mut hi) = (0.0, 1.0);
+    for _ in 0..STRENGTH_SEARCH_STEPS {
+        let mid = (lo + hi) / 2.0;
+        if fits(&neutral.slerp(target, mid)) { lo = mid; } else { hi = mid; }
+    }
+    (neutral.slerp(target, lo), lo)
+}
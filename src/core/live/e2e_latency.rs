@@ -0,0 +1,74 @@
+// live/e2e_latency.rs
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use parking_lot::Mutex;
+
+/// Most recent frames the percentiles are taken over, 10 s at 60 fps.
+const WINDOW_FRAMES: usize = 600;
+
+/// Latency of each frame from the reader taking it off the decoder to its push to the sink returning,
+/// on the live clock (see `StabilizationManager::use_live_clock`). With a `StreamPtsClock` that's stream
+/// time, i.e. how far the stream had moved on when the frame reached the sink.
+/// The camera and the display are outside of it, so it is a lower bound of glass-to-glass latency; `RenderTimingStats` tells how much of it is rendering, the rest is queueing.
+#[derive(Debug, Default)]
+pub struct EndToEndLatencyStats {
+    inner: Mutex<(u64, VecDeque<f64>)>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct EndToEndLatencySnapshot {
+    /// Frames measured so far.
+    pub frames: u64,
+    /// Percentiles over the last `WINDOW_FRAMES` frames, in ms.
+    pub p50_ms: f64,
+    pub p95_ms: f64,
+    pub p99_ms: f64,
+    pub max_ms: f64,
+}
+
+impl EndToEndLatencyStats {
+    /// A frame reached the sink `latency` after the reader took it off the decoder.
+    pub fn record(&self, latency: Duration) {
+        let mut inner = self.inner.lock();
+        inner.0 += 1;
+        if inner.1.len() == WINDOW_FRAMES { inner.1.pop_front(); }
+        inner.1.push_back(latency.as_secs_f64() * 1000.0);
+    }
+
+    pub fn snapshot(&self) -> EndToEndLatencySnapshot {
+        let (frames, mut latencies) = {
+            let inner = self.inner.lock();
+            (inner.0, inner.1.iter().copied().collect::<Vec<_>>())
+        };
+        if latencies.is_empty() { return EndToEndLatencySnapshot { frames, ..Default::default() }; }
+        latencies.sort_by(f64::total_cmp);
+        // Nearest rank
+        let at = |p: f64| latencies[((p * latencies.len() as f64).ceil() as usize).clamp(1, latencies.len()) - 1];
+        EndToEndLatencySnapshot { frames, p50_ms: at(0.50), p95_ms: at(0.95), p99_ms: at(0.99), max_ms: at(1.0) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percentiles_over_the_window() {
+        // Known spread: 1..=100 ms gives the percentiles back as they are
+        let stats = EndToEndLatencyStats::default();
+        assert_eq!(stats.snapshot(), EndToEndLatencySnapshot::default());
+        for ms in (1..=100).rev() {
+            stats.record(Duration::from_millis(ms));
+        }
+        let s = stats.snapshot();
+        assert_eq!((s.frames, s.p50_ms, s.p95_ms, s.p99_ms, s.max_ms), (100, 50.0, 95.0, 99.0, 100.0));
+
+        // Only the last `WINDOW_FRAMES` count
+        for _ in 0..WINDOW_FRAMES {
+            stats.record(Duration::from_millis(5));
+        }
+        let s = stats.snapshot();
+        assert_eq!((s.frames, s.p50_ms, s.max_ms), (100 + WINDOW_FRAMES as u64, 5.0, 5.0));
+    }
+}
@@ -0,0 +1,23 @@
+// live/error.rs
+use crate::GyroflowCoreError;
+
+#[derive(thiserror::Error, Debug)]
+pub enum LiveError {
+    #[error("No usable compute backend (tried: {})", .0.join(", "))]
+    NoComputeBackend(Vec<String>),
+
+    #[error("No IMU sample received within {:?}", .0)]
+    NoImu(std::time::Duration),
+
+    #[error("No STMap built yet")]
+    NoStmap,
+
+    #[error("Failed to build the STMaps: {0:?}")]
+    StmapBuild(anyhow::Error),
+
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
+    #[error(transparent)]
+    Core(#[from] GyroflowCoreError),
+}
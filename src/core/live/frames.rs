@@ -0,0 +1,101 @@
+// live/frames.rs
+use std::collections::BTreeMap;
+
+use parking_lot::RwLock;
+
+/// One decoded frame as seen by the reader.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FrameEntry {
+    pub ts_us: i64,
+    /// Dropped frames keep their index, but are never rendered and their maps are discarded.
+    pub dropped: bool,
+}
+
+#[derive(Debug, Default)]
+struct TimelineInner {
+    by_idx: BTreeMap<usize, FrameEntry>,
+    by_ts: BTreeMap<i64, usize>,
+    next_idx: usize,
+}
+
+/// Single source of truth for `frame_index <-> ts_us`, shared by the stream reader, the STMap worker
+/// and the render loop.
+///
+/// The reader assigns indices with `register`, so every other stage can resolve an index back to the
+/// exact frame it belongs to. A dropped frame leaves a hole instead of shifting the following indices.
+#[derive(Debug, Default)]
+pub struct FrameTimeline {
+    inner: RwLock<TimelineInner>,
+}
+
+impl FrameTimeline {
+    pub fn new() -> Self { Self::default() }
+
+    /// Assign the next frame index to a decoded frame.
+    pub fn register(&self, ts_us: i64) -> usize {
+        let mut inner = self.inner.write();
+        let idx = inner.next_idx;
+        inner.next_idx += 1;
+        inner.by_idx.insert(idx, FrameEntry { ts_us, dropped: false });
+        inner.by_ts.insert(ts_us, idx);
+        idx
+    }
+
+    /// Index the next `register` will assign.
+    pub fn next_index(&self) -> usize { self.inner.read().next_idx }
+
+    /// Continue numbering at `idx`. Indices only grow (caches downstream are keyed by index),
+    /// so a base below the next index is ignored; returns the index actually used.
+    pub fn continue_from(&self, idx: usize) -> usize {
+        let mut inner = self.inner.write();
+        if idx < inner.next_idx {
+            log::warn!("frame timeline: can't restart indices at {idx}, already at {}", inner.next_idx);
+        } else {
+            inner.next_idx = idx;
+        }
+        inner.next_idx
+    }
+
+    /// Timestamp of the most recently registered frame that is still tracked.
+    pub fn last_ts(&self) -> Option<i64> {
+        self.inner.read().by_idx.values().next_back().map(|e| e.ts_us)
+    }
+
+    pub fn mark_dropped(&self, idx: usize) {
+        if let Some(e) = self.inner.write().by_idx.get_mut(&idx) {
+            e.dropped = true;
+        }
+    }
+
+    pub fn entry(&self, idx: usize) -> Option<FrameEntry> {
+        self.inner.read().by_idx.get(&idx).copied()
+    }
+
+    /// Timestamp of a frame that is still going to be rendered.
+    pub fn ts_of(&self, idx: usize) -> Option<i64> {
+        self.entry(idx).filter(|e| !e.dropped).map(|e| e.ts_us)
+    }
+
+    pub fn index_of(&self, ts_us: i64) -> Option<usize> {
+        self.inner.read().by_ts.get(&ts_us).copied()
+    }
+
+    pub fn is_dropped(&self, idx: usize) -> bool {
+        self.entry(idx).is_some_and(|e| e.dropped)
+    }
+
+    /// Forget frames before `idx`, once the render loop is past them.
+    pub fn trim_before(&self, idx: usize) {
+        let mut inner = self.inner.write();
+        let kept = inner.by_idx.split_off(&idx);
+        let removed = std::mem::replace(&mut inner.by_idx, kept);
+        for (i, e) in removed {
+            if inner.by_ts.get(&e.ts_us) == Some(&i) {
+                inner.by_ts.remove(&e.ts_us);
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize { self.inner.read().by_idx.len() }
+    pub fn is_empty(&self) -> bool { self.len() == 0 }
+}
@@ -0,0 +1,179 @@
+// live/latency.rs
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+use log::{info, warn};
+
+use super::clock::{MonotonicClock, SharedClock};
+
+/// Frames the measured latency is averaged over before it is compared to the SLA.
+const SMOOTHING_FRAMES: f64 = 8.0;
+/// Consecutive frames over the SLA before the next degradation step is taken.
+const DEGRADE_AFTER_FRAMES: usize = 10;
+/// Consecutive frames under `RESTORE_RATIO` of the SLA before the last step is undone.
+const RESTORE_AFTER_FRAMES: usize = 60;
+/// Latency, relative to the SLA, that counts as recovered. Below 1 so the levels don't flap.
+const RESTORE_RATIO: f64 = 0.7;
+
+/// One step of the latency policy, cheapest loss of quality first.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum Degradation {
+    /// Skip frames that are already later than the SLA when they reach the renderer.
+    DropLateFrames,
+    /// Build the STMaps at a lower resolution, see `StmapsLive::set_map_scale`.
+    ReducedMapResolution,
+    /// Bilinear interpolation instead of the configured kernel.
+    FastInterpolation,
+}
+
+impl Degradation {
+    pub const ALL: [Degradation; 3] = [Degradation::DropLateFrames, Degradation::ReducedMapResolution, Degradation::FastInterpolation];
+}
+
+/// Latency SLA state shared with stats reporting, see `LatencySla`.
+#[derive(Debug, Default)]
+pub struct LatencySlaStats {
+    sla_ms: AtomicU64,     // f64 bits
+    latency_ms: AtomicU64, // f64 bits
+    level: AtomicUsize,
+    late_dropped: AtomicU64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct LatencySlaSnapshot {
+    /// Target frame-to-present latency, 0 when no SLA is set.
+    pub sla_ms: f64,
+    /// Smoothed measured latency.
+    pub latency_ms: f64,
+    /// Number of degradation steps in effect, 0 is full quality.
+    pub level: usize,
+    /// Frames skipped by `Degradation::DropLateFrames`.
+    pub late_dropped: u64,
+}
+
+impl LatencySlaStats {
+    pub fn record_late_drop(&self) {
+        self.late_dropped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> LatencySlaSnapshot {
+        LatencySlaSnapshot {
+            sla_ms: f64::from_bits(self.sla_ms.load(Ordering::Relaxed)),
+            latency_ms: f64::from_bits(self.latency_ms.load(Ordering::Relaxed)),
+            level: self.level.load(Ordering::Relaxed),
+            late_dropped: self.late_dropped.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Frame latency against the stream clock: how much later than the least delayed frame so far
+/// a frame is, given its stream timestamp. Covers queueing and processing, not the capture itself.
+/// Frames are timed with `clock`, the live clock of the manager in the render loop.
+pub struct StreamClock {
+    clock: SharedClock,
+    anchor: Option<(i64, i64)>,
+}
+
+impl Default for StreamClock {
+    fn default() -> Self { Self::new(Arc::new(MonotonicClock::new())) }
+}
+
+impl StreamClock {
+    pub fn new(clock: SharedClock) -> Self { Self { clock, anchor: None } }
+
+    /// Current time of the clock the frames are timed with.
+    pub fn now_us(&self) -> i64 { self.clock.now_us() }
+
+    pub fn latency_ms(&mut self, ts_us: i64) -> f64 {
+        let now_us = self.clock.now_us();
+        let (t0, ts0) = *self.anchor.get_or_insert((now_us, ts_us));
+        let lag_ms = (now_us - t0).max(0) as f64 / 1000.0 - (ts_us - ts0) as f64 / 1000.0;
+        if lag_ms < 0.0 {
+            // Earlier than any frame so far, it becomes the reference
+            self.anchor = Some((now_us, ts_us));
+            return 0.0;
+        }
+        lag_ms
+    }
+}
+
+/// Keeps the frame-to-present latency under a target by degrading quality in `steps` order
+/// while it's over, and restoring the steps in reverse once it has been well under for a while.
+pub struct LatencySla {
+    sla_ms: f64,
+    steps: Vec<Degradation>,
+    level: usize,
+    smoothed_ms: Option<f64>,
+    over: usize,
+    under: usize,
+    stats: Arc<LatencySlaStats>,
+}
+
+impl LatencySla {
+    /// `sla_ms` 0 disables the policy. `steps` lists the degradations the caller can apply.
+    pub fn new(sla_ms: f64, steps: &[Degradation], stats: Arc<LatencySlaStats>) -> Self {
+        let sla_ms = sla_ms.max(0.0);
+        stats.sla_ms.store(sla_ms.to_bits(), Ordering::Relaxed);
+        stats.level.store(0, Ordering::Relaxed);
+        Self { sla_ms, steps: steps.to_vec(), level: 0, smoothed_ms: None, over: 0, under: 0, stats }
+    }
+
+    pub fn is_enabled(&self) -> bool { self.sla_ms > 0.0 }
+
+    pub fn sla_ms(&self) -> f64 { self.sla_ms }
+
+    pub fn level(&self) -> usize { self.level }
+
+    /// Whether `step` is currently in effect.
+    pub fn is_active(&self, step: Degradation) -> bool {
+        self.steps[..self.level].contains(&step)
+    }
+
+    /// Whether a frame with this latency should be skipped instead of rendered.
+    pub fn should_drop(&self, latency_ms: f64) -> bool {
+        self.is_enabled() && self.is_active(Degradation::DropLateFrames) && latency_ms > self.sla_ms
+    }
+
+    /// Record the latency of a presented frame.
+    /// Returns the step that was taken (`true`) or undone (`false`) because of it, if any.
+    pub fn record(&mut self, latency_ms: f64) -> Option<(Degradation, bool)> {
+        if !self.is_enabled() { return None; }
+        let smoothed = match self.smoothed_ms {
+            Some(s) => s + (latency_ms - s) / SMOOTHING_FRAMES,
+            None => latency_ms,
+        };
+        self.smoothed_ms = Some(smoothed);
+        self.stats.latency_ms.store(smoothed.to_bits(), Ordering::Relaxed);
+
+        if smoothed > self.sla_ms {
+            self.under = 0;
+            self.over += 1;
+            if self.over >= DEGRADE_AFTER_FRAMES && self.level < self.steps.len() {
+                self.over = 0;
+                let step = self.steps[self.level];
+                self.set_level(self.level + 1);
+                warn!("live: latency {smoothed:.1} ms over the {:.0} ms SLA, degrading: {step:?} (level {})", self.sla_ms, self.level);
+                return Some((step, true));
+            }
+        } else if smoothed < self.sla_ms * RESTORE_RATIO {
+            self.over = 0;
+            self.under += 1;
+            if self.under >= RESTORE_AFTER_FRAMES && self.level > 0 {
+                self.under = 0;
+                let step = self.steps[self.level - 1];
+                self.set_level(self.level - 1);
+                info!("live: latency {smoothed:.1} ms back under the {:.0} ms SLA, restoring: {step:?} (level {})", self.sla_ms, self.level);
+                return Some((step, false));
+            }
+        } else {
+            self.over = 0;
+            self.under = 0;
+        }
+        None
+    }
+
+    fn set_level(&mut self, level: usize) {
+        self.level = level;
+        self.stats.level.store(level, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,141 @@
+// live/latency_profile.rs
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::{Receiver, RecvTimeoutError, TryRecvError};
+
+/// Channel polls `LatencyProfile::LowLatency` spins through before blocking, in the order of 100 µs.
+pub const LOW_LATENCY_SPINS: u32 = 10_000;
+/// Sleep between the channel polls of `LatencyProfile::PowerSaver`.
+pub const POWER_SAVER_POLL: Duration = Duration::from_millis(2);
+
+/// How the live loops (IMU consumer, STMap worker, render loop) wait for their next message,
+/// trading CPU for wake-up delay. See `StabilizationManager::set_live_latency_profile`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum LatencyProfile {
+    /// Poll the channel every `POWER_SAVER_POLL`. A message waits up to that long (1 ms on average),
+    /// in exchange a busy stream wakes the loop at most 500 times a second and bursts are taken in one go.
+    PowerSaver,
+    /// Block on the channel until the sender wakes the loop, tens of µs after the send.
+    #[default]
+    Balanced,
+    /// Busy-poll the channel `LOW_LATENCY_SPINS` times before blocking like `Balanced`, so a message
+    /// arriving shortly after the previous one is taken without a wake-up. Each wait keeps a core busy
+    /// for up to ~100 µs: at 60 fps that's under 1% of a core per loop, but a loop whose messages come
+    /// back to back spins nearly all the time. Meant for a dedicated machine.
+    LowLatency,
+}
+
+impl LatencyProfile {
+    pub const ALL: [LatencyProfile; 3] = [Self::PowerSaver, Self::Balanced, Self::LowLatency];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::PowerSaver => "power-saver",
+            Self::Balanced => "balanced",
+            Self::LowLatency => "low-latency",
+        }
+    }
+
+    /// `rx.recv_timeout(timeout)` with the wait strategy of this profile.
+    pub fn recv_timeout<T>(self, rx: &Receiver<T>, timeout: Duration) -> Result<T, RecvTimeoutError> {
+        let deadline = Instant::now() + timeout;
+        match self {
+            Self::Balanced => rx.recv_timeout(timeout),
+            Self::LowLatency => {
+                for _ in 0..LOW_LATENCY_SPINS {
+                    match rx.try_recv() {
+                        Ok(msg) => return Ok(msg),
+                        Err(TryRecvError::Disconnected) => return Err(RecvTimeoutError::Disconnected),
+                        Err(TryRecvError::Empty) => std::hint::spin_loop(),
+                    }
+                }
+                rx.recv_timeout(deadline.saturating_duration_since(Instant::now()))
+            }
+            Self::PowerSaver => loop {
+                match rx.try_recv() {
+                    Ok(msg) => return Ok(msg),
+                    Err(TryRecvError::Disconnected) => return Err(RecvTimeoutError::Disconnected),
+                    Err(TryRecvError::Empty) => {}
+                }
+                let left = deadline.saturating_duration_since(Instant::now());
+                if left.is_zero() { return Err(RecvTimeoutError::Timeout); }
+                thread::sleep(left.min(POWER_SAVER_POLL));
+            },
+        }
+    }
+}
+
+/// The profile in effect, read by the live loops on every wait without taking the params lock.
+#[derive(Debug)]
+pub struct LatencyProfileCell(AtomicU8);
+
+impl Default for LatencyProfileCell {
+    fn default() -> Self { Self(AtomicU8::new(LatencyProfile::default() as u8)) }
+}
+
+impl LatencyProfileCell {
+    pub fn get(&self) -> LatencyProfile {
+        LatencyProfile::ALL.get(self.0.load(Ordering::Relaxed) as usize).copied().unwrap_or_default()
+    }
+    pub fn set(&self, profile: LatencyProfile) {
+        self.0.store(profile as u8, Ordering::Relaxed);
+    }
+}
+
+impl fmt::Display for LatencyProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { f.write_str(self.name()) }
+}
+
+impl FromStr for LatencyProfile {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL.into_iter().find(|p| p.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| format!("unknown latency profile `{s}`, expected one of: power-saver, balanced, low-latency"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossbeam_channel::unbounded;
+
+    #[test]
+    fn low_latency_profile_wakes_up_sooner_than_power_saver() {
+        let mean_delay = |profile: LatencyProfile| {
+            let (tx, rx) = unbounded::<Instant>();
+            let sender = thread::spawn(move || {
+                for _ in 0..50 {
+                    thread::sleep(Duration::from_micros(700));
+                    tx.send(Instant::now()).unwrap();
+                }
+            });
+            let mut total = Duration::ZERO;
+            for _ in 0..50 {
+                total += profile.recv_timeout(&rx, Duration::from_secs(5)).unwrap().elapsed();
+            }
+            sender.join().unwrap();
+            assert_eq!(profile.recv_timeout(&rx, Duration::from_millis(5)), Err(RecvTimeoutError::Disconnected));
+            total / 50
+        };
+        let low_latency = mean_delay(LatencyProfile::LowLatency);
+        let power_saver = mean_delay(LatencyProfile::PowerSaver);
+        assert!(low_latency < power_saver, "low-latency {low_latency:?}, power-saver {power_saver:?}");
+
+        for profile in LatencyProfile::ALL {
+            assert_eq!(profile.name().parse::<LatencyProfile>(), Ok(profile));
+            let (_tx, rx) = unbounded::<()>();
+            let start = Instant::now();
+            assert_eq!(profile.recv_timeout(&rx, Duration::from_millis(20)), Err(RecvTimeoutError::Timeout));
+            assert!(start.elapsed() >= Duration::from_millis(20), "{profile}: {:?}", start.elapsed());
+
+            let cell = LatencyProfileCell::default();
+            assert_eq!(cell.get(), LatencyProfile::Balanced);
+            cell.set(profile);
+            assert_eq!(cell.get(), profile);
+        }
+    }
+}
@@ -0,0 +1,176 @@
+// live/latest_frame.rs
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crossbeam_channel::{bounded, Receiver, Sender, TrySendError};
+use parking_lot::{Mutex, RwLock};
+
+/// Buffers a `LatestFramePublisher` cycles through: the one readers get, the one a slow reader may
+/// still hold and the one being written.
+const FRAME_BUFFERS: usize = 3;
+
+/// Where the render loop publishes its output, see `LatestFramePublisher::shared`.
+pub type SharedLatestFrame = Arc<RwLock<Option<LatestFrame>>>;
+
+/// The most recent frame sent to the sink, for a UI or another consumer polling it at its own rate.
+/// Cloning it clones an `Arc`, not the pixels.
+#[derive(Debug, Clone)]
+pub struct LatestFrame {
+    /// Stream timestamp of the frame.
+    pub ts_us: i64,
+    pub width: usize,
+    pub height: usize,
+    /// 3 (RGB24) or 4 (RGBA), the sink's format.
+    pub bytes_per_pixel: usize,
+    /// `width * height * bytes_per_pixel` bytes, rows without padding.
+    pub data: Arc<Vec<u8>>,
+}
+
+/// Writer of a `SharedLatestFrame`, triple-buffered so the render loop never waits on readers.
+/// `StabilizationManager::live_latest_frame` is the one the render loop publishes to.
+///
+/// The frame is copied into a buffer no reader holds, outside of the lock; the write lock is only
+/// taken to swap it in. Readers take the read lock just as long as cloning the `LatestFrame`. A buffer
+/// is reused once every reader dropped it, and a reader that keeps frames around only costs a new
+/// allocation, never a stall. Meant for a single writer, the buffers of concurrent ones would alternate.
+///
+/// Nothing is copied until a reader shows up: a call to `shared` or `get`, or a subscriber.
+#[derive(Default)]
+pub struct LatestFramePublisher {
+    slot: SharedLatestFrame,
+    polled: AtomicBool,
+    // Only locked by the writer
+    spare: Mutex<Vec<Arc<Vec<u8>>>>,
+    subscribers: Mutex<Vec<Sender<LatestFrame>>>,
+}
+
+impl LatestFramePublisher {
+    /// The slot to poll, clone it into the UI or consumer thread.
+    pub fn shared(&self) -> SharedLatestFrame {
+        self.polled.store(true, Ordering::Relaxed);
+        self.slot.clone()
+    }
+
+    /// Every frame published from now on, for a consumer that wants all of them instead of polling
+    /// (see `LivePipeline::frame_stream`). A subscriber with `capacity` unread frames misses the next
+    /// ones, the render loop never waits for it. Dropping the receiver unsubscribes.
+    pub fn subscribe(&self, capacity: usize) -> Receiver<LatestFrame> {
+        let (tx, rx) = bounded(capacity.max(1));
+        self.subscribers.lock().push(tx);
+        rx
+    }
+
+    /// The most recent frame, `None` before the first one or after `clear`. The first call starts the
+    /// publishing, so it returns `None` until the next frame.
+    pub fn get(&self) -> Option<LatestFrame> {
+        self.polled.store(true, Ordering::Relaxed);
+        self.slot.read().clone()
+    }
+
+    pub fn publish(&self, ts_us: i64, size: (usize, usize), bytes_per_pixel: usize, buf: &[u8]) {
+        if !self.polled.load(Ordering::Relaxed) && self.subscribers.lock().is_empty() {
+            return;
+        }
+        let mut spare = self.spare.lock();
+        let mut data = match spare.iter().position(|b| Arc::strong_count(b) == 1) {
+            Some(pos) => spare.swap_remove(pos),
+            None => Arc::new(Vec::with_capacity(buf.len())),
+        };
+        let bytes = Arc::get_mut(&mut data).expect("spare frame buffer still shared");
+        bytes.clear();
+        bytes.extend_from_slice(buf);
+
+        let frame = LatestFrame { ts_us, width: size.0, height: size.1, bytes_per_pixel, data: data.clone() };
+        self.subscribers.lock().retain(|tx| !matches!(tx.try_send(frame.clone()), Err(TrySendError::Disconnected(_))));
+        // The previous frame's buffer is one of the spares, readers still holding it keep it alive
+        *self.slot.write() = Some(frame);
+        spare.push(data.clone());
+        if spare.len() > FRAME_BUFFERS {
+            // Buffers a reader keeps beyond the triple are left to it
+            spare.retain(|b| Arc::strong_count(b) == 1 || Arc::ptr_eq(b, &data));
+        }
+    }
+
+    /// Clear the slot, e.g. when the stream stops, so readers don't keep showing a stale frame.
+    pub fn clear(&self) { *self.slot.write() = None; }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+    use std::time::Duration;
+    use crate::live::{live_manager, LivePipeline};
+
+    #[test]
+    fn latest_frame_readers_see_updates_while_the_renderer_writes() {
+        let stab = live_manager();
+        let pipeline = LivePipeline::new(stab.clone(), None);
+        assert!(pipeline.latest_frame().is_none());
+
+        const FRAMES: i64 = 200;
+        let (w, h) = (16, 8);
+        let done = Arc::new(AtomicBool::new(false));
+        let reader = {
+            let slot = stab.live_latest_frame.shared();
+            let done = done.clone();
+            thread::spawn(move || {
+                let mut seen = Vec::new();
+                let mut held = Vec::new();
+                while !done.load(Ordering::Acquire) {
+                    thread::yield_now();
+                    let latest = slot.read().clone();
+                    if let Some(frame) = latest {
+                        // Every byte of a frame is its index, a torn frame would mix two
+                        assert_eq!((frame.width, frame.height, frame.bytes_per_pixel), (w, h, 4));
+                        assert_eq!(frame.data.len(), w * h * 4);
+                        assert!(frame.data.iter().all(|&b| b == frame.ts_us as u8), "frame {} torn", frame.ts_us);
+                        if seen.last() == Some(&frame.ts_us) { continue; }
+                        seen.push(frame.ts_us);
+                        // Some readers hold on to frames, the writer must not reuse those buffers
+                        if seen.len() % 7 == 0 { held.push(frame); }
+                    }
+                }
+                for frame in held {
+                    assert!(frame.data.iter().all(|&b| b == frame.ts_us as u8));
+                }
+                seen
+            })
+        };
+
+        let publisher = &stab.live_latest_frame;
+        for ts in 0..FRAMES {
+            publisher.publish(ts, (w, h), 4, &vec![ts as u8; w * h * 4]);
+            thread::sleep(Duration::from_micros(200));
+        }
+        done.store(true, Ordering::Release);
+        let seen = reader.join().unwrap();
+
+        assert!(seen.len() > 1, "the reader saw {} frames", seen.len());
+        assert!(seen.windows(2).all(|p| p[0] < p[1]), "frames went back in time: {seen:?}");
+        assert_eq!(pipeline.latest_frame().unwrap().ts_us, FRAMES - 1);
+
+        publisher.clear();
+        assert!(pipeline.latest_frame().is_none());
+    }
+
+    #[test]
+    fn frames_are_only_copied_once_someone_reads_them() {
+        let publisher = LatestFramePublisher::default();
+        publisher.publish(0, (2, 2), 4, &[0; 16]);
+        assert!(publisher.spare.lock().is_empty(), "copied a frame nobody reads");
+
+        let rx = publisher.subscribe(1);
+        publisher.publish(1, (2, 2), 4, &[1; 16]);
+        assert_eq!(rx.try_recv().unwrap().ts_us, 1);
+        // The subscriber left: dropped at the next frame, which is the last one copied
+        drop(rx);
+        publisher.publish(2, (2, 2), 4, &[2; 16]);
+        publisher.publish(3, (2, 2), 4, &[3; 16]);
+        assert_eq!(publisher.slot.read().as_ref().unwrap().ts_us, 2);
+
+        let slot = publisher.shared();
+        publisher.publish(4, (2, 2), 4, &[4; 16]);
+        assert_eq!(slot.read().as_ref().unwrap().ts_us, 4);
+    }
+}
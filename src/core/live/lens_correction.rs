@@ -0,0 +1,22 @@
+// live/lens_correction.rs
+
+/// Transition of the lens correction amount over stream time, see `StabilizationManager::set_live_lens_correction_amount`.
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct LensCorrectionRamp {
+    pub from: f64,
+    pub to: f64,
+    /// Frame timestamp the ramp starts at.
+    pub start_ms: f64,
+    pub duration_ms: f64,
+}
+
+impl LensCorrectionRamp {
+    /// Amount for a frame: `from` before the start, `to` after the end, linear in between.
+    pub fn amount_at(&self, timestamp_ms: f64) -> f64 {
+        if self.duration_ms <= 0.0 { return self.to; }
+        let t = ((timestamp_ms - self.start_ms) / self.duration_ms).clamp(0.0, 1.0);
+        self.from + (self.to - self.from) * t
+    }
+
+    pub fn end_ms(&self) -> f64 { self.start_ms + self.duration_ms.max(0.0) }
+}
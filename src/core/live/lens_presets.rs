@@ -0,0 +1,92 @@
+// live/lens_presets.rs
+use std::collections::BTreeMap;
+
+use parking_lot::RwLock;
+
+use crate::lens_profile::LensProfile;
+
+/// Named lens profiles of a live stream, for a camera with swappable lenses or a PTZ head with a few
+/// fixed zoom positions. `StabilizationManager::live_lens_presets` is the registry
+/// `add_live_lens_preset` fills and `activate_live_lens_preset` switches between.
+#[derive(Default)]
+pub struct LensPresets {
+    presets: RwLock<BTreeMap<String, LensProfile>>,
+    active: RwLock<Option<String>>,
+}
+
+impl LensPresets {
+    /// Register `profile` as `name`, replacing a preset of the same name. An active preset replaced
+    /// this way keeps the previous profile until it is activated again.
+    pub fn insert(&self, name: &str, profile: LensProfile) {
+        self.presets.write().insert(name.to_string(), profile);
+    }
+
+    pub fn remove(&self, name: &str) -> Option<LensProfile> {
+        let removed = self.presets.write().remove(name);
+        let mut active = self.active.write();
+        if active.as_deref() == Some(name) { *active = None; }
+        removed
+    }
+
+    pub fn get(&self, name: &str) -> Option<LensProfile> { self.presets.read().get(name).cloned() }
+
+    /// Preset names in alphabetical order.
+    pub fn names(&self) -> Vec<String> { self.presets.read().keys().cloned().collect() }
+
+    /// The preset last activated, `None` before that or once the lens was replaced by other means.
+    pub fn active(&self) -> Option<String> { self.active.read().clone() }
+
+    pub(crate) fn set_active(&self, name: Option<&str>) { *self.active.write() = name.map(str::to_string); }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::StabilizationManager;
+    use crate::live::live_manager;
+    use crate::stabilization::{ComputeParams, FrameTransform};
+
+    #[test]
+    fn lens_presets_switch_the_distortion_model() {
+        let stab = live_manager();
+        let preset = |model: &str, coeffs: Vec<f64>| {
+            let mut lens = LensProfile::default();
+            lens.distortion_model = Some(model.into());
+            lens.fisheye_params.camera_matrix = vec![[1000.0, 0.0, 960.0], [0.0, 1000.0, 540.0], [0.0, 0.0, 1.0]];
+            lens.fisheye_params.distortion_coeffs = coeffs;
+            lens
+        };
+        stab.add_live_lens_preset("wide", preset("opencv_fisheye", vec![-0.5, 0.0, 0.0, 0.0]));
+        stab.add_live_lens_preset("insta", preset("insta360", vec![-0.05, 0.01, 0.0, 0.001, -0.001, 0.9]));
+        stab.add_live_lens_preset("broken", preset("insta360", vec![-0.05, 0.01, 0.0, 0.001, -0.001]));
+        assert_eq!(stab.live_lens_presets.names(), ["broken", "insta", "wide"]);
+
+        let active = |stab: &StabilizationManager| {
+            let params = ComputeParams::from_manager(stab);
+            let (_, coeffs, r_limit, ..) = FrameTransform::get_lens_data_at_timestamp(&params, 0.0, false);
+            let expected = params.distortion_model.radial_distortion_limit(&coeffs).unwrap_or_default();
+            assert!((r_limit - expected).abs() < 1e-9, "r_limit {r_limit} not recomputed, expected {expected}");
+            (params.distortion_model.id(), r_limit)
+        };
+
+        stab.activate_live_lens_preset("wide").unwrap();
+        let (model, r_limit) = active(&stab);
+        assert_eq!(model, "opencv_fisheye");
+        assert!(r_limit > 0.0);
+        assert_eq!(stab.live_lens_presets.active().as_deref(), Some("wide"));
+
+        stab.activate_live_lens_preset("insta").unwrap();
+        assert_eq!(active(&stab).0, "insta360");
+        assert_eq!(stab.live_lens_presets.active().as_deref(), Some("insta"));
+
+        // Unknown or invalid presets keep the active lens
+        assert!(stab.activate_live_lens_preset("tele").is_err());
+        assert!(stab.activate_live_lens_preset("broken").is_err());
+        assert_eq!(active(&stab).0, "insta360");
+        assert_eq!(stab.lens.read().fisheye_params.distortion_coeffs.len(), 6);
+        assert_eq!(stab.live_lens_presets.active().as_deref(), Some("insta"));
+
+        stab.activate_live_lens_preset("wide").unwrap();
+        assert_eq!(active(&stab).0, "opencv_fisheye");
+    }
+}
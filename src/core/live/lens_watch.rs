@@ -0,0 +1,136 @@
+// live/lens_watch.rs
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use log::{debug, info, warn};
+use parking_lot::Mutex;
+
+use crate::StabilizationManager;
+use crate::lens_profile::LensProfile;
+
+/// Modification time and length of the watched file, a change in either triggers a reload.
+type FileStamp = (SystemTime, u64);
+
+#[derive(Default)]
+struct WatchState {
+    /// Stamp of the last profile applied (or of the file when the watcher was created).
+    applied: Option<FileStamp>,
+    /// Stamp of the last content that didn't parse, warned about once.
+    rejected: Option<FileStamp>,
+}
+
+/// Reloads a lens profile JSON file into the live stabilizer whenever it changes on disk, for
+/// iterating on a calibration while looking at the live output.
+///
+/// The file is polled by modification time and length. A changed file is only applied once it
+/// parses into a profile with a camera matrix and passes the live lens checks, so a half-written
+/// file is skipped and retried on the next poll, and the active lens stays as it was meanwhile.
+pub struct LensProfileWatcher {
+    path: PathBuf,
+    state: Mutex<WatchState>,
+    running: Arc<AtomicBool>,
+}
+
+impl LensProfileWatcher {
+    /// Watch `path`. The current content counts as already applied, only later changes are loaded.
+    pub fn new(path: impl Into<PathBuf>) -> Arc<Self> {
+        let path = path.into();
+        let state = WatchState { applied: Self::stamp(&path), rejected: None };
+        Arc::new(Self { path, state: Mutex::new(state), running: Arc::new(AtomicBool::new(false)) })
+    }
+
+    pub fn path(&self) -> &Path { &self.path }
+
+    fn stamp(path: &Path) -> Option<FileStamp> {
+        let meta = std::fs::metadata(path).ok()?;
+        Some((meta.modified().ok()?, meta.len()))
+    }
+
+    fn parse(path: &Path) -> Result<LensProfile, String> {
+        let data = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let lens = LensProfile::from_json(&data).map_err(|e| e.to_string())?;
+        if lens.fisheye_params.camera_matrix.len() != 3 {
+            return Err("no camera matrix".into());
+        }
+        Ok(lens)
+    }
+
+    /// Check the file once and apply it to `stab` if it changed. Returns `true` when a new profile was applied.
+    pub fn poll(&self, stab: &StabilizationManager) -> bool {
+        let Some(stamp) = Self::stamp(&self.path) else { return false };
+        let mut state = self.state.lock();
+        if state.applied == Some(stamp) { return false; }
+
+        match Self::parse(&self.path).and_then(|lens| stab.reload_live_lens_profile(lens).map_err(|e| e.to_string())) {
+            Ok(()) => {
+                info!("live: lens profile reloaded from {}", self.path.display());
+                *state = WatchState { applied: Some(stamp), rejected: None };
+                true
+            }
+            Err(e) => {
+                // Most likely caught mid-write, the final write changes the stamp again
+                if state.rejected != Some(stamp) {
+                    warn!("live: lens profile {} not reloaded, keeping the current one: {e}", self.path.display());
+                    state.rejected = Some(stamp);
+                } else {
+                    debug!("live: lens profile {} still not loadable", self.path.display());
+                }
+                false
+            }
+        }
+    }
+
+    /// Run `poll` every `period` on a background thread until `stop`.
+    pub fn spawn(self: &Arc<Self>, stab: Arc<StabilizationManager>, period: Duration) -> thread::JoinHandle<()> {
+        let this = Arc::clone(self);
+        self.running.store(true, Ordering::Relaxed);
+        thread::Builder::new()
+            .name("live_lens_watch".into())
+            .spawn(move || {
+                while this.running.load(Ordering::Relaxed) {
+                    thread::sleep(period);
+                    this.poll(&stab);
+                }
+            })
+            .expect("spawn live lens watcher")
+    }
+
+    pub fn stop(&self) { self.running.store(false, Ordering::Relaxed); }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::live::live_manager;
+
+    #[test]
+    fn lens_watcher_reloads_the_changed_profile() {
+        let profile = |k1: f64| format!(r#"{{"camera_brand":"Test","lens_model":"Watched","calib_dimension":{{"w":1920,"h":1080}},"fisheye_params":{{"camera_matrix":[[1000,0,960],[0,1000,540],[0,0,1]],"distortion_coeffs":[{k1},0.01,0,0]}}}}"#);
+        let path = std::env::temp_dir().join(format!("gyroflow_live_lens_{}.json", std::process::id()));
+        std::fs::write(&path, profile(0.1)).unwrap();
+
+        let stab = live_manager();
+        let watcher = LensProfileWatcher::new(&path);
+        // Unchanged since the watcher was created
+        assert!(!watcher.poll(&stab));
+
+        std::fs::write(&path, profile(0.25)).unwrap();
+        assert!(watcher.poll(&stab));
+        assert_eq!(stab.lens.read().fisheye_params.distortion_coeffs, vec![0.25, 0.01, 0.0, 0.0]);
+        assert!(!watcher.poll(&stab));
+
+        // Caught mid-write: not applied, the previous coefficients stay active
+        let full = profile(0.4);
+        std::fs::write(&path, &full[..full.len() / 2]).unwrap();
+        assert!(!watcher.poll(&stab));
+        assert_eq!(stab.lens.read().fisheye_params.distortion_coeffs[0], 0.25);
+
+        std::fs::write(&path, &full).unwrap();
+        assert!(watcher.poll(&stab));
+        assert_eq!(stab.lens.read().fisheye_params.distortion_coeffs[0], 0.4);
+        let _ = std::fs::remove_file(&path);
+    }
+}
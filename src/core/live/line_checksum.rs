@@ -0,0 +1,82 @@
+// live/line_checksum.rs
+//
+// Optional integrity check of the IMU line protocol for noisy links (e.g. serial-over-TCP bridges),
+// shared with the standalone `LiveGyroGen` generator, which includes this file by path. Keep it free
+// of crate imports and external dependencies.
+
+/// Separates a record from its checksum: `1000,0.1,0,0,0,0,1*0d28585f`.
+pub const CHECKSUM_SEPARATOR: char = '*';
+
+/// CRC-32 (IEEE 802.3, the one of zlib and Ethernet) of `data`.
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+/// `record` followed by `*` and its CRC-32 as 8 hex digits.
+pub fn append_checksum(record: &str) -> String {
+    format!("{record}{CHECKSUM_SEPARATOR}{:08x}", crc32(record.as_bytes()))
+}
+
+/// The record of a line without its checksum. A line without one is taken as is, loggers don't
+/// have to send it. `None` when the checksum doesn't match the record or isn't 8 hex digits.
+pub fn strip_checksum(line: &str) -> Option<&str> {
+    let Some((record, checksum)) = line.rsplit_once(CHECKSUM_SEPARATOR) else {
+        return Some(line);
+    };
+    let checksum = checksum.trim();
+    if checksum.len() != 8 || !checksum.bytes().all(|b| b.is_ascii_hexdigit()) { return None; }
+    let expected = u32::from_str_radix(checksum, 16).ok()?;
+    (crc32(record.as_bytes()) == expected).then_some(record)
+}
+
+/// Checksum state of one stream. Once the stream sent a checksummed line, lines without one are
+/// rejected too: their `*` was corrupted away rather than never sent.
+#[derive(Debug, Default)]
+pub struct LineChecksums {
+    required: bool,
+}
+
+impl LineChecksums {
+    /// `strip_checksum` of `line`, `None` as well for a line without a checksum once they're required.
+    pub fn strip<'a>(&mut self, line: &'a str) -> Option<&'a str> {
+        if !line.contains(CHECKSUM_SEPARATOR) {
+            return (!self.required).then_some(line);
+        }
+        let record = strip_checksum(line)?;
+        self.required = true;
+        Some(record)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn line_checksum_round_trip() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        let line = append_checksum("1000,0.1,0,0,0,0,1");
+        assert_eq!(line, "1000,0.1,0,0,0,0,1*0d28585f");
+        assert_eq!(strip_checksum(&line), Some("1000,0.1,0,0,0,0,1"));
+        assert_eq!(strip_checksum(&line.to_uppercase()), Some("1000,0.1,0,0,0,0,1"));
+        assert_eq!(strip_checksum("1000,0.1,0,0,0,0,2*0d28585f"), None);
+        assert_eq!(strip_checksum("1000,0.1,0,0,0,0,1*0d28585"), None);
+        assert_eq!(strip_checksum("1000,0.1,0,0,0,0,1"), Some("1000,0.1,0,0,0,0,1"));
+
+        let mut checksums = LineChecksums::default();
+        assert_eq!(checksums.strip("1000,0.1,0,0,0,0,1"), Some("1000,0.1,0,0,0,0,1"));
+        assert_eq!(checksums.strip("1000,0.1,0,0,0,0,2*0d28585f"), None);
+        assert_eq!(checksums.strip("1000,0.1,0,0,0,0,1"), Some("1000,0.1,0,0,0,0,1"));
+        assert_eq!(checksums.strip(&line), Some("1000,0.1,0,0,0,0,1"));
+        // The stream sends checksums, a line without one lost its `*` on the way
+        assert_eq!(checksums.strip("1000,0.1,0,0,0,0,10d28585f"), None);
+        assert_eq!(checksums.strip(&line), Some("1000,0.1,0,0,0,0,1"));
+    }
+}
@@ -0,0 +1,103 @@
+// live/log_throttle.rs
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use log::Level;
+use parking_lot::Mutex;
+
+/// Period of `LOG_THROTTLE`.
+pub const DEFAULT_THROTTLE_PERIOD: Duration = Duration::from_secs(1);
+
+/// Shared by the hot-path log sites of the live pipeline (stream reader, render loop, STMap worker).
+pub static LOG_THROTTLE: LogThrottle = LogThrottle::new(DEFAULT_THROTTLE_PERIOD);
+
+/// Rate limit for log lines that can repeat every frame or sample while a problem persists.
+///
+/// Occurrences are grouped by a static key (the log site, not the formatted message). The first
+/// one is logged as is, the following ones within `period` only counted; the next occurrence after
+/// that logs with "occurred N times in the last X s" appended, N counting it too. So a sustained
+/// problem costs one line per period and key instead of one per occurrence. `flush` reports what is
+/// still held back when the problem stopped before the period ran out.
+pub struct LogThrottle {
+    period: Duration,
+    // (key, last logged, occurrences since), a handful of keys at most
+    sites: Mutex<Vec<(&'static str, Instant, u64)>>,
+}
+
+impl LogThrottle {
+    pub const fn new(period: Duration) -> Self {
+        Self { period, sites: Mutex::new(Vec::new()) }
+    }
+
+    pub fn warn(&self, key: &'static str, message: fmt::Arguments) { self.log(Level::Warn, key, message); }
+
+    pub fn error(&self, key: &'static str, message: fmt::Arguments) { self.log(Level::Error, key, message); }
+
+    pub fn log(&self, level: Level, key: &'static str, message: fmt::Arguments) {
+        let now = Instant::now();
+        let Some((count, since)) = self.observe_at(key, now) else { return; };
+        if count > 1 {
+            log::log!(level, "{message} (occurred {count} times in the last {:.1} s)", now.saturating_duration_since(since).as_secs_f64());
+        } else {
+            log::log!(level, "{message}");
+        }
+    }
+
+    /// Count an occurrence of `key` at `now`. `Some((occurrences, since))` when it is to be logged,
+    /// with the occurrences since the previous line, this one included.
+    pub fn observe_at(&self, key: &'static str, now: Instant) -> Option<(u64, Instant)> {
+        let mut sites = self.sites.lock();
+        let Some((_, last, count)) = sites.iter_mut().find(|(k, _, _)| *k == key) else {
+            sites.push((key, now, 0));
+            return Some((1, now));
+        };
+        *count += 1;
+        if now.saturating_duration_since(*last) < self.period {
+            return None;
+        }
+        let logged = (*count, *last);
+        *last = now;
+        *count = 0;
+        Some(logged)
+    }
+
+    /// Log the occurrences still held back per key and start over, e.g. when the render loop exits.
+    pub fn flush(&self) {
+        let now = Instant::now();
+        for (key, count, since) in self.take_held() {
+            log::warn!("{key}: occurred {count} more times in the last {:.1} s", now.saturating_duration_since(since).as_secs_f64());
+        }
+    }
+
+    /// Forget all keys. Returns the ones with occurrences held back since their last line: the key,
+    /// the occurrences and the time of that line.
+    pub fn take_held(&self) -> Vec<(&'static str, u64, Instant)> {
+        self.sites.lock().drain(..).filter(|(_, _, count)| *count > 0).map(|(key, last, count)| (key, count, last)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repeated_log_lines_are_summarized() {
+        let throttle = LogThrottle::new(Duration::from_secs(1));
+        let t0 = Instant::now();
+
+        // 100 errors within a second: only the first is logged
+        let logged: Vec<_> = (0..100).filter_map(|i| throttle.observe_at("send", t0 + Duration::from_millis(i * 9))).collect();
+        assert_eq!(logged, vec![(1, t0)]);
+        // Other sites aren't held back by it
+        assert_eq!(throttle.observe_at("decode", t0 + Duration::from_millis(500)), Some((1, t0 + Duration::from_millis(500))));
+
+        // The next one after the period carries the count, the 99 held back and itself
+        let t1 = t0 + Duration::from_millis(1200);
+        assert_eq!(throttle.observe_at("send", t1), Some((100, t0)));
+        assert_eq!(throttle.observe_at("send", t1 + Duration::from_millis(10)), None);
+
+        // The problem stops: the held occurrence is reported on flush, the quiet site isn't
+        assert_eq!(throttle.take_held(), vec![("send", 1, t1)]);
+        assert_eq!(throttle.observe_at("send", t1 + Duration::from_millis(30)), Some((1, t1 + Duration::from_millis(30))));
+    }
+}
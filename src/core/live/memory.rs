@@ -0,0 +1,72 @@
+// live/memory.rs
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::gyro_source::LiveImuSample;
+use super::LiveImuMsg;
+
+/// Bound on the growable structures of a live session, split between them in fixed shares.
+///
+/// Every component evicts its oldest data first when it reaches its share:
+/// - frame queue (50%): the render loop skips the oldest queued frames, the reader waits while the queue is full
+/// - STMap cache (20%): maps of the oldest frames are dropped
+/// - quaternion buffers (20%, half each for the original and smoothed store): the oldest buffers are dropped,
+///   the newest one is always kept
+/// - IMU (10%, half for the channel, half for the ring): the ring drops its oldest samples, shortening the
+///   integrated history below the smoothing window if the share is too small; the channel drops new samples
+///   while full (`LiveIngestSnapshot::dropped_samples`), the ones already queued are older
+///
+/// Shares are in bytes of payload (frame pixels, map EXRs, samples, quaternions), allocator overhead is not counted.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MemoryBudget {
+    pub total_bytes: usize,
+}
+
+impl MemoryBudget {
+    pub fn from_mb(mb: f64) -> Self {
+        Self { total_bytes: (mb.max(0.0) * 1024.0 * 1024.0) as usize }
+    }
+
+    fn share(&self, percent: usize) -> usize { self.total_bytes / 100 * percent }
+
+    pub fn frame_queue_bytes(&self) -> usize { self.share(50) }
+    pub fn map_cache_bytes(&self) -> usize { self.share(20) }
+    /// Per quaternion store, there is one for the original and one for the smoothed orientation.
+    pub fn quat_store_bytes(&self) -> usize { self.share(10) }
+    pub fn imu_ring_bytes(&self) -> usize { self.share(5) }
+    pub fn imu_channel_bytes(&self) -> usize { self.share(5) }
+
+    /// Frames of `frame_bytes` each that fit the frame queue share, at least one.
+    pub fn queued_frames(&self, frame_bytes: usize) -> usize { (self.frame_queue_bytes() / frame_bytes.max(1)).max(1) }
+    pub fn imu_ring_samples(&self) -> usize { (self.imu_ring_bytes() / std::mem::size_of::<LiveImuSample>()).max(1) }
+    pub fn imu_channel_msgs(&self) -> usize { (self.imu_channel_bytes() / std::mem::size_of::<LiveImuMsg>()).max(1) }
+}
+
+/// Current memory use of each live component, in bytes, see `LivePipeline::memory_usage`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MemoryUsage {
+    pub imu_channel: usize,
+    pub imu_ring: usize,
+    pub quat_buffers: usize,
+    pub map_cache: usize,
+    pub frame_queue: usize,
+}
+
+impl MemoryUsage {
+    pub fn total(&self) -> usize {
+        self.imu_channel + self.imu_ring + self.quat_buffers + self.map_cache + self.frame_queue
+    }
+}
+
+/// Usage of the components that live outside the core (decoded frames, STMap cache), reported by their owners.
+#[derive(Debug, Default)]
+pub struct MemoryGauges {
+    map_cache: AtomicUsize,
+    frame_queue: AtomicUsize,
+}
+
+impl MemoryGauges {
+    pub fn set_map_cache(&self, bytes: usize) { self.map_cache.store(bytes, Ordering::Relaxed); }
+    pub fn set_frame_queue(&self, bytes: usize) { self.frame_queue.store(bytes, Ordering::Relaxed); }
+    pub fn map_cache(&self) -> usize { self.map_cache.load(Ordering::Relaxed) }
+    pub fn frame_queue(&self) -> usize { self.frame_queue.load(Ordering::Relaxed) }
+}
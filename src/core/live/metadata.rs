@@ -0,0 +1,82 @@
+// live/metadata.rs
+use std::collections::BTreeMap;
+
+use serde_json::json;
+
+use crate::StabilizationManager;
+use crate::lens_profile::LensProfile;
+
+/// Prefix of the global metadata keys written into live recordings.
+pub const METADATA_PREFIX: &str = "gyroflow_";
+
+/// Stable 64-bit FNV-1a of the lens profile JSON (without the fields `get_json_value` strips),
+/// so a recording can be matched to the exact profile it was corrected with.
+pub fn lens_checksum(lens: &LensProfile) -> String {
+    let json = lens.get_json_value().map(|v| v.to_string()).unwrap_or_default();
+    let mut h = 0xcbf29ce484222325u64;
+    for b in json.bytes() {
+        h ^= b as u64;
+        h = h.wrapping_mul(0x100000001b3);
+    }
+    format!("{h:016x}")
+}
+
+/// Global metadata of a live recording: smoothing parameters and the lens, keyed `gyroflow_*`.
+/// The values are plain strings (JSON for the structured ones), as containers store them.
+pub fn recording_metadata(stab: &StabilizationManager) -> BTreeMap<String, String> {
+    let mut md = BTreeMap::new();
+    let mut put = |k: &str, v: String| { md.insert(format!("{METADATA_PREFIX}{k}"), v); };
+
+    put("version", env!("CARGO_PKG_VERSION").to_string());
+    let params = stab.params.read();
+    let window = stab.gyro.read().live.read().as_ref().map(|st| *st.window.read()).unwrap_or_default();
+    let algorithm = params.live_smoothing_algorithm.clone();
+    let parameters = algorithm.as_ref().map(|_| stab.smoothing.read().current().get_parameters_json());
+    put("smoothing", json!({
+        // `None` is the built-in blend of the live integration
+        "algorithm": algorithm,
+        "parameters": parameters,
+        "window": { "pre_ms": window.pre_ms, "post_ms": window.post_ms, "center_ratio": window.center_ratio },
+        "max_crop": params.live_max_crop,
+        "mount_correction": params.live_mount_correction,
+    }).to_string());
+    put("lens_correction_amount", params.lens_correction_amount.to_string());
+    drop(params);
+
+    let lens = stab.lens.read();
+    put("lens", lens.get_display_name());
+    put("lens_checksum", lens_checksum(&lens));
+    md
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::live::live_manager;
+
+    #[test]
+    fn recording_metadata_describes_smoothing_and_lens() {
+        let stab = live_manager();
+        {
+            let mut lens = stab.lens.write();
+            lens.calib_dimension = crate::lens_profile::Dimensions { w: 1920, h: 1080 };
+            lens.fisheye_params.camera_matrix = vec![[1000.0, 0.0, 960.0], [0.0, 1000.0, 540.0], [0.0, 0.0, 1.0]];
+            lens.fisheye_params.distortion_coeffs = vec![0.1, 0.01, 0.0, 0.0];
+        }
+        stab.set_live_smoothing_algorithm("Plain 3D");
+
+        let md = recording_metadata(&stab);
+        assert!(md.keys().all(|k| k.starts_with(METADATA_PREFIX)));
+        let smoothing: serde_json::Value = serde_json::from_str(&md["gyroflow_smoothing"]).unwrap();
+        assert_eq!(smoothing["algorithm"], "Plain 3D");
+        assert!(smoothing["parameters"].is_array() || smoothing["parameters"].is_object());
+        assert_eq!(smoothing["window"]["post_ms"], 500.0);
+
+        // The checksum follows the profile content
+        let checksum = md["gyroflow_lens_checksum"].clone();
+        assert_eq!(checksum.len(), 16);
+        assert_eq!(recording_metadata(&stab)["gyroflow_lens_checksum"], checksum);
+        stab.lens.write().fisheye_params.distortion_coeffs[0] = 0.2;
+        assert_ne!(recording_metadata(&stab)["gyroflow_lens_checksum"], checksum);
+    }
+}
@@ -0,0 +1,1871 @@
+// live/mod.rs
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::{bounded, unbounded, Receiver, RecvTimeoutError, SendError, Sender, TrySendError};
+use log::{debug, info, warn};
+
+use crate::StabilizationManager;
+use crate::gpu::{BufferDescription, BufferSource, Buffers};
+use crate::stabilization::PixelType;
+use crate::gyro_source::{ClockSyncState, LiveImuSample};
+
+#[cfg(feature = "live-async")]
+pub mod async_channel;
+pub mod backend;
+pub mod clock;
+pub mod clock_wrap;
+pub mod column_swap;
+pub mod crop;
+pub mod e2e_latency;
+pub mod error;
+pub mod frames;
+pub mod latency;
+pub mod latency_profile;
+pub mod latest_frame;
+pub mod lens_correction;
+pub mod lens_presets;
+pub mod lens_watch;
+pub mod line_checksum;
+pub mod log_throttle;
+pub mod memory;
+pub mod metadata;
+#[cfg(feature = "testing")]
+pub mod motion;
+pub mod no_imu;
+pub mod overlay;
+pub mod param_log;
+pub mod reorder;
+pub mod rotation_guard;
+pub mod sensor_stuck;
+pub mod session;
+#[cfg(feature = "testing")]
+pub mod simulator;
+pub mod source_info;
+pub mod stmap_dump;
+pub mod stmap_render;
+pub mod texture_pool;
+pub mod timing;
+pub mod trace;
+pub mod watchdog;
+
+#[cfg(feature = "live-async")]
+pub use async_channel::{AsyncReceiver, AsyncSender, Disconnected};
+pub use backend::{BackendProbe, ComputeFallback};
+pub use clock::{Clock, ManualClock, MonotonicClock, SharedClock, StreamPtsClock};
+pub use clock_wrap::ClockUnwrapper;
+pub use column_swap::{ColumnOrder, ColumnSwapDetector};
+pub use e2e_latency::{EndToEndLatencySnapshot, EndToEndLatencyStats};
+pub use error::LiveError;
+pub use frames::FrameTimeline;
+pub use latency::{Degradation, LatencySla, LatencySlaSnapshot, StreamClock};
+pub use latency_profile::{LatencyProfile, LatencyProfileCell};
+pub use latest_frame::{LatestFrame, LatestFramePublisher, SharedLatestFrame};
+pub use lens_correction::LensCorrectionRamp;
+pub use lens_presets::LensPresets;
+pub use lens_watch::LensProfileWatcher;
+pub use line_checksum::{append_checksum, strip_checksum, LineChecksums};
+pub use log_throttle::{LogThrottle, LOG_THROTTLE};
+pub use memory::{MemoryBudget, MemoryUsage};
+#[cfg(feature = "testing")]
+pub use motion::{MotionGen, MotionProfile};
+pub use no_imu::{NoImu, DEFAULT_NO_IMU_TIMEOUT};
+pub use overlay::LiveOverlay;
+pub use reorder::ImuReorderBuffer;
+pub use rotation_guard::{RotationSpikeGuard, RotationSpikeSnapshot};
+pub use sensor_stuck::{SensorStuckDetector, DEFAULT_STUCK_DURATION, DEFAULT_STUCK_NOISE_FLOOR};
+pub use session::{FrameCounts, LiveSessionSnapshot, LiveSessionStats};
+#[cfg(feature = "testing")]
+pub use simulator::{simulate_imu_stream, ImuSimConfig};
+pub use source_info::SourceInfo;
+pub use stmap_dump::LatestStmap;
+pub use stmap_render::{MapRenderBackend, MapRenderer};
+pub use texture_pool::{TextureKey, TexturePool, TexturePoolStats};
+pub use timing::{FrameTiming, RenderStage, RenderTimingSnapshot, RenderTimingStats, StageTimer};
+pub use watchdog::{Heartbeat, HeartbeatAge, Watchdog};
+
+/// IMU sample together with the video-clock time (µs) it was received at.
+pub type LiveImuMsg = (LiveImuSample, i64);
+
+/// Upper bound of samples pushed under one lock acquisition.
+const MAX_IMU_BATCH: usize = 4096;
+
+/// Counters of the IMU consumer.
+#[derive(Debug, Default)]
+pub struct LiveIngestStats {
+    samples: AtomicU64,
+    batches: AtomicU64,
+    columns_swapped: AtomicBool,
+    late_samples: AtomicU64,
+    dropped_samples: AtomicU64,
+    sensor_stuck: AtomicBool,
+    sensor_stuck_alerts: AtomicU64,
+    idle_integrations: AtomicU64,
+    bad_checksum_lines: AtomicU64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LiveIngestSnapshot {
+    /// Samples pushed into the live ring.
+    pub samples: u64,
+    /// Batches pushed, i.e. gyro lock acquisitions of the consumer.
+    pub batches: u64,
+    /// The gyro and accel columns were detected as swapped and are being swapped back.
+    pub columns_swapped: bool,
+    /// Samples dropped by the reorder stage for arriving too late, see `ImuReorderBuffer`.
+    pub late_samples: u64,
+    /// Samples dropped because the IMU channel was full, see `MemoryBudget`.
+    pub dropped_samples: u64,
+    /// The SensorStuck alert is raised: the IMU has been reading zero, see `SensorStuckDetector`.
+    pub sensor_stuck: bool,
+    /// Times the SensorStuck alert was raised.
+    pub sensor_stuck_alerts: u64,
+    /// Periodic integrations skipped because no sample arrived since the last one.
+    pub idle_integrations: u64,
+    /// IMU lines dropped because their checksum didn't match, see `LineChecksums`.
+    pub bad_checksum_lines: u64,
+}
+
+impl LiveIngestStats {
+    fn record(&self, samples: usize) {
+        self.samples.fetch_add(samples as u64, Ordering::Relaxed);
+        self.batches.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> LiveIngestSnapshot {
+        LiveIngestSnapshot {
+            samples: self.samples.load(Ordering::Relaxed),
+            batches: self.batches.load(Ordering::Relaxed),
+            columns_swapped: self.columns_swapped.load(Ordering::Relaxed),
+            late_samples: self.late_samples.load(Ordering::Relaxed),
+            dropped_samples: self.dropped_samples.load(Ordering::Relaxed),
+            sensor_stuck: self.sensor_stuck.load(Ordering::Relaxed),
+            sensor_stuck_alerts: self.sensor_stuck_alerts.load(Ordering::Relaxed),
+            idle_integrations: self.idle_integrations.load(Ordering::Relaxed),
+            bad_checksum_lines: self.bad_checksum_lines.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Confidence below which a frame counts as low-confidence in `LiveConfidenceStats`.
+pub const LOW_CONFIDENCE: f64 = 0.5;
+
+/// Per-frame confidence of the live renders, see `GyroSource::live_confidence_at_timestamp`.
+#[derive(Debug, Default)]
+pub struct LiveConfidenceStats {
+    frames: AtomicU64,
+    low: AtomicU64,
+    last: AtomicU64, // f64 bits
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct LiveConfidenceSnapshot {
+    /// Frames rendered with a confidence.
+    pub frames: u64,
+    /// Frames under `LOW_CONFIDENCE`.
+    pub low: u64,
+    /// Confidence of the last frame.
+    pub last: f64,
+}
+
+impl LiveConfidenceStats {
+    pub fn record(&self, confidence: f64) {
+        self.frames.fetch_add(1, Ordering::Relaxed);
+        if confidence < LOW_CONFIDENCE {
+            self.low.fetch_add(1, Ordering::Relaxed);
+        }
+        self.last.store(confidence.to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> LiveConfidenceSnapshot {
+        LiveConfidenceSnapshot {
+            frames: self.frames.load(Ordering::Relaxed),
+            low: self.low.load(Ordering::Relaxed),
+            last: f64::from_bits(self.last.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+/// Ingestion side of the live pipeline.
+///
+/// Owns the IMU channel (the same one the TCP line server feeds), a consumer thread that drains
+/// whatever samples are queued and pushes them into the live ring in one go via
+/// `GyroSource::push_live_imu_batch`, and the periodic `integrate_live_data` trigger.
+/// A burst (e.g. after a network stall) therefore costs one lock acquisition and one integration.
+/// The integration period is measured on the live clock, `StabilizationManager::live_clock`.
+///
+/// Thread-safety: `LivePipeline` is `Send + Sync`. `push_imu` only sends into a crossbeam channel,
+/// so it can be called from any number of threads at once (e.g. through an `Arc<LivePipeline>`).
+/// Samples pushed from one thread keep their order; samples from different threads are interleaved
+/// in arrival order, unless `LivePipelineBuilder::imu_reorder_window` merges them by timestamp.
+/// The gyro lock is only taken by the consumer thread, never by the caller.
+pub struct LivePipeline {
+    stab: Arc<StabilizationManager>,
+    imu_tx: Sender<LiveImuMsg>,
+    running: Arc<AtomicBool>,
+    ingest: Arc<LiveIngestStats>,
+    no_imu_timeout: Duration,
+    _consumer: thread::JoinHandle<()>,
+}
+
+/// Default period of `integrate_live_data` while streaming.
+pub const DEFAULT_INTEGRATE_PERIOD: Duration = Duration::from_millis(10);
+
+/// Builder for `LivePipeline`, see `LivePipeline::builder`.
+pub struct LivePipelineBuilder {
+    stab: Arc<StabilizationManager>,
+    integrate_period: Option<Duration>,
+    fallback: ComputeFallback,
+    memory_budget: Option<MemoryBudget>,
+    auto_detect_column_swap: bool,
+    imu_reorder_window: Option<Duration>,
+    no_imu: Option<(NoImu, Duration)>,
+    stuck_sensor: Option<(f64, Duration)>,
+    integrate_when_idle: bool,
+}
+
+impl LivePipelineBuilder {
+    /// How often to run `integrate_live_data`.
+    pub fn integrate_period(mut self, period: Duration) -> Self {
+        self.integrate_period = Some(period);
+        self
+    }
+
+    /// Keep integrating every period while no IMU sample arrives. Off by default: an idle period is
+    /// skipped (see `LiveIngestSnapshot::idle_integrations`), so changes of the smoothing parameters
+    /// only reach the quaternion stores with the next sample.
+    pub fn integrate_when_idle(mut self, enabled: bool) -> Self {
+        self.integrate_when_idle = enabled;
+        self
+    }
+
+    /// Only buffer samples, e.g. when the quaternions are loaded from a file instead.
+    pub fn without_integration(mut self) -> Self {
+        self.integrate_period = None;
+        self
+    }
+
+    pub fn compute_fallback(mut self, fallback: ComputeFallback) -> Self {
+        self.fallback = fallback;
+        self
+    }
+
+    /// Bound the IMU channel, IMU ring and quaternion stores by their shares of `budget`.
+    /// The frame queue and STMap cache are owned by the caller, which sizes them from the same budget.
+    pub fn memory_budget(mut self, budget: MemoryBudget) -> Self {
+        self.memory_budget = Some(budget);
+        self
+    }
+
+    /// Check during warm-up whether the logger swapped the gyro and accel columns, and swap them back
+    /// if so, see `ColumnSwapDetector`. Samples are held back until that's decided.
+    pub fn auto_detect_column_swap(mut self, enabled: bool) -> Self {
+        self.auto_detect_column_swap = enabled;
+        self
+    }
+
+    /// Merge the samples of several IMU clients in timestamp order, holding them back for `window`
+    /// of sensor time. Later samples are dropped and counted in `LiveIngestSnapshot::late_samples`.
+    pub fn imu_reorder_window(mut self, window: Duration) -> Self {
+        self.imu_reorder_window = Some(window);
+        self
+    }
+
+    /// Raise the SensorStuck alert when every IMU reading stays within `noise_floor` of zero for `duration`,
+    /// see `SensorStuckDetector`. On by default with `DEFAULT_STUCK_NOISE_FLOOR` and `DEFAULT_STUCK_DURATION`.
+    pub fn stuck_sensor_detection(mut self, noise_floor: f64, duration: Duration) -> Self {
+        self.stuck_sensor = Some((noise_floor, duration));
+        self
+    }
+
+    pub fn without_stuck_sensor_detection(mut self) -> Self {
+        self.stuck_sensor = None;
+        self
+    }
+
+    /// What to output while no IMU sample arrived, and how long `LivePipeline::wait_for_imu` waits for one.
+    pub fn no_imu_behavior(mut self, behavior: NoImu, timeout: Duration) -> Self {
+        self.no_imu = Some((behavior, timeout));
+        self
+    }
+
+    pub fn start(self) -> Result<LivePipeline, LiveError> {
+        LivePipeline::start_with(self)
+    }
+}
+
+impl LivePipeline {
+    pub fn builder(stab: Arc<StabilizationManager>) -> LivePipelineBuilder {
+        LivePipelineBuilder { stab, integrate_period: Some(DEFAULT_INTEGRATE_PERIOD), fallback: ComputeFallback::default(), memory_budget: None, auto_detect_column_swap: false, imu_reorder_window: None, no_imu: None, stuck_sensor: Some((DEFAULT_STUCK_NOISE_FLOOR, DEFAULT_STUCK_DURATION)), integrate_when_idle: false }
+    }
+
+    /// Start the IMU consumer.
+    /// - integrate_period: how often to run `integrate_live_data`, `None` to only buffer samples
+    ///   (e.g. when the quaternions are loaded from a file instead)
+    pub fn new(stab: Arc<StabilizationManager>, integrate_period: Option<Duration>) -> Self {
+        Self::spawn(stab, integrate_period, None, false, None, Some((DEFAULT_STUCK_NOISE_FLOOR, DEFAULT_STUCK_DURATION)), false)
+    }
+
+    /// `imu_capacity` bounds the IMU channel, `push_imu` drops samples while it's full.
+    fn spawn(stab: Arc<StabilizationManager>, integrate_period: Option<Duration>, imu_capacity: Option<usize>, auto_detect_column_swap: bool, reorder_window: Option<Duration>, stuck_sensor: Option<(f64, Duration)>, integrate_when_idle: bool) -> Self {
+        let (imu_tx, imu_rx) = match imu_capacity {
+            Some(cap) => bounded::<LiveImuMsg>(cap),
+            None => unbounded::<LiveImuMsg>(),
+        };
+        let running = Arc::new(AtomicBool::new(true));
+        let ingest = Arc::new(LiveIngestStats::default());
+
+        let consumer = {
+            let stab = stab.clone();
+            let running = running.clone();
+            let ingest = ingest.clone();
+            thread::Builder::new()
+                .name("live_imu_consumer".into())
+                .spawn(move || {
+                    let reorder = reorder_window.map(|w| ImuReorderBuffer::new(w.as_micros() as i64));
+                    let stuck_sensor = stuck_sensor.map(|(noise_floor, duration)| SensorStuckDetector::new(noise_floor, duration));
+                    Self::consumer_loop(stab, imu_rx, integrate_period.map(|p| (p, integrate_when_idle)), running, ingest, auto_detect_column_swap.then(ColumnSwapDetector::new), reorder, stuck_sensor)
+                })
+                .expect("spawn live imu consumer")
+        };
+
+        Self { stab, imu_tx, running, ingest, no_imu_timeout: DEFAULT_NO_IMU_TIMEOUT, _consumer: consumer }
+    }
+
+    /// Probe the compute backends once, then start the IMU consumer.
+    /// Without a usable GPU either switches the stabilizer to the CPU path or fails with
+    /// `LiveError::NoComputeBackend`, depending on `fallback`.
+    pub fn start(stab: Arc<StabilizationManager>, integrate_period: Option<Duration>, fallback: ComputeFallback) -> Result<Self, LiveError> {
+        Self::start_with(LivePipelineBuilder { integrate_period, fallback, ..Self::builder(stab) })
+    }
+
+    fn start_with(builder: LivePipelineBuilder) -> Result<Self, LiveError> {
+        let LivePipelineBuilder { stab, integrate_period, fallback, memory_budget: budget, auto_detect_column_swap, imu_reorder_window, no_imu, stuck_sensor, integrate_when_idle } = builder;
+        let probe = backend::probe_compute_backends();
+        Self::apply_backend_probe(&stab, &probe, fallback)?;
+        if let Some(budget) = &budget {
+            Self::apply_memory_budget(&stab, budget);
+        }
+        let mut pipeline = Self::spawn(stab, integrate_period, budget.map(|b| b.imu_channel_msgs()), auto_detect_column_swap, imu_reorder_window, stuck_sensor, integrate_when_idle);
+        if let Some((behavior, timeout)) = no_imu {
+            pipeline.stab.set_live_no_imu_behavior(behavior);
+            pipeline.no_imu_timeout = timeout;
+        }
+        Ok(pipeline)
+    }
+
+    fn apply_memory_budget(stab: &StabilizationManager, budget: &MemoryBudget) {
+        info!("live: memory budget {:.1} MB", budget.total_bytes as f64 / (1024.0 * 1024.0));
+        stab.gyro.write().set_live_memory_limits(crate::gyro_source::LiveMemoryLimits {
+            imu_samples: budget.imu_ring_samples(),
+            quat_store_bytes: budget.quat_store_bytes(),
+        });
+    }
+
+    fn apply_backend_probe(stab: &StabilizationManager, probe: &BackendProbe, fallback: ComputeFallback) -> Result<(), LiveError> {
+        if let Some(selected) = probe.selected() {
+            info!("live: using {selected}");
+            return Ok(());
+        }
+        let attempted = probe.attempted_names();
+        match fallback {
+            ComputeFallback::Cpu => {
+                warn!("live: no GPU backend (tried: {}), rendering on the CPU", attempted.join(", "));
+                stab.set_device(-1);
+                Ok(())
+            }
+            ComputeFallback::Fail => Err(LiveError::NoComputeBackend(attempted)),
+        }
+    }
+
+    /// Sender for the IMU channel. It waits while a bounded channel is full, unlike `push_imu` which drops.
+    pub fn imu_sender(&self) -> Sender<LiveImuMsg> {
+        self.imu_tx.clone()
+    }
+
+    /// Feed a sample directly into the IMU channel, bypassing the network.
+    /// `video_us` is the video-clock time the sample belongs to.
+    /// When the channel is full (see `MemoryBudget`) the sample is dropped and counted in `LiveIngestSnapshot::dropped_samples`.
+    pub fn push_imu(&self, sample: LiveImuSample, video_us: i64) -> Result<(), SendError<LiveImuMsg>> {
+        match self.imu_tx.try_send((sample, video_us)) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Full(_)) => {
+                self.ingest.dropped_samples.fetch_add(1, Ordering::Relaxed);
+                Ok(())
+            }
+            Err(TrySendError::Disconnected(msg)) => Err(SendError(msg)),
+        }
+    }
+
+    /// `push_imu` at the current time of the live clock, e.g. the frame PTS of a `StreamPtsClock`.
+    pub fn push_imu_now(&self, sample: LiveImuSample) -> Result<(), SendError<LiveImuMsg>> {
+        self.push_imu(sample, self.stab.live_clock().now_us())
+    }
+
+    /// Async version of `imu_sender`, for feeding samples from async code with `.await`.
+    #[cfg(feature = "live-async")]
+    pub fn imu_sink(&self) -> AsyncSender<LiveImuMsg> {
+        AsyncSender::new(self.imu_tx.clone())
+    }
+
+    /// Frames the render loop sends to the sink, as an async stream. Up to `capacity` frames wait for
+    /// the consumer, later ones are skipped until it catches up (see `LatestFramePublisher::subscribe`).
+    #[cfg(feature = "live-async")]
+    pub fn frame_stream(&self, capacity: usize) -> AsyncReceiver<LatestFrame> {
+        AsyncReceiver::new(self.stab.live_latest_frame.subscribe(capacity))
+    }
+
+    pub fn stab(&self) -> &Arc<StabilizationManager> { &self.stab }
+
+    pub fn stop(&self) { self.running.store(false, Ordering::Relaxed); }
+
+    pub fn is_running(&self) -> bool { self.running.load(Ordering::Relaxed) }
+
+    pub fn ingest_stats(&self) -> LiveIngestSnapshot { self.ingest.snapshot() }
+
+    /// Count an IMU line the receiver dropped for a bad checksum in `LiveIngestSnapshot::bad_checksum_lines`.
+    pub fn record_bad_checksum_line(&self) { self.ingest.bad_checksum_lines.fetch_add(1, Ordering::Relaxed); }
+
+    /// Block until the first IMU sample reached the ring, for at most the `no_imu_behavior` timeout
+    /// of the live clock (see `StabilizationManager::use_live_clock`).
+    /// `Ok(false)` when none came and the stream goes on per `StabilizationParams::live_no_imu`,
+    /// `LiveError::NoImu` when that is `NoImu::Error`.
+    pub fn wait_for_imu(&self) -> Result<bool, LiveError> {
+        let clock = self.stab.live_clock();
+        let deadline = clock.now_us() + self.no_imu_timeout.as_micros() as i64;
+        while self.ingest.snapshot().samples == 0 {
+            if clock.now_us() >= deadline {
+                let behavior = self.stab.params.read().live_no_imu;
+                if behavior == NoImu::Error {
+                    return Err(LiveError::NoImu(self.no_imu_timeout));
+                }
+                warn!("live: no IMU sample within {:?}, continuing with {behavior:?}", self.no_imu_timeout);
+                return Ok(false);
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        Ok(true)
+    }
+
+    /// Stabilize a blank `w`x`h` frame of `T` once, so the one-time cost of the first `process_pixels`
+    /// (kernel compilation, GPU buffers, lens tables) is paid at startup instead of on the first live frame.
+    /// Sets the render parameters up for `w`x`h` unless they already are. Returns how long it took.
+    /// Call it once the lens is loaded (the kernels depend on its distortion model), from the thread that
+    /// renders the live frames: the GPU backends cache their kernels per thread.
+    pub fn prewarm<T: PixelType>(&self, w: usize, h: usize) -> Result<Duration, LiveError> {
+        let start = Instant::now();
+        if self.stab.stabilization.read().size != (w, h) {
+            self.stab.set_render_params((w, h), (w, h));
+        }
+        let (ow, oh) = self.stab.live_output_buffer_size();
+        let bpp = T::COUNT * T::SCALAR_BYTES;
+        let mut input = vec![0u8; w * h * bpp];
+        let mut output = vec![0u8; ow * oh * bpp];
+        let mut buffers = Buffers {
+            input:  BufferDescription { size: (w, h, w * bpp), rect: None, rotation: None, data: BufferSource::Cpu { buffer: &mut input }, texture_copy: false },
+            output: BufferDescription { size: (ow, oh, ow * bpp), rect: None, rotation: None, data: BufferSource::Cpu { buffer: &mut output }, texture_copy: false },
+        };
+        let processed = self.stab.process_pixels::<T>(0, None, &mut buffers)?;
+        let elapsed = start.elapsed();
+        info!("live: pre-warmed {} for {w}x{h} in {:.1} ms", processed.backend, elapsed.as_secs_f64() * 1000.0);
+        Ok(elapsed)
+    }
+
+    /// Write the most recent STMap pair of the `StmapsLive` worker to `dir`, for inspecting the correction in
+    /// Nuke, Fusion and the like. Without a worker the maps of the last rendered frame are built on the calling thread.
+    /// Returns the (undistort, redistort) paths, `LiveError::NoStmap` before the first frame.
+    pub fn dump_current_stmaps(&self, dir: impl AsRef<std::path::Path>) -> Result<(std::path::PathBuf, std::path::PathBuf), LiveError> {
+        let latest = &self.stab.live_latest_stmap;
+        let item = match latest.get() {
+            Some(item) => item,
+            None => {
+                let (frame_index, ts_us) = latest.last_rendered().ok_or(LiveError::NoStmap)?;
+                let job = crate::stmap_live::LiveFrameJob { frame_index, frame_ts_ms: ts_us as f64 / 1000.0 };
+                crate::stmap_live::StmapsLive::build_sync(&self.stab, job).map_err(LiveError::StmapBuild)?
+            }
+        };
+        let paths = stmap_dump::write_stmaps(&item, dir.as_ref())?;
+        info!("live: STMaps of frame {} written to {}", item.1, dir.as_ref().display());
+        Ok(paths)
+    }
+
+    /// Most recent frame the render loop sent to the sink, `None` before the first one. The render loop
+    /// only copies its frames once polled, so the first call returns `None` too.
+    /// Only clones an `Arc`, cheap enough to poll from a UI every repaint.
+    pub fn latest_frame(&self) -> Option<LatestFrame> { self.stab.live_latest_frame.get() }
+
+    /// Sensor → video clock mapping in use and the state of the fit behind it.
+    pub fn clock_sync_state(&self) -> ClockSyncState {
+        self.stab.gyro.read().live_clock_sync_state().unwrap_or_else(ClockSyncState::identity)
+    }
+
+    /// Back to the identity clock mapping with an empty fit, so it is estimated again from the next pairs.
+    pub fn reset_clock_sync(&self) { self.stab.gyro.write().reset_live_clock_sync(); }
+
+    /// Source as described by the last parsed header, mid-stream header updates included.
+    pub fn source_info(&self) -> SourceInfo { SourceInfo::from_metadata(&self.stab.gyro.read().file_metadata.read()) }
+
+    /// Current memory use of the live components, see `MemoryBudget`.
+    pub fn memory_usage(&self) -> MemoryUsage {
+        let (imu_ring, quat_buffers) = self.stab.gyro.read().live_memory_usage().unwrap_or_default();
+        MemoryUsage {
+            imu_channel: self.imu_tx.len() * std::mem::size_of::<LiveImuMsg>(),
+            imu_ring,
+            quat_buffers,
+            map_cache: self.stab.live_memory_gauges.map_cache(),
+            frame_queue: self.stab.live_memory_gauges.frame_queue(),
+        }
+    }
+
+    fn consumer_loop(
+        stab: Arc<StabilizationManager>,
+        imu_rx: Receiver<LiveImuMsg>,
+        integrate: Option<(Duration, bool)>,
+        running: Arc<AtomicBool>,
+        ingest: Arc<LiveIngestStats>,
+        mut column_swap: Option<ColumnSwapDetector>,
+        mut reorder: Option<ImuReorderBuffer>,
+        mut stuck_sensor: Option<SensorStuckDetector>,
+    ) {
+        let poll = integrate.map_or(Duration::from_millis(100), |(period, _)| period);
+        let clock = stab.live_clock();
+        let mut last_integrate = clock.now_us();
+        let mut counter: u64 = 0;
+        let mut batch: Vec<LiveImuMsg> = Vec::with_capacity(64);
+        // Samples held back until the column order is known
+        let mut warmup: Vec<LiveImuMsg> = Vec::new();
+
+        while running.load(Ordering::Relaxed) {
+            let profile = stab.live_latency_profile.get();
+            match profile.recv_timeout(&imu_rx, poll) {
+                Ok(first) => {
+                    batch.push(first);
+                    while batch.len() < MAX_IMU_BATCH {
+                        match imu_rx.try_recv() {
+                            Ok(msg) => batch.push(msg),
+                            Err(_) => break,
+                        }
+                    }
+                    if let Some(detector) = &mut column_swap {
+                        if detector.order().is_none() {
+                            batch.iter().for_each(|(sample, _)| { detector.observe(sample); });
+                            warmup.append(&mut batch);
+                            if detector.order().is_none() { continue; }
+                            batch = std::mem::take(&mut warmup);
+                            ingest.columns_swapped.store(detector.order() == Some(ColumnOrder::Swapped), Ordering::Relaxed);
+                        }
+                        for (sample, _) in &mut batch {
+                            *sample = detector.correct(*sample);
+                        }
+                    }
+                    if let Some(reorder) = &mut reorder {
+                        let late = batch.drain(..).filter(|msg| !reorder.push(*msg)).count();
+                        if late > 0 {
+                            ingest.late_samples.fetch_add(late as u64, Ordering::Relaxed);
+                            debug!("live: dropped {late} IMU samples that arrived too late to reorder");
+                        }
+                        reorder.release(&mut batch);
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => {
+                    // The clients went quiet, don't hold back the end of the stream
+                    if let Some(reorder) = &mut reorder { reorder.flush(&mut batch); }
+                }
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+
+            if !batch.is_empty() {
+                if let Some(detector) = &mut stuck_sensor {
+                    for (sample, _) in &batch {
+                        match detector.observe(sample) {
+                            Some(true) => {
+                                ingest.sensor_stuck.store(true, Ordering::Relaxed);
+                                ingest.sensor_stuck_alerts.fetch_add(1, Ordering::Relaxed);
+                            }
+                            Some(false) => ingest.sensor_stuck.store(false, Ordering::Relaxed),
+                            None => {}
+                        }
+                    }
+                }
+                stab.gyro.read().push_live_imu_batch(&batch);
+                ingest.record(batch.len());
+                for (sample, _) in &batch {
+                    if counter % 1000 == 0 { debug!("live: IMU sample: {sample}"); }
+                    counter += 1;
+                }
+                if batch.len() >= 100 { debug!("live: pushed a burst of {} IMU samples", batch.len()); }
+                batch.clear();
+            }
+
+            if let Some((period, when_idle)) = integrate {
+                let now_us = clock.now_us();
+                if now_us - last_integrate >= period.as_micros() as i64 {
+                    if when_idle {
+                        stab.integrate_live_data();
+                    } else if !stab.integrate_live_data_if_changed() {
+                        ingest.idle_integrations.fetch_add(1, Ordering::Relaxed);
+                    }
+                    last_integrate = now_us;
+                }
+            }
+        }
+
+        info!("live: IMU consumer exit");
+    }
+}
+
+impl Drop for LivePipeline {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// 1080p30 manager with live mode enabled, shared by the live tests.
+#[cfg(test)]
+pub(crate) fn live_manager() -> Arc<StabilizationManager> {
+    let stab = Arc::new(StabilizationManager::default());
+    stab.init_from_stream_data(30.0, (1920, 1080));
+    stab.gyro.read().enable_live(3.0, 1.0, 0.0, 30.0);
+    stab
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn push_imu_reaches_quat_store() {
+        let stab = live_manager();
+        let pipeline = LivePipeline::new(stab.clone(), Some(Duration::from_millis(10)));
+
+        // 1 s of samples @ 200 Hz, constant yaw rate
+        for i in 0..200_i64 {
+            let ts = i * 5_000;
+            pipeline.push_imu(LiveImuSample { ts_sensor_us: ts, gyro: [0.0, 0.0, 0.5], accel: Some([0.0, 0.0, 1.0]) }, ts).unwrap();
+        }
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let buf = loop {
+            let latest = stab.gyro.read().live.read().as_ref().and_then(|st| st.quat_buffer_store_org.get_latest_buffer());
+            if let Some(buf) = latest {
+                if buf.last_us >= 995_000 { break buf; }
+            }
+            assert!(Instant::now() < deadline, "quaternions never published");
+            thread::sleep(Duration::from_millis(10));
+        };
+
+        assert_eq!(buf.first_us, 0);
+        let q_start = buf.quat_at_ms(0.0).unwrap();
+        let q_end = buf.quat_at_ms(995.0).unwrap();
+        assert!(q_start.angle_to(&q_end) > 0.1);
+    }
+
+    #[test]
+    fn imu_burst_is_pushed_in_few_lock_acquisitions() {
+        let stab = live_manager();
+        let pipeline = LivePipeline::new(stab.clone(), None);
+
+        // Hold the gyro lock while a 1000-sample burst queues up, like a stalled network catching up
+        {
+            let _stall = stab.gyro.write();
+            for i in 0..1000_i64 {
+                let ts = i * 1_000;
+                pipeline.push_imu(LiveImuSample { ts_sensor_us: ts, gyro: [0.0, 0.0, 0.5], accel: Some([0.0, 0.0, 1.0]) }, ts).unwrap();
+            }
+            thread::sleep(Duration::from_millis(20));
+        }
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while pipeline.ingest_stats().samples < 1000 {
+            assert!(Instant::now() < deadline, "burst never consumed");
+            thread::sleep(Duration::from_millis(5));
+        }
+
+        // One lock per sample before batching, now the whole burst fits in a handful
+        let stats = pipeline.ingest_stats();
+        assert_eq!(stats.samples, 1000);
+        assert!(stats.batches <= 4, "{} lock acquisitions for 1000 samples", stats.batches);
+
+        let gyro = stab.gyro.read();
+        let live = gyro.live.read();
+        let st = live.as_ref().unwrap();
+        assert_eq!(st.ring.lock().snapshot(&st.sync.read()).len(), 1000);
+    }
+
+    #[test]
+    fn insta360_lens_validation() {
+        let stab = live_manager();
+        {
+            let mut lens = stab.lens.write();
+            lens.distortion_model = Some("insta360".into());
+            lens.fisheye_params.camera_matrix = vec![[1000.0, 0.0, 960.0], [0.0, 1000.0, 540.0], [0.0, 0.0, 1.0]];
+            lens.fisheye_params.distortion_coeffs = vec![-0.05, 0.01, 0.0, 0.001, -0.001];
+        }
+        assert!(stab.validate_live_lens().is_err());
+
+        stab.lens.write().fisheye_params.distortion_coeffs.push(0.9);
+        assert!(stab.validate_live_lens().is_ok());
+    }
+
+    #[test]
+    fn lens_profile_is_scaled_to_the_stream_resolution() {
+        use crate::stabilization::{ ComputeParams, FrameTransform };
+
+        let stab = live_manager();
+        {
+            let mut lens = stab.lens.write();
+            lens.calib_dimension = crate::lens_profile::Dimensions { w: 3840, h: 2160 };
+            lens.asymmetrical = true;
+            lens.fisheye_params.camera_matrix = vec![[1800.0, 0.0, 1930.0], [0.0, 1800.0, 1070.0], [0.0, 0.0, 1.0]];
+            lens.fisheye_params.distortion_coeffs = vec![0.05, -0.01, 0.002, -0.0005];
+        }
+        assert!(stab.validate_live_lens().is_ok());
+
+        // 4K calibration on a 1080p stream: focal length and center in 1080p pixels
+        let params = ComputeParams::from_manager(&stab);
+        let (k, ..) = FrameTransform::get_lens_data_at_timestamp(&params, 0.0, false);
+        assert!((k[(0, 0)] - 900.0).abs() < 1e-9 && (k[(1, 1)] - 900.0).abs() < 1e-9, "{k}");
+        assert!((k[(0, 2)] - 965.0).abs() < 1e-9 && (k[(1, 2)] - 535.0).abs() < 1e-9, "{k}");
+
+        let lens = stab.lens.read();
+        let mut scaled = lens.get_camera_matrix((1920, 1080), false);
+        lens.scale_camera_matrix(&mut scaled, (1920, 1080));
+        assert_eq!(scaled, k);
+    }
+
+    #[test]
+    fn insta360_distorted_frame_is_corrected() {
+        use crate::stabilization::{ KernelParams, Stabilization, distortion_models::DistortionModel };
+
+        const W: usize = 64;
+        const LINE_Y: f32 = 40.0;
+        let model = DistortionModel::from_name("insta360");
+        let mut params = KernelParams { width: W as i32, height: W as i32, f: [40.0, 40.0], c: [32.0, 32.0], ..Default::default() };
+        params.k[..6].copy_from_slice(&[-0.05, 0.01, 0.0, 0.001, -0.001, 0.9]);
+
+        // Synthetic distorted frame: a straight horizontal line in undistorted space, bent by the lens
+        let mut frame = vec![0u8; W * W];
+        for y in 0..W {
+            for x in 0..W {
+                let pt = ((x as f32 - params.c[0]) / params.f[0], (y as f32 - params.c[1]) / params.f[1]);
+                if let Some(u) = model.undistort_point(pt, &params) {
+                    if (u.1 * params.f[1] + params.c[1] - LINE_Y).abs() < 1.5 { frame[y * W + x] = 255; }
+                }
+            }
+        }
+
+        // Identity rotation, output camera == input camera
+        let (f, c) = (params.f[0], params.c[0]);
+        let mut m = [0.0f32; 14];
+        m[..9].copy_from_slice(&[1.0 / f, 0.0, -c / f, 0.0, 1.0 / f, -c / f, 0.0, 0.0, 1.0]);
+
+        let sample = |x: usize, y: usize| -> u8 {
+            let uv = Stabilization::rotate_and_distort((x as f32, y as f32), 0, &params, &[m], &model, None, 0.0, &[]).unwrap();
+            let (sx, sy) = (uv.0.round() as usize, uv.1.round() as usize);
+            frame[sy.min(W - 1) * W + sx.min(W - 1)]
+        };
+
+        // After correction the line must be straight again
+        for x in 16..48 {
+            assert_eq!(sample(x, LINE_Y as usize), 255, "line broken at x={x}");
+            assert_eq!(sample(x, LINE_Y as usize - 6), 0);
+            assert_eq!(sample(x, LINE_Y as usize + 6), 0);
+        }
+    }
+
+    #[test]
+    fn centered_vertical_crop_samples_center_of_output() {
+        use crate::gpu::{ BufferDescription, BufferSource };
+        use crate::util::map_coord;
+
+        let stab = live_manager();
+        stab.set_live_output_size(1920, 1080);
+        // 9:16 column in the middle of a 16:9 frame
+        let (cw, ch) = (608, 1080);
+        stab.set_live_output_rect((1920 - cw) / 2, 0, cw, ch);
+        assert_eq!(stab.live_output_buffer_size(), (cw, ch));
+
+        let mut buf = vec![0u8; cw * ch * 4];
+        let desc = BufferDescription { size: (cw, ch, cw * 4), rect: None, rotation: None, data: BufferSource::Cpu { buffer: &mut buf }, texture_copy: false };
+        let r = stab.stabilization.read().get_output_rect(&desc);
+
+        // Same mapping the kernels apply from buffer pixel to output pixel
+        let to_output = |x: f32, y: f32| (
+            map_coord(x, r[0] as f32, (r[0] + r[2]) as f32, 0.0, 1920.0),
+            map_coord(y, r[1] as f32, (r[1] + r[3]) as f32, 0.0, 1080.0),
+        );
+        assert_eq!(to_output(0.0, 0.0), (656.0, 0.0));
+        assert_eq!(to_output(cw as f32, ch as f32), (1264.0, 1080.0));
+        assert_eq!(to_output(cw as f32 / 2.0, ch as f32 / 2.0), (960.0, 540.0));
+
+        // Out of bounds rects are clamped to the output
+        stab.set_live_output_rect(1800, 0, cw, ch);
+        assert_eq!(stab.stabilization.read().output_crop, Some((1920 - cw, 0, cw, ch)));
+    }
+
+    #[test]
+    fn no_gpu_fails_or_falls_back_to_cpu() {
+        let stab = live_manager();
+        let probe = BackendProbe { attempted: vec![
+            ("OpenCL", Err("no platforms".into())),
+            ("wgpu", Err("no adapter".into())),
+        ] };
+
+        match LivePipeline::apply_backend_probe(&stab, &probe, ComputeFallback::Fail) {
+            Err(LiveError::NoComputeBackend(tried)) => assert_eq!(tried, vec!["OpenCL (no platforms)", "wgpu (no adapter)"]),
+            other => panic!("expected NoComputeBackend, got {other:?}"),
+        }
+
+        assert!(LivePipeline::apply_backend_probe(&stab, &probe, ComputeFallback::Cpu).is_ok());
+        assert_eq!(stab.params.read().current_device, -1);
+        assert_eq!(stab.stabilization.read().pending_device_change, Some(-1));
+    }
+
+    #[test]
+    fn alpha_mask_is_transparent_outside_the_frame() {
+        use crate::gpu::{ Buffers, BufferDescription, BufferSource };
+        use crate::stabilization::RGBA8;
+
+        const S: usize = 64;
+        let stab = Arc::new(StabilizationManager::default());
+        stab.init_from_stream_data(30.0, (S, S));
+        stab.gyro.read().enable_live(3.0, 1.0, 0.0, 30.0);
+        stab.set_device(-1);
+        stab.set_background_color(nalgebra::Vector4::new(10.0, 20.0, 30.0, 128.0));
+        stab.set_live_alpha_mask(true);
+        stab.set_fov(2.0); // zoomed out, so the output has borders beyond the source frame
+        stab.set_render_params((S, S), (S, S));
+
+        let mut input = vec![255u8; S * S * 4];
+        let mut output = vec![0u8; S * S * 4];
+        let mut buffers = Buffers {
+            input:  BufferDescription { size: (S, S, S * 4), rect: None, rotation: None, data: BufferSource::Cpu { buffer: &mut input },  texture_copy: false },
+            output: BufferDescription { size: (S, S, S * 4), rect: None, rotation: None, data: BufferSource::Cpu { buffer: &mut output }, texture_copy: false },
+        };
+        stab.process_pixels::<RGBA8>(0, Some(0), &mut buffers).unwrap();
+
+        let alpha = |x: usize, y: usize| output[(y * S + x) * 4 + 3];
+        assert_eq!(alpha(S / 2, S / 2), 255);
+        for (x, y) in [(0, 0), (S - 1, 0), (0, S - 1), (S - 1, S - 1)] {
+            assert_eq!(alpha(x, y), 0, "corner ({x}, {y}) should be transparent");
+        }
+
+        // Turning the mask off gives the background its alpha back
+        stab.set_live_alpha_mask(false);
+        assert_eq!(stab.params.read().background, nalgebra::Vector4::new(10.0, 20.0, 30.0, 128.0));
+    }
+
+    #[test]
+    fn grid_overlay_draws_on_output() {
+        use crate::gpu::{ Buffers, BufferDescription, BufferSource };
+        use crate::stabilization::RGBA8;
+
+        const S: usize = 90;
+        let stab = Arc::new(StabilizationManager::default());
+        stab.init_from_stream_data(30.0, (S, S));
+        stab.gyro.read().enable_live(3.0, 1.0, 0.0, 30.0);
+        stab.set_device(-1);
+        stab.set_live_overlay(LiveOverlay::Grid);
+        stab.set_render_params((S, S), (S, S));
+
+        let render = |stab: &StabilizationManager| {
+            let mut input = vec![128u8; S * S * 4];
+            let mut output = vec![0u8; S * S * 4];
+            let mut buffers = Buffers {
+                input:  BufferDescription { size: (S, S, S * 4), rect: None, rotation: None, data: BufferSource::Cpu { buffer: &mut input },  texture_copy: false },
+                output: BufferDescription { size: (S, S, S * 4), rect: None, rotation: None, data: BufferSource::Cpu { buffer: &mut output }, texture_copy: false },
+            };
+            stab.process_pixels::<RGBA8>(0, Some(0), &mut buffers).unwrap();
+            output
+        };
+        let px = |out: &[u8], x: usize, y: usize| -> [u8; 3] { let i = (y * S + x) * 4; [out[i], out[i + 1], out[i + 2]] };
+
+        // Thirds at 30 and 60: yellow at 50% over the gray input, everything else untouched
+        let output = render(&stab);
+        for (x, y) in [(10, 30), (45, 60), (30, 10), (60, 80), (30, 30)] {
+            let [r, g, b] = px(&output, x, y);
+            assert!(r > 180 && g > 180 && b < 110, "({x}, {y}) is not on the grid: {:?}", [r, g, b]);
+        }
+        for (x, y) in [(45, 45), (15, 15), (75, 45)] {
+            assert_eq!(px(&output, x, y), [128, 128, 128], "({x}, {y}) should not be drawn on");
+        }
+
+        stab.set_live_overlay(LiveOverlay::None);
+        let output = render(&stab);
+        assert_eq!(px(&output, 10, 30), [128, 128, 128]);
+    }
+
+    #[test]
+    fn live_interpolation_reaches_kernel_params() {
+        use crate::gpu::{ Buffers, BufferDescription, BufferSource };
+        use crate::stabilization::{ Interpolation, RGBA8 };
+
+        const S: usize = 32;
+        let stab = live_manager();
+        stab.set_render_params((S, S), (S, S));
+        let mut input = vec![0u8; S * S * 4];
+        let mut output = vec![0u8; S * S * 4];
+        let buffers = Buffers {
+            input:  BufferDescription { size: (S, S, S * 4), rect: None, rotation: None, data: BufferSource::Cpu { buffer: &mut input },  texture_copy: false },
+            output: BufferDescription { size: (S, S, S * 4), rect: None, rotation: None, data: BufferSource::Cpu { buffer: &mut output }, texture_copy: false },
+        };
+
+        let kernel_interpolation = || stab.stabilization.read().get_frame_transform_at::<RGBA8>(0, Some(0), &buffers).kernel_params.interpolation;
+        assert_eq!(kernel_interpolation(), Interpolation::Bilinear as i32);
+        for interpolation in [Interpolation::Bicubic, Interpolation::Lanczos4, Interpolation::Mitchell] {
+            stab.set_live_interpolation(interpolation);
+            assert_eq!(kernel_interpolation(), interpolation as i32);
+        }
+        // EWA filters also get their coefficients
+        assert_ne!(stab.stabilization.read().get_frame_transform_at::<RGBA8>(0, Some(0), &buffers).kernel_params.ewa_coeffs_p, [0.0; 4]);
+    }
+
+    #[test]
+    fn quat_trace_applied_is_correction() {
+        use std::collections::BTreeMap;
+        use nalgebra::Vector3;
+        use crate::gyro_source::{ Quat64, QuatBuffer };
+        use trace::QuatTrace;
+
+        let stab = live_manager();
+        // Camera held at a constant 0.2 rad yaw, smoothed path at identity
+        let yaw = Quat64::from_axis_angle(&Vector3::z_axis(), 0.2);
+        let org: BTreeMap<i64, Quat64> = (0..=40).map(|i| (i * 50_000, yaw)).collect();
+        let smoothed: BTreeMap<i64, Quat64> = org.keys().map(|&t| (t, Quat64::identity())).collect();
+        {
+            let gyro = stab.gyro.read();
+            let live = gyro.live.read();
+            let st = live.as_ref().unwrap();
+            st.quat_buffer_store_org.publish(QuatBuffer::from_btreemap(&org).unwrap());
+            st.quat_buffer_store_smoothed.publish(QuatBuffer::from_btreemap(&smoothed).unwrap());
+        }
+
+        let t = QuatTrace::at(&stab.gyro.read(), 15, 500_000);
+        assert!(t.org.angle_to(&yaw) < 1e-9);
+        assert!(t.applied.angle_to(&yaw.inverse()) < 1e-9);
+        assert!(t.to_string().starts_with("15,500000,"));
+        assert_eq!(t.to_string().split(',').count(), QuatTrace::CSV_HEADER.split(',').count());
+        assert_eq!(t.confidence, Some(1.0));
+    }
+
+    #[test]
+    fn tilted_accel_gravity_points_opposite() {
+        let stab = live_manager();
+        // Camera tilted 30° about Y: the accelerometer reads the upward reaction tilted towards +X
+        let tilt = 30f64.to_radians();
+        let accel = [tilt.sin(), 0.0, tilt.cos()];
+        for i in 0..100_i64 {
+            let ts = i * 5_000;
+            stab.gyro.read().push_live_imu(LiveImuSample { ts_sensor_us: ts, gyro: [0.0, 0.0, 0.0], accel: Some(accel) }, ts);
+        }
+        stab.gyro.write().integrate_live_data();
+
+        let g = stab.gyro.read().live_gravity_at_timestamp(250.0).expect("no live gravity");
+        assert!((g.norm() - 1.0).abs() < 1e-9);
+        assert!((g.x + tilt.sin()).abs() < 1e-6, "gravity x = {}", g.x);
+        assert!(g.y.abs() < 1e-6);
+        assert!((g.z + tilt.cos()).abs() < 1e-6, "gravity z = {}", g.z);
+    }
+
+    #[test]
+    fn sync_change_mid_stream_keeps_timestamps_consistent() {
+        let stab = live_manager();
+        let push = |range: std::ops::Range<i64>, b: i64| for i in range {
+            let ts = i * 5_000;
+            stab.gyro.read().push_live_imu(LiveImuSample { ts_sensor_us: ts, gyro: [0.0, 0.0, 0.5], accel: Some([0.0, 0.0, 1.0]) }, ts + b);
+        };
+
+        push(0..100, 0);
+        stab.gyro.write().integrate_live_data();
+        // Sensor clock turns out to be 20 ms behind the video clock
+        stab.gyro.write().set_live_clock_sync(1.0, 20_000.0);
+        push(100..200, 20_000);
+
+        let gyro = stab.gyro.read();
+        let live = gyro.live.read();
+        let st = live.as_ref().unwrap();
+        let samples = st.ring.lock().snapshot(&st.sync.read());
+        assert_eq!(samples.len(), 200);
+        assert_eq!(samples[0].ts_sensor_us, 20_000);
+        assert!(samples.windows(2).all(|w| w[1].ts_sensor_us - w[0].ts_sensor_us == 5_000));
+
+        // Buffers published before the change were rebuilt with the new mapping
+        let buf = st.quat_buffer_store_org.get_latest_buffer().unwrap();
+        assert_eq!((buf.first_us, buf.last_us), (20_000, 515_000));
+    }
+
+    #[test]
+    fn extreme_shake_never_exceeds_crop_cap() {
+        use std::collections::BTreeMap;
+        use nalgebra::{ Matrix3, Vector3 };
+        use crate::gyro_source::{ Quat64, QuatBuffer };
+        use crate::stabilization::{ ComputeParams, FrameTransform };
+
+        let stab = live_manager();
+        stab.set_render_params((1920, 1080), (1920, 1080));
+        stab.set_fov(0.5); // asks for 50% crop
+        stab.set_live_max_crop(Some(10.0));
+
+        // Corrections of up to ±0.4 rad on every axis, flipping every 10 ms
+        let org: BTreeMap<i64, Quat64> = (0..=300).map(|i| (i * 10_000, Quat64::identity())).collect();
+        let shake: BTreeMap<i64, Quat64> = org.keys().enumerate().map(|(i, &t)| {
+            let s = if i % 2 == 0 { 1.0 } else { -1.0 };
+            (t, Quat64::from_scaled_axis(Vector3::new(0.4 * s, -0.3 * s, 0.2 * s)))
+        }).collect();
+        {
+            let gyro = stab.gyro.read();
+            let live = gyro.live.read();
+            let st = live.as_ref().unwrap();
+            st.quat_buffer_store_org.publish(QuatBuffer::from_btreemap(&org).unwrap());
+            st.quat_buffer_store_smoothed.publish(QuatBuffer::from_btreemap(&shake).unwrap());
+        }
+
+        let params = ComputeParams::from_manager(&stab);
+        let k = stab.lens.read().get_camera_matrix((1920, 1080), false);
+        for frame in 0..60 {
+            let ft = FrameTransform::at_timestamp(&params, frame as f64 * 1000.0 / 30.0, frame);
+            assert!(ft.kernel_params.fov >= 0.9 - 1e-4, "frame {frame}: fov {} crops more than 10%", ft.kernel_params.fov);
+
+            // Every output corner must still come from inside the input frame
+            let m = ft.matrices[0];
+            let i_r: Matrix3<f64> = nalgebra::convert(Matrix3::new(m[0], m[1], m[2], m[3], m[4], m[5], m[6], m[7], m[8]));
+            for (x, y) in [(0.0, 0.0), (1920.0, 0.0), (0.0, 1080.0), (1920.0, 1080.0)] {
+                let ray = i_r * Vector3::new(x, y, 1.0);
+                let p = k * (ray / ray.z);
+                assert!(p.x > -1.0 && p.x < 1921.0 && p.y > -1.0 && p.y < 1081.0, "frame {frame}: corner ({x}, {y}) maps outside the input: {p:?}");
+            }
+        }
+
+        let stats = stab.live_crop_stats.snapshot();
+        assert_eq!(stats.frames, 60);
+        assert!(stats.cap_hits > 0);
+        assert!(stats.last_strength < 1.0);
+    }
+
+    #[test]
+    fn reversed_readout_mirrors_row_correction() {
+        use std::collections::BTreeMap;
+        use nalgebra::Vector3;
+        use crate::gyro_source::{ Quat64, QuatBuffer };
+        use crate::stabilization::{ ComputeParams, FrameTransform };
+        use crate::stabilization_params::ReadoutDirection;
+
+        let stab = live_manager();
+        stab.set_render_params((320, 240), (320, 240));
+        stab.params.write().frame_readout_time = 20.0;
+
+        // Constant 1 rad/s rotation, so every row of a rolling shutter frame needs a different correction
+        let org: BTreeMap<i64, Quat64> = (0..=100).map(|i| (i * 10_000, Quat64::from_scaled_axis(Vector3::new(0.2, 0.0, 1.0) * (i as f64 * 0.01)))).collect();
+        let smoothed: BTreeMap<i64, Quat64> = org.keys().map(|&t| (t, Quat64::identity())).collect();
+        {
+            let gyro = stab.gyro.read();
+            let live = gyro.live.read();
+            let st = live.as_ref().unwrap();
+            st.quat_buffer_store_org.publish(QuatBuffer::from_btreemap(&org).unwrap());
+            st.quat_buffer_store_smoothed.publish(QuatBuffer::from_btreemap(&smoothed).unwrap());
+        }
+
+        let mut params = ComputeParams::from_manager(&stab);
+        let forward = FrameTransform::at_timestamp(&params, 500.0, 15);
+        params.frame_readout_direction = ReadoutDirection::BottomToTop;
+        let reversed = FrameTransform::at_timestamp(&params, 500.0, 15);
+
+        let rows = forward.matrices.len();
+        assert_eq!(rows, 240);
+        assert_eq!(reversed.matrices.len(), rows);
+        assert!(forward.matrices[0] != forward.matrices[rows - 1], "rolling shutter correction is not applied per row");
+        for y in 0..rows {
+            for (a, b) in forward.matrices[y].iter().zip(&reversed.matrices[rows - 1 - y]) {
+                assert!((a - b).abs() < 1e-5, "row {y}: {:?} != {:?}", forward.matrices[y], reversed.matrices[rows - 1 - y]);
+            }
+        }
+    }
+
+    #[test]
+    fn buffer_switch_is_blended() {
+        use std::collections::BTreeMap;
+        use nalgebra::Vector3;
+        use crate::gyro_source::{ Quat64, QuatBuffer };
+
+        let stab = live_manager();
+        stab.set_live_buffer_blend(100.0);
+        let buffer = |yaw: f64| {
+            let q = Quat64::from_axis_angle(&Vector3::z_axis(), yaw);
+            let map: BTreeMap<i64, Quat64> = (0..=300).map(|i| (i * 10_000, q)).collect();
+            QuatBuffer::from_btreemap(&map).unwrap()
+        };
+        let gyro = stab.gyro.read();
+        let publish = |yaw: f64| gyro.live.read().as_ref().unwrap().quat_buffer_store_org.publish(buffer(yaw));
+
+        publish(0.0);
+        let mut applied = Vec::new();
+        for frame in 36..56 {
+            // The next integration lands between frames 40 and 41, with a 0.1 rad different estimate
+            if frame == 41 { publish(0.1); }
+            applied.push(gyro.org_quat_at_timestamp(frame as f64 * 1000.0 / 30.0));
+        }
+
+        let max_step = applied.windows(2).map(|w| w[0].angle_to(&w[1])).fold(0.0, f64::max);
+        assert!(max_step < 0.05, "applied quaternion jumped by {max_step} rad");
+        assert!(applied[0].angle() < 1e-9);
+        assert!((applied.last().unwrap().angle() - 0.1).abs() < 1e-9);
+    }
+
+    #[test]
+    fn param_log_round_trip() {
+        use param_log::ParamLog;
+
+        let path = std::env::temp_dir().join(format!("gyroflow_live_params_{}.jsonl", std::process::id()));
+        let stab = live_manager();
+        stab.start_live_param_log(&path).unwrap();
+        stab.set_live_max_crop(Some(12.0));
+        stab.set_live_alpha_mask(true);
+        stab.set_live_output_rect(100, 0, 608, 1080);
+        stab.stop_live_param_log();
+
+        let events = ParamLog::load(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+        assert_eq!(events.iter().map(|e| e.name.as_str()).collect::<Vec<_>>(), ["max_crop", "alpha_mask", "output_rect"]);
+        assert!(events.windows(2).all(|w| w[0].t_ms <= w[1].t_ms));
+
+        let replayed = live_manager();
+        replayed.set_render_params((1920, 1080), (1920, 1080));
+        assert!(events.iter().all(|e| ParamLog::apply(&replayed, e)));
+        assert_eq!(replayed.params.read().live_max_crop, Some(12.0));
+        assert_eq!(replayed.params.read().background[3], 0.0);
+        assert_eq!(replayed.stabilization.read().output_crop, Some((100, 0, 608, 1080)));
+    }
+
+    #[test]
+    fn smoothing_window_trades_latency_for_lookahead() {
+        use crate::gyro_source::{ Quat64, QuatBuffer, SmoothingWindow, TimeQuat };
+
+        let stab = live_manager();
+        // 1 s of IMU integrated so far, a constant 0.1 rad/s yaw
+        let quats: TimeQuat = (0..=200_i64).map(|i| (i * 5_000, Quat64::from_euler_angles(0.0, 0.0, i as f64 * 0.0005))).collect();
+        {
+            let gyro = stab.gyro.read();
+            let live = gyro.live.read();
+            let st = live.as_ref().unwrap();
+            st.quat_buffer_store_org.publish(QuatBuffer::from_btreemap(&quats).unwrap());
+        }
+        let newest_frame = |window: SmoothingWindow| {
+            stab.set_live_smoothing_window(window);
+            (0..=1000).rev().step_by(10).find(|&t| {
+                let gyro = stab.gyro.read();
+                let live = gyro.live.read();
+                live.as_ref().unwrap().quat_buffer_store_org.get_buffer_for_time(t as f64, &window).is_some()
+            })
+        };
+
+        // Default: 500 ms look-ahead, so only frames up to 500 ms can be rendered
+        let quality = SmoothingWindow { center_ratio: 1.0, ..Default::default() };
+        assert_eq!(stab.gyro.read().live_smoothing_window(), Some(SmoothingWindow::default()));
+        assert_eq!(newest_frame(quality), Some(500));
+        // 100 ms look-ahead: 400 ms less latency
+        let low_latency = SmoothingWindow { post_ms: 100.0, ..quality };
+        assert_eq!(newest_frame(low_latency), Some(900));
+
+        // Look-ahead longer than the 3 s retention extends it
+        stab.set_live_smoothing_window(SmoothingWindow { pre_ms: 1000.0, post_ms: 4000.0, center_ratio: 1.0 });
+        let gyro = stab.gyro.read();
+        let live = gyro.live.read();
+        assert_eq!(live.as_ref().unwrap().ring.lock().keep_us, 5_000_000);
+    }
+
+    #[test]
+    fn switching_of_method_mid_run() {
+        use crate::synchronization::OfMethod;
+
+        let stab = live_manager();
+        let (w, h) = (64, 64);
+        let detect = |frame: usize| {
+            let img = image::GrayImage::from_fn(w, h, |x, y| image::Luma([(((x + frame as u32 * 2) / 8 + y / 8) % 2 * 200) as u8]));
+            stab.pose_estimator.detect_features(frame, frame as i64 * 33_333, Arc::new(img), w, h, stab.live_of_method().index());
+        };
+        let params = crate::stabilization::ComputeParams::from_manager(&stab);
+
+        stab.set_live_of_method(OfMethod::Akaze);
+        (0..3).for_each(detect);
+        assert_eq!(stab.pose_estimator.sync_results.read().len(), 3);
+
+        // Nothing estimated yet, the Akaze frames can't pair with PyrLK ones
+        stab.set_live_of_method(OfMethod::PyrLK);
+        assert!(stab.pose_estimator.sync_results.read().is_empty());
+        (3..6).for_each(detect);
+        stab.pose_estimator.process_detected_frames(30.0, 30.0, &params);
+
+        (6..8).for_each(detect);
+        // Same method again is a no-op
+        stab.set_live_of_method(OfMethod::PyrLK);
+        assert!(stab.pose_estimator.sync_results.read().values().any(|f| f.frame_no == 7));
+        stab.set_live_of_method(OfMethod::Dis);
+        (8..10).for_each(detect);
+        stab.pose_estimator.process_detected_frames(30.0, 30.0, &params);
+        stab.pose_estimator.cache_optical_flow(1);
+
+        assert_eq!(stab.live_of_method(), OfMethod::Dis);
+        let frames: Vec<usize> = stab.pose_estimator.sync_results.read().values().map(|f| f.frame_no).collect();
+        assert!(frames.contains(&8) && frames.contains(&9), "{frames:?}");
+        assert!(frames.iter().all(|&f| f >= 3), "{frames:?}");
+    }
+
+    #[test]
+    fn intermittent_missing_accel() {
+        use crate::gyro_source::live::held_accels;
+
+        let up = [0.0, 0.0, 1.0];
+        let sample = |i: i64, accel| LiveImuSample { ts_sensor_us: i * 5_000, gyro: [0.0, 0.0, 0.5], accel };
+        let held = held_accels(&[sample(0, None), sample(1, Some([0.1, 0.0, 1.0])), sample(2, None), sample(3, Some(up)), sample(4, None)]);
+        assert_eq!(held, vec![[0.1, 0.0, 1.0], [0.1, 0.0, 1.0], [0.1, 0.0, 1.0], up, up]);
+        assert_eq!(held_accels(&[sample(0, None)]), vec![[0.0; 3]]);
+        assert_eq!(sample(0, None).accel_or_zero(), [0.0; 3]);
+
+        // Every third sample (and the first few) without accel integrates like the full stream
+        let (full, gappy) = (live_manager(), live_manager());
+        for i in 0..200_i64 {
+            full.gyro.read().push_live_imu(sample(i, Some(up)), i * 5_000);
+            let accel = if i < 5 || i % 3 == 0 { None } else { Some(up) };
+            gappy.gyro.read().push_live_imu(sample(i, accel), i * 5_000);
+        }
+        full.gyro.write().integrate_live_data();
+        gappy.gyro.write().integrate_live_data();
+
+        let quats = |stab: &StabilizationManager| stab.gyro.read().live.read().as_ref().unwrap().quat_buffer_store_org.get_latest_buffer().expect("no quaternions").quats.clone();
+        let (a, b) = (quats(&full), quats(&gappy));
+        assert_eq!(a.len(), b.len());
+        for ((ta, qa), (tb, qb)) in a.iter().zip(&b) {
+            assert_eq!(ta, tb);
+            assert!(qb.coords.iter().all(|c| c.is_finite()));
+            assert!(qa.angle_to(qb) < 1e-9, "orientation differs at {ta} us");
+        }
+        let g = gappy.gyro.read().live_gravity_at_timestamp(500.0).expect("no live gravity");
+        assert!((g.z + 1.0).abs() < 1e-6, "gravity {g:?}");
+
+        // Gyro-only stream still integrates
+        let gyro_only = live_manager();
+        for i in 0..200_i64 {
+            gyro_only.gyro.read().push_live_imu(sample(i, None), i * 5_000);
+        }
+        gyro_only.gyro.write().integrate_live_data();
+        assert!(quats(&gyro_only).values().all(|q| q.coords.iter().all(|c| c.is_finite())));
+        assert!(gyro_only.gyro.read().live_gravity_at_timestamp(500.0).is_none());
+    }
+
+    #[test]
+    fn digital_zoom_samples_center_crop() {
+        use crate::stabilization::{ComputeParams, undistort_points_for_optical_flow};
+
+        let stab = live_manager();
+        {
+            let mut lens = stab.lens.write();
+            lens.calib_dimension = crate::lens_profile::Dimensions { w: 1920, h: 1080 };
+            lens.fisheye_params.camera_matrix = vec![[1000.0, 0.0, 960.0], [0.0, 1000.0, 540.0], [0.0, 0.0, 1.0]];
+            lens.fisheye_params.distortion_coeffs = vec![0.05, 0.01, 0.0, 0.0];
+        }
+        stab.recompute_undistortion();
+        // Rays the lens correction sees for frame pixels, as the live optical flow undistorts them
+        let rays = |points: &[(f32, f32)]| undistort_points_for_optical_flow(points, 0, &ComputeParams::from_manager(&stab), (1920, 1080));
+        let edges = [(1919.0, 540.0), (960.0, 0.0), (0.0, 1079.0), (1919.0, 1079.0)];
+        // The same pixels in the central half of the full sensor
+        let halfway = edges.map(|(x, y)| (960.0 + (x - 960.0) / 2.0, 540.0 + (y - 540.0) / 2.0));
+        let full_edges = rays(&edges);
+        let full_halfway = rays(&halfway);
+
+        stab.set_live_digital_zoom(2.0);
+        assert_eq!(stab.live_digital_zoom(), 2.0);
+        // The edges of the 2x frame see what the full sensor sees halfway to its edges
+        for (zoomed, full) in rays(&edges).iter().zip(&full_halfway) {
+            assert!((zoomed.0 - full.0).abs() < 1e-4 && (zoomed.1 - full.1).abs() < 1e-4, "{zoomed:?} != {full:?}");
+        }
+
+        stab.set_live_digital_zoom(1.0);
+        assert_eq!(stab.live_digital_zoom(), 1.0);
+        assert!(stab.gyro.read().file_metadata.read().digital_zoom.is_none());
+        assert_eq!(rays(&edges), full_edges);
+    }
+
+    #[test]
+    fn latency_sla_degrades_and_restores_in_order() {
+        let stab = live_manager();
+        let mut sla = LatencySla::new(100.0, &Degradation::ALL, stab.live_latency_stats.clone());
+        assert!(!sla.should_drop(500.0));
+
+        // Latency stuck at 180 ms: one more step every few frames, in policy order
+        let mut taken = Vec::new();
+        for _ in 0..200 {
+            if let Some((step, degraded)) = sla.record(180.0) {
+                assert!(degraded);
+                taken.push(step);
+            }
+        }
+        assert_eq!(taken, Degradation::ALL);
+        assert!(sla.should_drop(150.0) && !sla.should_drop(80.0));
+        let stats = stab.live_latency_stats.snapshot();
+        assert_eq!((stats.sla_ms, stats.level), (100.0, 3));
+        assert!((stats.latency_ms - 180.0).abs() < 1e-9);
+
+        // Just under the SLA is not enough to restore anything
+        for _ in 0..200 {
+            assert_eq!(sla.record(90.0), None);
+        }
+        assert_eq!(sla.level(), 3);
+
+        // Recovered: the steps are undone in reverse
+        let mut restored = Vec::new();
+        for _ in 0..400 {
+            if let Some((step, degraded)) = sla.record(40.0) {
+                assert!(!degraded);
+                restored.push(step);
+            }
+        }
+        assert_eq!(restored, [Degradation::FastInterpolation, Degradation::ReducedMapResolution, Degradation::DropLateFrames]);
+        assert_eq!(stab.live_latency_stats.snapshot().level, 0);
+        assert!(!sla.should_drop(150.0));
+
+        // Without an SLA nothing is measured
+        let mut off = LatencySla::new(0.0, &Degradation::ALL, Default::default());
+        assert_eq!(off.record(1000.0), None);
+
+        let now = Arc::new(ManualClock::new(0));
+        let mut clock = StreamClock::new(now.clone());
+        assert_eq!(clock.latency_ms(0), 0.0);
+        now.advance(Duration::from_millis(83));
+        let lag = clock.latency_ms(33_333);
+        assert!((lag - 49.667).abs() < 1e-3, "{lag}");
+    }
+
+    #[test]
+    fn confidence_drops_with_imu_coverage() {
+        use std::collections::BTreeMap;
+        use crate::gyro_source::{ Quat64, QuatBuffer, OFF_CENTER_CONFIDENCE };
+
+        let stab = live_manager();
+        assert_eq!(stab.gyro.read().live_confidence_at_timestamp(1000.0), Some(0.0));
+
+        // 0..2 s of orientation, the default window wants 500 ms of look-ahead
+        let quats: BTreeMap<i64, Quat64> = (0..=40).map(|i| (i * 50_000, Quat64::identity())).collect();
+        {
+            let gyro = stab.gyro.read();
+            let live = gyro.live.read();
+            let st = live.as_ref().unwrap();
+            st.quat_buffer_store_org.publish(QuatBuffer::from_btreemap(&quats).unwrap());
+            st.quat_buffer_store_smoothed.publish(QuatBuffer::from_btreemap(&quats).unwrap());
+        }
+        let confidence = |ms: f64| stab.gyro.read().live_confidence_at_timestamp(ms).unwrap();
+
+        assert_eq!(confidence(1000.0), 1.0);
+        // Only 200 ms of look-ahead left, and off-center
+        assert!((confidence(1800.0) - 0.4 * OFF_CENTER_CONFIDENCE).abs() < 1e-9, "{}", confidence(1800.0));
+        assert!(confidence(1900.0) < confidence(1800.0));
+        // Past the data the orientation is extrapolated
+        assert_eq!(confidence(2100.0), 0.0);
+
+        // A clock fit that only agrees with 7 of its 10 pairs scales the confidence down
+        {
+            let mut gyro = stab.gyro.write();
+            for i in 0..10_i64 {
+                let skew = if [2, 5, 8].contains(&i) { 50_000 } else { 0 };
+                gyro.update_live_clock_sync_from_pair(i * 100_000, i * 100_000 + skew);
+            }
+        }
+        assert_eq!(stab.gyro.read().live_clock_sync_inlier_ratio(), Some(0.7));
+        assert!((confidence(1000.0) - 0.7).abs() < 1e-9);
+    }
+
+    #[test]
+    fn long_session_stays_within_memory_budget() {
+        let stab = live_manager();
+        let budget = MemoryBudget::from_mb(1.0);
+        let pipeline = LivePipeline::builder(stab.clone())
+            .without_integration()
+            .compute_fallback(ComputeFallback::Cpu)
+            .without_stuck_sensor_detection()
+            .memory_budget(budget)
+            .start()
+            .unwrap();
+
+        // 60 s of 1 kHz IMU, integrated every 100 ms
+        let mut pushed = 0;
+        for block in 0..600_i64 {
+            for i in 0..100 {
+                let ts = (block * 100 + i) * 1_000;
+                pipeline.push_imu(LiveImuSample { ts_sensor_us: ts, gyro: [0.1, 0.0, 0.2], accel: Some([0.0, 0.0, 1.0]) }, ts).unwrap();
+            }
+            pushed += 100;
+            let deadline = Instant::now() + Duration::from_secs(5);
+            let delivered = || { let s = pipeline.ingest_stats(); s.samples + s.dropped_samples };
+            while delivered() < pushed && Instant::now() < deadline {
+                thread::sleep(Duration::from_millis(1));
+            }
+            stab.gyro.write().integrate_live_data();
+
+            let usage = pipeline.memory_usage();
+            assert!(usage.total() <= budget.total_bytes, "block {block}: {usage:?}");
+            assert!(usage.imu_ring <= budget.imu_ring_bytes(), "block {block}: {usage:?}");
+            assert!(usage.quat_buffers <= 2 * budget.quat_store_bytes(), "block {block}: {usage:?}");
+            assert!(usage.imu_channel <= budget.imu_channel_bytes(), "block {block}: {usage:?}");
+        }
+        pipeline.stop();
+
+        // The caps were actually hit: the ring is full and the stores dropped old buffers
+        let gyro = stab.gyro.read();
+        let live = gyro.live.read();
+        let st = live.as_ref().unwrap();
+        assert_eq!(st.ring.lock().buf.len(), budget.imu_ring_samples());
+        assert!(st.quat_buffer_store_org.bytes() > budget.quat_store_bytes() / 2);
+        // Oldest data went first: the newest buffer reaches the end of the session
+        assert!(st.quat_buffer_store_org.get_latest_buffer().unwrap().last_us >= 59_900_000);
+    }
+
+    #[test]
+    fn mount_correction_rotates_the_stabilized_view() {
+        use std::collections::BTreeMap;
+        use nalgebra::{ Matrix3, Vector3 };
+        use crate::gyro_source::{ Quat64, QuatBuffer };
+        use crate::stabilization::{ ComputeParams, FrameTransform };
+
+        let stab = live_manager();
+        stab.set_render_params((1920, 1080), (1920, 1080));
+
+        // Shaky camera, fully smoothed away
+        let org: BTreeMap<i64, Quat64> = (0..=300).map(|i| {
+            let s = if i % 2 == 0 { 1.0 } else { -1.0 };
+            (i * 10_000, Quat64::from_scaled_axis(Vector3::new(0.05 * s, -0.04 * s, 0.03 * s)))
+        }).collect();
+        let smoothed: BTreeMap<i64, Quat64> = org.keys().map(|&t| (t, Quat64::identity())).collect();
+        {
+            let gyro = stab.gyro.read();
+            let live = gyro.live.read();
+            let st = live.as_ref().unwrap();
+            st.quat_buffer_store_org.publish(QuatBuffer::from_btreemap(&org).unwrap());
+            st.quat_buffer_store_smoothed.publish(QuatBuffer::from_btreemap(&smoothed).unwrap());
+        }
+
+        // Camera ray seen at the center of the output
+        let center_rays = || -> Vec<Vector3<f64>> {
+            let params = ComputeParams::from_manager(&stab);
+            (0..30).map(|frame| {
+                let m = FrameTransform::at_timestamp(&params, frame as f64 * 1000.0 / 30.0, frame).matrices[0];
+                let i_r: Matrix3<f64> = nalgebra::convert(Matrix3::new(m[0], m[1], m[2], m[3], m[4], m[5], m[6], m[7], m[8]));
+                (i_r * Vector3::new(960.0, 540.0, 1.0)).normalize()
+            }).collect()
+        };
+        let level = center_rays();
+        stab.set_live_mount_correction(0.0, 0.0, 90.0);
+        let mounted = center_rays();
+
+        for (frame, (a, b)) in level.iter().zip(&mounted).enumerate() {
+            // Still stabilized: the view doesn't move with the shake
+            assert!((b - mounted[0]).norm() < 1e-3, "frame {frame}: view moved to {b:?}");
+            // And turned by the mount
+            let angle = a.angle(b).to_degrees();
+            assert!((angle - 90.0).abs() < 0.1, "frame {frame}: view turned by {angle}°");
+        }
+
+        stab.set_live_mount_correction(0.0, 0.0, 0.0);
+        assert!(stab.params.read().live_mount_correction.is_none());
+    }
+
+    #[test]
+    fn swapped_gyro_accel_columns_are_detected_and_corrected() {
+        // Camera at rest: ~1 g of gravity, a little sensor noise on the gyro
+        let still = |i: i64| {
+            let n = (i as f64 * 0.7).sin() * 0.01;
+            LiveImuSample { ts_sensor_us: i * 5_000, gyro: [n, -n, 0.5 * n], accel: Some([0.05 + n, 0.99, 0.1]) }
+        };
+        let swapped = |i: i64| {
+            let s = still(i);
+            LiveImuSample { gyro: s.accel.unwrap(), accel: Some(s.gyro), ..s }
+        };
+
+        let mut detector = ColumnSwapDetector::new();
+        let order = (0..400).find_map(|i| detector.observe(&still(i)));
+        assert_eq!(order, Some(ColumnOrder::Expected));
+        assert_eq!(detector.correct(still(1)).gyro, still(1).gyro);
+
+        let stab = live_manager();
+        let pipeline = LivePipeline::builder(stab.clone())
+            .without_integration()
+            .compute_fallback(ComputeFallback::Cpu)
+            .without_stuck_sensor_detection()
+            .auto_detect_column_swap(true)
+            .start()
+            .unwrap();
+        for i in 0..400 {
+            pipeline.push_imu(swapped(i), i * 5_000).unwrap();
+        }
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while pipeline.ingest_stats().samples < 400 {
+            assert!(Instant::now() < deadline, "samples never pushed");
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert!(pipeline.ingest_stats().columns_swapped);
+
+        // Every sample reaches the ring in the right order, the warm-up ones included
+        let gyro = stab.gyro.read();
+        let live = gyro.live.read();
+        let st = live.as_ref().unwrap();
+        let samples = st.ring.lock().snapshot(&st.sync.read());
+        assert_eq!(samples.len(), 400);
+        for (i, s) in samples.iter().enumerate() {
+            assert_eq!(s.gyro, still(i as i64).gyro, "sample {i}");
+            assert_eq!(s.accel, still(i as i64).accel, "sample {i}");
+        }
+    }
+
+    #[test]
+    fn lens_correction_amount_blends_between_lens_and_corrected_geometry() {
+        use nalgebra::Vector2;
+        use crate::stabilization::{ KernelParams, Stabilization, distortion_models::DistortionModel };
+
+        let model = DistortionModel::from_name("opencv_fisheye");
+        let mut params = KernelParams::default();
+        params.width = 1920; params.height = 1080;
+        params.output_width = 1920; params.output_height = 1080;
+        params.f = [900.0, 900.0];
+        params.c = [960.0, 540.0];
+        params.k[..4].copy_from_slice(&[0.05, 0.01, -0.005, 0.001]);
+        params.fov = 1.5;
+        let c = Vector2::new(960.0f32, 540.0);
+        let out_f = 900.0 / 1.5;
+        // Unrotated output camera, as `FrameTransform` sets it up: inverse of the output camera matrix
+        let mut m = [0.0f32; 14];
+        m[..9].copy_from_slice(&[1.0 / out_f, 0.0, -c.x / out_f, 0.0, 1.0 / out_f, -c.y / out_f, 0.0, 0.0, 1.0]);
+        let source = |p: Vector2<f32>| {
+            let uv = Stabilization::rotate_and_distort((p.x, p.y), 0, &params, &[m], &model, None, 0.0, &[]).unwrap();
+            Vector2::new(uv.0, uv.1)
+        };
+        let blended = |p: Vector2<f32>, amount: f32| {
+            let mut kp = params;
+            kp.lens_correction_amount = amount;
+            Stabilization::add_lens_distortion(p, &kp, &model, None)
+        };
+
+        for p in [Vector2::new(960.0, 540.0), Vector2::new(1500.0, 300.0), Vector2::new(100.0, 1000.0), Vector2::new(1900.0, 540.0)] {
+            // 1: fully corrected, the position goes to the projection untouched
+            assert!((blended(p, 1.0) - p).norm() < 1e-4);
+
+            // 0: the source pixel is the output pixel scaled by the fov, i.e. the uncorrected lens image
+            let lens = c + (p - c) * params.fov;
+            let s = source(blended(p, 0.0));
+            assert!((s - lens).norm() < 0.05, "{p:?}: {s:?} instead of {lens:?}");
+
+            // 0.5: the ray halfway between the corrected and the lens one
+            let r = (p - c) / out_f;
+            let undistorted = model.undistort_point((r.x, r.y), &params).unwrap();
+            let half = (Vector2::new(undistorted.0, undistorted.1) + r) * 0.5;
+            let ray = (blended(p, 0.5) - c) / out_f;
+            assert!((ray - half).norm() < 1e-5, "{p:?}: ray {ray:?} instead of {half:?}");
+            // and its source pixel between both geometries
+            let (full, none) = (source(p), lens);
+            let mid = source(blended(p, 0.5));
+            assert!((mid - c).norm() >= (full - c).norm().min((none - c).norm()) - 0.05);
+            assert!((mid - c).norm() <= (full - c).norm().max((none - c).norm()) + 0.05);
+        }
+    }
+
+    #[test]
+    fn lens_correction_ramps_over_stream_time() {
+        use crate::stabilization::{ ComputeParams, FrameTransform };
+
+        let stab = live_manager();
+        stab.set_render_params((1920, 1080), (1920, 1080));
+        let amount_at = |ms: f64| {
+            let params = ComputeParams::from_manager(&stab);
+            FrameTransform::at_timestamp(&params, ms, (ms / 1000.0 * 30.0) as usize).kernel_params.lens_correction_amount as f64
+        };
+
+        stab.live_on_new_frame(30, 1000.0, 1);
+        stab.set_live_lens_correction_amount(0.0, 500.0);
+        assert!((amount_at(900.0) - 1.0).abs() < 1e-6);
+        assert!((amount_at(1250.0) - 0.5).abs() < 1e-6);
+        assert!(amount_at(1500.0).abs() < 1e-6);
+        assert!(amount_at(3000.0).abs() < 1e-6);
+
+        // Reversed halfway through: starts from the amount in effect, no jump
+        stab.live_on_new_frame(37, 1250.0, 1);
+        stab.set_live_lens_correction_amount(1.0, 250.0);
+        assert!((amount_at(1250.0) - 0.5).abs() < 1e-6);
+        assert!((amount_at(1500.0) - 1.0).abs() < 1e-6);
+
+        // Without a ramp it's immediate
+        stab.set_live_lens_correction_amount(0.25, 0.0);
+        assert!((amount_at(1250.0) - 0.25).abs() < 1e-6);
+    }
+
+    #[test]
+    fn live_smoothing_algorithm_selects_the_smoothed_store() {
+        let stab = live_manager();
+        // 2 s of a steady pan at 0.5 rad/s
+        for i in 0..400_i64 {
+            let ts = i * 5_000;
+            stab.gyro.read().push_live_imu(LiveImuSample { ts_sensor_us: ts, gyro: [0.0, 0.0, 0.5], accel: Some([0.0, 0.0, 1.0]) }, ts);
+        }
+        let smoothed_at = |name: &str, t_ms: f64| {
+            stab.set_live_smoothing_algorithm(name);
+            stab.integrate_live_data();
+            let gyro = stab.gyro.read();
+            let live = gyro.live.read();
+            let st = live.as_ref().unwrap();
+            let org = st.quat_buffer_store_org.get_latest_buffer().unwrap().quat_at_ms(t_ms).unwrap();
+            (org, st.quat_buffer_store_smoothed.get_latest_buffer().unwrap().quat_at_ms(t_ms).unwrap())
+        };
+
+        let (org, follow) = smoothed_at("No smoothing", 1500.0);
+        let (_, locked) = smoothed_at("fixed camera", 1500.0);
+        assert_eq!(stab.params.read().live_smoothing_algorithm.as_deref(), Some("Fixed camera"));
+        // No smoothing follows the pan, the fixed camera doesn't
+        assert!(follow.angle_to(&org) < 1e-6);
+        let (_, locked_later) = smoothed_at("Fixed camera", 1900.0);
+        assert!(locked.angle_to(&locked_later) < 1e-6);
+        assert!(follow.angle_to(&locked) > 0.1, "both algorithms gave {follow:?}");
+
+        // Unknown names keep the current algorithm
+        stab.set_live_smoothing_algorithm("does not exist");
+        assert_eq!(stab.params.read().live_smoothing_algorithm.as_deref(), Some("Fixed camera"));
+    }
+
+    #[cfg(feature = "live-async")]
+    #[test]
+    fn async_stream_yields_stabilized_frames_in_order() {
+        use futures::StreamExt;
+
+        let stab = live_manager();
+        let pipeline = LivePipeline::new(stab.clone(), Some(Duration::from_millis(10)));
+        let stream = pipeline.frame_stream(16);
+
+        // Render thread: stabilizes 2x2 RGBA frames through an identity map and presents them like the render loop
+        let render_stab = stab.clone();
+        let render = thread::spawn(move || {
+            let coords = [0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 1.0, 1.0];
+            for i in 0..10_u8 {
+                let input = vec![i; 16];
+                let mut output = vec![0; 16];
+                stmap_render::remap_rgba_cpu(&input, (2, 2), &coords, &mut output, (2, 2));
+                render_stab.live_latest_frame.publish(i as i64 * 33_333, (2, 2), 4, &output);
+            }
+        });
+
+        let frames: Vec<LatestFrame> = pollster::block_on(async {
+            let mut imu = pipeline.imu_sink();
+            for i in 0..200_i64 {
+                let ts = i * 5_000;
+                imu.send((LiveImuSample { ts_sensor_us: ts, gyro: [0.0, 0.0, 0.5], accel: Some([0.0, 0.0, 1.0]) }, ts)).await.unwrap();
+            }
+            // The publisher outlives the stream, it never ends on its own
+            stream.take(10).collect().await
+        });
+        render.join().unwrap();
+
+        assert_eq!(frames.len(), 10);
+        for (i, frame) in frames.iter().enumerate() {
+            assert_eq!((frame.ts_us, frame.width, frame.height, frame.bytes_per_pixel), (i as i64 * 33_333, 2, 2, 4));
+            assert!(frame.data.iter().all(|&b| b == i as u8));
+        }
+        // The async sink fed the same IMU channel as `push_imu`
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while pipeline.ingest_stats().samples < 200 {
+            assert!(Instant::now() < deadline, "IMU samples never arrived");
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn wrapped_sensor_clock_is_unwrapped_into_a_continuous_timeline() {
+        let stab = live_manager();
+        // 32-bit µs counter, starting 100 ms before it wraps
+        let mut clock = ClockUnwrapper::from_bits(32, 1.0);
+        assert_eq!(clock.period_us(), 1 << 32);
+        let start = (1_i64 << 32) - 100_000;
+        let mut timeline = Vec::new();
+        for i in 0..200_i64 {
+            let raw = (start + i * 5_000) % (1 << 32);
+            let ts = clock.unwrap(raw);
+            timeline.push(ts);
+            stab.gyro.read().push_live_imu(LiveImuSample { ts_sensor_us: ts, gyro: [0.0, 0.0, 0.5], accel: Some([0.0, 0.0, 1.0]) }, ts);
+        }
+        assert_eq!(clock.wraps(), 1);
+        assert!(timeline.windows(2).all(|w| w[1] - w[0] == 5_000));
+        // A late sample from before the wrap keeps its place
+        assert_eq!(clock.unwrap((1 << 32) - 5_000), start + 95_000);
+        assert_eq!(clock.unwrap(1_000_000), (1 << 32) + 1_000_000);
+
+        // The ring kept every sample in order instead of evicting across a 71 min gap
+        let gyro = stab.gyro.read();
+        let live = gyro.live.read();
+        let st = live.as_ref().unwrap();
+        let samples = st.ring.lock().snapshot(&st.sync.read());
+        assert_eq!(samples.len(), 200);
+        assert!(samples.windows(2).all(|w| w[1].ts_sensor_us - w[0].ts_sensor_us == 5_000));
+    }
+
+    #[test]
+    fn external_orientation_wins_over_the_imu() {
+        use nalgebra::Vector3;
+        use crate::gyro_source::Quat64;
+        use crate::stabilization::{ ComputeParams, FrameTransform };
+
+        let tracked = |i: i64| Quat64::from_scaled_axis(Vector3::new(0.02 * i as f64, -0.01 * i as f64, 0.005 * i as f64));
+        let external_only = live_manager();
+        let with_imu = live_manager();
+        for i in 0..400_i64 {
+            let ts = i * 5_000;
+            with_imu.gyro.read().push_live_imu(LiveImuSample { ts_sensor_us: ts, gyro: [0.3, 0.0, 0.5], accel: Some([0.0, 0.0, 1.0]) }, ts);
+        }
+        with_imu.integrate_live_data();
+        for stab in [&external_only, &with_imu] {
+            for i in 0..30 {
+                stab.set_live_external_orientation(i * 33_333, tracked(i));
+            }
+        }
+
+        let gyro = with_imu.gyro.read();
+        assert_eq!(gyro.org_quat_at_timestamp(500.0), Quat64::identity());
+        assert!(gyro.smoothed_quat_at_timestamp(10.0 * 33.333).angle_to(&tracked(10)) < 1e-9);
+        // Halfway between two frames
+        assert!(gyro.smoothed_quat_at_timestamp(10.5 * 33.333).angle_to(&tracked(10).slerp(&tracked(11), 0.5)) < 1e-6);
+        assert_eq!(gyro.live_confidence_at_timestamp(500.0), Some(1.0));
+        drop(gyro);
+
+        // Both render with the tracked orientation only
+        let (p_ext, p_imu) = (ComputeParams::from_manager(&external_only), ComputeParams::from_manager(&with_imu));
+        for frame in [5_usize, 20] {
+            let ts_ms = frame as f64 * 33.333;
+            let (ext, imu) = (FrameTransform::at_timestamp(&p_ext, ts_ms, frame), FrameTransform::at_timestamp(&p_imu, ts_ms, frame));
+            assert_eq!(ext.matrices[0], imu.matrices[0], "frame {frame}");
+        }
+
+        with_imu.clear_live_external_orientation();
+        assert_ne!(with_imu.gyro.read().org_quat_at_timestamp(500.0), Quat64::identity());
+    }
+
+    #[test]
+    fn preview_orientation_selects_the_applied_store() {
+        use std::collections::BTreeMap;
+        use nalgebra::Vector3;
+        use crate::gyro_source::{ Quat64, QuatBuffer, QuatKind };
+        use crate::stabilization::{ ComputeParams, FrameTransform };
+
+        let org: BTreeMap<i64, Quat64> = (0..=100).map(|i| (i * 10_000, Quat64::from_scaled_axis(Vector3::new(0.1, -0.3, 1.0) * (i as f64 * 0.01)))).collect();
+        let identity: BTreeMap<i64, Quat64> = org.keys().map(|&t| (t, Quat64::identity())).collect();
+        let publish = |stab: &StabilizationManager, smoothed: &BTreeMap<i64, Quat64>| {
+            let gyro = stab.gyro.read();
+            let live = gyro.live.read();
+            let st = live.as_ref().unwrap();
+            st.quat_buffer_store_org.publish(QuatBuffer::from_btreemap(&org).unwrap());
+            st.quat_buffer_store_smoothed.publish(QuatBuffer::from_btreemap(smoothed).unwrap());
+        };
+        // Without rolling shutter the frame is rendered to `target * org⁻¹ * org`, i.e. to the target store's quaternion
+        let stab = live_manager();
+        publish(&stab, &identity);
+        let rendered_to = |target: &BTreeMap<i64, Quat64>| {
+            let reference = live_manager();
+            publish(&reference, target);
+            FrameTransform::at_timestamp(&ComputeParams::from_manager(&reference), 500.0, 15).matrices[0]
+        };
+
+        let at_500 = |stab: &StabilizationManager| FrameTransform::at_timestamp(&ComputeParams::from_manager(stab), 500.0, 15).matrices[0];
+        assert_eq!(at_500(&stab), rendered_to(&identity));
+
+        stab.set_live_preview_orientation(QuatKind::Org);
+        assert_eq!(at_500(&stab), rendered_to(&org));
+        assert_ne!(at_500(&stab), rendered_to(&identity));
+
+        stab.set_live_preview_orientation(QuatKind::Smoothed);
+        assert_eq!(at_500(&stab), rendered_to(&identity));
+    }
+
+    #[test]
+    fn of_roi_tracks_the_background() {
+        use crate::synchronization::{ FeatureRoi, OfMethod, OpticalFlowMethod, OpticalFlowTrait };
+
+        let (w, h) = (160_u32, 120_u32);
+        // Static blocky noise background, a 48x48 noise square in the center moving 4 px right per frame
+        let noise = |x: u32, y: u32, seed: u32| ((x / 4).wrapping_mul(73_856_093) ^ (y / 4).wrapping_mul(19_349_663) ^ seed).wrapping_mul(2_654_435_761) >> 24;
+        let frame = |i: u32| image::GrayImage::from_fn(w, h, |x, y| {
+            let (ox, oy) = (56 + i * 4, 36);
+            let v = if (ox..ox + 48).contains(&x) && (oy..oy + 48).contains(&y) { noise(x - i * 4, y, 0x5bd1e995) } else { noise(x, y, 0) };
+            image::Luma([v as u8])
+        });
+        let detect = |i: u32| OpticalFlowMethod::detect_features(OfMethod::Akaze.index(), i as i64 * 33_333, Arc::new(frame(i)), w, h);
+        let (a, b) = (detect(0), detect(1));
+
+        let roi = FeatureRoi::Border { margin: 0.25 };
+        let stab = live_manager();
+        stab.set_live_of_roi(roi);
+        assert_eq!(*stab.pose_estimator.feature_roi.read(), roi);
+
+        let (from, to) = roi.filter_pair(a.optical_flow_to(&b), (w, h)).unwrap();
+        assert!(from.len() >= 5, "{} background matches", from.len());
+        assert!(from.iter().all(|&p| roi.contains(p, (w, h))));
+        let mut shifts: Vec<f32> = from.iter().zip(&to).map(|(p, q)| ((q.0 - p.0).powi(2) + (q.1 - p.1).powi(2)).sqrt()).collect();
+        shifts.sort_by(f32::total_cmp);
+        assert!(shifts[shifts.len() / 2] < 1.0, "median shift {} px, the moving object leaks into the ROI", shifts[shifts.len() / 2]);
+
+        assert!(FeatureRoi::Rect { x: 0.0, y: 0.0, w: 0.5, h: 1.0 }.contains((10.0, 100.0), (w, h)));
+        assert!(!FeatureRoi::Rect { x: 0.0, y: 0.0, w: 0.5, h: 1.0 }.contains((100.0, 100.0), (w, h)));
+    }
+
+    #[cfg(feature = "testing")]
+    #[test]
+    fn simulated_imu_stream_feeds_the_pipeline() {
+        let stab = live_manager();
+        let pipeline = LivePipeline::builder(stab.clone()).compute_fallback(ComputeFallback::Cpu).start().unwrap();
+        let config = ImuSimConfig { profile: MotionProfile::Shake, rate_hz: 500.0, duration: Some(Duration::from_secs(1)), realtime: false, seed: 3 };
+
+        let collect = || simulate_imu_stream(config).iter().map(|s| (s.ts_sensor_us, s.gyro, s.accel)).collect::<Vec<_>>();
+        let samples = collect();
+        assert_eq!(samples.len(), 501);
+        assert!(samples.iter().enumerate().all(|(i, s)| s.0 == i as i64 * 2_000));
+        assert_eq!(samples, collect(), "same seed, same samples");
+
+        for s in simulate_imu_stream(config).iter() {
+            pipeline.push_imu(s, s.ts_sensor_us).unwrap();
+        }
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while pipeline.ingest_stats().samples < 501 && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(5));
+        }
+        assert_eq!(pipeline.ingest_stats().samples, 501);
+        assert!(!pipeline.ingest_stats().sensor_stuck);
+
+        // Real time: the second half of a 200 ms stream takes about 100 ms to arrive
+        let rx = simulate_imu_stream(ImuSimConfig { rate_hz: 100.0, duration: Some(Duration::from_millis(200)), ..Default::default() });
+        let start = Instant::now();
+        assert_eq!(rx.iter().count(), 21);
+        assert!(start.elapsed() >= Duration::from_millis(150), "{:?}", start.elapsed());
+    }
+
+    #[test]
+    fn prewarm_takes_the_setup_off_the_first_frame() {
+        use crate::stabilization::RGBA8;
+
+        const SIZE: (usize, usize) = (160, 90);
+        let stab = live_manager();
+        stab.set_device(-1);
+        let pipeline = LivePipeline::new(stab.clone(), None);
+        let mut input = vec![128u8; SIZE.0 * SIZE.1 * 4];
+        let mut output = vec![0u8; SIZE.0 * SIZE.1 * 4];
+        let mut buffers = Buffers {
+            input:  BufferDescription { size: (SIZE.0, SIZE.1, SIZE.0 * 4), rect: None, rotation: None, data: BufferSource::Cpu { buffer: &mut input },  texture_copy: false },
+            output: BufferDescription { size: (SIZE.0, SIZE.1, SIZE.0 * 4), rect: None, rotation: None, data: BufferSource::Cpu { buffer: &mut output }, texture_copy: false },
+        };
+        // Whether `process_pixels` would skip the backend setup for these buffers
+        let ready = |buffers: &Buffers| {
+            let undist = stab.stabilization.read();
+            !undist.initialized_backend.is_none() && undist.initialized_backend.get_hash() == undist.get_current_checksum(buffers)
+        };
+        assert!(!ready(&buffers));
+
+        pipeline.prewarm::<RGBA8>(SIZE.0, SIZE.1).unwrap();
+        assert_eq!(stab.stabilization.read().size, SIZE);
+        assert_eq!(stab.live_output_buffer_size(), SIZE);
+        assert!(ready(&buffers), "the first frame would set the backend up again");
+
+        stab.process_pixels::<RGBA8>(33_333, None, &mut buffers).unwrap();
+        assert!(ready(&buffers));
+    }
+}
@@ -0,0 +1,152 @@
+// live/motion.rs
+//
+// Synthetic camera motion for `simulate_imu_stream` and the standalone `LiveGyroGen` generator,
+// which includes this file by path. Keep it free of crate imports and external dependencies.
+
+use std::f64::consts::PI;
+use std::fmt;
+use std::str::FromStr;
+
+/// Standard gravity, m/s².
+const GRAVITY: f64 = 9.80665;
+/// Step the random walk of `LiveGyroGen` was tuned for, `RANDOM_WALK_RHO` applies per step of it.
+const RANDOM_WALK_STEP: f64 = 0.01;
+const RANDOM_WALK_RHO: f64 = 0.92;
+/// Per-axis scale (deg/s) and starting rate of the random walk.
+const RANDOM_WALK_SCALE: [f64; 3] = [11.333, 5.133, 17.133];
+const RANDOM_WALK_START: [f64; 3] = [17.0, 14.0, 19.0];
+/// Sensor noise on every profile, deg/s and m/s². Also keeps `Still` above a stuck sensor's noise floor.
+const GYRO_NOISE: f64 = 0.05;
+const ACCEL_NOISE: f64 = 0.02;
+
+/// How the simulated camera moves.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MotionProfile {
+    /// On a tripod: sensor noise only.
+    Still,
+    /// Smooth, slowly wandering rotation on all axes, what `LiveGyroGen` always sent.
+    #[default]
+    RandomWalk,
+    /// Constant 20 deg/s pan around the vertical axis.
+    Pan,
+    /// Handheld jitter: a few deg/s of 2-9 Hz shake on all axes.
+    Shake,
+}
+
+impl MotionProfile {
+    pub const ALL: [MotionProfile; 4] = [Self::Still, Self::RandomWalk, Self::Pan, Self::Shake];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Still => "still",
+            Self::RandomWalk => "random-walk",
+            Self::Pan => "pan",
+            Self::Shake => "shake",
+        }
+    }
+}
+
+impl fmt::Display for MotionProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { f.write_str(self.name()) }
+}
+
+impl FromStr for MotionProfile {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL.into_iter().find(|p| p.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| format!("unknown motion profile `{s}`, expected one of: still, random-walk, pan, shake"))
+    }
+}
+
+/// Generates the gyro and accelerometer readings of a `MotionProfile`, one step at a time.
+/// The same profile and seed give the same readings.
+#[derive(Clone, Debug)]
+pub struct MotionGen {
+    profile: MotionProfile,
+    rng: u64,
+    t: f64,
+    rate: [f64; 3],
+    rate_velocity: [f64; 3],
+}
+
+impl MotionGen {
+    pub fn new(profile: MotionProfile, seed: u64) -> Self {
+        Self { profile, rng: seed, t: 0.0, rate: RANDOM_WALK_START, rate_velocity: RANDOM_WALK_SCALE }
+    }
+
+    /// Advance by `dt` seconds: the angular rate in deg/s and the acceleration in m/s², gravity along +Y.
+    pub fn step(&mut self, dt: f64) -> ([f64; 3], [f64; 3]) {
+        self.t += dt;
+        let t = self.t;
+        let rate = match self.profile {
+            MotionProfile::Still => [0.0; 3],
+            MotionProfile::RandomWalk => {
+                // AR(1) angular acceleration with the correlation of `RANDOM_WALK_RHO` per 10 ms, whatever the rate
+                let rho = RANDOM_WALK_RHO.powf(dt / RANDOM_WALK_STEP);
+                let noise = [self.gaussian(), self.gaussian(), self.gaussian()];
+                for (((rate, velocity), scale), noise) in self.rate.iter_mut().zip(&mut self.rate_velocity).zip(RANDOM_WALK_SCALE).zip(noise) {
+                    *velocity = rho * *velocity + scale * (1.0 - rho * rho).sqrt() * noise;
+                    *rate += *velocity * dt;
+                }
+                self.rate
+            }
+            MotionProfile::Pan => [0.0, 20.0, 0.0],
+            MotionProfile::Shake => {
+                let shake = |amp: f64, hz: f64, phase: f64| amp * (2.0 * PI * hz * t + phase).sin();
+                [
+                    shake(4.0, 3.1, 0.0) + shake(1.5, 8.7, 1.0),
+                    shake(3.0, 2.3, 2.0) + shake(1.0, 7.9, 0.5),
+                    shake(2.0, 4.3, 1.5),
+                ]
+            }
+        };
+        let gyro = rate.map(|r| r + GYRO_NOISE * self.gaussian());
+        let accel = [0.0, GRAVITY, 0.0].map(|a| a + ACCEL_NOISE * self.gaussian());
+        (gyro, accel)
+    }
+
+    /// SplitMix64
+    fn next_u64(&mut self) -> u64 {
+        self.rng = self.rng.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.rng;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Standard normal sample (Box-Muller).
+    fn gaussian(&mut self) -> f64 {
+        let u1 = ((self.next_u64() >> 11) as f64 + 1.0) / (1u64 << 53) as f64;
+        let u2 = (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64;
+        (-2.0 * u1.ln()).sqrt() * (2.0 * PI * u2).cos()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn motion_profiles_are_deterministic_and_distinct() {
+        let run = |profile, seed| {
+            let mut motion = MotionGen::new(profile, seed);
+            (0..400).map(|_| motion.step(0.005)).collect::<Vec<_>>()
+        };
+        let mean_rate = |samples: &[([f64; 3], [f64; 3])]| {
+            samples.iter().map(|(g, _)| g.iter().map(|v| v * v).sum::<f64>().sqrt()).sum::<f64>() / samples.len() as f64
+        };
+        for profile in MotionProfile::ALL {
+            assert_eq!(run(profile, 7), run(profile, 7), "{profile}");
+            assert_eq!(profile.name().parse::<MotionProfile>(), Ok(profile));
+            // Gravity along +Y whatever the motion
+            let gravity = run(profile, 7).iter().map(|(_, a)| a[1]).sum::<f64>() / 400.0;
+            assert!((gravity - 9.81).abs() < 0.05, "{profile}: {gravity}");
+        }
+        assert!(mean_rate(&run(MotionProfile::Still, 1)) < 0.2);
+        let pan = run(MotionProfile::Pan, 1);
+        assert!((pan.iter().map(|(g, _)| g[1]).sum::<f64>() / 400.0 - 20.0).abs() < 0.1);
+        assert!(mean_rate(&run(MotionProfile::Shake, 1)) > 2.0);
+        assert_ne!(run(MotionProfile::RandomWalk, 1), run(MotionProfile::RandomWalk, 2));
+        assert!("orbit".parse::<MotionProfile>().is_err());
+    }
+}
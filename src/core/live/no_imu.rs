@@ -0,0 +1,73 @@
+// live/no_imu.rs
+use std::time::Duration;
+
+/// How long `LivePipeline::wait_for_imu` waits for the first sample by default.
+pub const DEFAULT_NO_IMU_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Output while video flows but no IMU sample ever arrived, e.g. the logger isn't connected.
+///
+/// Only the start of a stream is covered: once samples arrived, a gap in them is a warm-up or
+/// a dropout, handled like any other.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum NoImu {
+    /// The input frames copied to the output, no lens correction either.
+    Passthrough,
+    /// Lens correction with the identity rotation, what an empty quaternion store renders anyway.
+    #[default]
+    UndistortOnly,
+    /// `LivePipeline::wait_for_imu` fails once its timeout passed.
+    Error,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+    use crate::StabilizationManager;
+    use crate::gyro_source::{LiveImuSample, Quat64, QuatBuffer};
+    use crate::live::{live_manager, ComputeFallback, LiveError, LivePipeline};
+    use crate::stabilization::{ComputeParams, FrameTransform};
+
+    #[test]
+    fn missing_imu_follows_the_no_imu_behavior() {
+        let start = |behavior: NoImu| {
+            let stab = live_manager();
+            let pipeline = LivePipeline::builder(stab.clone())
+                .compute_fallback(ComputeFallback::Cpu)
+                .no_imu_behavior(behavior, Duration::from_millis(50))
+                .start()
+                .unwrap();
+            (stab, pipeline)
+        };
+        let at_500 = |stab: &StabilizationManager| FrameTransform::at_timestamp(&ComputeParams::from_manager(stab), 500.0, 15).matrices[0];
+
+        // Passthrough: the stream goes on and the render loop is told to copy the frames
+        let (stab, pipeline) = start(NoImu::Passthrough);
+        assert!(!pipeline.wait_for_imu().unwrap());
+        assert_eq!(stab.live_no_imu_action(), Some(NoImu::Passthrough));
+
+        // UndistortOnly: frames are rendered like with a store full of identity rotations
+        let (stab, pipeline) = start(NoImu::UndistortOnly);
+        assert!(!pipeline.wait_for_imu().unwrap());
+        assert_eq!(stab.live_no_imu_action(), Some(NoImu::UndistortOnly));
+        let reference = live_manager();
+        {
+            let identity: BTreeMap<i64, Quat64> = (0..=100).map(|i| (i * 10_000, Quat64::identity())).collect();
+            let gyro = reference.gyro.read();
+            let live = gyro.live.read();
+            let st = live.as_ref().unwrap();
+            st.quat_buffer_store_org.publish(QuatBuffer::from_btreemap(&identity).unwrap());
+            st.quat_buffer_store_smoothed.publish(QuatBuffer::from_btreemap(&identity).unwrap());
+        }
+        assert_eq!(at_500(&stab), at_500(&reference));
+
+        // Error: waiting for the IMU fails once the timeout passed
+        let (stab, pipeline) = start(NoImu::Error);
+        assert!(matches!(pipeline.wait_for_imu(), Err(LiveError::NoImu(t)) if t == Duration::from_millis(50)));
+
+        // The first sample ends the no-IMU case, whatever the behavior
+        pipeline.push_imu(LiveImuSample { ts_sensor_us: 0, gyro: [0.0; 3], accel: Some([0.0, 0.0, 1.0]) }, 0).unwrap();
+        assert!(pipeline.wait_for_imu().unwrap());
+        assert_eq!(stab.live_no_imu_action(), None);
+    }
+}
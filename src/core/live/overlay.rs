@@ -0,0 +1,62 @@
+// live/overlay.rs
+use crate::gpu::drawing::{ Alpha, Color, DrawCanvas, Stage };
+
+/// Framing guide drawn on the stabilized output, through the same drawing canvas the kernels
+/// already blend in (`Stage::OnOutput`), so it costs nothing extra per pixel.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum LiveOverlay {
+    #[default]
+    None,
+    /// Rule-of-thirds grid.
+    Grid,
+    /// Cross at the center of the output.
+    Crosshair,
+    /// Outline of the 90% action-safe area.
+    SafeArea,
+}
+
+/// Fraction of the output inside the safe area outline.
+const SAFE_AREA: f64 = 0.9;
+
+impl LiveOverlay {
+    /// Draw the overlay for an output of `output_size` pixels.
+    pub fn draw(self, canvas: &mut DrawCanvas, output_size: (usize, usize), y_inverted: bool) {
+        let (w, h) = (output_size.0 as i32, output_size.1 as i32);
+        if w == 0 || h == 0 { return; }
+        // One canvas cell covers `scale` pixels, so stepping by it hits every cell once
+        let step = canvas.scale.max(1);
+        let mut hline = |canvas: &mut DrawCanvas, y: i32, x0: i32, x1: i32, color: Color, alpha: Alpha| {
+            for x in (x0.max(0)..x1.min(w)).step_by(step) {
+                canvas.put_pixel(x, y, color, alpha, Stage::OnOutput, y_inverted, 1);
+            }
+        };
+        let vline = |canvas: &mut DrawCanvas, x: i32, y0: i32, y1: i32, color: Color, alpha: Alpha| {
+            for y in (y0.max(0)..y1.min(h)).step_by(step) {
+                canvas.put_pixel(x, y, color, alpha, Stage::OnOutput, y_inverted, 1);
+            }
+        };
+
+        match self {
+            LiveOverlay::None => {}
+            LiveOverlay::Grid => {
+                for i in 1..3 {
+                    hline(canvas, h * i / 3, 0, w, Color::Yellow, Alpha::Alpha50);
+                    vline(canvas, w * i / 3, 0, h, Color::Yellow, Alpha::Alpha50);
+                }
+            }
+            LiveOverlay::Crosshair => {
+                let (cx, cy, arm) = (w / 2, h / 2, w.min(h) / 20);
+                hline(canvas, cy, cx - arm, cx + arm + 1, Color::Red, Alpha::Alpha100);
+                vline(canvas, cx, cy - arm, cy + arm + 1, Color::Red, Alpha::Alpha100);
+            }
+            LiveOverlay::SafeArea => {
+                let mx = (w as f64 * (1.0 - SAFE_AREA) / 2.0).round() as i32;
+                let my = (h as f64 * (1.0 - SAFE_AREA) / 2.0).round() as i32;
+                hline(canvas, my, mx, w - mx, Color::Green, Alpha::Alpha75);
+                hline(canvas, h - my - 1, mx, w - mx, Color::Green, Alpha::Alpha75);
+                vline(canvas, mx, my, h - my, Color::Green, Alpha::Alpha75);
+                vline(canvas, w - mx - 1, my, h - my, Color::Green, Alpha::Alpha75);
+            }
+        }
+    }
+}
@@ -0,0 +1,185 @@
+// live/param_log.rs
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use crossbeam_channel::{unbounded, Sender};
+use log::{error, info, warn};
+
+use crate::StabilizationManager;
+
+/// One parameter change of a live session, one JSON object per line in the sidecar.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ParamEvent {
+    /// Milliseconds since the log was started.
+    pub t_ms: f64,
+    /// Wall clock, milliseconds since the unix epoch.
+    pub unix_ms: u64,
+    /// Live timeline position (`duration_ms` of the stream so far) when the change was made.
+    pub video_ms: f64,
+    /// Name of the `set_live_*` setter, without the prefix.
+    pub name: String,
+    pub value: serde_json::Value,
+}
+
+/// Sidecar JSONL log of live parameter changes.
+///
+/// `record` only sends the event to a writer thread, so it never blocks on disk IO.
+/// The file is flushed after every burst of events and when the log is dropped.
+pub struct ParamLog {
+    path: PathBuf,
+    started: Instant,
+    tx: Option<Sender<ParamEvent>>,
+    writer: Option<thread::JoinHandle<()>>,
+}
+
+impl ParamLog {
+    pub fn create(path: &Path) -> std::io::Result<Self> {
+        let mut out = BufWriter::new(File::create(path)?);
+        let (tx, rx) = unbounded::<ParamEvent>();
+        let name = path.display().to_string();
+        let writer = thread::Builder::new()
+            .name("live_param_log".into())
+            .spawn(move || {
+                while let Ok(ev) = rx.recv() {
+                    let mut res = Self::write_event(&mut out, &ev);
+                    for ev in rx.try_iter() {
+                        res = res.and_then(|_| Self::write_event(&mut out, &ev));
+                    }
+                    if let Err(e) = res.and_then(|_| out.flush()) {
+                        error!("live: failed to write parameter log {name}: {e}");
+                        return;
+                    }
+                }
+            })?;
+        info!("live: logging parameter changes to {}", path.display());
+        Ok(Self { path: path.to_path_buf(), started: Instant::now(), tx: Some(tx), writer: Some(writer) })
+    }
+
+    fn write_event(out: &mut impl Write, ev: &ParamEvent) -> std::io::Result<()> {
+        serde_json::to_writer(&mut *out, ev)?;
+        out.write_all(b"\n")
+    }
+
+    pub fn path(&self) -> &Path { &self.path }
+
+    pub fn record(&self, video_ms: f64, name: &str, value: serde_json::Value) {
+        let ev = ParamEvent {
+            t_ms: self.started.elapsed().as_secs_f64() * 1000.0,
+            unix_ms: SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or_default(),
+            video_ms,
+            name: name.to_string(),
+            value,
+        };
+        if let Some(tx) = &self.tx {
+            let _ = tx.send(ev);
+        }
+    }
+
+    /// Read back a sidecar written by `ParamLog`, skipping lines that don't parse.
+    pub fn load(path: &Path) -> std::io::Result<Vec<ParamEvent>> {
+        let mut events = Vec::new();
+        for (i, line) in BufReader::new(File::open(path)?).lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() { continue; }
+            match serde_json::from_str(&line) {
+                Ok(ev) => events.push(ev),
+                Err(e) => warn!("{}:{}: invalid parameter event: {e}", path.display(), i + 1),
+            }
+        }
+        Ok(events)
+    }
+
+    /// Re-apply a recorded event, returns `false` for unknown or malformed events.
+    pub fn apply(stab: &StabilizationManager, ev: &ParamEvent) -> bool {
+        let v = &ev.value;
+        let usize_at = |i: usize| v.get(i).and_then(|x| x.as_u64()).map(|x| x as usize);
+        match ev.name.as_str() {
+            "output_size" => match (usize_at(0), usize_at(1)) {
+                (Some(w), Some(h)) => stab.set_live_output_size(w, h),
+                _ => return false,
+            },
+            "output_rect" => match (usize_at(0), usize_at(1), usize_at(2), usize_at(3)) {
+                (Some(x), Some(y), Some(w), Some(h)) => stab.set_live_output_rect(x, y, w, h),
+                (None, ..) if v.is_null() => stab.clear_live_output_rect(),
+                _ => return false,
+            },
+            "alpha_mask" => match v.as_bool() {
+                Some(b) => stab.set_live_alpha_mask(b),
+                None => return false,
+            },
+            "max_crop" => stab.set_live_max_crop(v.as_f64()),
+            "buffer_blend" => match v.as_f64() {
+                Some(ms) => stab.set_live_buffer_blend(ms),
+                None => return false,
+            },
+            "overlay" => match serde_json::from_value(v.clone()) {
+                Ok(overlay) => stab.set_live_overlay(overlay),
+                Err(_) => return false,
+            },
+            "interpolation" => match serde_json::from_value(v.clone()) {
+                Ok(interpolation) => stab.set_live_interpolation(interpolation),
+                Err(_) => return false,
+            },
+            "mount_correction" => match (v.get(0).and_then(|x| x.as_f64()), v.get(1).and_then(|x| x.as_f64()), v.get(2).and_then(|x| x.as_f64())) {
+                (Some(roll), Some(pitch), Some(yaw)) => stab.set_live_mount_correction(roll, pitch, yaw),
+                _ => return false,
+            },
+            "lens_correction_amount" => match (v.get("amount").and_then(|x| x.as_f64()), v.get("ramp_ms").and_then(|x| x.as_f64())) {
+                (Some(amount), Some(ramp_ms)) => stab.set_live_lens_correction_amount(amount, ramp_ms),
+                _ => return false,
+            },
+            "max_rotation_delta_deg" => stab.set_live_max_rotation_delta(v.as_f64()),
+            "gyro_lpf_cutoff_hz" => stab.set_live_gyro_lpf_cutoff_hz(v.as_f64()),
+            "no_imu_behavior" => match serde_json::from_value(v.clone()) {
+                Ok(behavior) => stab.set_live_no_imu_behavior(behavior),
+                Err(_) => return false,
+            },
+            "latency_profile" => match serde_json::from_value(v.clone()) {
+                Ok(profile) => stab.set_live_latency_profile(profile),
+                Err(_) => return false,
+            },
+            "preview_orientation" => match serde_json::from_value(v.clone()) {
+                Ok(kind) => stab.set_live_preview_orientation(kind),
+                Err(_) => return false,
+            },
+            "smoothing_algorithm" => match v.as_str() {
+                Some(name) => stab.set_live_smoothing_algorithm(name),
+                None => return false,
+            },
+            "digital_zoom" => match v.as_f64() {
+                Some(zoom) => stab.set_live_digital_zoom(zoom),
+                None => return false,
+            },
+            "of_method" => match serde_json::from_value(v.clone()) {
+                Ok(method) => stab.set_live_of_method(method),
+                Err(_) => return false,
+            },
+            "of_roi" => match serde_json::from_value(v.clone()) {
+                Ok(roi) => stab.set_live_of_roi(roi),
+                Err(_) => return false,
+            },
+            "smoothing_window" => {
+                let field = |k: &str| v.get(k).and_then(|x| x.as_f64());
+                match (field("pre_ms"), field("post_ms"), field("center_ratio")) {
+                    (Some(pre_ms), Some(post_ms), Some(center_ratio)) => stab.set_live_smoothing_window(crate::gyro_source::SmoothingWindow { pre_ms, post_ms, center_ratio }),
+                    _ => return false,
+                }
+            },
+            _ => return false,
+        }
+        true
+    }
+}
+
+impl Drop for ParamLog {
+    fn drop(&mut self) {
+        // Closing the channel lets the writer drain and flush
+        self.tx.take();
+        if let Some(w) = self.writer.take() {
+            let _ = w.join();
+        }
+    }
+}
@@ -0,0 +1,158 @@
+// live/reorder.rs
+use std::collections::BTreeMap;
+
+use super::LiveImuMsg;
+
+/// Puts the IMU samples of several clients back in `ts_sensor_us` order before they reach the ring.
+///
+/// Samples are held until the newest timestamp seen is `window_us` past them, then released oldest
+/// first, so two streams that lag each other by less than the window come out merged. A sample older
+/// than the last released one can't be put in order anymore and is dropped as late. Samples with equal
+/// timestamps keep their arrival order.
+///
+/// A sample more than the window behind the last released one is a restart of the sensor clock, not
+/// a late sample: the order starts over from it, after whatever is still buffered from before.
+#[derive(Debug)]
+pub struct ImuReorderBuffer {
+    window_us: i64,
+    /// Keyed by (clock epoch, timestamp, arrival)
+    pending: BTreeMap<(u64, i64, u64), LiveImuMsg>,
+    seq: u64,
+    epoch: u64,
+    newest_us: Option<i64>,
+    released_us: Option<i64>,
+    late: u64,
+}
+
+impl ImuReorderBuffer {
+    pub fn new(window_us: i64) -> Self {
+        Self { window_us: window_us.max(0), pending: BTreeMap::new(), seq: 0, epoch: 0, newest_us: None, released_us: None, late: 0 }
+    }
+
+    pub fn window_us(&self) -> i64 { self.window_us }
+
+    /// Samples dropped for arriving after their place in the order was released.
+    pub fn late(&self) -> u64 { self.late }
+
+    pub fn pending(&self) -> usize { self.pending.len() }
+
+    /// Sensor clock restarts seen, see the type docs.
+    pub fn restarts(&self) -> u64 { self.epoch }
+
+    /// Buffer a sample, `false` if it was dropped as late.
+    pub fn push(&mut self, msg: LiveImuMsg) -> bool {
+        let ts = msg.0.ts_sensor_us;
+        if let Some(released) = self.released_us.filter(|r| ts < *r) {
+            if released - ts <= self.window_us {
+                self.late += 1;
+                return false;
+            }
+            self.epoch += 1;
+            self.newest_us = None;
+            self.released_us = None;
+        }
+        self.newest_us = Some(self.newest_us.map_or(ts, |n| n.max(ts)));
+        self.pending.insert((self.epoch, ts, self.seq), msg);
+        self.seq += 1;
+        true
+    }
+
+    /// Move the samples that are out of the window to `out`, in timestamp order.
+    pub fn release(&mut self, out: &mut Vec<LiveImuMsg>) {
+        let Some(newest) = self.newest_us else { return; };
+        self.release_until(newest - self.window_us, out);
+    }
+
+    /// Move all buffered samples to `out`, e.g. when the stream went quiet.
+    pub fn flush(&mut self, out: &mut Vec<LiveImuMsg>) {
+        self.release_until(i64::MAX, out);
+    }
+
+    fn release_until(&mut self, until_us: i64, out: &mut Vec<LiveImuMsg>) {
+        while let Some(entry) = self.pending.first_entry() {
+            let (epoch, ts, _) = *entry.key();
+            // Samples from before a clock restart go first, whatever their timestamp
+            let current = epoch == self.epoch;
+            if current && ts > until_us { break; }
+            out.push(entry.remove());
+            if current {
+                self.released_us = Some(ts);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+    use std::time::{Duration, Instant};
+    use crate::gyro_source::LiveImuSample;
+    use crate::live::{live_manager, ComputeFallback, LivePipeline};
+
+    fn sample(ts: i64) -> LiveImuMsg { (LiveImuSample { ts_sensor_us: ts, gyro: [0.0; 3], accel: None }, ts) }
+
+    fn released(buf: &mut ImuReorderBuffer) -> Vec<i64> {
+        let mut out = Vec::new();
+        buf.release(&mut out);
+        out.iter().map(|(s, _)| s.ts_sensor_us).collect()
+    }
+
+    #[test]
+    fn sensor_clock_restart_starts_a_new_order() {
+        let mut buf = ImuReorderBuffer::new(50_000);
+        for ts in [1_000_000, 1_020_000, 1_010_000, 1_040_000, 1_030_000, 1_100_000] {
+            assert!(buf.push(sample(ts)));
+        }
+        assert_eq!(released(&mut buf), [1_000_000, 1_010_000, 1_020_000, 1_030_000, 1_040_000]);
+
+        // Behind the last released sample by less than the window: late
+        assert!(!buf.push(sample(1_005_000)));
+        assert_eq!((buf.late(), buf.restarts()), (1, 0));
+
+        // Far behind: the sensor clock restarted, nothing of the new stream is dropped as late
+        for ts in [1_000, 3_000, 2_000, 80_000] {
+            assert!(buf.push(sample(ts)));
+        }
+        assert_eq!((buf.late(), buf.restarts()), (1, 1));
+        // What was left of the old stream first, then the new one in order
+        assert_eq!(released(&mut buf), [1_100_000, 1_000, 2_000, 3_000]);
+        assert!(buf.push(sample(40_000)));
+        assert!(!buf.push(sample(2_500)));
+    }
+
+    #[test]
+    fn reorder_window_merges_interleaved_clients() {
+        let stab = live_manager();
+        let pipeline = LivePipeline::builder(stab.clone())
+            .without_integration()
+            .compute_fallback(ComputeFallback::Cpu)
+            .imu_reorder_window(Duration::from_millis(50))
+            .start()
+            .unwrap();
+
+        // Two 100 Hz clients 5 ms apart, arriving in chunks of 4 samples: client A's chunk first, then B's
+        for chunk in 0..25_i64 {
+            for client_offset in [0, 5_000] {
+                for i in 0..4 {
+                    pipeline.imu_sender().send(sample(chunk * 40_000 + i * 10_000 + client_offset)).unwrap();
+                }
+            }
+        }
+        // Way behind what was released already
+        pipeline.imu_sender().send(sample(0)).unwrap();
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while pipeline.ingest_stats().samples < 200 || pipeline.ingest_stats().late_samples < 1 {
+            assert!(Instant::now() < deadline, "samples never released: {:?}", pipeline.ingest_stats());
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(pipeline.ingest_stats().late_samples, 1);
+
+        let gyro = stab.gyro.read();
+        let live = gyro.live.read();
+        let ts: Vec<i64> = live.as_ref().unwrap().ring.lock().buf.iter().map(|s| s.ts_sensor_us).collect();
+        assert_eq!(ts.len(), 200);
+        assert!(ts.windows(2).all(|w| w[0] < w[1]), "ring out of order");
+    }
+}
@@ -0,0 +1,130 @@
+// live/rotation_guard.rs
+use log::{info, warn};
+use parking_lot::Mutex;
+
+use crate::gyro_source::Quat64;
+
+/// Consecutive frames a spike is held for at most. A jump that lasts longer is real motion
+/// (or an orientation that stays off), holding it any longer would freeze the output.
+pub const MAX_HELD_FRAMES: u32 = 5;
+
+/// Limit on how far the applied rotation may move from one frame to the next, against single
+/// corrupt IMU samples violently rotating the output for a frame.
+///
+/// Each new frame timestamp is compared with the rotation applied to the previous one. Beyond
+/// `max_delta_deg` that previous rotation is held and the frame counted as a spike, for up to
+/// `MAX_HELD_FRAMES` frames in a row. The guard follows one stream of frames: the live manager
+/// advances it once per frame (`StabilizationManager::live_on_new_frame`), the renderers only read
+/// the rotation applied at that frame with `applied_at`, so maps built ahead or offline renders
+/// can't move it.
+#[derive(Debug, Default)]
+pub struct RotationSpikeGuard {
+    state: Mutex<GuardState>,
+}
+
+#[derive(Debug, Default)]
+struct GuardState {
+    last: Option<(f64, Quat64)>,
+    held_frames: u32,
+    frames: u64,
+    spikes: u64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RotationSpikeSnapshot {
+    /// Frames checked against the limit.
+    pub frames: u64,
+    /// Frames that got the previous frame's rotation instead of their own.
+    pub spikes: u64,
+}
+
+impl RotationSpikeGuard {
+    /// Advance to the frame at `timestamp_ms` and return the rotation to apply to it: `rotation`, or the
+    /// previous frame's when it's more than `max_delta_deg` away.
+    pub fn limit(&self, timestamp_ms: f64, rotation: Quat64, max_delta_deg: f64) -> Quat64 {
+        let mut st = self.state.lock();
+        let Some((last_ts, last)) = st.last else {
+            st.last = Some((timestamp_ms, rotation));
+            st.frames += 1;
+            return rotation;
+        };
+        if timestamp_ms == last_ts { return last; }
+        if timestamp_ms < last_ts { return rotation; }
+
+        st.frames += 1;
+        let delta_deg = last.angle_to(&rotation).to_degrees();
+        let applied = if delta_deg > max_delta_deg && st.held_frames < MAX_HELD_FRAMES {
+            st.held_frames += 1;
+            st.spikes += 1;
+            warn!("live: rotation spike of {delta_deg:.1}° at {timestamp_ms:.3} ms (limit {max_delta_deg:.1}°), holding the previous rotation");
+            last
+        } else {
+            if st.held_frames >= MAX_HELD_FRAMES {
+                info!("live: rotation jump of {delta_deg:.1}° persisted for {} frames, following it", st.held_frames);
+            }
+            st.held_frames = 0;
+            rotation
+        };
+        st.last = Some((timestamp_ms, applied));
+        applied
+    }
+
+    /// Rotation applied to the frame at `timestamp_ms`, `None` unless it is the last frame `limit` was advanced to.
+    pub fn applied_at(&self, timestamp_ms: f64) -> Option<Quat64> {
+        self.state.lock().last.filter(|(ts, _)| *ts == timestamp_ms).map(|(_, rotation)| rotation)
+    }
+
+    pub fn snapshot(&self) -> RotationSpikeSnapshot {
+        let st = self.state.lock();
+        RotationSpikeSnapshot { frames: st.frames, spikes: st.spikes }
+    }
+
+    /// Forget the previous frame and the counters, e.g. when the limit changes.
+    pub fn reset(&self) { *self.state.lock() = GuardState::default(); }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+    use nalgebra::Vector3;
+    use crate::StabilizationManager;
+    use crate::gyro_source::QuatBuffer;
+    use crate::live::live_manager;
+    use crate::stabilization::{ComputeParams, FrameTransform};
+
+    #[test]
+    fn rotation_spike_is_held_at_the_previous_frame() {
+        // Slow pan, 1.1° per 40 ms frame, and a corrupt sample 90° off at 520 ms
+        let smooth: BTreeMap<i64, Quat64> = (0..=100).map(|i| (i * 10_000, Quat64::from_scaled_axis(Vector3::new(0.0, 1.0, 0.0) * (i as f64 * 0.005)))).collect();
+        let mut spiky = smooth.clone();
+        spiky.insert(520_000, spiky[&520_000] * Quat64::from_scaled_axis(Vector3::new(1.0, 0.0, 0.0) * std::f64::consts::FRAC_PI_2));
+        let with_quats = |quats: &BTreeMap<i64, Quat64>| {
+            let stab = live_manager();
+            {
+                let gyro = stab.gyro.read();
+                let live = gyro.live.read();
+                let st = live.as_ref().unwrap();
+                st.quat_buffer_store_org.publish(QuatBuffer::from_btreemap(quats).unwrap());
+                st.quat_buffer_store_smoothed.publish(QuatBuffer::from_btreemap(quats).unwrap());
+            }
+            stab
+        };
+        let render = |stab: &StabilizationManager, ts_ms: f64| FrameTransform::at_timestamp(&ComputeParams::from_manager(stab), ts_ms, (ts_ms / 40.0) as usize).matrices[0];
+
+        let (clean, unguarded, guarded) = (with_quats(&smooth), with_quats(&spiky), with_quats(&spiky));
+        guarded.set_live_max_rotation_delta(Some(5.0));
+        for frame in 0..25 {
+            let ts = frame as f64 * 40.0;
+            guarded.live_on_new_frame(frame, ts, 1);
+            let out = render(&guarded, ts);
+            if ts == 520.0 {
+                assert_ne!(render(&unguarded, ts), render(&clean, ts));
+                assert!((out - render(&clean, ts - 40.0)).abs().max() < 1e-9, "spike not held at the previous rotation");
+            } else {
+                assert_eq!(out, render(&clean, ts), "frame {frame} changed by the guard");
+            }
+        }
+        assert_eq!(guarded.live_rotation_guard.snapshot().spikes, 1);
+    }
+}
@@ -0,0 +1,101 @@
+// live/sensor_stuck.rs
+use std::time::Duration;
+
+use log::{info, warn};
+
+use crate::gyro_source::LiveImuSample;
+
+/// Largest absolute reading still taken as zero by default. Real sensors are never this quiet, even
+/// at rest their noise is orders of magnitude above it.
+pub const DEFAULT_STUCK_NOISE_FLOOR: f64 = 1e-9;
+/// How long of sensor time the readings stay at zero by default before the sensor counts as stuck.
+pub const DEFAULT_STUCK_DURATION: Duration = Duration::from_secs(2);
+
+/// Flags a sensor that keeps streaming zeros, typically an IMU disconnected from a logger that still
+/// runs. The integration turns that into the identity rotation without anything looking wrong.
+///
+/// A sample reads zero when its gyro components, and its accel ones if it has any, are all within
+/// `noise_floor` of 0. Zero samples spanning `duration` of sensor time raise the alert, the first
+/// sample that isn't zero clears it.
+#[derive(Debug)]
+pub struct SensorStuckDetector {
+    noise_floor: f64,
+    duration_us: i64,
+    zero_since_us: Option<i64>,
+    stuck: bool,
+}
+
+impl SensorStuckDetector {
+    pub fn new(noise_floor: f64, duration: Duration) -> Self {
+        Self { noise_floor: noise_floor.max(0.0), duration_us: duration.as_micros() as i64, zero_since_us: None, stuck: false }
+    }
+
+    pub fn is_stuck(&self) -> bool { self.stuck }
+
+    /// Look at one sample, `Some(stuck)` when the state changes.
+    pub fn observe(&mut self, sample: &LiveImuSample) -> Option<bool> {
+        let zero = sample.gyro.iter().chain(sample.accel.iter().flatten()).all(|v| v.abs() <= self.noise_floor);
+        if !zero {
+            self.zero_since_us = None;
+            if !self.stuck { return None; }
+            self.stuck = false;
+            info!("live: the IMU reads non-zero values again, SensorStuck cleared");
+            return Some(false);
+        }
+        let since = *self.zero_since_us.get_or_insert(sample.ts_sensor_us);
+        let zero_us = sample.ts_sensor_us - since;
+        if self.stuck || zero_us < self.duration_us { return None; }
+        self.stuck = true;
+        warn!("live: SensorStuck, every IMU reading has been zero for {:.1} s, is the sensor disconnected?", zero_us as f64 / 1_000_000.0);
+        Some(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+    use std::time::Instant;
+    use crate::live::{live_manager, ComputeFallback, LivePipeline};
+
+    #[test]
+    fn all_zero_imu_raises_sensor_stuck() {
+        let stab = live_manager();
+        let pipeline = LivePipeline::builder(stab.clone())
+            .compute_fallback(ComputeFallback::Cpu)
+            .stuck_sensor_detection(1e-6, Duration::from_millis(500))
+            .start()
+            .unwrap();
+        let push = |from_ms: i64, to_ms: i64, gyro: [f64; 3], accel: Option<[f64; 3]>| {
+            for ms in (from_ms..to_ms).step_by(5) {
+                pipeline.push_imu(LiveImuSample { ts_sensor_us: ms * 1000, gyro, accel }, ms * 1000).unwrap();
+            }
+        };
+        let settled = |samples: u64| {
+            let deadline = Instant::now() + Duration::from_secs(5);
+            while pipeline.ingest_stats().samples < samples {
+                assert!(Instant::now() < deadline, "samples never ingested");
+                thread::sleep(Duration::from_millis(5));
+            }
+            pipeline.ingest_stats()
+        };
+
+        // A working sensor at rest: noise on the gyro, gravity on the accel
+        push(0, 1000, [1e-3, -2e-3, 5e-4], Some([0.0, 0.0, 1.0]));
+        assert!(!settled(200).sensor_stuck);
+
+        // Disconnected: zeros for less than the duration, then past it
+        push(1000, 1400, [0.0; 3], Some([0.0; 3]));
+        assert!(!settled(280).sensor_stuck);
+        push(1400, 1600, [0.0; 3], Some([0.0; 3]));
+        let stats = settled(320);
+        assert!(stats.sensor_stuck);
+        assert_eq!(stats.sensor_stuck_alerts, 1);
+
+        // Back: the alert clears, the count stays
+        push(1600, 1700, [1e-3, 0.0, 0.0], Some([0.0, 0.0, 1.0]));
+        let stats = settled(340);
+        assert!(!stats.sensor_stuck);
+        assert_eq!(stats.sensor_stuck_alerts, 1);
+    }
+}
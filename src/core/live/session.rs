@@ -0,0 +1,102 @@
+// live/session.rs
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Frame counters of one stretch of a live session.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FrameCounts {
+    /// Frames taken off the reader's queue.
+    pub received: u64,
+    /// Frames that went through stabilization.
+    pub stabilized: u64,
+    /// Frames handed to the sink, repeated previews included.
+    pub presented: u64,
+    /// Frames skipped: dropped by the reader, over the frame queue budget or over the latency SLA.
+    pub dropped: u64,
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    received: AtomicU64,
+    stabilized: AtomicU64,
+    presented: AtomicU64,
+    dropped: AtomicU64,
+}
+
+impl Counters {
+    fn snapshot(&self) -> FrameCounts {
+        FrameCounts {
+            received: self.received.load(Ordering::Relaxed),
+            stabilized: self.stabilized.load(Ordering::Relaxed),
+            presented: self.presented.load(Ordering::Relaxed),
+            dropped: self.dropped.load(Ordering::Relaxed),
+        }
+    }
+
+    fn reset(&self) {
+        self.received.store(0, Ordering::Relaxed);
+        self.stabilized.store(0, Ordering::Relaxed);
+        self.presented.store(0, Ordering::Relaxed);
+        self.dropped.store(0, Ordering::Relaxed);
+    }
+}
+
+/// Frame counters of a live session that survive stream reconnects.
+///
+/// The render loop is restarted for every connection of the stream reader, so it only reports events here
+/// and calls `begin_connection` when it starts. The session totals keep growing, the per-connection
+/// counters start over.
+#[derive(Debug, Default)]
+pub struct LiveSessionStats {
+    session: Counters,
+    connection: Counters,
+    connections: AtomicU64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LiveSessionSnapshot {
+    /// Since the start of the session.
+    pub session: FrameCounts,
+    /// Since the last (re)connect.
+    pub connection: FrameCounts,
+    /// Connections so far, the first one included.
+    pub connections: u64,
+}
+
+impl LiveSessionSnapshot {
+    pub fn reconnects(&self) -> u64 { self.connections.saturating_sub(1) }
+}
+
+impl LiveSessionStats {
+    pub fn begin_connection(&self) {
+        self.connection.reset();
+        self.connections.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_received(&self) {
+        self.session.received.fetch_add(1, Ordering::Relaxed);
+        self.connection.received.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_stabilized(&self) {
+        self.session.stabilized.fetch_add(1, Ordering::Relaxed);
+        self.connection.stabilized.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_presented(&self) {
+        self.session.presented.fetch_add(1, Ordering::Relaxed);
+        self.connection.presented.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_dropped(&self) {
+        self.session.dropped.fetch_add(1, Ordering::Relaxed);
+        self.connection.dropped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> LiveSessionSnapshot {
+        LiveSessionSnapshot {
+            session: self.session.snapshot(),
+            connection: self.connection.snapshot(),
+            connections: self.connections.load(Ordering::Relaxed),
+        }
+    }
+}
@@ -0,0 +1,61 @@
+// live/simulator.rs
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::{bounded, Receiver};
+
+use super::motion::{MotionGen, MotionProfile};
+use crate::gyro_source::LiveImuSample;
+
+/// Settings of `simulate_imu_stream`.
+#[derive(Clone, Copy, Debug)]
+pub struct ImuSimConfig {
+    pub profile: MotionProfile,
+    /// Samples per second.
+    pub rate_hz: f64,
+    /// Length of the stream, `None` to run until the receiver is dropped.
+    pub duration: Option<Duration>,
+    /// Send the samples in real time like a connected logger, or as fast as they are received.
+    pub realtime: bool,
+    /// Same seed, same samples.
+    pub seed: u64,
+}
+
+impl Default for ImuSimConfig {
+    fn default() -> Self {
+        Self { profile: MotionProfile::default(), rate_hz: 200.0, duration: None, realtime: true, seed: 0 }
+    }
+}
+
+/// Synthetic IMU samples of `config.profile`, for examples and tests that need an IMU stream without
+/// a socket or the `LiveGyroGen` generator (which uses the same motion profiles).
+///
+/// A generator thread sends the samples, timestamped `i / rate_hz` on the sensor clock, gyro in deg/s
+/// like a logger sends them and accel in m/s². The channel holds a second of samples; without
+/// `realtime` the thread waits on it, so the consumer sets the pace. The stream ends after
+/// `config.duration`, or when the receiver is dropped.
+pub fn simulate_imu_stream(config: ImuSimConfig) -> Receiver<LiveImuSample> {
+    let rate_hz = config.rate_hz.max(1.0);
+    let (tx, rx) = bounded(rate_hz.ceil() as usize);
+    thread::Builder::new()
+        .name("live_imu_sim".into())
+        .spawn(move || {
+            let period = 1.0 / rate_hz;
+            let samples = config.duration.map(|d| (d.as_secs_f64() * rate_hz).floor() as u64 + 1);
+            let mut motion = MotionGen::new(config.profile, config.seed);
+            let start = Instant::now();
+            let mut i = 0u64;
+            while samples.is_none_or(|n| i < n) {
+                let t_s = i as f64 * period;
+                if config.realtime {
+                    thread::sleep(Duration::from_secs_f64(t_s).saturating_sub(start.elapsed()));
+                }
+                let (gyro, accel) = motion.step(period);
+                let sample = LiveImuSample { ts_sensor_us: (t_s * 1_000_000.0).round() as i64, gyro, accel: Some(accel) };
+                if tx.send(sample).is_err() { break; }
+                i += 1;
+            }
+        })
+        .expect("spawn live imu simulator");
+    rx
+}
@@ -0,0 +1,29 @@
+// live/source_info.rs
+use crate::gyro_source::FileMetadata;
+
+/// What the stream header says about the source, for display (e.g. "Stabilizing: PotatoCam Mark1 @ 30fps").
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SourceInfo {
+    /// `vendor` of the header, "Gyroflow Live Stream" without one.
+    pub detected_source: Option<String>,
+    pub camera_identifier: Option<String>,
+    /// Name or id of the lens profile, or the `name`/`lens_model` of an inline JSON profile.
+    pub lens_profile: Option<String>,
+    pub fwversion: Option<String>,
+    pub frame_rate: Option<f64>,
+}
+
+impl SourceInfo {
+    pub fn from_metadata(md: &FileMetadata) -> Self {
+        let lens_profile = md.lens_profile.as_ref().and_then(|v| {
+            v.as_str().or_else(|| v.get("name").or_else(|| v.get("lens_model")).and_then(|n| n.as_str())).map(str::to_string)
+        });
+        Self {
+            detected_source: md.detected_source.clone(),
+            camera_identifier: md.camera_identifier.as_ref().map(|id| id.identifier.clone()).filter(|id| !id.is_empty()),
+            lens_profile,
+            fwversion: md.additional_data.get("fwversion").and_then(|v| v.as_str()).map(str::to_string),
+            frame_rate: md.frame_rate,
+        }
+    }
+}
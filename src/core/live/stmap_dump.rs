@@ -0,0 +1,96 @@
+// live/stmap_dump.rs
+use std::path::{Path, PathBuf};
+
+use parking_lot::Mutex;
+
+use crate::stmap_live::StmapItem;
+
+/// Most recent STMap pair built by a `StmapsLive` worker, for `LivePipeline::dump_current_stmaps`.
+/// Holding it costs two `Arc` clones per frame, the EXR bytes aren't copied.
+/// Renderers without a worker only record the frame they rendered, its maps are built when dumped.
+#[derive(Debug, Default)]
+pub struct LatestStmap {
+    item: Mutex<Option<StmapItem>>,
+    rendered: Mutex<Option<(usize, i64)>>,
+}
+
+impl LatestStmap {
+    pub fn publish(&self, item: &StmapItem) { *self.item.lock() = Some(item.clone()); }
+
+    pub fn get(&self) -> Option<StmapItem> { self.item.lock().clone() }
+
+    /// Record the (index, timestamp µs) of the frame just rendered.
+    pub fn rendered(&self, frame_index: usize, ts_us: i64) { *self.rendered.lock() = Some((frame_index, ts_us)); }
+
+    /// Frame of the last `rendered` call, `None` before the first frame.
+    pub fn last_rendered(&self) -> Option<(usize, i64)> { *self.rendered.lock() }
+
+    /// Forget the map, e.g. when it was built for a previous input resolution.
+    pub fn clear(&self) {
+        *self.item.lock() = None;
+        *self.rendered.lock() = None;
+    }
+}
+
+/// Write the EXRs of `item` to `dir`, named like the STMap export of the app:
+/// `<name>-undistort-<frame>.exr` and `<name>-redistort-<frame>.exr`. Returns (undistort, redistort).
+pub fn write_stmaps(item: &StmapItem, dir: &Path) -> std::io::Result<(PathBuf, PathBuf)> {
+    let (fname_base, frame, dist, undist) = item;
+    std::fs::create_dir_all(dir)?;
+    let undist_path = dir.join(format!("{fname_base}-undistort-{frame}.exr"));
+    let dist_path = dir.join(format!("{fname_base}-redistort-{frame}.exr"));
+    std::fs::write(&undist_path, undist.as_slice())?;
+    std::fs::write(&dist_path, dist.as_slice())?;
+    Ok((undist_path, dist_path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use crate::StabilizationManager;
+    use crate::live::{LiveError, LivePipeline};
+    use crate::stmap_live::{LiveFrameJob, StmapsLive};
+
+    #[test]
+    fn current_stmaps_are_dumped_as_exr() {
+        let (w, h) = (64, 36);
+        let stab = Arc::new(StabilizationManager::default());
+        stab.init_from_stream_data(30.0, (w, h));
+        stab.gyro.read().enable_live(3.0, 1.0, 0.0, 30.0);
+        {
+            let mut lens = stab.lens.write();
+            lens.calib_dimension = crate::lens_profile::Dimensions { w, h };
+            lens.fisheye_params.camera_matrix = vec![[50.0, 0.0, 32.0], [0.0, 50.0, 18.0], [0.0, 0.0, 1.0]];
+            lens.fisheye_params.distortion_coeffs = vec![0.05, 0.01, 0.0, 0.0];
+        }
+        let pipeline = LivePipeline::new(stab.clone(), None);
+        let dir = std::env::temp_dir().join(format!("gyroflow_live_stmaps_{}", std::process::id()));
+        assert!(matches!(pipeline.dump_current_stmaps(&dir), Err(LiveError::NoStmap)));
+
+        // Rendered without a worker: the maps of the last rendered frame are built for the dump
+        stab.live_on_new_frame(5, 200.0, 1);
+        let (undist_path, _) = pipeline.dump_current_stmaps(&dir).unwrap();
+        assert!(undist_path.file_name().unwrap().to_string_lossy().ends_with("-undistort-5.exr"));
+        let built = StmapsLive::build_sync(&stab, LiveFrameJob { frame_index: 5, frame_ts_ms: 200.0 }).unwrap();
+        assert_eq!(std::fs::read(&undist_path).unwrap(), *built.3);
+
+        let maps = StmapsLive::new(stab.clone());
+        maps.submit_frame(3, 100_000);
+        let (_, frame, dist, undist) = maps.recv_map().unwrap().unwrap();
+        maps.stop();
+        assert_eq!(frame, 3);
+
+        let (undist_path, dist_path) = pipeline.dump_current_stmaps(&dir).unwrap();
+        assert!(undist_path.file_name().unwrap().to_string_lossy().ends_with("-undistort-3.exr"));
+        assert!(dist_path.file_name().unwrap().to_string_lossy().ends_with("-redistort-3.exr"));
+        let (undist_file, dist_file) = (std::fs::read(&undist_path).unwrap(), std::fs::read(&dist_path).unwrap());
+        let _ = std::fs::remove_dir_all(&dir);
+        assert_eq!(undist_file, *undist);
+        assert_eq!(dist_file, *dist);
+
+        let decoded = crate::stmap::decode_stmap(&dist_file).expect("the dumped map is not a valid EXR");
+        assert_eq!((decoded.width, decoded.height), (w, h));
+        assert!(crate::stmap::decode_stmap(&undist_file).is_some());
+    }
+}
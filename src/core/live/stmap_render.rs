@@ -0,0 +1,181 @@
+// live/stmap_render.rs
+use log::{info, warn};
+
+use crate::gpu::wgpu_stmap::WgpuStmap;
+use super::texture_pool::{TexturePoolStats, DEFAULT_FRAMES_IN_FLIGHT};
+
+/// Where STMaps are applied to the frame pixels.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum MapRenderBackend {
+    #[default]
+    Cpu,
+    /// wgpu compute shader, falls back to the CPU when no adapter is usable
+    Gpu,
+}
+
+/// Bilinear remap of tightly packed RGBA8 `input` through `coords` (input pixel x, y per output pixel).
+///
+/// Coordinates outside the input are clamped to the edge.
+pub fn remap_rgba_cpu(input: &[u8], in_size: (usize, usize), coords: &[f32], output: &mut [u8], out_size: (usize, usize)) {
+    use rayon::prelude::*;
+    let (w, h) = in_size;
+    if w == 0 || h == 0 { return; }
+    let (max_x, max_y) = ((w - 1) as f32, (h - 1) as f32);
+    output[..out_size.0 * out_size.1 * 4]
+        .par_chunks_exact_mut(4)
+        .zip(coords.par_chunks_exact(2))
+        .for_each(|(px, uv)| {
+            let u = uv[0].clamp(0.0, max_x);
+            let v = uv[1].clamp(0.0, max_y);
+            let (x0, y0) = (u.floor() as usize, v.floor() as usize);
+            let (x1, y1) = ((x0 + 1).min(w - 1), (y0 + 1).min(h - 1));
+            let (tx, ty) = (u - x0 as f32, v - y0 as f32);
+            let at = |x: usize, y: usize, c: usize| input[(y * w + x) * 4 + c] as f32;
+            for c in 0..4 {
+                let top    = at(x0, y0, c) + (at(x1, y0, c) - at(x0, y0, c)) * tx;
+                let bottom = at(x0, y1, c) + (at(x1, y1, c) - at(x0, y1, c)) * tx;
+                px[c] = (top + (bottom - top) * ty).round().clamp(0.0, 255.0) as u8;
+            }
+        });
+}
+
+/// Applies STMaps with the selected backend. The CPU path is always available as a fallback.
+pub struct MapRenderer {
+    backend: MapRenderBackend,
+    gpu: Option<WgpuStmap>,
+}
+
+impl MapRenderer {
+    pub fn new(backend: MapRenderBackend) -> Self {
+        Self::with_frames_in_flight(backend, DEFAULT_FRAMES_IN_FLIGHT)
+    }
+
+    /// `frames_in_flight` sizes the GPU buffer pools, see `TexturePool`.
+    pub fn with_frames_in_flight(backend: MapRenderBackend, frames_in_flight: usize) -> Self {
+        let gpu = match backend {
+            MapRenderBackend::Cpu => None,
+            MapRenderBackend::Gpu => match WgpuStmap::with_frames_in_flight(frames_in_flight) {
+                Ok(g) => { info!("live: applying STMaps on the GPU"); Some(g) },
+                Err(e) => { warn!("live: GPU STMap backend unavailable ({e:?}), using the CPU"); None },
+            },
+        };
+        Self { backend, gpu }
+    }
+
+    pub fn requested_backend(&self) -> MapRenderBackend { self.backend }
+
+    /// Backend actually in use.
+    pub fn backend(&self) -> MapRenderBackend {
+        if self.gpu.is_some() { MapRenderBackend::Gpu } else { MapRenderBackend::Cpu }
+    }
+
+    /// Recycled vs newly allocated GPU buffers, `None` on the CPU.
+    pub fn pool_stats(&self) -> Option<TexturePoolStats> { self.gpu.as_ref().map(|g| g.pool_stats()) }
+
+    /// Remap RGBA8 `input` into `output`, see `remap_rgba_cpu`.
+    pub fn render(&mut self, input: &[u8], in_size: (usize, usize), coords: &[f32], output: &mut [u8], out_size: (usize, usize)) {
+        if let Some(gpu) = self.gpu.as_mut() {
+            if gpu.apply(input, in_size, coords, output, out_size) {
+                return;
+            }
+            warn!("live: GPU STMap pass failed, switching to the CPU");
+            self.gpu = None;
+        }
+        remap_rgba_cpu(input, in_size, coords, output, out_size);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    /// Rotated and scaled STMap over a noisy RGBA image, in input pixel coordinates.
+    fn stmap_fixture(in_size: (usize, usize), out_size: (usize, usize)) -> (Vec<u8>, Vec<f32>) {
+        let input: Vec<u8> = (0..in_size.0 * in_size.1 * 4).map(|i| ((i * 2654435761usize) >> 13) as u8).collect();
+        let (sin, cos) = 0.05f32.sin_cos();
+        let (cx, cy) = (in_size.0 as f32 / 2.0, in_size.1 as f32 / 2.0);
+        let (sx, sy) = (in_size.0 as f32 / out_size.0 as f32 * 0.9, in_size.1 as f32 / out_size.1 as f32 * 0.9);
+        let coords = (0..out_size.1).flat_map(|y| (0..out_size.0).flat_map(move |x| {
+            let (dx, dy) = ((x as f32 - out_size.0 as f32 / 2.0) * sx, (y as f32 - out_size.1 as f32 / 2.0) * sy);
+            [cx + dx * cos - dy * sin, cy + dx * sin + dy * cos]
+        })).collect();
+        (input, coords)
+    }
+
+    #[test]
+    fn gpu_stmap_matches_cpu_bilinear() {
+        let mut gpu = MapRenderer::new(MapRenderBackend::Gpu);
+        if gpu.backend() != MapRenderBackend::Gpu {
+            eprintln!("no wgpu adapter, skipping");
+            return;
+        }
+        let (in_size, out_size) = ((333, 187), (320, 180));
+        let (input, coords) = stmap_fixture(in_size, out_size);
+
+        let mut cpu_out = vec![0u8; out_size.0 * out_size.1 * 4];
+        let mut gpu_out = cpu_out.clone();
+        remap_rgba_cpu(&input, in_size, &coords, &mut cpu_out, out_size);
+        gpu.render(&input, in_size, &coords, &mut gpu_out, out_size);
+
+        // Rounding of the interpolation weights may differ by one step
+        let max_diff = cpu_out.iter().zip(&gpu_out).map(|(a, b)| a.abs_diff(*b)).max().unwrap();
+        assert!(max_diff <= 1, "GPU output differs from the CPU by {max_diff}");
+        assert_eq!(gpu.backend(), MapRenderBackend::Gpu);
+    }
+
+    #[test]
+    fn gpu_buffers_are_recycled_after_warm_up() {
+        let mut gpu = MapRenderer::with_frames_in_flight(MapRenderBackend::Gpu, 2);
+        if gpu.backend() != MapRenderBackend::Gpu {
+            eprintln!("no wgpu adapter, skipping");
+            return;
+        }
+        let (in_size, out_size) = ((64, 48), (32, 24));
+        let (input, coords) = stmap_fixture(in_size, out_size);
+        let mut output = vec![0u8; out_size.0 * out_size.1 * 4];
+        for _ in 0..100 {
+            gpu.render(&input, in_size, &coords, &mut output, out_size);
+        }
+        // One allocation while warming up, every later frame reuses the buffers and their bind group
+        assert_eq!(gpu.pool_stats(), Some(TexturePoolStats { hits: 99, misses: 1, evicted: 0 }));
+
+        // A new resolution allocates once more
+        let (input, coords) = stmap_fixture(out_size, out_size);
+        for _ in 0..10 {
+            gpu.render(&input, out_size, &coords, &mut output, out_size);
+        }
+        assert_eq!(gpu.pool_stats(), Some(TexturePoolStats { hits: 108, misses: 2, evicted: 0 }));
+        assert_eq!(MapRenderer::new(MapRenderBackend::Cpu).pool_stats(), None);
+    }
+
+    /// 4K throughput of both backends, run with `--nocapture` for the numbers (build with `--release`
+    /// for meaningful ones). The GPU output is checked against the CPU's too.
+    #[test]
+    fn stmap_backend_throughput_4k() {
+        let size = (3840, 2160);
+        let (input, coords) = stmap_fixture(size, size);
+        let mut outputs = Vec::new();
+        for backend in [MapRenderBackend::Cpu, MapRenderBackend::Gpu] {
+            let mut r = MapRenderer::new(backend);
+            if r.backend() != backend {
+                eprintln!("{backend:?} backend unavailable, skipping");
+                continue;
+            }
+            let mut output = vec![0u8; size.0 * size.1 * 4];
+            r.render(&input, size, &coords, &mut output, size); // warm-up, allocates the buffers
+            let n = 3;
+            let t = Instant::now();
+            for _ in 0..n {
+                r.render(&input, size, &coords, &mut output, size);
+            }
+            let per_frame = t.elapsed().as_secs_f64() * 1000.0 / n as f64;
+            println!("{backend:?}: {per_frame:.2} ms/frame ({:.1} fps) at 4K", 1000.0 / per_frame);
+            outputs.push(output);
+        }
+        if let [cpu, gpu] = &outputs[..] {
+            let max_diff = cpu.iter().zip(gpu).map(|(a, b)| a.abs_diff(*b)).max().unwrap();
+            assert!(max_diff <= 1, "GPU output differs from the CPU by {max_diff} at 4K");
+        }
+    }
+}
@@ -0,0 +1,133 @@
+// live/texture_pool.rs
+
+/// Frames in flight assumed by `MapRenderer::new`: the one rendered and the one being uploaded.
+pub const DEFAULT_FRAMES_IN_FLIGHT: usize = 2;
+
+/// Resolutions kept in a pool at once. Idle textures of the least recently used one are freed beyond it.
+const MAX_POOL_KEYS: usize = 4;
+
+/// What a pooled texture is interchangeable by.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TextureKey {
+    pub width: usize,
+    pub height: usize,
+    pub format: wgpu::TextureFormat,
+}
+
+impl TextureKey {
+    pub fn new(size: (usize, usize), format: wgpu::TextureFormat) -> Self {
+        Self { width: size.0, height: size.1, format }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TexturePoolStats {
+    /// Acquires served by a recycled texture.
+    pub hits: u64,
+    /// Acquires that allocated a new texture.
+    pub misses: u64,
+    /// Released textures freed because the pool was full.
+    pub evicted: u64,
+}
+
+/// Recycles per-frame GPU resources (textures, or the buffers standing in for them) across frames,
+/// so a long session doesn't allocate and free a frame's worth of GPU memory every frame.
+///
+/// Up to `frames_in_flight` idle resources are kept per key (a `TextureKey`, or a tuple of them for
+/// resources that go together), what the pipeline can have in use at once; anything released beyond
+/// that is freed. After the first frames every acquire is a hit, as long as the resolution doesn't change.
+pub struct TexturePool<T, K = TextureKey> {
+    frames_in_flight: usize,
+    // (key, last use, idle resources), a handful of keys at most
+    idle: Vec<(K, u64, Vec<T>)>,
+    uses: u64,
+    stats: TexturePoolStats,
+}
+
+impl<T, K: Copy + PartialEq> TexturePool<T, K> {
+    pub fn new(frames_in_flight: usize) -> Self {
+        Self { frames_in_flight: frames_in_flight.max(1), idle: Vec::new(), uses: 0, stats: TexturePoolStats::default() }
+    }
+
+    pub fn frames_in_flight(&self) -> usize { self.frames_in_flight }
+
+    /// An idle resource for `key`, or a new one from `alloc`.
+    pub fn acquire(&mut self, key: K, alloc: impl FnOnce() -> T) -> T {
+        self.uses += 1;
+        if let Some((_, last_use, idle)) = self.idle.iter_mut().find(|(k, _, _)| *k == key) {
+            *last_use = self.uses;
+            if let Some(tex) = idle.pop() {
+                self.stats.hits += 1;
+                return tex;
+            }
+        }
+        self.stats.misses += 1;
+        alloc()
+    }
+
+    /// Hand a resource back for the next frames.
+    pub fn release(&mut self, key: K, tex: T) {
+        self.uses += 1;
+        let pos = match self.idle.iter().position(|(k, _, _)| *k == key) {
+            Some(pos) => pos,
+            None => {
+                if self.idle.len() >= MAX_POOL_KEYS {
+                    let lru = self.idle.iter().enumerate().min_by_key(|(_, (_, last_use, _))| *last_use).map(|(i, _)| i).unwrap_or_default();
+                    let (_, _, freed) = self.idle.swap_remove(lru);
+                    self.stats.evicted += freed.len() as u64;
+                }
+                self.idle.push((key, self.uses, Vec::new()));
+                self.idle.len() - 1
+            }
+        };
+        let (_, last_use, idle) = &mut self.idle[pos];
+        *last_use = self.uses;
+        if idle.len() < self.frames_in_flight {
+            idle.push(tex);
+        } else {
+            self.stats.evicted += 1;
+        }
+    }
+
+    /// Idle resources held.
+    pub fn idle(&self) -> usize { self.idle.iter().map(|(_, _, idle)| idle.len()).sum() }
+
+    pub fn stats(&self) -> TexturePoolStats { self.stats }
+
+    /// Free every idle resource.
+    pub fn clear(&mut self) { self.idle.clear(); }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn texture_pool_stops_allocating_after_warm_up() {
+        let mut pool = TexturePool::new(2);
+        let mut allocated = 0;
+        let key = TextureKey::new((1920, 1080), wgpu::TextureFormat::Rgba8Unorm);
+        let frame = |pool: &mut TexturePool<Vec<u8>>, allocated: &mut usize| {
+            // Two frames in flight: the next one is uploaded while the previous one renders
+            let a = pool.acquire(key, || { *allocated += 1; vec![0u8; 16] });
+            let b = pool.acquire(key, || { *allocated += 1; vec![0u8; 16] });
+            pool.release(key, a);
+            pool.release(key, b);
+        };
+        frame(&mut pool, &mut allocated);
+        assert_eq!(allocated, 2);
+
+        for _ in 0..500 {
+            frame(&mut pool, &mut allocated);
+        }
+        assert_eq!(allocated, 2, "steady state allocated");
+        assert_eq!(pool.stats(), TexturePoolStats { hits: 1000, misses: 2, evicted: 0 });
+        assert_eq!(pool.idle(), 2);
+
+        // A new resolution warms up again, the old one stays available
+        let small = TextureKey::new((1280, 720), wgpu::TextureFormat::Rgba8Unorm);
+        let tex = pool.acquire(small, || { allocated += 1; vec![0u8; 4] });
+        pool.release(small, tex);
+        assert_eq!((allocated, pool.stats().misses, pool.idle()), (3, 3, 3));
+    }
+}
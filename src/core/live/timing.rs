@@ -0,0 +1,150 @@
+// live/timing.rs
+use std::time::Instant;
+
+use parking_lot::Mutex;
+
+/// Frames the average breakdown is smoothed over.
+const AVERAGE_FRAMES: f64 = 30.0;
+
+/// Stages of rendering one frame, in the order the render loop goes through them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RenderStage {
+    /// Getting the frame's transform ready: `live_on_new_frame`, or waiting for the STMap of a map-based renderer.
+    MapWait,
+    /// Copying the input and allocating the output buffers.
+    BufferSetup,
+    /// `process_pixels`, or the identity fallback when it fails.
+    Process,
+    /// Deflicker, compare overlay and pixel format conversion.
+    Post,
+    /// Pushing the frame to the sink.
+    Sink,
+}
+
+/// Time spent per stage on one frame, in ms.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FrameTiming {
+    pub map_wait_ms: f64,
+    pub buffer_setup_ms: f64,
+    pub process_ms: f64,
+    pub post_ms: f64,
+    pub sink_ms: f64,
+    /// From the start of `MapWait` to the sink push returning.
+    pub total_ms: f64,
+}
+
+impl FrameTiming {
+    pub fn stages_ms(&self) -> f64 {
+        self.map_wait_ms + self.buffer_setup_ms + self.process_ms + self.post_ms + self.sink_ms
+    }
+
+    fn stage_mut(&mut self, stage: RenderStage) -> &mut f64 {
+        match stage {
+            RenderStage::MapWait => &mut self.map_wait_ms,
+            RenderStage::BufferSetup => &mut self.buffer_setup_ms,
+            RenderStage::Process => &mut self.process_ms,
+            RenderStage::Post => &mut self.post_ms,
+            RenderStage::Sink => &mut self.sink_ms,
+        }
+    }
+
+    fn blend(&mut self, other: &FrameTiming, a: f64) {
+        for (s, o) in [
+            (&mut self.map_wait_ms, other.map_wait_ms),
+            (&mut self.buffer_setup_ms, other.buffer_setup_ms),
+            (&mut self.process_ms, other.process_ms),
+            (&mut self.post_ms, other.post_ms),
+            (&mut self.sink_ms, other.sink_ms),
+            (&mut self.total_ms, other.total_ms),
+        ] {
+            *s += (o - *s) * a;
+        }
+    }
+}
+
+/// `Instant` checkpoints through one frame. Every `lap` charges the time since the previous
+/// checkpoint to a stage, so the stages add up to the total.
+#[derive(Debug, Clone, Copy)]
+pub struct StageTimer {
+    start: Instant,
+    last: Instant,
+    timing: FrameTiming,
+}
+
+impl StageTimer {
+    pub fn start() -> Self { Self::start_at(Instant::now()) }
+
+    pub fn start_at(at: Instant) -> Self {
+        Self { start: at, last: at, timing: FrameTiming::default() }
+    }
+
+    /// The time since the last checkpoint was spent in `stage`.
+    pub fn lap(&mut self, stage: RenderStage) { self.lap_at(stage, Instant::now()); }
+
+    pub fn lap_at(&mut self, stage: RenderStage, at: Instant) {
+        *self.timing.stage_mut(stage) += at.saturating_duration_since(self.last).as_secs_f64() * 1000.0;
+        self.last = at;
+    }
+
+    /// Breakdown up to the last checkpoint.
+    pub fn finish(&self) -> FrameTiming {
+        FrameTiming { total_ms: self.last.saturating_duration_since(self.start).as_secs_f64() * 1000.0, ..self.timing }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RenderTimingSnapshot {
+    /// Frames timed so far.
+    pub frames: u64,
+    pub last: FrameTiming,
+    /// Running average over about the last `AVERAGE_FRAMES` frames.
+    pub average: FrameTiming,
+}
+
+/// Per-stage timing of the render loop, see `StageTimer`.
+#[derive(Debug, Default)]
+pub struct RenderTimingStats {
+    inner: Mutex<RenderTimingSnapshot>,
+}
+
+impl RenderTimingStats {
+    pub fn record(&self, timing: FrameTiming) {
+        let mut s = self.inner.lock();
+        s.frames += 1;
+        s.last = timing;
+        if s.frames == 1 {
+            s.average = timing;
+        } else {
+            s.average.blend(&timing, 1.0 / AVERAGE_FRAMES.min(s.frames as f64));
+        }
+    }
+
+    pub fn snapshot(&self) -> RenderTimingSnapshot { *self.inner.lock() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn render_stage_times_add_up_to_the_total() {
+        let stats = RenderTimingStats::default();
+        for _ in 0..3 {
+            let mut timer = StageTimer::start();
+            for (stage, ms) in [(RenderStage::MapWait, 2), (RenderStage::BufferSetup, 1), (RenderStage::Process, 5), (RenderStage::Post, 1), (RenderStage::Sink, 3)] {
+                thread::sleep(Duration::from_millis(ms));
+                timer.lap(stage);
+            }
+            let timing = timer.finish();
+            assert!((timing.stages_ms() - timing.total_ms).abs() < 1e-6, "{timing:?}");
+            assert!(timing.process_ms >= 5.0 && timing.sink_ms >= 3.0 && timing.map_wait_ms >= 2.0, "{timing:?}");
+            stats.record(timing);
+        }
+        let snap = stats.snapshot();
+        assert_eq!(snap.frames, 3);
+        assert!((snap.average.stages_ms() - snap.average.total_ms).abs() < 1e-6, "{snap:?}");
+        assert!(snap.average.total_ms >= 12.0);
+    }
+}
@@ -0,0 +1,59 @@
+// live/trace.rs
+use std::fmt;
+
+use crate::gyro_source::{GyroSource, Quat64};
+
+/// Log target of the per-frame quaternion trace, enable with e.g.
+/// `RUST_LOG=gyroflow_core::live::quat_trace=trace`.
+pub const QUAT_TRACE_TARGET: &str = "gyroflow_core::live::quat_trace";
+
+/// Quaternions used for one frame, for diffing the live path against an offline export.
+#[derive(Debug, Clone, Copy)]
+pub struct QuatTrace {
+    pub frame_idx: usize,
+    pub ts_us: i64,
+    pub org: Quat64,
+    pub smoothed: Quat64,
+    /// Correction applied to the frame, `smoothed * org⁻¹`.
+    /// Same as the main matrix in `FrameTransform` when there's no rolling shutter correction.
+    pub applied: Quat64,
+    /// `GyroSource::live_confidence_at_timestamp`, empty in the CSV outside live.
+    pub confidence: Option<f64>,
+}
+
+impl QuatTrace {
+    pub const CSV_HEADER: &'static str = "frame,ts_us,org_w,org_x,org_y,org_z,smooth_w,smooth_x,smooth_y,smooth_z,applied_w,applied_x,applied_y,applied_z,confidence";
+
+    /// Looks the quaternions up the same way the renderer does (`quat_buffer_store_org` / `_smoothed` first).
+    pub fn at(gyro: &GyroSource, frame_idx: usize, ts_us: i64) -> Self {
+        let ts_ms = ts_us as f64 / 1000.0;
+        let org = gyro.org_quat_at_timestamp(ts_ms);
+        let smoothed = gyro.smoothed_quat_at_timestamp(ts_ms);
+        let confidence = gyro.live_confidence_at_timestamp(ts_ms);
+        Self { frame_idx, ts_us, org, smoothed, applied: smoothed * org.inverse(), confidence }
+    }
+}
+
+/// CSV line matching `CSV_HEADER`.
+impl fmt::Display for QuatTrace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{},{}", self.frame_idx, self.ts_us)?;
+        for q in [&self.org, &self.smoothed, &self.applied] {
+            write!(f, ",{:.6},{:.6},{:.6},{:.6}", q.w, q.i, q.j, q.k)?;
+        }
+        match self.confidence {
+            Some(c) => write!(f, ",{c:.3}"),
+            None => write!(f, ","),
+        }
+    }
+}
+
+/// Log the quaternions of a frame at trace level. Does nothing (no lookups) unless the target is enabled.
+pub fn trace_frame(gyro: &GyroSource, frame_idx: usize, ts_us: i64) -> Option<QuatTrace> {
+    if !log::log_enabled!(target: QUAT_TRACE_TARGET, log::Level::Trace) {
+        return None;
+    }
+    let t = QuatTrace::at(gyro, frame_idx, ts_us);
+    log::trace!(target: QUAT_TRACE_TARGET, "{t}");
+    Some(t)
+}
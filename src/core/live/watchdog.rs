@@ -0,0 +1,206 @@
+// live/watchdog.rs
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use log::{error, info, warn};
+use parking_lot::Mutex;
+
+/// Liveness signal of one pipeline thread, `beat` once per loop iteration.
+///
+/// Cheap to clone and to beat (one relaxed atomic store), so it can be passed into any loop.
+#[derive(Clone, Debug)]
+pub struct Heartbeat {
+    epoch: Instant,
+    last_us: Arc<AtomicU64>,
+}
+
+impl Default for Heartbeat {
+    fn default() -> Self { Self::new() }
+}
+
+impl Heartbeat {
+    pub fn new() -> Self {
+        Self { epoch: Instant::now(), last_us: Arc::new(AtomicU64::new(0)) }
+    }
+
+    pub fn beat(&self) {
+        self.last_us.store(self.epoch.elapsed().as_micros() as u64, Ordering::Relaxed);
+    }
+
+    /// Time since the last `beat` (or since creation).
+    pub fn age(&self) -> Duration {
+        self.epoch.elapsed().saturating_sub(Duration::from_micros(self.last_us.load(Ordering::Relaxed)))
+    }
+}
+
+/// Heartbeat age of one watched component, see `Watchdog::ages`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeartbeatAge {
+    pub name: String,
+    pub age: Duration,
+    pub timeout: Duration,
+    pub stalled: bool,
+}
+
+type RestartFn = Arc<dyn Fn() + Send + Sync>;
+
+struct Watched {
+    name: String,
+    heartbeat: Heartbeat,
+    timeout: Duration,
+    restart: Option<RestartFn>,
+    stalled: bool,
+}
+
+/// Watches the heartbeats of the pipeline threads (render loop, STMap worker).
+///
+/// A component whose heartbeat is older than its timeout is logged as stalled once, and reported
+/// as recovered as soon as it beats again. With a restart callback, the callback is called instead
+/// and is expected to spawn a replacement that beats the same `Heartbeat`; the stuck thread itself
+/// can't be killed and is left behind. The replacement gets a full timeout before the next restart.
+#[derive(Default)]
+pub struct Watchdog {
+    watched: Mutex<Vec<Watched>>,
+    running: Arc<AtomicBool>,
+}
+
+impl Watchdog {
+    pub fn new() -> Arc<Self> { Arc::new(Self::default()) }
+
+    /// Watch `heartbeat`, only logging when it stalls.
+    pub fn watch(&self, name: &str, heartbeat: &Heartbeat, timeout: Duration) {
+        self.add(name, heartbeat, timeout, None);
+    }
+
+    /// Watch `heartbeat` and call `restart` each time it stalls.
+    pub fn watch_with_restart(&self, name: &str, heartbeat: &Heartbeat, timeout: Duration, restart: impl Fn() + Send + Sync + 'static) {
+        self.add(name, heartbeat, timeout, Some(Arc::new(restart)));
+    }
+
+    fn add(&self, name: &str, heartbeat: &Heartbeat, timeout: Duration, restart: Option<RestartFn>) {
+        // Grace period: the component may not have entered its loop yet
+        heartbeat.beat();
+        self.watched.lock().push(Watched { name: name.to_string(), heartbeat: heartbeat.clone(), timeout, restart, stalled: false });
+    }
+
+    /// Check every heartbeat once, returns the components that stalled since the last check.
+    pub fn check(&self) -> Vec<String> {
+        let mut stalled = Vec::new();
+        let mut restarts = Vec::new();
+        for w in self.watched.lock().iter_mut() {
+            let age = w.heartbeat.age();
+            if age >= w.timeout && !w.stalled {
+                w.stalled = true;
+                error!("watchdog: {} stalled, no heartbeat for {} ms (timeout {} ms)", w.name, age.as_millis(), w.timeout.as_millis());
+                stalled.push(w.name.clone());
+                if let Some(restart) = &w.restart {
+                    warn!("watchdog: restarting {}", w.name);
+                    // The replacement is a fresh component, give it a full timeout to come up
+                    w.stalled = false;
+                    w.heartbeat.beat();
+                    restarts.push(restart.clone());
+                }
+            } else if age < w.timeout && w.stalled {
+                w.stalled = false;
+                info!("watchdog: {} recovered", w.name);
+            }
+        }
+        // Outside the lock, a restart may register new components
+        for restart in restarts {
+            restart();
+        }
+        stalled
+    }
+
+    /// Current heartbeat age of every watched component, for stats and health reporting.
+    pub fn ages(&self) -> Vec<HeartbeatAge> {
+        self.watched.lock().iter().map(|w| HeartbeatAge {
+            name: w.name.clone(),
+            age: w.heartbeat.age(),
+            timeout: w.timeout,
+            stalled: w.stalled,
+        }).collect()
+    }
+
+    /// Run `check` every `period` on a background thread until `stop`.
+    pub fn spawn(self: &Arc<Self>, period: Duration) -> thread::JoinHandle<()> {
+        let this = Arc::clone(self);
+        self.running.store(true, Ordering::Relaxed);
+        thread::Builder::new()
+            .name("live_watchdog".into())
+            .spawn(move || {
+                while this.running.load(Ordering::Relaxed) {
+                    thread::sleep(period);
+                    this.check();
+                }
+            })
+            .expect("spawn live watchdog")
+    }
+
+    pub fn stop(&self) { self.running.store(false, Ordering::Relaxed); }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    #[test]
+    fn watchdog_detects_stalled_render_loop() {
+        let watchdog = Watchdog::new();
+        let (render_hb, worker_hb) = (Heartbeat::new(), Heartbeat::new());
+        let restarts = Arc::new(AtomicUsize::new(0));
+        watchdog.watch("render loop", &render_hb, Duration::from_millis(50));
+        {
+            let restarts = restarts.clone();
+            watchdog.watch_with_restart("stmap worker", &worker_hb, Duration::from_millis(50), move || { restarts.fetch_add(1, Ordering::Relaxed); });
+        }
+
+        // Pipeline threads that beat every 5 ms, the render loop hangs while `hang` is set
+        let (hang, quit) = (Arc::new(AtomicBool::new(false)), Arc::new(AtomicBool::new(false)));
+        let threads: Vec<_> = [(render_hb.clone(), true), (worker_hb.clone(), false)].into_iter().map(|(hb, hangs)| {
+            let (hang, quit) = (hang.clone(), quit.clone());
+            thread::spawn(move || {
+                while !quit.load(Ordering::Relaxed) {
+                    if !(hangs && hang.load(Ordering::Relaxed)) { hb.beat(); }
+                    thread::sleep(Duration::from_millis(5));
+                }
+            })
+        }).collect();
+
+        thread::sleep(Duration::from_millis(100));
+        assert!(watchdog.check().is_empty());
+        assert!(watchdog.ages().iter().all(|h| h.age < Duration::from_millis(50)));
+
+        hang.store(true, Ordering::Relaxed);
+        thread::sleep(Duration::from_millis(120));
+        let ages = watchdog.ages();
+        assert_eq!(ages[0].name, "render loop");
+        assert!(ages[0].age >= Duration::from_millis(100), "age {:?}", ages[0].age);
+        assert!(ages[1].age < Duration::from_millis(50));
+        assert_eq!(watchdog.check(), vec!["render loop".to_string()]);
+        assert!(watchdog.ages()[0].stalled);
+        assert_eq!(restarts.load(Ordering::Relaxed), 0);
+
+        // Reported once per stall, not on every check
+        assert!(watchdog.check().is_empty());
+
+        hang.store(false, Ordering::Relaxed);
+        thread::sleep(Duration::from_millis(30));
+        assert!(watchdog.check().is_empty());
+        assert!(!watchdog.ages()[0].stalled);
+
+        // A stalled component with a restart callback is restarted and gets a new grace period
+        quit.store(true, Ordering::Relaxed);
+        threads.into_iter().for_each(|t| t.join().unwrap());
+        thread::sleep(Duration::from_millis(60));
+        let mut stalled = watchdog.check();
+        stalled.sort();
+        assert_eq!(stalled, vec!["render loop".to_string(), "stmap worker".to_string()]);
+        assert_eq!(restarts.load(Ordering::Relaxed), 1);
+        assert!(watchdog.check().is_empty());
+        assert_eq!(restarts.load(Ordering::Relaxed), 1);
+    }
+}
@@ -54,6 +54,13 @@ pub struct ComputeParams {
     pub smoothing_fov_limit_per_frame: Vec<f64>,
     pub max_zoom: Option<f64>,
     pub max_zoom_iterations: usize,
+    pub live_max_crop: Option<f64>,
+    pub live_crop_stats: Arc<crate::live::crop::LiveCropStats>,
+    pub live_mount_correction: Option<crate::gyro_source::Quat64>,
+    pub live_preview_orientation: crate::gyro_source::QuatKind,
+    pub live_max_rotation_delta_deg: Option<f64>,
+    pub live_rotation_guard: Arc<crate::live::RotationSpikeGuard>,
+    pub live_lens_correction_ramp: Option<crate::live::LensCorrectionRamp>,
 
     pub zooming_debug_points: bool,
 
@@ -80,6 +87,16 @@ impl ComputeParams {
             smoothing_fov_limit_per_frame: Vec::new(),
             max_zoom: params.max_zoom.clone(),
             max_zoom_iterations: params.max_zoom_iterations,
+            live_max_crop: params.live_max_crop,
+            live_crop_stats: mgr.live_crop_stats.clone(),
+            // Pitch around x, yaw around y, roll around the optical axis z
+            live_mount_correction: params.live_mount_correction.map(|(roll, pitch, yaw)| {
+                crate::gyro_source::Quat64::from_euler_angles(pitch.to_radians(), yaw.to_radians(), roll.to_radians())
+            }),
+            live_preview_orientation: params.live_preview_orientation,
+            live_max_rotation_delta_deg: params.live_max_rotation_delta_deg,
+            live_rotation_guard: mgr.live_rotation_guard.clone(),
+            live_lens_correction_ramp: params.live_lens_correction_ramp,
 
             frame_count: params.frame_count,
             fov_scale: params.fov,
@@ -168,6 +185,9 @@ impl std::fmt::Debug for ComputeParams {
          .field("output_height",        &self.output_height)
          .field("video_rotation",       &self.video_rotation)
          .field("lens_correction_amount",    &self.lens_correction_amount)
+         .field("live_lens_correction_ramp", &self.live_lens_correction_ramp)
+         .field("live_preview_orientation",  &self.live_preview_orientation)
+         .field("live_max_rotation_delta_deg", &self.live_max_rotation_delta_deg)
          .field("light_refraction_coefficient", &self.light_refraction_coefficient)
          .field("background_mode",           &self.background_mode)
          .field("background_margin",         &self.background_margin)
@@ -74,18 +74,18 @@ pub const COEFFS: [f32; 64+128+256 + 9*4 + 4] = [
     1.0, 0.75, 0.50, 0.25,
 ];
 
-// const COLORS: [Vector4<f32>; 9] = [
-//     Vector4::new(0.0,   0.0,   0.0,     0.0), // None
-//     Vector4::new(255.0, 0.0,   0.0,   255.0), // Red
-//     Vector4::new(0.0,   255.0, 0.0,   255.0), // Green
-//     Vector4::new(0.0,   0.0,   255.0, 255.0), // Blue
-//     Vector4::new(254.0, 251.0, 71.0,  255.0), // Yellow
-//     Vector4::new(200.0, 200.0, 0.0,   255.0), // Yellow2
-//     Vector4::new(255.0, 0.0,   255.0, 255.0), // Magenta
-//     Vector4::new(0.0,   128.0, 255.0, 255.0), // Blue2
-//     Vector4::new(0.0,   200.0, 200.0, 255.0)  // Blue3
-// ];
-// const ALPHAS: [f32; 4] = [ 1.0, 0.75, 0.50, 0.25 ];
+const COLORS: [Vector4<f32>; 9] = [
+    Vector4::new(0.0,   0.0,   0.0,     0.0), // None
+    Vector4::new(255.0, 0.0,   0.0,   255.0), // Red
+    Vector4::new(0.0,   255.0, 0.0,   255.0), // Green
+    Vector4::new(0.0,   0.0,   255.0, 255.0), // Blue
+    Vector4::new(254.0, 251.0, 71.0,  255.0), // Yellow
+    Vector4::new(200.0, 200.0, 0.0,   255.0), // Yellow2
+    Vector4::new(255.0, 0.0,   255.0, 255.0), // Magenta
+    Vector4::new(0.0,   128.0, 255.0, 255.0), // Blue2
+    Vector4::new(0.0,   200.0, 200.0, 255.0)  // Blue3
+];
+const ALPHAS: [f32; 4] = [ 1.0, 0.75, 0.50, 0.25 ];
 
 impl Stabilization {
     pub fn undistort_image_cpu_spirv<T: PixelType>(buffers: &mut Buffers, params: &KernelParams, distortion_model: &DistortionModel, digital_lens: Option<&DistortionModel>, matrices: &[[f32; 14]], drawing: &[u8]) -> bool {
@@ -130,6 +130,46 @@ impl Stabilization {
         }
     }
 
+    /// Partially re-applies the lens distortion to an output position (before rotation), for `lens_correction_amount` < 1.
+    ///
+    /// The output position is normalized with the output focal length `f / fov`, the same one `rotate_and_distort`
+    /// projects with, and the resulting ray is blended between the undistorted ray and the position itself.
+    /// At 0 the source pixel is then `c + fov * (p - c)`, i.e. the uncorrected lens image, at 1 it's the full correction.
+    /// Must be kept in sync with the shaders.
+    pub fn add_lens_distortion(out_pos: Vector2<f32>, params: &KernelParams, distortion_model: &DistortionModel, digital_lens: Option<&DistortionModel>) -> Vector2<f32> {
+        let out_c = Vector2::new(params.output_width as f32 / 2.0, params.output_height as f32 / 2.0);
+        let out_f = Vector2::new(params.f[0] / params.fov, params.f[1] / params.fov);
+
+        let mut new_out_pos = out_pos;
+
+        if (params.flags & 2) == 2 { // Has digial lens
+            if let Some(digital) = digital_lens {
+                if let Some(pt) = digital.undistort_point((new_out_pos.x, new_out_pos.y), params) {
+                    new_out_pos.x = pt.0;
+                    new_out_pos.y = pt.1;
+                }
+            }
+        }
+
+        new_out_pos = (new_out_pos - out_c).component_div(&out_f);
+        if let Some(pt) = distortion_model.undistort_point((new_out_pos.x, new_out_pos.y), params) {
+            new_out_pos.x = pt.0;
+            new_out_pos.y = pt.1;
+        }
+        if params.light_refraction_coefficient != 1.0 && params.light_refraction_coefficient > 0.0 {
+            let r = new_out_pos.norm();
+            if r != 0.0 {
+                let sin_theta_d = (r / (1.0 + r * r).sqrt()) / params.light_refraction_coefficient;
+                let r_d = sin_theta_d / (1.0 - sin_theta_d * sin_theta_d).sqrt();
+                let factor = r_d / r;
+                new_out_pos *= factor;
+            }
+        }
+        new_out_pos = (new_out_pos.component_mul(&out_f)) + out_c;
+
+        new_out_pos * (1.0 - params.lens_correction_amount) + (out_pos * params.lens_correction_amount)
+    }
+
     pub fn rotate_and_distort(pos: (f32, f32), idx: usize, params: &KernelParams, matrices: &[[f32; 14]], distortion_model: &DistortionModel, digital_lens: Option<&DistortionModel>, r_limit_sq: f32, mesh_data: &[f64]) -> Option<(f32, f32)> {
         let matrices = matrices[idx];
         let _x = (pos.0 * matrices[0]) + (pos.1 * matrices[1]) + matrices[2] + params.translation3d[0];
@@ -231,24 +271,25 @@ impl Stabilization {
     // https://github.com/opencv/opencv/blob/2b60166e5c65f1caccac11964ad760d847c536e4/modules/calib3d/src/fisheye.cpp#L465-L567
     // https://github.com/opencv/opencv/blob/2b60166e5c65f1caccac11964ad760d847c536e4/modules/imgproc/src/opencl/remap.cl#L390-L498
     pub fn undistort_image_cpu<const I: i32, T: PixelType>(buffers: &mut Buffers, params: &KernelParams, distortion_model: &DistortionModel, digital_lens: Option<&DistortionModel>, matrices: &[[f32; 14]], drawing: &[u8], mesh_data: &[f32]) -> bool {
-        // #[cold]
-        // fn draw_pixel(pix: &mut Vector4<f32>, x: i32, y: i32, is_input: bool, width: i32, params: &KernelParams, drawing: &[u8]) {
-        //     if drawing.is_empty() || (params.flags & 8) == 0 { return; }
-        //     let pos = ((y as f32 / params.canvas_scale).floor() * (width as f32) + (x as f32 / params.canvas_scale).floor()).round() as usize;
-        //     if let Some(&data) = drawing.get(pos) {
-        //         if data > 0 {
-        //             let color = (data & 0xF8) >> 3;
-        //             let alpha = (data & 0x06) >> 1;
-        //             let stage = data & 1;
-        //             if ((stage == 0 && is_input) || (stage == 1 && !is_input)) && color < 9 {
-        //                 let colorf = COLORS[color as usize];
-        //                 let alphaf = ALPHAS[alpha as usize];
-        //                 *pix = colorf * alphaf + *pix * (1.0 - alphaf);
-        //                 pix.w = 255.0;
-        //             }
-        //         }
-        //     }
-        // }
+        fn draw_pixel(pix: &mut Vector4<f32>, x: i32, y: i32, is_input: bool, params: &KernelParams, drawing: &[u8]) {
+            if drawing.is_empty() || (params.flags & 8) == 0 { return; }
+            let scale = params.canvas_scale.max(1.0);
+            let width = (params.width.max(params.output_width) as f32 / scale).floor();
+            let pos = ((y as f32 / scale).floor() * width + (x as f32 / scale).floor()).round() as usize;
+            if let Some(&data) = drawing.get(pos) {
+                if data > 0 {
+                    let color = (data & 0xF8) >> 3;
+                    let alpha = (data & 0x06) >> 1;
+                    let stage = data & 1;
+                    if ((stage == 0 && is_input) || (stage == 1 && !is_input)) && color < 9 {
+                        let colorf = COLORS[color as usize] * (params.max_pixel_value / 255.0);
+                        let alphaf = ALPHAS[alpha as usize];
+                        *pix = colorf * alphaf + *pix * (1.0 - alphaf);
+                        pix.w = colorf.w;
+                    }
+                }
+            }
+        }
 
         // From 0-255(JPEG/Full) to 16-235(MPEG/Limited)
         #[cold]
@@ -414,7 +455,7 @@ impl Stabilization {
 }
 
 
-        fn undistort_coord(mut out_pos: Vector2<f32>, params: &KernelParams, matrices: &[[f32; 14]], distortion_model: &DistortionModel, digital_lens: Option<&DistortionModel>, r_limit_sq: f32, mesh_data: &[f64], out_c: &Vector2<f32>, out_f: &Vector2<f32>) -> Option<Vector2<f32>> {
+        fn undistort_coord(mut out_pos: Vector2<f32>, params: &KernelParams, matrices: &[[f32; 14]], distortion_model: &DistortionModel, digital_lens: Option<&DistortionModel>, r_limit_sq: f32, mesh_data: &[f64]) -> Option<Vector2<f32>> {
             out_pos.x = map_coord(out_pos.x, params.output_rect[0] as f32, (params.output_rect[0] + params.output_rect[2]) as f32, 0.0, params.output_width  as f32);
             out_pos.y = map_coord(out_pos.y, params.output_rect[1] as f32, (params.output_rect[1] + params.output_rect[3]) as f32, 0.0, params.output_height as f32);
             out_pos.x += params.translation2d[0];
@@ -423,34 +464,7 @@ impl Stabilization {
             ///////////////////////////////////////////////////////////////////
             // Add lens distortion back
             if params.lens_correction_amount < 1.0 {
-                let mut new_out_pos = out_pos;
-
-                if (params.flags & 2) == 2 { // Has digial lens
-                    if let Some(digital) = digital_lens {
-                        if let Some(pt) = digital.undistort_point((new_out_pos.x, new_out_pos.y), params) {
-                            new_out_pos.x = pt.0;
-                            new_out_pos.y = pt.1;
-                        }
-                    }
-                }
-
-                new_out_pos = (new_out_pos - out_c).component_div(out_f);
-                if let Some(pt) = distortion_model.undistort_point((new_out_pos.x, new_out_pos.y), params) {
-                    new_out_pos.x = pt.0;
-                    new_out_pos.y = pt.1;
-                }
-                if params.light_refraction_coefficient != 1.0 && params.light_refraction_coefficient > 0.0 {
-                    let r = new_out_pos.norm();
-                    if r != 0.0 {
-                        let sin_theta_d = (r / (1.0 + r * r).sqrt()) / params.light_refraction_coefficient;
-                        let r_d = sin_theta_d / (1.0 - sin_theta_d * sin_theta_d).sqrt();
-                        let factor = r_d / r;
-                        new_out_pos *= factor;
-                    }
-                }
-                new_out_pos = (new_out_pos.component_mul(out_f)) + out_c;
-
-                out_pos = new_out_pos * (1.0 - params.lens_correction_amount) + (out_pos * params.lens_correction_amount);
+                out_pos = Stabilization::add_lens_distortion(out_pos, params, distortion_model, digital_lens);
             }
             ///////////////////////////////////////////////////////////////////
 
@@ -518,10 +532,6 @@ impl Stabilization {
                 let bg = Vector4::<f32>::new(params.background[0], params.background[1], params.background[2], params.background[3]) * params.max_pixel_value;
                 let bg_t: T = PixelType::from_float(bg);
 
-                let factor = (1.0 - params.lens_correction_amount).max(0.001); // FIXME: this is close but wrong
-                let out_c = Vector2::new(params.output_width as f32 / 2.0, params.output_height as f32 / 2.0);
-                let out_f = Vector2::new(params.f[0] / params.fov / factor, params.f[1] / params.fov / factor);
-
                 // let drawing_enabled = !drawing.is_empty() && (params.flags & 8) == 8;
                 let fill_bg = (params.flags & 4) == 4;
                 let fix_range = (params.flags & 1) == 1;
@@ -545,7 +555,7 @@ impl Stabilization {
 
                         if out_pos.0 >= 0.0 && out_pos.1 >= 0.0 && (out_pos.0 as i32) < params.output_width && (out_pos.1 as i32) < params.output_height {
 
-                            // let p = out_pos;
+                            let p = out_pos;
                             let mut pixel = bg;
 
                             let pix_out = bytemuck::from_bytes_mut(pix_chunk); // treat this byte chunk as `T`
@@ -557,12 +567,12 @@ impl Stabilization {
 
                             let position = Vector2::new(x as f32, y as f32);
 
-                            if let Some(mut uv) = undistort_coord(position, params, matrices, distortion_model, digital_lens, r_limit_sq, &mesh_data, &out_c, &out_f) {
+                            if let Some(mut uv) = undistort_coord(position, params, matrices, distortion_model, digital_lens, r_limit_sq, &mesh_data) {
                                 let mut jac = Vector4::new(1.0, 0.0, 0.0, 1.0);
                                 if I > 8 {
                                     let eps = 0.01;
-                                    let xyx = undistort_coord(position + Vector2::new(eps, 0.0), params, matrices, distortion_model, digital_lens, r_limit_sq, &mesh_data, &out_c, &out_f).unwrap_or_default() - uv;
-                                    let xyy = undistort_coord(position + Vector2::new(0.0, eps), params, matrices, distortion_model, digital_lens, r_limit_sq, &mesh_data, &out_c, &out_f).unwrap_or_default() - uv;
+                                    let xyx = undistort_coord(position + Vector2::new(eps, 0.0), params, matrices, distortion_model, digital_lens, r_limit_sq, &mesh_data).unwrap_or_default() - uv;
+                                    let xyy = undistort_coord(position + Vector2::new(0.0, eps), params, matrices, distortion_model, digital_lens, r_limit_sq, &mesh_data).unwrap_or_default() - uv;
                                     jac = Vector4::new(xyx.x / eps, xyy.x / eps, xyx.y / eps, xyy.y / eps);
                                 }
 
@@ -599,7 +609,7 @@ impl Stabilization {
                                     let c1 = sample_input_at::<I, T>(uv, &jac, input, params, &bg, drawing);
                                     let c2 = sample_input_at::<I, T>(pt2, &jac, input, params, &bg, drawing); // FIXME: jac should be adjusted for pt2
                                     pixel = c1 * alpha + c2 * (1.0 - alpha);
-                                    // draw_pixel(&mut pixel, p.0 as i32, p.1 as i32, false, params.output_width, params, drawing);
+                                    draw_pixel(&mut pixel, p.0 as i32, p.1 as i32, false, params, drawing);
                                     if fix_range {
                                         remap_colorrange(&mut pixel, is_y)
                                     }
@@ -609,7 +619,7 @@ impl Stabilization {
 
                                 pixel = sample_input_at::<I, T>(uv, &jac, input, params, &bg, drawing);
                             }
-                            // draw_pixel(&mut pixel, p.0 as i32, p.1 as i32, false, params.output_width, params, drawing);
+                            draw_pixel(&mut pixel, p.0 as i32, p.1 as i32, false, params, drawing);
 
                             if fix_range {
                                 remap_colorrange(&mut pixel, is_y)
@@ -34,6 +34,15 @@ impl FrameTransform {
         }
         frame_readout_time * scale
     }
+    /// Capture time of readout row (or column, for horizontal readout) `row` out of `rows`.
+    ///
+    /// A negative `frame_readout_time` means the sensor is read from the far edge (BottomToTop, RightToLeft or an
+    /// inverted framebuffer): row `rows - 1` is exposed first and row 0 last, over the same interval as the forward direction.
+    fn row_timestamp(timestamp_ms: f64, frame_readout_time: f64, rows: f64, row: f64) -> f64 {
+        let row_time = frame_readout_time.abs() / rows.max(1.0);
+        let order = if frame_readout_time < 0.0 { rows - 1.0 - row } else { row };
+        timestamp_ms - frame_readout_time.abs() / 2.0 + row_time * order
+    }
     fn get_new_k(params: &ComputeParams, camera_matrix: &Matrix3<f64>, fov: f64) -> Matrix3<f64> {
         let horizontal_ratio = if params.lens.input_horizontal_stretch > 0.01 { params.lens.input_horizontal_stretch } else { 1.0 };
 
@@ -53,10 +62,43 @@ impl FrameTransform {
         let mut fov_scale = params.keyframes.value_at_video_timestamp(&KeyframeType::Fov, timestamp_ms).unwrap_or(params.fov_scale);
         fov_scale += if params.fov_overview && use_fovs && !for_ui { 1.0 } else { 0.0 };
         let mut fov = if use_fovs { params.fovs.get(frame).unwrap_or(if params.fovs.len() > 1 { params.fovs.last().unwrap() } else { &1.0 }) * fov_scale } else { 1.0 }.max(0.001);
+        if let Some(max_crop) = params.live_max_crop {
+            fov = fov.max(crate::live::crop::min_fov_for_crop(max_crop));
+        }
         fov *= params.width as f64 / params.output_width.max(1) as f64;
         fov
     }
 
+    fn camera_rotation(params: &ComputeParams, image_rotation: &Matrix3<f64>, quat: &crate::gyro_source::Quat64) -> Matrix3<f64> {
+        let mut r = image_rotation * *quat.to_rotation_matrix().matrix();
+        if params.framebuffer_inverted {
+            r[(0, 2)] *= -1.0; r[(1, 2)] *= -1.0;
+            r[(2, 0)] *= -1.0; r[(2, 1)] *= -1.0;
+        } else {
+            r[(0, 1)] *= -1.0; r[(0, 2)] *= -1.0;
+            r[(1, 0)] *= -1.0; r[(2, 0)] *= -1.0;
+        }
+        r
+    }
+
+    /// Orientation of the `kind` store at `timestamp_ms`, before the live rotation guard.
+    pub fn preview_quat(kind: crate::gyro_source::QuatKind, gyro: &crate::gyro_source::GyroSource, timestamp_ms: f64) -> crate::gyro_source::Quat64 {
+        match kind {
+            crate::gyro_source::QuatKind::Smoothed => gyro.smoothed_quat_at_timestamp(timestamp_ms),
+            crate::gyro_source::QuatKind::Org => gyro.org_quat_at_timestamp(timestamp_ms),
+        }
+    }
+
+    /// Orientation the frame is rendered to, the smoothed one unless the live preview selects another store.
+    /// With `live_max_rotation_delta_deg`, the live frame gets the rotation `RotationSpikeGuard` applied to it.
+    fn target_quat(params: &ComputeParams, gyro: &crate::gyro_source::GyroSource, timestamp_ms: f64) -> crate::gyro_source::Quat64 {
+        let quat = Self::preview_quat(params.live_preview_orientation, gyro, timestamp_ms);
+        match params.live_max_rotation_delta_deg {
+            Some(_) => params.live_rotation_guard.applied_at(timestamp_ms).unwrap_or(quat),
+            None => quat,
+        }
+    }
+
     pub fn get_lens_data_at_timestamp(params: &ComputeParams, timestamp_ms: f64, invert_asym_lens: bool) -> (Matrix3<f64>, [f64; 12], f64, f64, f64, Option<f64>) {
         let mut interpolated_lens = None;
         let gyro = params.gyro.read();
@@ -108,22 +150,10 @@ impl FrameTransform {
         drop(file_metadata);
         drop(gyro);
 
-        let (calib_width, calib_height) = if lens.calib_dimension.w > 0 && lens.calib_dimension.h > 0 {
-            (lens.calib_dimension.w as f64, lens.calib_dimension.h as f64)
-        } else {
-            (params.width.max(1) as f64, params.height.max(1) as f64)
-        };
-
-        let input_horizontal_stretch = if lens.input_horizontal_stretch > 0.01 { lens.input_horizontal_stretch } else { 1.0 };
-        let input_vertical_stretch = if lens.input_vertical_stretch > 0.01 { lens.input_vertical_stretch } else { 1.0 };
+        let (input_horizontal_stretch, input_vertical_stretch) = lens.input_stretch();
 
         if stretch_lens {
-            let lens_ratiox = (params.width as f64 / calib_width) * input_horizontal_stretch;
-            let lens_ratioy = (params.height as f64 / calib_height) * input_vertical_stretch;
-            camera_matrix[(0, 0)] *= lens_ratiox;
-            camera_matrix[(1, 1)] *= lens_ratioy;
-            camera_matrix[(0, 2)] *= lens_ratiox;
-            camera_matrix[(1, 2)] *= lens_ratioy;
+            lens.scale_camera_matrix(&mut camera_matrix, (params.width, params.height));
         }
         if digital_zoom > 0.0 {
             camera_matrix[(0, 0)] *= digital_zoom;
@@ -139,7 +169,10 @@ impl FrameTransform {
         let video_rotation = params.keyframes.value_at_video_timestamp(&KeyframeType::VideoRotation, timestamp_ms).unwrap_or(params.video_rotation);
         let background_margin = params.keyframes.value_at_video_timestamp(&KeyframeType::BackgroundMargin, timestamp_ms).unwrap_or(params.background_margin);
         let background_feather = params.keyframes.value_at_video_timestamp(&KeyframeType::BackgroundFeather, timestamp_ms).unwrap_or(params.background_margin_feather);
-        let lens_correction_amount = params.keyframes.value_at_video_timestamp(&KeyframeType::LensCorrectionStrength, timestamp_ms).unwrap_or(params.lens_correction_amount);
+        let lens_correction_amount = match params.live_lens_correction_ramp {
+            Some(ramp) => ramp.amount_at(timestamp_ms),
+            None => params.keyframes.value_at_video_timestamp(&KeyframeType::LensCorrectionStrength, timestamp_ms).unwrap_or(params.lens_correction_amount),
+        };
         let adaptive_zoom_center_x = params.keyframes.value_at_video_timestamp(&KeyframeType::ZoomingCenterX, timestamp_ms).unwrap_or(params.adaptive_zoom_center_offset.0);
         let mut adaptive_zoom_center_y = params.keyframes.value_at_video_timestamp(&KeyframeType::ZoomingCenterY, timestamp_ms).unwrap_or(params.adaptive_zoom_center_offset.1);
 
@@ -190,9 +223,8 @@ impl FrameTransform {
         // ----------- Rolling shutter correction -----------
         let frame_readout_time = Self::get_frame_readout_time(&params, true, timestamp_ms, &file_metadata);
 
-        let row_readout_time = frame_readout_time / if params.frame_readout_direction.is_horizontal() { params.width } else { params.height } as f64;
+        let readout_rows = if params.frame_readout_direction.is_horizontal() { params.width } else { params.height } as f64;
         let timestamp_ms = timestamp_ms + file_metadata.per_frame_time_offsets.get(frame).unwrap_or(&0.0);
-        let start_ts = timestamp_ms - (frame_readout_time / 2.0);
         // ----------- Rolling shutter correction -----------
 
         // let frame_period = 1000.0 / params.scaled_fps as f64;
@@ -211,7 +243,23 @@ impl FrameTransform {
         let image_rotation = Matrix3::new_rotation(video_rotation * (std::f64::consts::PI / 180.0));
 
         let quat1 = gyro.org_quat_at_timestamp(timestamp_ms).inverse();
-        let smoothed_quat1 = gyro.smoothed_quat_at_timestamp(timestamp_ms);
+        let mut smoothed_quat1 = Self::target_quat(params, gyro, timestamp_ms);
+
+        // Live crop cap: reduce the correction instead of zooming in further than allowed
+        if params.live_max_crop.is_some() {
+            // Rotation of the first row is `smoothed * base`, `base⁻¹` is the smoothed quat that applies no correction
+            let base = quat1 * gyro.org_quat_at_timestamp(Self::row_timestamp(timestamp_ms, frame_readout_time, readout_rows, 0.0));
+            let (limited, strength) = crate::live::crop::limit_correction(&base.inverse(), &smoothed_quat1, |s| {
+                let r = Self::camera_rotation(params, &image_rotation, &(s * base));
+                crate::live::crop::output_fits_input(&r, &new_k, &camera_matrix, (params.width, params.height), (params.output_width, params.output_height))
+            });
+            smoothed_quat1 = limited;
+            params.live_crop_stats.record(strength);
+        }
+        // Live mount correction: a fixed rotation of the stabilized orientation, not limited by the crop cap
+        if let Some(mount) = params.live_mount_correction {
+            smoothed_quat1 *= mount;
+        }
         
         
 
@@ -219,10 +267,11 @@ impl FrameTransform {
         let rows = if frame_readout_time.abs() > 0.0 { if params.frame_readout_direction.is_horizontal() { params.width } else { params.height } } else { 1 };
 
         let matrices = (0..rows).into_par_iter().map(|y| {
+            // Matrices are indexed by spatial row, the readout direction only changes when each row was exposed
             let quat_time = if frame_readout_time.abs() > 0.0 {
-                start_ts + row_readout_time * y as f64
+                Self::row_timestamp(timestamp_ms, frame_readout_time, readout_rows, y as f64)
             } else {
-                start_ts
+                timestamp_ms
             };
             let readout_quat = gyro.org_quat_at_timestamp(quat_time);
             let quat = smoothed_quat1
@@ -251,14 +300,7 @@ impl FrameTransform {
             }
 
 
-            let mut r = image_rotation * *quat.to_rotation_matrix().matrix();
-            if params.framebuffer_inverted {
-                r[(0, 2)] *= -1.0; r[(1, 2)] *= -1.0;
-                r[(2, 0)] *= -1.0; r[(2, 1)] *= -1.0;
-            } else {
-                r[(0, 1)] *= -1.0; r[(0, 2)] *= -1.0;
-                r[(1, 0)] *= -1.0; r[(2, 0)] *= -1.0;
-            }
+            let mut r = Self::camera_rotation(params, &image_rotation, &quat);
 
             let (mut sx, mut sy, mut ra, mut ox, mut oy) = if let Some(is) = file_metadata.camera_stab_data.get(frame) {
                 // let ts = ((row_readout_time * y as f64 + frame_period * frame as f64) * 1000.0).round() as i64;
@@ -368,24 +410,23 @@ impl FrameTransform {
         // ----------- Rolling shutter correction -----------
         let frame_readout_time = Self::get_frame_readout_time(params, false, timestamp_ms, &file_metadata);
 
-        let row_readout_time = frame_readout_time / if params.frame_readout_direction.is_horizontal() { params.width } else { params.height } as f64;
+        let readout_rows = if params.frame_readout_direction.is_horizontal() { params.width } else { params.height } as f64;
         let timestamp_ms = timestamp_ms + gyro.file_metadata.read().per_frame_time_offsets.get(frame).unwrap_or(&0.0);
-        let start_ts = timestamp_ms - (frame_readout_time / 2.0);
         // ----------- Rolling shutter correction -----------
 
         let image_rotation = Matrix3::new_rotation(video_rotation * (std::f64::consts::PI / 180.0));
 
         let quat1 = gyro.org_quat_at_timestamp(timestamp_ms).inverse();
-        let smoothed_quat1 = gyro.smoothed_quat_at_timestamp(timestamp_ms);
+        let smoothed_quat1 = Self::target_quat(params, gyro, timestamp_ms);
 
         // Only compute 1 matrix if not using rolling shutter correction
         let points_iter = if frame_readout_time.abs() > 0.0 { points } else { &[(0.0, 0.0)] };
 
         let rotations: Vec<Matrix3<f64>> = points_iter.iter().map(|&(x, y)| {
             let quat_time = if frame_readout_time.abs() > 0.0 {
-                start_ts + row_readout_time * if params.frame_readout_direction.is_horizontal() { x } else { y } as f64
+                Self::row_timestamp(timestamp_ms, frame_readout_time, readout_rows, if params.frame_readout_direction.is_horizontal() { x } else { y } as f64)
             } else {
-                start_ts
+                timestamp_ms
             };
             let quat = smoothed_quat1
                      * quat1
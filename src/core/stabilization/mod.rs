@@ -158,6 +158,7 @@ pub struct Stabilization {
 
     pub size:        (usize, usize), // width, height
     pub output_size: (usize, usize), // width, height
+    pub output_crop: Option<(usize, usize, usize, usize)>, // x, y, width, height - region of the output written to the buffer
 
     pub interpolation: Interpolation,
     pub kernel_flags: KernelParamsFlags,
@@ -176,6 +177,8 @@ pub struct Stabilization {
 
     pub share_wgpu_instances: bool,
     pub cache_frame_transform: bool,
+    /// Live: the CPU kernel draws the canvas too (a framing overlay is set), offline only the GPU kernels do.
+    pub live_overlay: bool,
     next_backend: Option<&'static str>
 }
 
@@ -185,6 +188,8 @@ pub struct ProcessedInfo {
     pub minimal_fov: f64,
     pub focal_length: Option<f64>,
     pub backend: &'static str,
+    /// Live: how well IMU data and clock sync cover this frame, 0..1, see `GyroSource::live_confidence_at_timestamp`.
+    pub confidence: Option<f64>,
 }
 
 impl Stabilization {
@@ -210,12 +215,22 @@ impl Stabilization {
         ret
     }
 
+    /// Kernel `output_rect` for the output buffer.
+    /// With `output_crop` set the buffer only holds that region of the full output, so the rect is shifted by -x, -y
+    /// and spans the full output size, e.g. buffer pixel 0 maps to output pixel x.
+    pub fn get_output_rect(&self, desc: &BufferDescription) -> [i32; 4] {
+        if let Some(c) = self.output_crop {
+            return [-(c.0 as i32), -(c.1 as i32), self.output_size.0 as i32, self.output_size.1 as i32];
+        }
+        Self::get_rect(desc)
+    }
+
     pub fn get_kernel_flags(&self, frame: usize, buffers: &Buffers) -> KernelParamsFlags {
         let mut kernel_flags = self.kernel_flags.clone();
         kernel_flags.set(KernelParamsFlags::HAS_DIGITAL_LENS, self.compute_params.digital_lens.is_some());
         kernel_flags.set(KernelParamsFlags::HORIZONTAL_RS, self.compute_params.frame_readout_direction.is_horizontal());
         kernel_flags.set(KernelParamsFlags::HAS_SOURCE_RECT, buffers.input.rect.is_some() || self.size.0 != buffers.input.size.0 || self.size.1 != buffers.input.size.1);
-        kernel_flags.set(KernelParamsFlags::HAS_OUTPUT_RECT, buffers.output.rect.is_some() || self.output_size.0 != buffers.output.size.0 || self.output_size.1 != buffers.output.size.1);
+        kernel_flags.set(KernelParamsFlags::HAS_OUTPUT_RECT, buffers.output.rect.is_some() || self.output_crop.is_some() || self.output_size.0 != buffers.output.size.0 || self.output_size.1 != buffers.output.size.1);
         kernel_flags.set(KernelParamsFlags::FRAMEBUFFER_INVERTED, self.compute_params.framebuffer_inverted);
         kernel_flags.set(KernelParamsFlags::ANY_UNDERWATER, (self.compute_params.light_refraction_coefficient != 1.0 && self.compute_params.light_refraction_coefficient > 0.0) || self.compute_params.keyframes.is_keyframed(&crate::KeyframeType::LightRefractionCoeff));
 
@@ -307,7 +322,7 @@ impl Stabilization {
         }
 
         transform.kernel_params.source_rect = Self::get_rect(&buffers.input);
-        transform.kernel_params.output_rect = Self::get_rect(&buffers.output);
+        transform.kernel_params.output_rect = self.get_output_rect(&buffers.output);
 
         transform
     }
@@ -324,7 +339,7 @@ impl Stabilization {
             if itm.kernel_params.input_rotation != buffers.input.rotation.unwrap_or(0.0) ||
                itm.kernel_params.output_rotation != buffers.output.rotation.unwrap_or(0.0) ||
                itm.kernel_params.source_rect != Self::get_rect(&buffers.input) ||
-               itm.kernel_params.output_rect != Self::get_rect(&buffers.output) {
+               itm.kernel_params.output_rect != self.get_output_rect(&buffers.output) {
                 log::warn!("Updating stab params at {timestamp_us}");
                 insert = true;
             }
@@ -359,6 +374,19 @@ impl Stabilization {
         crc32fast::hash(self.get_current_key(buffers).as_bytes())
     }
 
+    fn new_canvas(size: (usize, usize), output_size: (usize, usize)) -> DrawCanvas {
+        DrawCanvas::new(size.0, size.1, output_size.0, output_size.1, (size.1 as f64 / 720.0).max(1.0) as usize)
+    }
+
+    /// Enable or disable the drawing canvas, allocating it for the current sizes if needed.
+    pub fn set_drawing_enabled(&mut self, enabled: bool) {
+        self.kernel_flags.set(KernelParamsFlags::DRAWING_ENABLED, enabled);
+        let d = &self.drawing;
+        if enabled && (d.width, d.height, d.output_width, d.output_height) != (self.size.0, self.size.1, self.output_size.0, self.output_size.1) {
+            self.drawing = Self::new_canvas(self.size, self.output_size);
+        }
+    }
+
     pub fn init_size(&mut self, size: (usize, usize), output_size: (usize, usize)) {
         self.initialized_backend = BackendType::None;
         #[cfg(feature = "use-opencl")]
@@ -369,7 +397,7 @@ impl Stabilization {
         self.output_size = output_size;
 
         if self.kernel_flags.contains(KernelParamsFlags::DRAWING_ENABLED) {
-            self.drawing = DrawCanvas::new(size.0, size.1, output_size.0, output_size.1, (size.1 as f64 / 720.0).max(1.0) as usize);
+            self.drawing = Self::new_canvas(size, output_size);
         }
 
         self.stab_data.clear();
@@ -631,6 +659,7 @@ impl Stabilization {
             minimal_fov:  itm.minimal_fov,
             focal_length: itm.focal_length,
             backend:      "",
+            confidence:   None,
         };
         let drawing_buffer = self.drawing.get_buffer();
 
@@ -793,6 +822,7 @@ impl Stabilization {
         }
 
         // CPU path (no logging here anymore)
+        let cpu_drawing: &[u8] = if self.live_overlay { drawing_buffer } else { &[] };
         let ok = match self.interpolation {
             Interpolation::Bilinear => {
                 Self::undistort_image_cpu::<2, T>(
@@ -801,7 +831,7 @@ impl Stabilization {
                     &self.compute_params.distortion_model,
                     self.compute_params.digital_lens.as_ref(),
                     &itm.matrices,
-                    drawing_buffer,
+                    cpu_drawing,
                     &itm.mesh_data,
                 )
             }
@@ -812,7 +842,7 @@ impl Stabilization {
                     &self.compute_params.distortion_model,
                     self.compute_params.digital_lens.as_ref(),
                     &itm.matrices,
-                    drawing_buffer,
+                    cpu_drawing,
                     &itm.mesh_data,
                 )
             }
@@ -823,7 +853,7 @@ impl Stabilization {
                     &self.compute_params.distortion_model,
                     self.compute_params.digital_lens.as_ref(),
                     &itm.matrices,
-                    drawing_buffer,
+                    cpu_drawing,
                     &itm.mesh_data,
                 )
             }
@@ -834,7 +864,7 @@ impl Stabilization {
                     &self.compute_params.distortion_model,
                     self.compute_params.digital_lens.as_ref(),
                     &itm.matrices,
-                    drawing_buffer,
+                    cpu_drawing,
                     &itm.mesh_data,
                 )
             }
@@ -845,7 +875,7 @@ impl Stabilization {
                     &self.compute_params.distortion_model,
                     self.compute_params.digital_lens.as_ref(),
                     &itm.matrices,
-                    drawing_buffer,
+                    cpu_drawing,
                     &itm.mesh_data,
                 )
             }
@@ -856,7 +886,7 @@ impl Stabilization {
                     &self.compute_params.distortion_model,
                     self.compute_params.digital_lens.as_ref(),
                     &itm.matrices,
-                    drawing_buffer,
+                    cpu_drawing,
                     &itm.mesh_data,
                 )
             }
@@ -867,7 +897,7 @@ impl Stabilization {
                     &self.compute_params.distortion_model,
                     self.compute_params.digital_lens.as_ref(),
                     &itm.matrices,
-                    drawing_buffer,
+                    cpu_drawing,
                     &itm.mesh_data,
                 )
             }
@@ -66,8 +66,28 @@ impl ReadoutDirection {
 pub struct StabilizationParams {
     pub size: (usize, usize), // Full resolution input size
     pub output_size: (usize, usize), // Full resoution output size
+    pub live_output_size: Option<(usize, usize)>, // Live: explicit output size, not fitted to the input aspect
+    pub live_max_crop: Option<f64>, // Live: max crop in % of the input, limits fov and stabilization strength
+    #[serde(default)]
+    pub live_overlay: crate::live::LiveOverlay, // Live: framing guide drawn on the output
+    #[serde(default)]
+    pub live_mount_correction: Option<(f64, f64, f64)>, // Live: fixed (roll, pitch, yaw) in degrees applied to the stabilized orientation
+    #[serde(default)]
+    pub live_preview_orientation: crate::gyro_source::QuatKind, // Live: store the frame is rendered to, `Org` previews the unstabilized feed
+    #[serde(default)]
+    pub live_max_rotation_delta_deg: Option<f64>, // Live: max change of the applied rotation between frames, larger jumps are held as spikes
+    #[serde(default)]
+    pub live_no_imu: crate::live::NoImu, // Live: output while no IMU sample arrived yet
+    #[serde(default)]
+    pub live_smoothing_algorithm: Option<String>, // Live: name of the smoothing algorithm filling the smoothed quaternions, `None` for the built-in blend
+    #[serde(default)]
+    pub live_lens_correction_ramp: Option<crate::live::LensCorrectionRamp>, // Live: transition of `lens_correction_amount` in progress
+    #[serde(default)]
+    pub live_latency_profile: crate::live::LatencyProfile, // Live: how the live loops wait for their next message
 
     pub background: Vector4<f32>,
+    #[serde(default)]
+    pub live_mask_saved_background: Option<(f32, BackgroundMode)>, // Live: background alpha and mode before `set_live_alpha_mask`, restored when it's turned off
 
     pub frame_readout_time: f64,
     pub frame_readout_direction: ReadoutDirection,
@@ -142,6 +162,16 @@ impl Default for StabilizationParams {
 
             size: (0, 0),
             output_size: (0, 0),
+            live_output_size: None,
+            live_max_crop: None,
+            live_overlay: Default::default(),
+            live_mount_correction: None,
+            live_preview_orientation: Default::default(),
+            live_max_rotation_delta_deg: None,
+            live_no_imu: Default::default(),
+            live_smoothing_algorithm: None,
+            live_lens_correction_ramp: None,
+            live_latency_profile: Default::default(),
 
             video_rotation: 0.0,
 
@@ -164,6 +194,7 @@ impl Default for StabilizationParams {
             zooming_debug_points: BTreeMap::new(),
 
             background: Vector4::new(0.0, 0.0, 0.0, 0.0),
+            live_mask_saved_background: None,
 
             of_method: 2,
 
@@ -277,6 +308,7 @@ impl StabilizationParams {
             show_detected_features:    self.show_detected_features,
             show_optical_flow:         self.show_optical_flow,
             background:                self.background,
+            live_mask_saved_background: self.live_mask_saved_background,
             adaptive_zoom_window:      self.adaptive_zoom_window,
             framebuffer_inverted:      self.framebuffer_inverted,
             lens_correction_amount:    self.lens_correction_amount,
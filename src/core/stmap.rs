@@ -1,9 +1,16 @@
 use crate::{ stabilization::*, zooming::* };
 use exr::prelude::*;
+use exr::meta::attribute::Text;
 use rayon::{ slice::ParallelSliceMut, iter::IndexedParallelIterator, iter::ParallelIterator };
 use crate::StabilizationManager;
 
-pub fn generate_stmaps(stab: &StabilizationManager, per_frame: bool) -> impl Iterator<Item = (String, usize, Vec<u8>, Vec<u8>)> { // (frame, undistort, redistort)
+pub fn generate_stmaps(stab: &StabilizationManager, per_frame: bool) -> impl Iterator<Item = std::result::Result<(String, usize, Vec<u8>, Vec<u8>), StMapError>> { // (frame, undistort, redistort)
+    generate_stmaps_with(stab, per_frame, StMapChannels::default(), StMapBlocks::default())
+}
+
+/// `generate_stmaps` with a choice of EXR channel and block layout.
+/// The per-frame body is duplicated in `StmapsLive::build_maps_for_frame_live`, changes go to both.
+pub fn generate_stmaps_with(stab: &StabilizationManager, per_frame: bool, channels: StMapChannels, blocks: StMapBlocks) -> impl Iterator<Item = std::result::Result<(String, usize, Vec<u8>, Vec<u8>), StMapError>> {
 
     //gets the with and height from the stabilization manager.
     let (width, height) = {
@@ -49,7 +56,7 @@ pub fn generate_stmaps(stab: &StabilizationManager, per_frame: bool) -> impl Ite
 
     //iterator over the frames to generate the stmaps. 
     //frame params is the index of the frame
-    (0..compute_params.frame_count).map(move |frame| {
+    (0..compute_params.frame_count).map(move |frame| -> std::result::Result<_, StMapError> {
         let timestamp = crate::timestamp_at_frame(frame as i32, compute_params.scaled_fps); //compute the timestamp for the frame
 
 
@@ -110,7 +117,7 @@ pub fn generate_stmaps(stab: &StabilizationManager, per_frame: bool) -> impl Ite
             //calculate for each pixel (x,y ) the ssource pixel
             //EXR is a file form that comntatin indepth information about pixels and image.
             //we create a lookup table for pixels so we can rotate them
-        let undist = parallel_exr(new_width, new_height, |x, y| {
+        let undist = parallel_exr(new_width, new_height, channels, blocks, |x, y| {
             ///////////////////////////////////////////////////////////////////
             // Calculate source `y` for rolling shutter
             let mut sy = if compute_params.frame_readout_direction.is_horizontal() {
@@ -132,7 +139,7 @@ pub fn generate_stmaps(stab: &StabilizationManager, per_frame: bool) -> impl Ite
 
             let idx = sy.min(transform.kernel_params.matrix_count as usize - 1);
             Stabilization::rotate_and_distort((x as f32, y as f32), idx, &transform.kernel_params, &transform.matrices, &compute_params.distortion_model, compute_params.digital_lens.as_ref(), r_limit_sq, &mesh_data)
-        });
+        })?;
 
         //returning to the original size
         compute_params.width              = width; compute_params.height              = height;
@@ -140,36 +147,214 @@ pub fn generate_stmaps(stab: &StabilizationManager, per_frame: bool) -> impl Ite
 
 
         //build redistort map as EXR in parallel
-        let dist = parallel_exr(width, height, |x, y| {
+        let dist = parallel_exr(width, height, channels, blocks, |x, y| {
             let distorted = [(x as f32, y as f32)];
             let (camera_matrix, distortion_coeffs, _p, rotations, is, mesh) = FrameTransform::at_timestamp_for_points(&compute_params, &distorted, timestamp, Some(frame), true);
             undistort_points(&distorted, camera_matrix, &distortion_coeffs, rotations[0], None, Some(rotations), &compute_params, 1.0, timestamp, is, mesh).first().copied()
-        });
+        })?;
 
-        (filename_base.clone(), frame, dist, undist) //RETURN THis tuple per frame
+        Ok((filename_base.clone(), frame, dist, undist)) //RETURN THis tuple per frame
     })
 }
+/// Why an STMap couldn't be written.
+#[derive(thiserror::Error, Debug)]
+pub enum StMapError {
+    #[error("Failed to write EXR: {0}")]
+    Encode(#[from] exr::error::Error),
+}
+
+/// Channel layout of the STMap EXRs. R and G always hold the normalized source x and 1 - y.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum StMapChannels {
+    /// Two channels, R and G only. Readers that expect a blue channel get zero.
+    RgZero,
+    /// B is a coverage mask: 1 where the pixel maps into the source frame, 0 outside the lens FOV.
+    RgMask,
+    /// B = 0, the layout Nuke and most compositors expect.
+    #[default]
+    Rgb,
+}
+
+/// Block layout of the STMap EXRs. `decode_stmap` reads both.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum StMapBlocks {
+    /// Whole rows, what every reader supports.
+    #[default]
+    Scanline,
+    /// Square tiles of `size` pixels, for reading parts of the map without decoding the rest
+    /// (e.g. streaming map tiles to the GPU). `size` 0 is `DEFAULT_STMAP_TILE_SIZE`.
+    Tiles { size: usize },
+}
+
+/// Tile size of `StMapBlocks::Tiles` when none is given.
+pub const DEFAULT_STMAP_TILE_SIZE: usize = 64;
+
+impl StMapBlocks {
+    fn exr_blocks(self) -> exr::image::Blocks {
+        match self {
+            Self::Scanline => exr::image::Blocks::ScanLines,
+            Self::Tiles { size } => {
+                let size = if size == 0 { DEFAULT_STMAP_TILE_SIZE } else { size };
+                exr::image::Blocks::Tiles(Vec2(size, size))
+            }
+        }
+    }
+}
+
+/// Layer name marking an EXR written with `StMapChannels::RgMask`.
+pub const STMAP_MASK_LAYER: &str = "stmap_mask";
+
+/// Source coordinate written for pixels that don't map into the source frame (beyond the lens' `r_limit`),
+/// same as the `-99999` of the GPU kernels.
+pub const STMAP_INVALID_COORD: f32 = -99999.0;
+
+/// Whether a decoded source coordinate is a real one and not `STMAP_INVALID_COORD`.
+/// The EXR round trip doesn't keep the sentinel exact, so anything far below the frame counts.
+pub fn is_valid_stmap_coord(c: f32) -> bool { c > STMAP_INVALID_COORD / 2.0 }
+
+/// Decoded STMap, see `decode_stmap`.
+#[derive(Debug, Clone)]
+pub struct DecodedStmap {
+    pub width: usize,
+    pub height: usize,
+    /// Source pixel coordinates, `x, y` pairs, row-major.
+    pub coords: Vec<f32>,
+    /// Coverage from the blue channel, only for maps written with `StMapChannels::RgMask`.
+    pub mask: Option<Vec<bool>>,
+}
+
+impl DecodedStmap {
+    /// Whether pixel `i` (row-major) maps into the source frame, from the mask or the invalid coordinate sentinel.
+    pub fn is_valid(&self, i: usize) -> bool {
+        if let Some(mask) = &self.mask {
+            return mask.get(i).copied().unwrap_or_default();
+        }
+        self.coords.get(i * 2..i * 2 + 2).is_some_and(|c| is_valid_stmap_coord(c[0]) && is_valid_stmap_coord(c[1]))
+    }
+}
+
 //the parallel exr function
-fn parallel_exr(width: usize, height: usize, cb: impl Fn(f32, f32) -> Option<(f32, f32)> + Sync) -> Vec<u8> {
-    let mut coords = vec![0.0f32; width * height * 2];
-    coords.par_chunks_mut(width * 2).enumerate().for_each(|(y, row)| { // Parallel iterator over buffer rows
-        row.chunks_mut(2).enumerate().for_each(|(x, pix)| { // iterator over row pixels
+fn parallel_exr(width: usize, height: usize, channels: StMapChannels, blocks: StMapBlocks, cb: impl Fn(f32, f32) -> Option<(f32, f32)> + Sync) -> std::result::Result<Vec<u8>, StMapError> {
+    let mut coords = vec![STMAP_INVALID_COORD; width * height * 2];
+    let mut covered = vec![false; width * height];
+    coords.par_chunks_mut(width * 2).zip(covered.par_chunks_mut(width)).enumerate().for_each(|(y, (row, row_covered))| { // Parallel iterator over buffer rows
+        row.chunks_mut(2).zip(row_covered.iter_mut()).enumerate().for_each(|(x, (pix, covered))| { // iterator over row pixels
+            // Pixels outside the lens FOV keep the invalid sentinel
             if let Some(pt) = cb(x as f32, y as f32) {
                 pix[0] = pt.0;
                 pix[1] = pt.1;
+                *covered = true;
             }
         });
     });
-    let channels = SpecificChannels::rgb(|Vec2(x, y)| (
-                   coords[y * width * 2 + x * 2 + 0] / width as f32,
-            1.0 - (coords[y * width * 2 + x * 2 + 1] / height as f32),
-            0.0
-    ) );
+    encode_stmap(width, height, &coords, &covered, channels, blocks)
+}
+
+/// Encode pixel coordinates (`x, y` pairs, row-major) as an STMap EXR.
+/// `covered` (one flag per pixel) is only used by `StMapChannels::RgMask`.
+pub fn encode_stmap(width: usize, height: usize, coords: &[f32], covered: &[bool], channels: StMapChannels, blocks: StMapBlocks) -> std::result::Result<Vec<u8>, StMapError> {
+    let u = |x: usize, y: usize| coords[y * width * 2 + x * 2] / width as f32;
+    let v = |x: usize, y: usize| 1.0 - (coords[y * width * 2 + x * 2 + 1] / height as f32);
     let mut data = Vec::new();
-    let mut img = Image::from_channels((width, height), channels);
-    img.layer_data.encoding.compression = Compression::ZIP16;
-    if let Err(e) = img.write().to_buffered(std::io::Cursor::new(&mut data)) {
-        ::log::error!("Failed to write EXR: {e:?}");
+    let out = std::io::Cursor::new(&mut data);
+    match channels {
+        StMapChannels::Rgb => {
+            let mut img = Image::from_channels((width, height), SpecificChannels::rgb(|Vec2(x, y)| (u(x, y), v(x, y), 0.0)));
+            img.layer_data.encoding.compression = Compression::ZIP16;
+            img.layer_data.encoding.blocks = blocks.exr_blocks();
+            img.write().to_buffered(out)
+        }
+        StMapChannels::RgMask => {
+            let mask = |x: usize, y: usize| if covered.get(y * width + x).copied().unwrap_or_default() { 1.0 } else { 0.0 };
+            let mut img = Image::from_channels((width, height), SpecificChannels::rgb(|Vec2(x, y)| (u(x, y), v(x, y), mask(x, y))));
+            img.layer_data.encoding.compression = Compression::ZIP16;
+            img.layer_data.encoding.blocks = blocks.exr_blocks();
+            img.layer_data.attributes.layer_name = Some(Text::from(STMAP_MASK_LAYER));
+            img.write().to_buffered(out)
+        }
+        StMapChannels::RgZero => {
+            let rg = SpecificChannels::build().with_channel("R").with_channel("G").with_pixel_fn(|Vec2(x, y)| (u(x, y), v(x, y)));
+            let mut img = Image::from_channels((width, height), rg);
+            img.layer_data.encoding.compression = Compression::ZIP16;
+            img.layer_data.encoding.blocks = blocks.exr_blocks();
+            img.write().to_buffered(out)
+        }
+    }?;
+    Ok(data)
+}
+
+/// Read an STMap written by `encode_stmap` (any channel or block layout) or another tool (R and G, optional B).
+pub fn decode_stmap(exr_bytes: &[u8]) -> Option<DecodedStmap> {
+    let img = read()
+        .no_deep_data()
+        .largest_resolution_level()
+        .specific_channels()
+        .required("R")
+        .required("G")
+        .optional("B", 0.0_f32)
+        .collect_pixels(
+            |size: Vec2<usize>, _| (size.width(), vec![(0.0f32, 0.0f32, 0.0f32); size.area()]),
+            |(w, pixels): &mut (usize, Vec<(f32, f32, f32)>), pos: Vec2<usize>, px: (f32, f32, f32)| pixels[pos.y() * *w + pos.x()] = px,
+        )
+        .first_valid_layer()
+        .all_attributes()
+        .from_buffered(std::io::Cursor::new(exr_bytes))
+        .map_err(|e| ::log::error!("Failed to read EXR: {e:?}"))
+        .ok()?;
+
+    let (width, height) = (img.layer_data.size.width(), img.layer_data.size.height());
+    let has_mask = img.layer_data.attributes.layer_name.as_ref().is_some_and(|n| n.to_string() == STMAP_MASK_LAYER);
+    let pixels = &img.layer_data.channel_data.pixels.1;
+    let coords = pixels.iter().flat_map(|&(r, g, _)| [r * width as f32, (1.0 - g) * height as f32]).collect();
+    let mask = has_mask.then(|| pixels.iter().map(|&(_, _, b)| b >= 0.5).collect());
+    Some(DecodedStmap { width, height, coords, mask })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mask_channel_round_trip() {
+        let (w, h) = (8, 4);
+        let coords: Vec<f32> = (0..w * h).flat_map(|i| [(i % w) as f32 + 0.5, (i / w) as f32 + 0.25]).collect();
+        // Left column outside the lens FOV
+        let covered: Vec<bool> = (0..w * h).map(|i| i % w != 0).collect();
+
+        let decoded = decode_stmap(&encode_stmap(w, h, &coords, &covered, StMapChannels::RgMask, StMapBlocks::Scanline).unwrap()).unwrap();
+        assert_eq!((decoded.width, decoded.height), (w, h));
+        assert_eq!(decoded.mask.as_ref(), Some(&covered));
+        assert!(decoded.coords.iter().zip(&coords).all(|(a, b)| (a - b).abs() < 1e-4));
+
+        // No mask in the other layouts
+        for channels in [StMapChannels::Rgb, StMapChannels::RgZero] {
+            let decoded = decode_stmap(&encode_stmap(w, h, &coords, &covered, channels, StMapBlocks::Scanline).unwrap()).unwrap();
+            assert!(decoded.mask.is_none(), "{channels:?}");
+            assert!(decoded.coords.iter().zip(&coords).all(|(a, b)| (a - b).abs() < 1e-4), "{channels:?}");
+        }
+    }
+
+    #[test]
+    fn tiled_round_trip_matches_scanline() {
+        // Not a multiple of the tile size, the last row and column of tiles are partial
+        let (w, h) = (100, 70);
+        let coords: Vec<f32> = (0..w * h).flat_map(|i| [(i % w) as f32 * 1.01 + 0.5, (i / w) as f32 * 0.99 + 0.25]).collect();
+
+        let scanline = encode_stmap(w, h, &coords, &[], StMapChannels::Rgb, StMapBlocks::Scanline).unwrap();
+        let tiled = encode_stmap(w, h, &coords, &[], StMapChannels::Rgb, StMapBlocks::Tiles { size: 32 }).unwrap();
+        assert_ne!(scanline, tiled);
+
+        let is_tiled = |bytes: &[u8]| exr::meta::MetaData::read_from_buffered(std::io::Cursor::new(bytes), false).unwrap().headers[0].blocks.has_tiles();
+        assert!(!is_tiled(&scanline));
+        assert!(is_tiled(&tiled));
+
+        let (scanline, tiled) = (decode_stmap(&scanline).unwrap(), decode_stmap(&tiled).unwrap());
+        assert_eq!((tiled.width, tiled.height), (w, h));
+        assert_eq!(tiled.coords, scanline.coords);
+        assert!(tiled.coords.iter().zip(&coords).all(|(a, b)| (a - b).abs() < 1e-3));
+
+        // No tile size given: the default one
+        let default_tiles = encode_stmap(w, h, &coords, &[], StMapChannels::Rgb, StMapBlocks::Tiles { size: 0 }).unwrap();
+        assert_eq!(default_tiles, encode_stmap(w, h, &coords, &[], StMapChannels::Rgb, StMapBlocks::Tiles { size: DEFAULT_STMAP_TILE_SIZE }).unwrap());
     }
-    data
 }
@@ -1,12 +1,13 @@
 
-use std::sync::{Arc, Mutex, atomic::{AtomicBool, Ordering}};
+use std::sync::{Arc, Mutex, atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering}};
 use std::thread;
 use std::time::Duration;
 
-use crossbeam_channel::{Receiver, SendError, Sender, TrySendError, unbounded};
-use log::{debug, error, info, warn};
-use exr::prelude::{SpecificChannels, Vec2, Image, Compression, WritableImage};
+use crossbeam_channel::{Receiver, SendError, SendTimeoutError, Sender, TrySendError, bounded, unbounded};
+use log::{debug, error, info};
 use crate::{StabilizationManager, stabilization::*, zooming::*};
+use crate::live::{Heartbeat, LOG_THROTTLE};
+use crate::stmap::{StMapBlocks, StMapError};
 use rayon::prelude::ParallelSliceMut;
 use rayon::iter::ParallelIterator;
 use rayon::iter::IndexedParallelIterator;
@@ -19,158 +20,290 @@ pub struct LiveFrameJob {
     pub frame_ts_ms: f64,
 }
 
-/// Same shape as generate_stmaps() emits.
-pub type StmapItem = (String, usize, Vec<u8>, Vec<u8>);
+/// Same shape as generate_stmaps() emits, but the EXR buffers are shared:
+/// when a map is identical to the previous frame's, the same `Arc` is sent again.
+pub type StmapItem = (String, usize, Arc<Vec<u8>>, Arc<Vec<u8>>);
+
+/// Sent instead of the maps of a frame whose build failed (an encode even on its retry), so the renderer can tell it from
+/// a real map and stops waiting for one that won't come.
+#[derive(thiserror::Error, Debug)]
+#[error("STMaps of frame {frame_index} failed to build: {error:#}")]
+pub struct StmapBuildFailed {
+    pub frame_index: usize,
+    pub error: anyhow::Error,
+}
+
+impl StmapBuildFailed {
+    /// The EXR encoder failed, rather than the computation of the map.
+    pub fn encoder_error(&self) -> Option<&StMapError> { self.error.downcast_ref() }
+}
+
+/// What the worker sends for each frame job.
+pub type StmapResult = Result<StmapItem, StmapBuildFailed>;
+
+/// Encodes map coordinates (`x, y` pairs, row-major) of the given size as an EXR, see `StmapsLive::set_encoder`.
+pub type StmapEncoder = Arc<dyn Fn(usize, usize, &[f32], StMapBlocks) -> Result<Vec<u8>, StMapError> + Send + Sync>;
+
+/// Default quantization step (in pixels) used when comparing consecutive maps.
+pub const DEFAULT_REUSE_TOLERANCE_PX: f32 = 0.01;
+
+/// Pending frame jobs kept by `StmapsLive::new`.
+pub const DEFAULT_INPUT_QUEUE_CAP: usize = 2;
+
+/// What `StmapsLive::submit_frame` does when the input queue is full.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum QueuePolicy {
+    /// Drop the oldest pending job, so the latency stays bounded. For live use.
+    #[default]
+    DropOldest,
+    /// Wait until the worker takes a job, every submitted frame gets its maps (batch use like `generate_stmaps`).
+    Block,
+}
+
+/// Remembers the last encoded map and reuses it when the new coordinates
+/// match within `tolerance_px` (coordinates are quantized to that step before hashing).
+#[derive(Default)]
+pub struct MapReuse {
+    pub tolerance_px: f32,
+    last: Option<(u64, Arc<Vec<u8>>)>,
+}
+
+impl MapReuse {
+    pub fn new(tolerance_px: f32) -> Self { Self { tolerance_px, last: None } }
+
+    fn hash_coords(&self, coords: &[f32]) -> u64 {
+        use std::hash::{Hash, Hasher};
+        let mut h = std::collections::hash_map::DefaultHasher::new();
+        let step = self.tolerance_px.max(f32::EPSILON);
+        coords.len().hash(&mut h);
+        for c in coords {
+            ((c / step).round() as i64).hash(&mut h);
+        }
+        h.finish()
+    }
+
+    /// Returns the cached buffer if `coords` match the previous map, otherwise encodes and caches a new one.
+    /// The bool is `true` when the buffer was reused.
+    /// A failed encode leaves the cache as it was.
+    pub fn get_or_encode(&mut self, coords: &[f32], encode: impl FnOnce() -> Result<Vec<u8>, StMapError>) -> Result<(Arc<Vec<u8>>, bool), StMapError> {
+        let hash = self.hash_coords(coords);
+        if let Some((last_hash, buf)) = &self.last {
+            if *last_hash == hash {
+                return Ok((buf.clone(), true));
+            }
+        }
+        let buf = Arc::new(encode()?);
+        self.last = Some((hash, buf.clone()));
+        Ok((buf, false))
+    }
+
+    pub fn clear(&mut self) { self.last = None; }
+}
+
+/// Counters describing how many maps were reused instead of freshly allocated.
+#[derive(Default, Debug)]
+pub struct MapReuseStats {
+    pub built: AtomicUsize,
+    pub reused: AtomicUsize,
+    pub saved_bytes: AtomicUsize,
+}
 
 pub struct StmapsLive {
     tx_in: Sender<LiveFrameJob>,
-    rx_out: Receiver<StmapItem>,
+    // Only used to pop the oldest job in `QueuePolicy::DropOldest`
+    rx_in: Receiver<LiveFrameJob>,
+    policy: QueuePolicy,
+    dropped_jobs: AtomicUsize,
+    rx_out: Receiver<StmapResult>,
     running: Arc<AtomicBool>,
+    reuse_stats: Arc<MapReuseStats>,
+    heartbeat: Heartbeat,
+    map_scale: Arc<AtomicU64>, // f64 bits
+    blocks: Arc<Mutex<StMapBlocks>>,
+    encoder: Arc<Mutex<StmapEncoder>>,
     _worker: thread::JoinHandle<()>,
 }
 
 impl StmapsLive {
-    /// Create a live STMaps worker with bounded queues.
-    /// - in_cap: how many pending frame jobs we queue
-    /// - out_cap: how many finished stmaps we keep for the render thread
+    /// Create a live STMaps worker, dropping the oldest job beyond `DEFAULT_INPUT_QUEUE_CAP` pending ones.
     pub fn new(stab: Arc<StabilizationManager>) -> Self {
-        let (tx_in, rx_in) = unbounded::<LiveFrameJob>();
-        let (tx_out, rx_out) = unbounded::<StmapItem>();
+        Self::with_queue(stab, DEFAULT_INPUT_QUEUE_CAP, QueuePolicy::DropOldest)
+    }
+
+    /// Create a STMaps worker with a bounded input queue.
+    /// - in_cap: how many pending frame jobs we queue
+    /// - policy: what `submit_frame` does when `in_cap` jobs are pending
+    pub fn with_queue(stab: Arc<StabilizationManager>, in_cap: usize, policy: QueuePolicy) -> Self {
+        let (tx_in, rx_in) = bounded::<LiveFrameJob>(in_cap.max(1));
+        let (tx_out, rx_out) = unbounded::<StmapResult>();
         let running = Arc::new(AtomicBool::new(true));
+        let reuse_stats = Arc::new(MapReuseStats::default());
 
         let running_flag = running.clone();
+        let stats = reuse_stats.clone();
+        let heartbeat = Heartbeat::new();
+        let worker_heartbeat = heartbeat.clone();
+        let map_scale = Arc::new(AtomicU64::new(1.0f64.to_bits()));
+        let worker_map_scale = map_scale.clone();
+        let blocks = Arc::new(Mutex::new(StMapBlocks::default()));
+        let worker_blocks = blocks.clone();
+        let encoder: StmapEncoder = Arc::new(Self::encode_exr_with);
+        let encoder = Arc::new(Mutex::new(encoder));
+        let worker_encoder = encoder.clone();
+        let worker_rx_in = rx_in.clone();
 
         println!("Starting stmaps_live worker...");
         let worker = thread::Builder::new()
             .name("stmaps_live_worker".into())
             .spawn(move || {
-                Self::worker_loop(stab, rx_in, tx_out, running_flag);
+                Self::worker_loop(stab, worker_rx_in, tx_out, running_flag, stats, worker_heartbeat, worker_map_scale, worker_blocks, worker_encoder);
             })
             .expect("spawn stmaps live worker");
 
 
-        Self { tx_in, rx_out, running, _worker: worker }
+        Self { tx_in, rx_in, policy, dropped_jobs: AtomicUsize::new(0), rx_out, running, reuse_stats, heartbeat, map_scale, blocks, encoder, _worker: worker }
+    }
+
+    /// Resolution of the maps relative to the frame, 0.1..=1. A lower resolution is cheaper to build;
+    /// STMaps hold normalized coordinates, so a reduced map still covers the whole frame and is
+    /// sampled with interpolation. Takes effect from the next submitted frame.
+    pub fn set_map_scale(&self, scale: f64) {
+        self.map_scale.store(scale.clamp(0.1, 1.0).to_bits(), Ordering::Relaxed);
     }
 
-     pub fn rx(&self) -> Receiver<StmapItem> {
+    pub fn map_scale(&self) -> f64 { f64::from_bits(self.map_scale.load(Ordering::Relaxed)) }
+
+    /// Scanline or tiled EXRs, scanline by default. Takes effect from the next submitted frame.
+    pub fn set_blocks(&self, blocks: StMapBlocks) { *self.blocks.lock().unwrap() = blocks; }
+
+    pub fn blocks(&self) -> StMapBlocks { *self.blocks.lock().unwrap() }
+
+    /// Replace the EXR encoder of the maps, `encode_exr_with` by default. Applies from the next job.
+    pub fn set_encoder(&self, encoder: StmapEncoder) { *self.encoder.lock().unwrap() = encoder; }
+
+    /// How many maps were built vs reused, and how many EXR bytes were not re-allocated.
+    pub fn reuse_stats(&self) -> &MapReuseStats { &self.reuse_stats }
+
+    /// Beats once per worker loop iteration, to be registered with a `live::Watchdog`.
+    pub fn heartbeat(&self) -> &Heartbeat { &self.heartbeat }
+
+     pub fn rx(&self) -> Receiver<StmapResult> {
         self.rx_out.clone()
     }
 
 
 
-    /// Non-blocking: submit a frame job.
-    /// If the queue is full, drop the **oldest** job to keep latency bounded.
+    pub fn queue_policy(&self) -> QueuePolicy { self.policy }
+
+    /// Jobs dropped from a full input queue so far, always 0 with `QueuePolicy::Block`.
+    pub fn dropped_jobs(&self) -> usize { self.dropped_jobs.load(Ordering::Relaxed) }
+
+    /// Submit a frame job. If the queue is full, `QueuePolicy::DropOldest` drops the **oldest**
+    /// job to keep latency bounded, `QueuePolicy::Block` waits until there is space.
     pub fn submit_frame(&self, frame_index: usize, ts_us: i64) {
-        let job = LiveFrameJob {
+        let mut job = LiveFrameJob {
             frame_index,
             frame_ts_ms: ts_us as f64 / 1000.0,
         };
-        match self.tx_in.send(job) {
-            Ok(_) => {}
-            Err(SendError(_)) => {
-                error!("stmaps_live: input channel disconnected");
-            } 
+        if self.policy == QueuePolicy::Block {
+            // Re-check `running`, a stopped worker never makes space
+            while self.running.load(Ordering::Relaxed) {
+                match self.tx_in.send_timeout(job, Duration::from_millis(50)) {
+                    Ok(_) => return,
+                    Err(SendTimeoutError::Timeout(j)) => job = j,
+                    Err(SendTimeoutError::Disconnected(_)) => break,
+                }
+            }
+            error!("stmaps_live: worker stopped, frame {} not submitted", job.frame_index);
+            return;
+        }
+        loop {
+            match self.tx_in.try_send(job) {
+                Ok(_) => return,
+                Err(TrySendError::Full(j)) => {
+                    job = j;
+                    if let Ok(old) = self.rx_in.try_recv() {
+                        self.dropped_jobs.fetch_add(1, Ordering::Relaxed);
+                        debug!("stmaps_live: input queue full, dropped frame {}", old.frame_index);
+                    }
+                }
+                Err(TrySendError::Disconnected(_)) => {
+                    error!("stmaps_live: input channel disconnected");
+                    return;
+                }
+            }
         }
     }
 
     /// Non-blocking: try to pop a finished stmap item (same type as generate_stmaps()).
-    pub fn try_pop_map(&self) -> Option<StmapItem> {
+    pub fn try_pop_map(&self) -> Option<StmapResult> {
         self.rx_out.try_recv().ok()
     }
 
     /// Optional blocking pop (if you prefer render thread to wait):
-    pub fn recv_map(&self) -> Option<StmapItem> {
+    pub fn recv_map(&self) -> Option<StmapResult> {
         self.rx_out.recv().ok()
     }
 
     pub fn stop(&self) { self.running.store(false, Ordering::Relaxed); }
 
+    /// Build the maps of one frame on the calling thread, at full resolution and without reuse of
+    /// earlier maps. For tests and single-shot use, the worker builds the same maps.
+    pub fn build_sync(stab: &StabilizationManager, job: LiveFrameJob) -> Result<StmapItem, anyhow::Error> {
+        MapBuilder::new(stab).build(stab, job, 1.0, StMapBlocks::default(), &Self::encode_exr_with, &MapReuseStats::default())
+    }
+
     fn worker_loop(
         stab: Arc<StabilizationManager>,
         rx_in: Receiver<LiveFrameJob>,
-        tx_out: Sender<StmapItem>,
+        tx_out: Sender<StmapResult>,
         running: Arc<AtomicBool>,
+        stats: Arc<MapReuseStats>,
+        heartbeat: Heartbeat,
+        map_scale: Arc<AtomicU64>,
+        blocks: Arc<Mutex<StMapBlocks>>,
+        encoder: Arc<Mutex<StmapEncoder>>,
     ) {
-        println!("Starting stmaps_live worker loop...");
-        // --------- GLOBAL CACHE (recomputed on param/lens changes) ---------
-        // filename_base mirrors generate_stmaps()
-        let filename_base = {
-            let lens = stab.lens.read();
-            format!("{}-{}-{}-{}",
-                crate::filesystem::get_filename(&stab.input_file.read().url),
-                lens.camera_brand, lens.camera_model, lens.lens_model
-            )
-            .replace("/", "-").replace("\\", "-").replace(":", "-")
-            .replace("+", "-").replace("'", "-").replace("\"", "-")
-            .replace(" ", "-")
-        };
-
-        // Precompute kernel flags once (direction may change if params change; watch for that if needed)
-        let mut kernel_flags = KernelParamsFlags::empty();
-        {
-            let p = ComputeParams::from_manager(&stab);
-            kernel_flags.set(KernelParamsFlags::HAS_DIGITAL_LENS, p.digital_lens.is_some());
-            kernel_flags.set(KernelParamsFlags::HORIZONTAL_RS, p.frame_readout_direction.is_horizontal());
-        }
-
-        // Optional: remember last hash of params/lens to refresh cache when needed
-        let mut last_params_fingerprint: Option<u64> = None;
+        let mut builder = MapBuilder::new(&stab);
+        let mut last_encoder = encoder.lock().unwrap().clone();
 
         while running.load(Ordering::Relaxed) {
-            let job = match rx_in.recv_timeout(Duration::from_millis(10)) {
-                Ok(j) => {println!("got live frameJob."); j},
-                Err(crossbeam_channel::RecvTimeoutError::Timeout) => {print!("couldnt get live frameJob.") ;continue},
-                Err(_) => {print!("couldnt get live frameJob."); break},
+            heartbeat.beat();
+            let profile = stab.live_latency_profile.get();
+            let job = match profile.recv_timeout(&rx_in, Duration::from_millis(10)) {
+                Ok(j) => j,
+                Err(crossbeam_channel::RecvTimeoutError::Timeout) => continue,
+                Err(_) => break,
             };
 
-            
-
-            // ComputeParams fresh per job, similar to generate_stmaps()
-            let mut compute_params = ComputeParams::from_manager(&stab);
-            compute_params.adaptive_zoom_window = -1.0;
-            compute_params.frame_count = 1; // live: one frame
-            compute_params.keyframes.clear();
-            compute_params.suppress_rotation = true;
-            compute_params.fov_algorithm_margin = 0.0;
-            compute_params.fovs.clear();
-            compute_params.minimal_fovs.clear();
-
-            // Invalidate global bits if params changed (optional hash)
-            let this_fingerprint = Self::fingerprint_params(&compute_params);
-            if last_params_fingerprint != Some(this_fingerprint) {
-                debug!("stmaps_live: params/lens changed → refresh cached globals");
-                // If you need to rebuild bigger globals, do it here.
-                last_params_fingerprint = Some(this_fingerprint);
-            }
-
             // Build maps for one frame @ live timestamp.
-            match Self::build_maps_for_frame_live(
-                &stab,
-                compute_params,
-                kernel_flags,
-                &filename_base,
-                job.frame_index,
-                job.frame_ts_ms,
-            ) {
+            let blocks = *blocks.lock().unwrap();
+            let encoder = encoder.lock().unwrap().clone();
+            if !Arc::ptr_eq(&encoder, &last_encoder) {
+                // The cached maps came from the previous encoder
+                builder.undist_reuse.clear();
+                builder.dist_reuse.clear();
+                last_encoder = encoder.clone();
+            }
+            let map_scale = f64::from_bits(map_scale.load(Ordering::Relaxed));
+            match builder.build(&stab, job, map_scale, blocks, &*encoder, &stats) {
                 Ok(item) => {
-                    match tx_out.send(item){
-                        //debugging purpose
-                        Ok(_) => {println!("stmaps_live: sent stmap for frame {}", job.frame_index);},
-                        Err(SendError(_)) => {
-                            error!("stmaps_live: output channel disconnected");
-                        }
+                    stab.live_latest_stmap.publish(&item);
+                    if let Err(SendError(_)) = tx_out.send(Ok(item)) {
+                        LOG_THROTTLE.error("stmaps_live: output", format_args!("stmaps_live: output channel disconnected"));
                     }
                 }
                 Err(e) => {
-                    warn!("stmaps_live: failed to build maps for frame {} ts={:.3}ms: {e:?}",
-                          job.frame_index, job.frame_ts_ms);
-                    // You may still send a placeholder so the renderer does not stall:
-                    let _ = tx_out.send((filename_base.clone(), job.frame_index, vec![], vec![]));
+                    LOG_THROTTLE.warn("stmaps_live: build", format_args!("stmaps_live: failed to build maps for frame {} ts={:.3}ms: {e:?}",
+                          job.frame_index, job.frame_ts_ms));
+                    // Tell the renderer, so it does not stall waiting for these maps
+                    let _ = tx_out.send(Err(StmapBuildFailed { frame_index: job.frame_index, error: e }));
                 }
             }
         }
 
-        info!("stmaps_live: worker exit");
+        info!("stmaps_live: worker exit (built {} maps, reused {}, saved {} bytes)",
+              stats.built.load(Ordering::Relaxed), stats.reused.load(Ordering::Relaxed), stats.saved_bytes.load(Ordering::Relaxed));
     }
 
     #[inline]
@@ -183,6 +316,8 @@ impl StmapsLive {
     }
 
     /// This is the single-frame worker; it mirrors your generate_stmaps body, parameterized by timestamp_ms.
+    /// Its maps are byte-identical to the `generate_stmaps` frame at the same timestamp, keep them that way
+    /// (the `live_map_matches_the_generate_stmaps_frame` test checks it).
     fn build_maps_for_frame_live(
         stab: &StabilizationManager,
         mut compute_params: ComputeParams,
@@ -190,6 +325,12 @@ impl StmapsLive {
         filename_base: &str,
         frame: usize,
         timestamp_ms: f64,
+        map_scale: f64,
+        blocks: StMapBlocks,
+        encode: &dyn Fn(usize, usize, &[f32], StMapBlocks) -> Result<Vec<u8>, StMapError>,
+        dist_reuse: &mut MapReuse,
+        undist_reuse: &mut MapReuse,
+        stats: &MapReuseStats,
     ) -> Result<StmapItem, anyhow::Error> {
         let (width, height) = {
             let params = stab.params.read();
@@ -247,7 +388,7 @@ impl StmapsLive {
 
         // undist
         let mesh_data2 = transform.mesh_data.iter().map(|x| *x as f64).collect::<Vec<f64>>();
-        let undist = Self::parallel_exr(new_width, new_height, |x, y| {
+        let (undist_coords, undist_w, undist_h) = Self::parallel_coords(new_width, new_height, map_scale, |x, y| {
             let mut sy = if compute_params.frame_readout_direction.is_horizontal() {
                 (x.round() as i32).min(transform.kernel_params.width).max(0) as usize
             } else {
@@ -279,7 +420,7 @@ impl StmapsLive {
         compute_params.width        = width;  compute_params.height        = height;
         compute_params.output_width = width;  compute_params.output_height = height;
 
-        let dist = Self::parallel_exr(width, height, |x, y| {
+        let (dist_coords, dist_w, dist_h) = Self::parallel_coords(width, height, map_scale, |x, y| {
             let distorted = [(x as f32, y as f32)];
             let (camera_matrix, distortion_coeffs, _p, rotations, is, mesh) =
                 FrameTransform::at_timestamp_for_points(&compute_params, &distorted, timestamp_ms, Some(frame), true);
@@ -289,31 +430,343 @@ impl StmapsLive {
             ).first().copied()
         });
 
+        // One retry of the encoder, it can fail transiently (e.g. out of memory for a moment); the coordinates stay computed
+        let encode_retrying = |w: usize, h: usize, coords: &[f32]| encode(w, h, coords, blocks).or_else(|e| {
+            LOG_THROTTLE.warn("stmaps_live: retry", format_args!("stmaps_live: failed to encode the maps of frame {frame}, retrying: {e:?}"));
+            encode(w, h, coords, blocks)
+        });
+        let undist = Self::reuse_or_encode(undist_reuse, stats, &undist_coords, undist_w, undist_h, &encode_retrying)?;
+        let dist = Self::reuse_or_encode(dist_reuse, stats, &dist_coords, dist_w, dist_h, &encode_retrying)?;
+
         Ok((filename_base.to_string(), frame, dist, undist))
     }
 
+    fn reuse_or_encode(reuse: &mut MapReuse, stats: &MapReuseStats, coords: &[f32], width: usize, height: usize, encode: impl FnOnce(usize, usize, &[f32]) -> Result<Vec<u8>, StMapError>) -> Result<Arc<Vec<u8>>, StMapError> {
+        let (buf, reused) = reuse.get_or_encode(coords, || encode(width, height, coords))?;
+        if reused {
+            stats.reused.fetch_add(1, Ordering::Relaxed);
+            stats.saved_bytes.fetch_add(buf.len(), Ordering::Relaxed);
+        } else {
+            stats.built.fetch_add(1, Ordering::Relaxed);
+        }
+        Ok(buf)
+    }
 
-    fn parallel_exr(width: usize, height: usize, cb: impl Fn(f32, f32) -> Option<(f32, f32)> + Sync) -> Vec<u8> {
-        let mut coords = vec![0.0f32; width * height * 2];
-        coords.par_chunks_mut(width * 2).enumerate().for_each(|(y, row)| { // Parallel iterator over buffer rows
+    /// Coordinates of a `width` x `height` map, evaluated on a grid reduced by `scale`.
+    /// Returns the coordinates in the reduced pixel space and the reduced size.
+    fn parallel_coords(width: usize, height: usize, scale: f64, cb: impl Fn(f32, f32) -> Option<(f32, f32)> + Sync) -> (Vec<f32>, usize, usize) {
+        let w = ((width as f64 * scale).round() as usize).clamp(1, width.max(1));
+        let h = ((height as f64 * scale).round() as usize).clamp(1, height.max(1));
+        let (sx, sy) = (w as f32 / width.max(1) as f32, h as f32 / height.max(1) as f32);
+        // Pixels outside the lens FOV keep the invalid sentinel, see `crate::stmap::STMAP_INVALID_COORD`
+        let mut coords = vec![crate::stmap::STMAP_INVALID_COORD; w * h * 2];
+        coords.par_chunks_mut(w * 2).enumerate().for_each(|(y, row)| { // Parallel iterator over buffer rows
             row.chunks_mut(2).enumerate().for_each(|(x, pix)| { // iterator over row pixels
-                if let Some(pt) = cb(x as f32, y as f32) {
-                    pix[0] = pt.0;
-                    pix[1] = pt.1;
+                if let Some(pt) = cb(x as f32 / sx, y as f32 / sy) {
+                    pix[0] = pt.0 * sx;
+                    pix[1] = pt.1 * sy;
                 }
             });
         });
-        let channels = SpecificChannels::rgb(|Vec2(x, y)| (
-                    coords[y * width * 2 + x * 2 + 0] / width as f32,
-                1.0 - (coords[y * width * 2 + x * 2 + 1] / height as f32),
-                0.0
-        ) );
-        let mut data = Vec::new();
-        let mut img = Image::from_channels((width, height), channels);
-        img.layer_data.encoding.compression = Compression::ZIP16;
-        if let Err(e) = img.write().to_buffered(std::io::Cursor::new(&mut data)) {
-            ::log::error!("Failed to write EXR: {e:?}");
+        (coords, w, h)
+    }
+
+    /// Encode pixel coordinates (`x, y` pairs, row-major) as an STMap EXR.
+    pub fn encode_exr(width: usize, height: usize, coords: &[f32]) -> Result<Vec<u8>, StMapError> {
+        Self::encode_exr_with(width, height, coords, StMapBlocks::Scanline)
+    }
+
+    /// `encode_exr` with a choice of scanline or tiled blocks.
+    pub fn encode_exr_with(width: usize, height: usize, coords: &[f32], blocks: StMapBlocks) -> Result<Vec<u8>, StMapError> {
+        crate::stmap::encode_stmap(width, height, coords, &[], crate::stmap::StMapChannels::Rgb, blocks)
+    }
+}
+
+/// State kept between the frames of one map stream: reuse of identical maps and the
+/// per-session constants of the kernel.
+struct MapBuilder {
+    // filename_base mirrors generate_stmaps()
+    filename_base: String,
+    kernel_flags: KernelParamsFlags,
+    dist_reuse: MapReuse,
+    undist_reuse: MapReuse,
+    // Optional: remember last hash of params/lens to refresh cache when needed
+    last_params_fingerprint: Option<u64>,
+    // The cached maps are encoded with these blocks
+    last_blocks: StMapBlocks,
+}
+
+impl MapBuilder {
+    fn new(stab: &StabilizationManager) -> Self {
+        // --------- GLOBAL CACHE (recomputed on param/lens changes) ---------
+        let filename_base = {
+            let lens = stab.lens.read();
+            format!("{}-{}-{}-{}",
+                crate::filesystem::get_filename(&stab.input_file.read().url),
+                lens.camera_brand, lens.camera_model, lens.lens_model
+            )
+            .replace("/", "-").replace("\\", "-").replace(":", "-")
+            .replace("+", "-").replace("'", "-").replace("\"", "-")
+            .replace(" ", "-")
+        };
+
+        // Precompute kernel flags once (direction may change if params change; watch for that if needed)
+        let mut kernel_flags = KernelParamsFlags::empty();
+        {
+            let p = ComputeParams::from_manager(stab);
+            kernel_flags.set(KernelParamsFlags::HAS_DIGITAL_LENS, p.digital_lens.is_some());
+            kernel_flags.set(KernelParamsFlags::HORIZONTAL_RS, p.frame_readout_direction.is_horizontal());
+        }
+
+        Self {
+            filename_base,
+            kernel_flags,
+            dist_reuse: MapReuse::new(DEFAULT_REUSE_TOLERANCE_PX),
+            undist_reuse: MapReuse::new(DEFAULT_REUSE_TOLERANCE_PX),
+            last_params_fingerprint: None,
+            last_blocks: StMapBlocks::default(),
+        }
+    }
+
+    fn build(&mut self, stab: &StabilizationManager, job: LiveFrameJob, map_scale: f64, blocks: StMapBlocks, encode: &dyn Fn(usize, usize, &[f32], StMapBlocks) -> Result<Vec<u8>, StMapError>, stats: &MapReuseStats) -> Result<StmapItem, anyhow::Error> {
+        // ComputeParams fresh per job, similar to generate_stmaps()
+        let mut compute_params = ComputeParams::from_manager(stab);
+        compute_params.adaptive_zoom_window = -1.0;
+        compute_params.frame_count = 1; // live: one frame
+        compute_params.keyframes.clear();
+        compute_params.suppress_rotation = true;
+        compute_params.fov_algorithm_margin = 0.0;
+        compute_params.fovs.clear();
+        compute_params.minimal_fovs.clear();
+
+        // Invalidate global bits if params changed (optional hash)
+        let this_fingerprint = StmapsLive::fingerprint_params(&compute_params);
+        if self.last_params_fingerprint != Some(this_fingerprint) {
+            debug!("stmaps_live: params/lens changed → refresh cached globals");
+            // If you need to rebuild bigger globals, do it here.
+            self.undist_reuse.clear();
+            self.dist_reuse.clear();
+            self.last_params_fingerprint = Some(this_fingerprint);
+        }
+        if self.last_blocks != blocks {
+            self.undist_reuse.clear();
+            self.dist_reuse.clear();
+            self.last_blocks = blocks;
+        }
+
+        StmapsLive::build_maps_for_frame_live(
+            stab,
+            compute_params,
+            self.kernel_flags,
+            &self.filename_base,
+            job.frame_index,
+            job.frame_ts_ms,
+            map_scale,
+            blocks,
+            encode,
+            &mut self.dist_reuse,
+            &mut self.undist_reuse,
+            stats,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn map_reuse_within_tolerance() {
+        let mut reuse = MapReuse::new(0.01);
+        let coords = vec![10.0f32, 20.0, 30.5, 40.25];
+        let mut encodes = 0;
+
+        let (a, reused) = reuse.get_or_encode(&coords, || { encodes += 1; Ok(vec![1, 2, 3]) }).unwrap();
+        assert!(!reused);
+
+        // Sub-tolerance jitter (static shot) → same allocation
+        let jittered: Vec<f32> = coords.iter().map(|c| c + 0.0001).collect();
+        let (b, reused) = reuse.get_or_encode(&jittered, || { encodes += 1; Ok(vec![4, 5, 6]) }).unwrap();
+        assert!(reused);
+        assert!(Arc::ptr_eq(&a, &b));
+
+        // Real motion → fresh buffer
+        let moved: Vec<f32> = coords.iter().map(|c| c + 1.0).collect();
+        let (c, reused) = reuse.get_or_encode(&moved, || { encodes += 1; Ok(vec![7, 8, 9]) }).unwrap();
+        assert!(!reused);
+        assert!(!Arc::ptr_eq(&a, &c));
+        assert_eq!(encodes, 2);
+    }
+
+    #[test]
+    fn build_sync_produces_maps_of_the_frame_size() {
+        let (w, h) = (192, 108);
+        let stab = StabilizationManager::default();
+        stab.init_from_stream_data(30.0, (w, h));
+        stab.gyro.read().enable_live(3.0, 1.0, 0.0, 30.0);
+        {
+            let mut lens = stab.lens.write();
+            lens.calib_dimension = crate::lens_profile::Dimensions { w, h };
+            lens.fisheye_params.camera_matrix = vec![[150.0, 0.0, 96.0], [0.0, 150.0, 54.0], [0.0, 0.0, 1.0]];
+            lens.fisheye_params.distortion_coeffs = vec![0.05, 0.01, 0.0, 0.0];
+        }
+
+        let (_, frame, dist, undist) = StmapsLive::build_sync(&stab, LiveFrameJob { frame_index: 7, frame_ts_ms: 233.3 }).unwrap();
+        assert_eq!(frame, 7);
+        let dist = crate::stmap::decode_stmap(&dist).expect("distort map is not a valid EXR");
+        assert_eq!((dist.width, dist.height), (w, h));
+        assert_eq!(dist.coords.len(), w * h * 2);
+        assert!(dist.coords.iter().all(|c| c.is_finite()));
+
+        let undist = crate::stmap::decode_stmap(&undist).expect("undistort map is not a valid EXR");
+        assert!(undist.width > 0 && undist.height > 0);
+        assert_eq!(undist.coords.len(), undist.width * undist.height * 2);
+    }
+
+    #[test]
+    fn pixels_beyond_r_limit_get_the_invalid_sentinel() {
+        let (w, h) = (192, 108);
+        let stab = StabilizationManager::default();
+        stab.init_from_stream_data(30.0, (w, h));
+        stab.gyro.read().enable_live(3.0, 1.0, 0.0, 30.0);
+        {
+            // Wide fisheye whose model only holds up to a radius of 0.3 (normalized), the frame corners are at ~0.73
+            let mut lens = stab.lens.write();
+            lens.calib_dimension = crate::lens_profile::Dimensions { w, h };
+            lens.fisheye_params.camera_matrix = vec![[150.0, 0.0, 96.0], [0.0, 150.0, 54.0], [0.0, 0.0, 1.0]];
+            lens.fisheye_params.distortion_coeffs = vec![0.2, 0.05, 0.0, 0.0];
+            lens.fisheye_params.radial_distortion_limit = Some(0.3);
+        }
+
+        let (_, _, _, undist) = StmapsLive::build_sync(&stab, LiveFrameJob { frame_index: 0, frame_ts_ms: 0.0 }).unwrap();
+        let undist = crate::stmap::decode_stmap(&undist).unwrap();
+        let (uw, uh) = (undist.width, undist.height);
+        let at = |x: usize, y: usize| y * uw + x;
+
+        for i in [at(0, 0), at(uw - 1, 0), at(0, uh - 1), at(uw - 1, uh - 1)] {
+            assert!(!undist.is_valid(i), "corner pixel {i} maps to {:?}", &undist.coords[i * 2..i * 2 + 2]);
+            assert!(undist.coords[i * 2] < -90000.0 && undist.coords[i * 2 + 1] < -90000.0);
+        }
+        let center = at(uw / 2, uh / 2);
+        assert!(undist.is_valid(center));
+        let c = &undist.coords[center * 2..center * 2 + 2];
+        assert!((0.0..w as f32).contains(&c[0]) && (0.0..h as f32).contains(&c[1]), "center maps to {c:?}");
+    }
+
+    #[test]
+    fn block_policy_processes_every_frame() {
+        let (w, h) = (64, 36);
+        let stab = StabilizationManager::default();
+        stab.init_from_stream_data(30.0, (w, h));
+        stab.gyro.read().enable_live(3.0, 1.0, 0.0, 30.0);
+        {
+            let mut lens = stab.lens.write();
+            lens.calib_dimension = crate::lens_profile::Dimensions { w, h };
+            lens.fisheye_params.camera_matrix = vec![[50.0, 0.0, 32.0], [0.0, 50.0, 18.0], [0.0, 0.0, 1.0]];
+            lens.fisheye_params.distortion_coeffs = vec![0.05, 0.01, 0.0, 0.0];
+        }
+
+        // A single slot, so nearly every submit finds the queue full
+        let st = StmapsLive::with_queue(Arc::new(stab), 1, QueuePolicy::Block);
+        let frames = 12;
+        for i in 0..frames {
+            st.submit_frame(i, i as i64 * 33_333);
+        }
+        let got: Vec<usize> = (0..frames).map(|_| st.recv_map().unwrap().unwrap().1).collect();
+        st.stop();
+        assert_eq!(got, (0..frames).collect::<Vec<_>>());
+        assert_eq!(st.dropped_jobs(), 0);
+    }
+
+    #[test]
+    fn encoder_failure_is_retried_then_reported() {
+        let (w, h) = (64, 36);
+        let stab = StabilizationManager::default();
+        stab.init_from_stream_data(30.0, (w, h));
+        stab.gyro.read().enable_live(3.0, 1.0, 0.0, 30.0);
+        {
+            let mut lens = stab.lens.write();
+            lens.calib_dimension = crate::lens_profile::Dimensions { w, h };
+            lens.fisheye_params.camera_matrix = vec![[50.0, 0.0, 32.0], [0.0, 50.0, 18.0], [0.0, 0.0, 1.0]];
+            lens.fisheye_params.distortion_coeffs = vec![0.05, 0.01, 0.0, 0.0];
+        }
+        let st = StmapsLive::with_queue(Arc::new(stab), 1, QueuePolicy::Block);
+
+        // Fails the first `failures` encodes, then encodes as usual
+        let encoder_failing = |failures: usize| -> (StmapEncoder, Arc<AtomicUsize>) {
+            let calls = Arc::new(AtomicUsize::new(0));
+            let counter = calls.clone();
+            let encoder: StmapEncoder = Arc::new(move |w: usize, h: usize, coords: &[f32], blocks: StMapBlocks| {
+                if counter.fetch_add(1, Ordering::Relaxed) < failures {
+                    return Err(StMapError::Encode(exr::error::Error::Invalid("simulated encoder failure".into())));
+                }
+                StmapsLive::encode_exr_with(w, h, coords, blocks)
+            });
+            (encoder, calls)
+        };
+
+        // A single failure is covered by the retry of that encode alone
+        let (encoder, calls) = encoder_failing(1);
+        st.set_encoder(encoder);
+        st.submit_frame(0, 0);
+        let (_, frame, dist, undist) = st.recv_map().unwrap().expect("retry should have built the maps");
+        assert_eq!(frame, 0);
+        assert!(!dist.is_empty() && !undist.is_empty());
+        assert_eq!(calls.load(Ordering::Relaxed), 3);
+
+        // A persistent one gives a typed failure instead of empty maps, after one retry
+        let (encoder, calls) = encoder_failing(usize::MAX);
+        st.set_encoder(encoder);
+        st.submit_frame(1, 33_333);
+        let failed = st.recv_map().unwrap().expect_err("maps of a failing encoder");
+        assert_eq!(failed.frame_index, 1);
+        assert!(matches!(failed.encoder_error(), Some(StMapError::Encode(_))), "{failed}");
+        assert_eq!(calls.load(Ordering::Relaxed), 2);
+        st.stop();
+    }
+
+    #[test]
+    fn live_map_matches_the_generate_stmaps_frame() {
+        use std::collections::BTreeMap;
+        use nalgebra::Vector3;
+        use crate::gyro_source::{ Quat64, QuatBuffer };
+
+        // `build_maps_for_frame_live` is a copy of the `generate_stmaps` body, this keeps the two in step.
+        // Both suppress the rotation and use a static zoom. They legitimately differ in the frame count of
+        // the params (1 live, the clip's for `generate_stmaps`, only read with per-frame lens parameters) and
+        // in the timestamp (the job's vs `timestamp_at_frame`), so the job gets the one `generate_stmaps` uses.
+        let (w, h) = (96, 54);
+        let stab = StabilizationManager::default();
+        stab.init_from_stream_data(30.0, (w, h));
+        stab.gyro.read().enable_live(3.0, 1.0, 0.0, 30.0);
+        {
+            let mut lens = stab.lens.write();
+            lens.calib_dimension = crate::lens_profile::Dimensions { w, h };
+            lens.fisheye_params.camera_matrix = vec![[75.0, 0.0, 48.0], [0.0, 75.0, 27.0], [0.0, 0.0, 1.0]];
+            lens.fisheye_params.distortion_coeffs = vec![0.05, 0.01, 0.0, 0.0];
+        }
+        {
+            let mut params = stab.params.write();
+            params.frame_count = 10;
+            params.frame_readout_time = 12.0; // rolling shutter, so a matrix per row
+        }
+        {
+            let org: BTreeMap<i64, Quat64> = (0..=100).map(|i| (i * 10_000, Quat64::from_scaled_axis(Vector3::new(0.2, -0.1, 0.4) * (i as f64 * 0.01)))).collect();
+            let identity: BTreeMap<i64, Quat64> = org.keys().map(|&t| (t, Quat64::identity())).collect();
+            let gyro = stab.gyro.read();
+            let live = gyro.live.read();
+            let st = live.as_ref().unwrap();
+            st.quat_buffer_store_org.publish(QuatBuffer::from_btreemap(&org).unwrap());
+            st.quat_buffer_store_smoothed.publish(QuatBuffer::from_btreemap(&identity).unwrap());
+        }
+
+        for frame in [0, 6] {
+            let expected = crate::stmap::generate_stmaps(&stab, true).nth(frame).expect("generate_stmaps frame").expect("encode");
+            let ts = crate::timestamp_at_frame(frame as i32, ComputeParams::from_manager(&stab).scaled_fps);
+            let (name, idx, dist, undist) = StmapsLive::build_sync(&stab, LiveFrameJob { frame_index: frame, frame_ts_ms: ts }).unwrap();
+            assert_eq!((name, idx), (expected.0, expected.1));
+            assert!(*dist == expected.2, "frame {frame}: distort maps differ");
+            assert!(*undist == expected.3, "frame {frame}: undistort maps differ");
         }
-        data
     }
 }
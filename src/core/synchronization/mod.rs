@@ -67,6 +67,8 @@ pub struct PoseEstimator {
     pub sync_results: Arc<RwLock<BTreeMap<i64, FrameResult>>>,
     pub estimated_gyro: Arc<RwLock<BTreeMap<i64, TimeIMU>>>,
     pub estimated_quats: Arc<RwLock<TimeQuat>>,
+    /// Only features in this part of the frame are used for the pose.
+    pub feature_roi: RwLock<FeatureRoi>,
     pub lpf: AtomicU32,
     pub every_nth_frame: AtomicU32,
     pub pose_method: AtomicU32,
@@ -122,6 +124,7 @@ impl PoseEstimator {
         }
 
         let results = self.sync_results.clone();
+        let roi = *self.feature_roi.read();
         let mut pose = EstimatePoseMethod::from(self.pose_method.load(SeqCst));
         pose.init(params);
         frames_to_process.par_iter().for_each(move |(ts, next_ts)| {
@@ -138,7 +141,8 @@ impl PoseEstimator {
                             // Unlock the mutex for estimate_pose
                             drop(l);
 
-                            if let Some(rot) = pose.estimate_pose(&curr_of.optical_flow_to(&next_of), curr_of.size(), params, *ts, *next_ts) {
+                            let pair = roi.filter_pair(curr_of.optical_flow_to(&next_of), curr_of.size());
+                            if let Some(rot) = pose.estimate_pose(&pair, curr_of.size(), params, *ts, *next_ts) {
                                 let mut l = results.write();
                                 if let Some(x) = l.get_mut(ts) {
                                     x.rotation = Some(rot);
@@ -218,6 +222,19 @@ impl PoseEstimator {
             }
         }
     }
+    /// Drop the frames whose pose wasn't estimated yet and free the method state of the others.
+    /// Frames detected with different optical flow methods can't be matched, so this is needed
+    /// when the method changes mid-run. Returns the number of dropped frames.
+    pub fn discard_pending(&self) -> usize {
+        let mut l = self.sync_results.write();
+        let before = l.len();
+        l.retain(|_, fr| fr.rotation.is_some());
+        for fr in l.values_mut() {
+            fr.of_method.cleanup();
+        }
+        before - l.len()
+    }
+
     pub fn cleanup(&self) {
         let mut l = self.sync_results.write();
         for (_, i) in l.iter_mut(){
@@ -17,6 +17,62 @@ pub trait OpticalFlowTrait {
     fn can_cleanup(&self) -> bool;
 }
 
+/// Optical flow method selectable at runtime, maps to the `of_method` index of the sync params.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum OfMethod {
+    Akaze,
+    PyrLK,
+    #[default]
+    Dis,
+}
+impl OfMethod {
+    pub fn index(self) -> u32 {
+        match self {
+            Self::Akaze => 0,
+            Self::PyrLK => 1,
+            Self::Dis   => 2,
+        }
+    }
+    pub fn from_index(i: u32) -> Option<Self> {
+        match i {
+            0 => Some(Self::Akaze),
+            1 => Some(Self::PyrLK),
+            2 => Some(Self::Dis),
+            _ => None
+        }
+    }
+}
+
+/// Part of the frame whose features are used for the pose, in fractions of the frame size.
+/// Restricting it to the border keeps a moving subject in the center from being taken for camera motion.
+#[derive(Clone, Copy, Debug, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FeatureRoi {
+    #[default]
+    Full,
+    /// The band of `margin` (0..0.5) along every edge, where the background usually is.
+    Border { margin: f32 },
+    /// Operator-defined rectangle.
+    Rect { x: f32, y: f32, w: f32, h: f32 },
+}
+impl FeatureRoi {
+    pub fn contains(&self, (x, y): (f32, f32), (width, height): (u32, u32)) -> bool {
+        let (nx, ny) = (x / width.max(1) as f32, y / height.max(1) as f32);
+        match *self {
+            Self::Full => true,
+            Self::Border { margin } => nx < margin || ny < margin || nx > 1.0 - margin || ny > 1.0 - margin,
+            Self::Rect { x, y, w, h } => nx >= x && ny >= y && nx <= x + w && ny <= y + h,
+        }
+    }
+
+    /// Keep the matches whose feature in the first frame lies in the ROI.
+    pub fn filter_pair(&self, pair: OpticalFlowPair, size: (u32, u32)) -> OpticalFlowPair {
+        if *self == Self::Full { return pair; }
+        let (from, to) = pair?;
+        Some(from.into_iter().zip(to).filter(|(p, _)| self.contains(*p, size)).unzip())
+    }
+}
+
 #[enum_delegate::implement(OpticalFlowTrait)]
 #[derive(Clone)]
 pub enum OpticalFlowMethod {
@@ -0,0 +1,338 @@
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+use clap::{CommandFactory, Parser};
+use clap::error::ErrorKind;
+use log::LevelFilter;
+
+use gyroflow_core::live::{LatencyProfile, NoImu};
+use gyroflow_core::stabilization::Interpolation;
+
+use crate::render_live::{CompareMode, PresentRate, ProcessingOrder, TransitionPolicy, FormatChangePolicy};
+use crate::restream::StreamTarget;
+use crate::supersample;
+
+/// Real-time gyro stabilization of a video stream with IMU data received over TCP.
+#[derive(Parser, Debug, Clone)]
+#[command(name = "live", version, about)]
+pub struct Args {
+    /// Address the IMU line server listens on (the generator connects here)
+    #[arg(long, default_value = "127.0.0.1:7007")]
+    pub imu_addr: String,
+
+    /// Video stream or file to stabilize (anything ffmpeg can open)
+    #[arg(long, default_value = "C:\\git\\videos\\gyrovid.mp4")]
+    pub video_url: String,
+
+    /// Input video size
+    #[arg(long, default_value_t = 2704)]
+    pub width: usize,
+    #[arg(long, default_value_t = 2028)]
+    pub height: usize,
+
+    /// Input frame rate, until the IMU header says otherwise
+    #[arg(long, default_value_t = 30.0)]
+    pub fps: f64,
+
+    /// Downscale decoded frames so neither side exceeds this many pixels (keeps the aspect ratio)
+    #[arg(long, value_name = "PIXELS", value_parser = clap::value_parser!(u32).range(2..))]
+    pub max_dimension: Option<u32>,
+
+    /// Restart the stream reader up to this many times when it dies (0 = stop rendering instead)
+    #[arg(long, default_value_t = 0)]
+    pub reader_restarts: u32,
+
+    /// Render through STMaps built by a background worker instead of running the stabilization kernel
+    /// on every frame. The maps correct the lens and rolling shutter only, like the exported STMaps
+    #[arg(long)]
+    pub stmap_render: bool,
+
+    /// Apply STMaps on the GPU (wgpu), falls back to the CPU when no adapter is usable
+    #[arg(long)]
+    pub gpu_maps: bool,
+
+    /// Time the live pipeline by the stream's frame timestamps instead of the wall clock, e.g. to
+    /// process a recorded file faster than real time
+    #[arg(long)]
+    pub stream_clock: bool,
+
+    /// Write the maps of --stmap-render as tiled EXRs with tiles of this many pixels instead of
+    /// scanlines, so parts of a map can be read without decoding the rest (0 = 64)
+    #[arg(long, value_name = "PIXELS", requires = "stmap_render")]
+    pub stmap_tile_size: Option<usize>,
+
+    /// With --stmap-render, blend the map of a frame the worker dropped under load from the maps of
+    /// its neighbours instead of reusing the last one
+    #[arg(long, requires = "stmap_render")]
+    pub interpolate_missing_maps: bool,
+
+    /// A/B view of raw and stabilized frames: split (raw left, stabilized right) or toggle
+    #[arg(long, value_enum, default_value = "off")]
+    pub compare: CompareMode,
+
+    /// Frames between switches in `--compare toggle`
+    #[arg(long, default_value_t = 30, value_parser = clap::value_parser!(u64).range(1..))]
+    pub compare_every: u64,
+
+    /// Pixel interpolation: bilinear (fastest), bicubic, lanczos4, robidoux-sharp, robidoux, mitchell or catmull-rom
+    #[arg(long, default_value = "bilinear", value_parser = parse_interpolation)]
+    pub interpolation: Interpolation,
+
+    /// Render at this multiple of the output resolution and downsample, against aliasing of rotated
+    /// edges (1 = off, 1.5 to 2 is typical; the cost grows with its square)
+    #[arg(long, value_name = "FACTOR", default_value_t = 1.0)]
+    pub supersample: f32,
+
+    /// Stabilize in the input pixel format and convert after (pre), or convert to the sink format
+    /// first (post); post is cheaper for RGBA input into an RGB24 sink
+    #[arg(long, value_enum, default_value = "pre")]
+    pub processing_order: ProcessingOrder,
+
+    /// Frames of the previous resolution still in flight when the source changes resolution:
+    /// drop them, or letterbox them into the new size
+    #[arg(long, value_enum, default_value = "drop")]
+    pub resolution_transition: TransitionPolicy,
+
+    /// Frames in another pixel format than the previous ones: switch to it (NV12 gets converted to
+    /// the sink format), or keep the format of the first frame and drop the others
+    #[arg(long, value_enum, default_value = "adapt")]
+    pub format_change: FormatChangePolicy,
+
+    /// Even out brightness flicker (auto-exposure, rolling shutter) of the output, 0 (off) to 1
+    #[arg(long, value_name = "STRENGTH", default_value_t = 0.0)]
+    pub deflicker_strength: f64,
+
+    /// Frame-to-present latency target; while it's exceeded, late frames are dropped and then
+    /// interpolation falls back to bilinear, restored once latency recovers (0 = no target)
+    #[arg(long, value_name = "MS", default_value_t = 0.0)]
+    pub latency_sla_ms: f64,
+
+    /// How the IMU, STMap and render loops wait for work: power-saver (poll every 2 ms), balanced
+    /// (block until woken) or low-latency (busy-poll ~100 µs first, costs CPU, for dedicated machines)
+    #[arg(long, default_value = "balanced", value_parser = str::parse::<LatencyProfile>)]
+    pub latency_profile: LatencyProfile,
+
+    /// Bound the IMU buffers, quaternion stores and decoded frame queue of the session to this much
+    /// memory in total, dropping their oldest data first (default: unbounded)
+    #[arg(long, value_name = "MB")]
+    pub memory_budget_mb: Option<f64>,
+
+    /// Output while no IMU client sent anything: passthrough (the input as is), undistort (lens
+    /// correction only) or error (stop after --no-imu-timeout-ms)
+    #[arg(long, default_value = "undistort", value_parser = parse_no_imu)]
+    pub no_imu: NoImu,
+
+    /// How long to wait for the first IMU sample before --no-imu applies
+    #[arg(long, value_name = "MS", default_value_t = 5000)]
+    pub no_imu_timeout_ms: u64,
+
+    /// Log the render loop as stalled when it makes no progress for this long (0 = no watchdog)
+    #[arg(long, value_name = "MS", default_value_t = 2000)]
+    pub watchdog_timeout_ms: u64,
+
+    /// Frame rate of the preview / recording: `auto` for the frame rate of the stream, or a fixed rate
+    #[arg(long, value_name = "FPS", default_value = "auto", value_parser = parse_present_rate)]
+    pub present_fps: PresentRate,
+
+    /// Send at most this many frames per second to the preview / recording / restream, picked evenly
+    /// by timestamp (e.g. 30 from a 60 fps source); the skipped frames aren't stabilized
+    #[arg(long, value_name = "FPS", value_parser = clap::value_parser!(u32).range(1..))]
+    pub output_fps_cap: Option<u32>,
+
+    /// Stabilize only 1 of every N frames and repeat the last stabilized one in the preview, to save power
+    #[arg(long, value_name = "N", default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..), conflicts_with = "record")]
+    pub preview_stride: u32,
+
+    /// Show the stabilized output in ffplay (default when not recording)
+    #[arg(long, conflicts_with = "record")]
+    pub preview: bool,
+
+    /// Write the stabilized output to a video file instead of previewing it
+    #[arg(long, value_name = "PATH")]
+    pub record: Option<PathBuf>,
+
+    /// Embed the smoothing parameters and lens checksum in the recording and write the per-frame
+    /// quaternions next to it (`<record>.quats.csv`)
+    #[arg(long, requires = "record")]
+    pub record_metadata: bool,
+
+    /// Keep the last N seconds of the stabilized output in memory; pressing Enter writes them to a file
+    /// (RGB24, about 6 MB per 1080p frame)
+    #[arg(long, value_name = "SECS", default_value_t = 0.0)]
+    pub replay_buffer_secs: f64,
+
+    /// Directory the instant replays are written to
+    #[arg(long, value_name = "PATH", default_value = ".")]
+    pub replay_dir: PathBuf,
+
+    /// Typing `stmaps` and Enter writes the STMaps of the current frame to this directory as EXR
+    #[arg(long, value_name = "PATH")]
+    pub stmap_dump_dir: Option<PathBuf>,
+
+    /// Also push the stabilized output to this RTMP or RTSP server (rtmp://host/app/key, rtsp://host:port/path)
+    #[arg(long, value_name = "URL")]
+    pub stream_url: Option<String>,
+
+    /// ffmpeg video encoder of `--stream-url`
+    #[arg(long, default_value = "libx264")]
+    pub stream_codec: String,
+
+    /// Video bitrate of `--stream-url`
+    #[arg(long, value_name = "KBPS", default_value_t = 4000, value_parser = clap::value_parser!(u32).range(1..))]
+    pub stream_bitrate_kbps: u32,
+
+    /// Also write the stabilized output to a shared memory ring of this name, for a compositor on the same machine
+    #[arg(long, value_name = "NAME")]
+    pub shm_name: Option<String>,
+
+    /// Frames kept in the `--shm-name` ring
+    #[arg(long, value_name = "N", default_value_t = crate::shm_sink::DEFAULT_SLOTS)]
+    pub shm_slots: usize,
+
+    /// Log level (off, error, warn, info, debug, trace). Defaults to RUST_LOG
+    #[arg(long)]
+    pub log_level: Option<LevelFilter>,
+
+    /// Lens profile name or id from the lens database, used when the IMU header doesn't specify one
+    #[arg(long, conflicts_with = "lens_file")]
+    pub lens: Option<String>,
+
+    /// Lens profile JSON file, used when the IMU header doesn't specify one
+    #[arg(long, value_name = "PATH")]
+    pub lens_file: Option<PathBuf>,
+
+    /// Reload `--lens-file` into the running stream whenever the file changes, for calibration iterations
+    #[arg(long, requires = "lens_file")]
+    pub watch_lens: bool,
+
+    /// Load quaternions from a Gyroflow CSV export instead of integrating the IMU stream
+    #[arg(long, value_name = "PATH")]
+    pub load_quats: Option<PathBuf>,
+
+    /// Accept several IMU clients at once and merge their samples in timestamp order, holding them
+    /// back this long (sensor time); samples that arrive later than that are dropped
+    #[arg(long, value_name = "MS")]
+    pub imu_reorder_ms: Option<u64>,
+
+    /// Check at the start of the stream whether the logger swapped the gyro and accel columns
+    /// (1 g on the gyro at rest) and swap them back if so
+    #[arg(long)]
+    pub auto_detect_column_swap: bool,
+
+    /// Stabilize a blank frame once the IMU header arrived, so the kernel setup doesn't delay the first live frame.
+    /// The frame is `--width` x `--height` downscaled by `--max-dimension`, the size the reader delivers
+    #[arg(long)]
+    pub prewarm: bool,
+
+    /// Run the pipeline on synthetic IMU data and frames for a few seconds, print a report and exit (0 = pass)
+    #[arg(long)]
+    pub selftest: bool,
+
+    /// Log every live parameter change to this JSONL file, for auditing or replaying the session
+    #[arg(long, value_name = "PATH")]
+    pub param_log: Option<PathBuf>,
+}
+
+fn parse_present_rate(s: &str) -> Result<PresentRate, String> {
+    if s.eq_ignore_ascii_case("auto") { return Ok(PresentRate::Auto); }
+    s.parse().map(PresentRate::Fixed).map_err(|_| format!("'{s}' is neither 'auto' nor a frame rate"))
+}
+
+fn parse_interpolation(s: &str) -> Result<Interpolation, String> {
+    Ok(match s.to_ascii_lowercase().as_str() {
+        "bilinear"       => Interpolation::Bilinear,
+        "bicubic"        => Interpolation::Bicubic,
+        "lanczos4"       => Interpolation::Lanczos4,
+        "robidoux-sharp" => Interpolation::RobidouxSharp,
+        "robidoux"       => Interpolation::Robidoux,
+        "mitchell"       => Interpolation::Mitchell,
+        "catmull-rom"    => Interpolation::CatmullRom,
+        _ => return Err(format!("unknown interpolation '{s}'")),
+    })
+}
+
+fn parse_no_imu(s: &str) -> Result<NoImu, String> {
+    Ok(match s.to_ascii_lowercase().as_str() {
+        "passthrough" => NoImu::Passthrough,
+        "undistort"   => NoImu::UndistortOnly,
+        "error"       => NoImu::Error,
+        _ => return Err(format!("unknown no-IMU behavior '{s}'")),
+    })
+}
+
+impl Args {
+    /// Parse the command line, print usage and exit on invalid arguments.
+    pub fn parse_and_validate() -> Self {
+        let args = Self::parse();
+        if let Err(msg) = args.validate() {
+            Self::command().error(ErrorKind::ValueValidation, msg).exit();
+        }
+        args
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.imu_addr.parse::<SocketAddr>().is_err() {
+            return Err(format!("--imu-addr: '{}' is not a valid ip:port", self.imu_addr));
+        }
+        if self.video_url.trim().is_empty() {
+            return Err("--video-url must not be empty".into());
+        }
+        if self.width == 0 || self.height == 0 {
+            return Err(format!("invalid input size {}x{}", self.width, self.height));
+        }
+        if !(self.fps > 0.0 && self.fps <= 1000.0) {
+            return Err(format!("--fps: {} is out of range (0, 1000]", self.fps));
+        }
+        if let PresentRate::Fixed(fps) = self.present_fps {
+            if !(fps > 0.0 && fps <= 1000.0) {
+                return Err(format!("--present-fps: {fps} is out of range (0, 1000]"));
+            }
+        }
+        if !(0.0..=1.0).contains(&self.deflicker_strength) {
+            return Err(format!("--deflicker-strength: {} is out of range [0, 1]", self.deflicker_strength));
+        }
+        if !(supersample::MIN_FACTOR..=supersample::MAX_FACTOR).contains(&self.supersample) {
+            return Err(format!("--supersample: {} is out of range [{}, {}]", self.supersample, supersample::MIN_FACTOR, supersample::MAX_FACTOR));
+        }
+        if !(self.replay_buffer_secs >= 0.0 && self.replay_buffer_secs.is_finite()) {
+            return Err(format!("--replay-buffer-secs: {} must be 0 or more", self.replay_buffer_secs));
+        }
+        if let Some(target) = self.stream_target() {
+            target.container().map_err(|e| format!("--stream-url: {e}"))?;
+        }
+        if !(self.latency_sla_ms >= 0.0 && self.latency_sla_ms.is_finite()) {
+            return Err(format!("--latency-sla-ms: {} must be 0 or more", self.latency_sla_ms));
+        }
+        if let Some(mb) = self.memory_budget_mb {
+            if !(mb > 0.0 && mb.is_finite()) {
+                return Err(format!("--memory-budget-mb: {mb} must be more than 0"));
+            }
+        }
+        for (flag, path) in [("--lens-file", &self.lens_file), ("--load-quats", &self.load_quats)] {
+            if let Some(p) = path {
+                if !p.is_file() {
+                    return Err(format!("{flag}: {} does not exist", p.display()));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Where `--stream-url` pushes the stabilized output.
+    pub fn stream_target(&self) -> Option<StreamTarget> {
+        self.stream_url.as_ref().map(|url| StreamTarget { url: url.clone(), codec: self.stream_codec.clone(), bitrate_kbps: self.stream_bitrate_kbps })
+    }
+
+    /// Lens from the command line, in the same form as the `lensprofile` header value.
+    pub fn lens_profile(&self) -> Result<Option<serde_json::Value>, String> {
+        if let Some(name) = &self.lens {
+            return Ok(Some(serde_json::Value::String(name.clone())));
+        }
+        if let Some(path) = &self.lens_file {
+            let data = std::fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.display()))?;
+            let json = serde_json::from_str(&data).map_err(|e| format!("{}: {e}", path.display()))?;
+            return Ok(Some(json));
+        }
+        Ok(None)
+    }
+}
@@ -0,0 +1,134 @@
+// live/deflicker.rs
+// Per-frame brightness normalization of the stabilized output, against auto-exposure and
+// rolling shutter flicker. Not a full deflicker: one global gain per frame.
+
+/// Frames the running target brightness averages over.
+const TARGET_FRAMES: f64 = 15.0;
+/// Relative luma deviation from the target that may be a real brightness change.
+const SCENE_CHANGE_RATIO: f64 = 0.2;
+/// Consecutive frames past `SCENE_CHANGE_RATIO` on the same side before the target follows the scene.
+const SCENE_CHANGE_FRAMES: usize = 8;
+/// Bound of the per-frame gain (and of its inverse).
+const MAX_GAIN: f64 = 1.5;
+/// Only every n-th pixel is measured.
+const SAMPLE_STEP: usize = 4;
+
+/// Mean Rec.601 luma (0..255) of packed RGB (`bpp` 3) or RGBA (`bpp` 4) pixels.
+pub fn mean_luma(buf: &[u8], bpp: usize) -> f64 {
+    let (mut sum, mut n) = (0.0, 0usize);
+    for px in buf.chunks_exact(bpp).step_by(SAMPLE_STEP) {
+        sum += 0.299 * px[0] as f64 + 0.587 * px[1] as f64 + 0.114 * px[2] as f64;
+        n += 1;
+    }
+    if n == 0 { 0.0 } else { sum / n as f64 }
+}
+
+/// Scales every frame towards a running target brightness.
+///
+/// `strength` 0 leaves frames untouched, 1 corrects the full deviation from the target.
+/// A deviation that lasts `SCENE_CHANGE_FRAMES` frames on the same side is taken as a real
+/// change of scene brightness, the target jumps to it instead of fighting it.
+pub struct Deflicker {
+    strength: f64,
+    target: Option<f64>,
+    /// Consecutive frames past `SCENE_CHANGE_RATIO`, signed by the side of the deviation.
+    shift: isize,
+    lut: [u8; 256],
+    lut_gain: f64,
+}
+
+impl Deflicker {
+    pub fn new(strength: f64) -> Self {
+        Self { strength: strength.clamp(0.0, 1.0), target: None, shift: 0, lut: std::array::from_fn(|i| i as u8), lut_gain: 1.0 }
+    }
+
+    pub fn is_enabled(&self) -> bool { self.strength > 0.0 }
+
+    /// Normalize one packed RGB/RGBA frame in place (alpha is kept), returns the applied gain.
+    pub fn apply(&mut self, buf: &mut [u8], bpp: usize) -> f64 {
+        if !self.is_enabled() || bpp < 3 { return 1.0; }
+        let luma = mean_luma(buf, bpp);
+        if luma < 1.0 { return 1.0; } // black frame, nothing to match
+
+        let target = *self.target.get_or_insert(luma);
+        let deviation = (luma - target) / target;
+        if deviation.abs() > SCENE_CHANGE_RATIO {
+            let side = deviation.signum() as isize;
+            self.shift = if self.shift.signum() == side { self.shift + side } else { side };
+            if self.shift.unsigned_abs() >= SCENE_CHANGE_FRAMES {
+                log::debug!("deflicker: brightness changed {target:.1} -> {luma:.1}, following the scene");
+                self.target = Some(luma);
+                self.shift = 0;
+                return 1.0;
+            }
+        } else {
+            self.shift = 0;
+        }
+        self.target = Some(target + (luma - target) / TARGET_FRAMES);
+
+        let gain = (1.0 + self.strength * (target / luma - 1.0)).clamp(1.0 / MAX_GAIN, MAX_GAIN);
+        if (gain - 1.0).abs() < 1e-3 { return 1.0; }
+        if (gain - self.lut_gain).abs() >= 1e-3 {
+            self.lut = std::array::from_fn(|i| (i as f64 * gain).round().min(255.0) as u8);
+            self.lut_gain = gain;
+        }
+        for px in buf.chunks_exact_mut(bpp) {
+            for c in &mut px[..3] {
+                *c = self.lut[*c as usize];
+            }
+        }
+        gain
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(level: u8) -> Vec<u8> {
+        let mut buf = vec![level; 16 * 16 * 4];
+        buf.chunks_exact_mut(4).for_each(|px| px[3] = 255);
+        buf
+    }
+
+    fn variance(v: &[f64]) -> f64 {
+        let mean = v.iter().sum::<f64>() / v.len() as f64;
+        v.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / v.len() as f64
+    }
+
+    #[test]
+    fn alternating_brightness_is_evened_out() {
+        let mut deflicker = Deflicker::new(1.0);
+        let (mut before, mut after) = (Vec::new(), Vec::new());
+        for k in 0..60 {
+            let mut buf = frame(if k % 2 == 0 { 100 } else { 130 });
+            before.push(mean_luma(&buf, 4));
+            deflicker.apply(&mut buf, 4);
+            after.push(mean_luma(&buf, 4));
+            assert!(buf.chunks_exact(4).all(|px| px[3] == 255), "alpha changed");
+        }
+        // Past the warm-up of the running target
+        let (before, after) = (&before[20..], &after[20..]);
+        assert!(variance(after) < variance(before) * 0.05, "variance {} -> {}", variance(before), variance(after));
+
+        let mut off = Deflicker::new(0.0);
+        let mut buf = frame(100);
+        assert_eq!(off.apply(&mut buf, 4), 1.0);
+        assert_eq!(buf, frame(100));
+    }
+
+    #[test]
+    fn sustained_brightness_change_is_followed() {
+        let mut deflicker = Deflicker::new(1.0);
+        for _ in 0..20 {
+            deflicker.apply(&mut frame(80), 4);
+        }
+        // Lights on: corrected for a few frames, then the new level is kept
+        let gains: Vec<f64> = (0..20).map(|_| deflicker.apply(&mut frame(160), 4)).collect();
+        assert!(gains[0] < 1.0);
+        assert!(gains[SCENE_CHANGE_FRAMES..].iter().all(|&g| g == 1.0), "{gains:?}");
+        let mut buf = frame(160);
+        deflicker.apply(&mut buf, 4);
+        assert_eq!(buf, frame(160));
+    }
+}
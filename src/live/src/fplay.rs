@@ -1,14 +1,15 @@
 use anyhow::{anyhow, bail, Result};
 use std::io::Write;
 use std::net::{TcpStream, Shutdown};
-use std::process::{Command, Stdio};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
 use std::sync::{Mutex, OnceLock};
 use std::sync::atomic::{AtomicBool, Ordering};
 
 use crate::live_pix_fmt::PixelFormat;
 
 impl PixelFormat {
-    fn ffmpeg_name(self) -> &'static str {
+    pub fn ffmpeg_name(self) -> &'static str {
         match self {
             PixelFormat::Rgb24 => "rgb24",
             PixelFormat::Rgba  => "rgba",
@@ -16,7 +17,7 @@ impl PixelFormat {
         }
     }
 
-    fn bytes_per_pixel(self) -> usize {
+    pub fn bytes_per_pixel(self) -> usize {
         match self {
             PixelFormat::Rgb24 => 3,
             PixelFormat::Rgba  => 4,
@@ -50,8 +51,75 @@ pub fn set_require_min_frames(enabled: bool) {
     REQUIRE_MIN_FRAMES.store(enabled, Ordering::Relaxed);
 }
 
+static RECORD_PATH: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+/// Encode to this file with ffmpeg instead of opening an ffplay window. Must be set before `init_ffplay`.
+pub fn set_record_path(path: Option<PathBuf>) {
+    *RECORD_PATH.lock().unwrap() = path;
+}
+
 const PORT: u16 = 5000;
 
+#[cfg(test)]
+static TEST_SOCKET: Mutex<Option<TcpStream>> = Mutex::new(None);
+
+/// The next `init_ffplay` sends to `socket` instead of spawning a player, for tests that drive the render loop.
+#[cfg(test)]
+pub fn connect_next_to(socket: TcpStream) {
+    *TEST_SOCKET.lock().unwrap() = Some(socket);
+}
+
+/// Output encoding of `--record` and of instant replays.
+const ENCODER_ARGS: [&str; 4] = ["-c:v", "libx264", "-pix_fmt", "yuv420p"];
+
+static RECORD_METADATA: Mutex<Vec<(String, String)>> = Mutex::new(Vec::new());
+
+/// Global metadata written into recordings and instant replays from now on, e.g. `live::metadata::recording_metadata`.
+pub fn set_record_metadata(metadata: impl IntoIterator<Item = (String, String)>) {
+    *RECORD_METADATA.lock().unwrap() = metadata.into_iter().collect();
+}
+
+/// `ENCODER_ARGS` followed by the `-metadata` of `set_record_metadata`.
+fn encoder_args() -> Vec<String> {
+    let mut args: Vec<String> = ENCODER_ARGS.iter().map(|s| s.to_string()).collect();
+    let metadata = RECORD_METADATA.lock().unwrap();
+    if !metadata.is_empty() {
+        // MP4/MOV only keep their standard tags without this
+        args.extend(["-movflags".into(), "use_metadata_tags".into()]);
+    }
+    for (k, v) in metadata.iter() {
+        args.extend(["-metadata".into(), format!("{k}={v}")]);
+    }
+    args
+}
+
+/// Whether `init_ffplay` records to a file instead of previewing.
+pub fn is_recording() -> bool { RECORD_PATH.lock().unwrap().is_some() }
+
+/// ffmpeg encoding raw `props` frames written to its stdin into `path`.
+pub fn spawn_encoder(props: FProps, path: &Path) -> Result<Child> {
+    if props.pixel_format.bytes_per_pixel() == 0 {
+        bail!("spawn_encoder: pixel format {:?} is not supported here", props.pixel_format);
+    }
+    let child = Command::new("ffmpeg")
+        .args(["-y", "-loglevel", "error"])
+        .args(["-f", "rawvideo", "-pixel_format", props.pixel_format.ffmpeg_name()])
+        .args(["-video_size", &format!("{}x{}", props.width, props.height), "-framerate", &props.fps.to_string()])
+        .args(["-i", "-"])
+        .args(encoder_args())
+        .arg(path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::inherit())
+        .spawn()?;
+    Ok(child)
+}
+
+/// Properties of the running sink, `None` before `init_ffplay`.
+pub fn props() -> Option<FProps> {
+    slot().lock().unwrap().as_ref().map(|p| p.props)
+}
+
 static PLAYER: OnceLock<Mutex<Option<VideoPlayer>>> = OnceLock::new();
 fn slot() -> &'static Mutex<Option<VideoPlayer>> {
     PLAYER.get_or_init(|| Mutex::new(None))
@@ -81,18 +149,40 @@ pub fn init_ffplay(width: u32, height: u32, fps: f64, pixel_format: PixelFormat)
     }
 
     let props = FProps { width, height, fps, pixel_format };
+    #[cfg(test)]
+    if let Some(socket) = TEST_SOCKET.lock().unwrap().take() {
+        *guard = Some(VideoPlayer { props, socket, started: true, min_buffered_frames: 1, buffer: Vec::new() });
+        return Ok(());
+    }
     let ffmpeg_pix_fmt = pixel_format.ffmpeg_name();
 
-    // Spawn ffplay in listen mode
-    let _child = Command::new("ffplay")
-        .args([
-            "-loglevel", "error", "-autoexit",
-            "-f", "rawvideo",
-            "-pixel_format", ffmpeg_pix_fmt,
-            "-video_size", &format!("{}x{}", width, height),
-            "-framerate", &fps.to_string(),
-            &format!("tcp://127.0.0.1:{}?listen=1", PORT),
-        ])
+    let input_args = [
+        "-f".to_string(), "rawvideo".to_string(),
+        "-pixel_format".to_string(), ffmpeg_pix_fmt.to_string(),
+        "-video_size".to_string(), format!("{}x{}", width, height),
+        "-framerate".to_string(), fps.to_string(),
+    ];
+    let input_url = format!("tcp://127.0.0.1:{}?listen=1", PORT);
+
+    // Spawn ffplay (or ffmpeg when recording) in listen mode
+    let record_path = RECORD_PATH.lock().unwrap().clone();
+    let mut cmd = if let Some(path) = &record_path {
+        println!("Recording stabilized output to {}", path.display());
+        let mut cmd = Command::new("ffmpeg");
+        cmd.args(["-y", "-loglevel", "error"])
+            .args(&input_args)
+            .args(["-i", &input_url])
+            .args(encoder_args())
+            .arg(path);
+        cmd
+    } else {
+        let mut cmd = Command::new("ffplay");
+        cmd.args(["-loglevel", "error", "-autoexit"])
+            .args(&input_args)
+            .arg(&input_url);
+        cmd
+    };
+    let _child = cmd
         .stdin(Stdio::null())
         .stdout(Stdio::null())
         .stderr(Stdio::inherit())
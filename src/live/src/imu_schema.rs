@@ -0,0 +1,174 @@
+// live/imu_schema.rs
+use std::sync::RwLock;
+
+/// Column layout of the IMU records, taken from the header's `t,...` line.
+///
+/// Fields are looked up by name, so loggers may order columns however they like.
+/// Columns the live path doesn't use (temperature, magnetometer, ...) are skipped.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ImuSchema {
+    pub t: usize,
+    pub gyro: [usize; 3],
+    pub accel: Option<[usize; 3]>,
+    pub columns: usize,
+}
+
+/// Value of the time column, as sent (no `tscale` applied).
+///
+/// Integers are kept exact: nanosecond timestamps since the epoch don't fit the 53 bits of an `f64`.
+/// Loggers that print the time as a float (`1.0e9`, `1000.0`) give a `Float`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ImuTime {
+    Int(i128),
+    Float(f64),
+}
+
+impl ImuTime {
+    pub fn parse(token: &str) -> Option<Self> {
+        let token = token.trim();
+        if let Ok(v) = token.parse::<i128>() {
+            return Some(Self::Int(v));
+        }
+        parse_number(token).map(Self::Float)
+    }
+
+    /// Time in microseconds, `scale` is the number of microseconds per unit of the column.
+    /// Integers stay exact when `scale` or its inverse is a whole number (the usual s/ms/us/ns units).
+    pub fn to_us(self, scale: f64) -> i64 {
+        let whole = |x: f64| (x >= 1.0 && (x - x.round()).abs() < 1e-9 * x).then(|| x.round() as i128);
+        let us = match self {
+            Self::Int(v) => {
+                if let Some(m) = whole(scale) {
+                    v.saturating_mul(m)
+                } else if let Some(d) = whole(1.0 / scale) {
+                    // Round half up, also for negative times
+                    v.div_euclid(d) + (2 * v.rem_euclid(d) >= d) as i128
+                } else {
+                    return Self::Float(v as f64).to_us(scale);
+                }
+            }
+            Self::Float(v) => return (v * scale).clamp(i64::MIN as f64, i64::MAX as f64).round() as i64,
+        };
+        us.clamp(i64::MIN as i128, i64::MAX as i128) as i64
+    }
+}
+
+/// Finite number with surrounding whitespace, `None` for anything else (`nan`, `inf`, garbage).
+pub fn parse_number(token: &str) -> Option<f64> {
+    token.trim().parse::<f64>().ok().filter(|v| v.is_finite())
+}
+
+/// One record mapped by name, values as sent (no scaling applied).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ImuRecord {
+    pub t: ImuTime,
+    pub gyro: [f64; 3],
+    pub accel: Option<[f64; 3]>,
+}
+
+impl Default for ImuSchema {
+    /// `t,gx,gy,gz,ax,ay,az`
+    fn default() -> Self {
+        Self { t: 0, gyro: [1, 2, 3], accel: Some([4, 5, 6]), columns: 7 }
+    }
+}
+
+impl ImuSchema {
+    /// Parse a column header line like `t,gx,gy,gz,ax,ay,az`.
+    /// Returns `None` when the time column or any gyro axis is missing.
+    pub fn from_header_line(line: &str) -> Option<Self> {
+        let names: Vec<String> = line.trim().split(',').map(|c| c.trim().to_ascii_lowercase()).collect();
+        let col = |aliases: &[&str]| names.iter().position(|n| aliases.contains(&n.as_str()));
+
+        let t = col(&["t", "ts", "time", "timestamp"])?;
+        let gyro = [col(&["gx", "gyro_x"])?, col(&["gy", "gyro_y"])?, col(&["gz", "gyro_z"])?];
+        let accel = match (col(&["ax", "accel_x"]), col(&["ay", "accel_y"]), col(&["az", "accel_z"])) {
+            (Some(x), Some(y), Some(z)) => Some([x, y, z]),
+            _ => None,
+        };
+        Some(Self { t, gyro, accel, columns: names.len() })
+    }
+
+    pub fn parse(&self, line: &str) -> Option<ImuRecord> {
+        let fields: Vec<&str> = line.split(',').collect();
+        let num = |i: usize| parse_number(fields.get(i)?);
+
+        let t = ImuTime::parse(fields.get(self.t)?)?;
+        let gyro = [num(self.gyro[0])?, num(self.gyro[1])?, num(self.gyro[2])?];
+        let accel = match self.accel {
+            Some([x, y, z]) => Some([num(x)?, num(y)?, num(z)?]),
+            None => None,
+        };
+        Some(ImuRecord { t, gyro, accel })
+    }
+}
+
+static SCHEMA: RwLock<Option<ImuSchema>> = RwLock::new(None);
+
+/// Schema used by `parse_imu_line`, set when a client sends its header.
+pub fn set_imu_schema(schema: ImuSchema) {
+    log::info!("IMU columns: {schema:?}");
+    *SCHEMA.write().unwrap() = Some(schema);
+}
+
+pub fn imu_schema() -> ImuSchema {
+    SCHEMA.read().unwrap().clone().unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reordered_columns() {
+        let schema = ImuSchema::from_header_line("t,ax,ay,az,gx,gy,gz").unwrap();
+        let rec = schema.parse("1000,0.1,0.2,9.8,1.5,2.5,3.5").unwrap();
+        assert_eq!(rec.t, ImuTime::Int(1000));
+        assert_eq!(rec.gyro, [1.5, 2.5, 3.5]);
+        assert_eq!(rec.accel, Some([0.1, 0.2, 9.8]));
+
+        // Default layout still parses the same record correctly
+        assert_eq!(ImuSchema::default(), ImuSchema::from_header_line("t,gx,gy,gz,ax,ay,az").unwrap());
+    }
+
+    #[test]
+    fn extra_magnetometer_columns_are_ignored() {
+        let schema = ImuSchema::from_header_line("t,gx,gy,gz,ax,ay,az,mx,my,mz").unwrap();
+        let rec = schema.parse("42,0.01,0.02,0.03,0.0,0.0,1.0,25.0,-3.0,40.0").unwrap();
+        assert_eq!(rec.gyro, [0.01, 0.02, 0.03]);
+        assert_eq!(rec.accel, Some([0.0, 0.0, 1.0]));
+
+        // Gyro-only logger with a temperature column
+        let schema = ImuSchema::from_header_line("t,temp,gx,gy,gz").unwrap();
+        let rec = schema.parse("7,36.5,1,2,3").unwrap();
+        assert_eq!((rec.gyro, rec.accel), ([1.0, 2.0, 3.0], None));
+
+        assert!(ImuSchema::from_header_line("t,ax,ay,az").is_none());
+        assert!(schema.parse("7,36.5,1,2").is_none());
+    }
+
+    #[test]
+    fn padded_exponential_and_float_timestamps() {
+        let schema = ImuSchema::default();
+        let rec = schema.parse("  12 ,\t0.5 , 1e-2,-2.5E+1, 0,0,1\r").unwrap();
+        assert_eq!(rec.t, ImuTime::Int(12));
+        assert_eq!(rec.gyro, [0.5, 0.01, -25.0]);
+
+        // Same instant in every form, column in milliseconds
+        for t in ["1500", " 1500 ", "1.5e3", "1.5E+03", "1500.0", "+1500"] {
+            let rec = schema.parse(&format!("{t},0,0,0,0,0,1")).unwrap_or_else(|| panic!("'{t}' rejected"));
+            assert_eq!(rec.t.to_us(1000.0), 1_500_000, "'{t}'");
+        }
+
+        // Nanoseconds since the epoch stay exact, as an f64 they would be off by up to 128 ns
+        let rec = schema.parse("1700000000123456789,0,0,0,0,0,1").unwrap();
+        assert_eq!(rec.t.to_us(1e-9 / 1e-6), 1_700_000_000_123_457);
+        assert_eq!(ImuTime::Int(-1500).to_us(0.001), -1);
+        assert_eq!(ImuTime::Float(1.0e9).to_us(1.0), 1_000_000_000);
+
+        for bad in ["12a", "1 2", "nan", "inf", "", "0x10"] {
+            assert!(schema.parse(&format!("{bad},0,0,0,0,0,1")).is_none(), "'{bad}' accepted");
+        }
+        assert!(schema.parse("0,0,NaN,0,0,0,1").is_none());
+    }
+}
@@ -14,11 +14,11 @@ use ffmpeg::format::{self, context::Input};
 use ffmpeg::frame;
 use ffmpeg::software::scaling::{context::Context as Scaler, flag::Flags};
 use ffmpeg::util::format::Pixel;
-use std::time::Instant;
 use ffmpeg_next::Dictionary;
 use ffmpeg::util::rational::Rational;
 use ffmpeg_next::Rescale;
 use gyroflow_core::stmap_live::StmapsLive;
+use gyroflow_core::live::{Clock, FrameTimeline, SharedClock, LOG_THROTTLE};
 use std::sync::Arc;
 use std::fmt;
 
@@ -47,9 +47,70 @@ pub struct LiveFrame {
     pub height: u32,
     pub pix_fmt: PixelFormat, // <-- use PixelFormat here
     pub data: Vec<u8>,
+    /// YUV matrix the stream signals, `None` when unspecified.
+    pub colorspace: Option<YuvMatrix>,
+    /// Frame rate of the stream as detected by the reader, `None` when it doesn't say.
+    pub source_fps: Option<f64>,
+    /// Live clock time (µs) the reader took the frame off the decoder, for the ingest-to-sink latency.
+    pub ingest_us: i64,
+}
+
+/// YUV -> RGB matrix of limited range (16..235) 8-bit video.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum YuvMatrix {
+    Bt601,
+    Bt709,
+    Bt2020,
+}
+
+impl YuvMatrix {
+    /// Matrix signalled by the decoder, `None` for unspecified or unsupported ones.
+    pub fn from_space(space: ffmpeg::util::color::Space) -> Option<Self> {
+        use ffmpeg::util::color::Space;
+        match space {
+            Space::BT709 => Some(Self::Bt709),
+            Space::BT470BG | Space::SMPTE170M => Some(Self::Bt601),
+            Space::BT2020NCL | Space::BT2020CL => Some(Self::Bt2020),
+            _ => None,
+        }
+    }
+
+    /// Usual matrix of unsignalled video: BT.709 from 720p up, BT.601 for SD.
+    pub fn default_for(width: u32, height: u32) -> Self {
+        if width >= 1280 || height >= 720 { Self::Bt709 } else { Self::Bt601 }
+    }
+
+    /// Luma weights (Kr, Kb).
+    fn kr_kb(self) -> (f32, f32) {
+        match self {
+            Self::Bt601 => (0.299, 0.114),
+            Self::Bt709 => (0.2126, 0.0722),
+            Self::Bt2020 => (0.2627, 0.0593),
+        }
+    }
+
+    /// RGB of one limited range YUV sample.
+    pub fn to_rgb(self, y: u8, u: u8, v: u8) -> [u8; 3] {
+        let (kr, kb) = self.kr_kb();
+        let kg = 1.0 - kr - kb;
+        let c = (y as f32 - 16.0) * 255.0 / 219.0;
+        let d = (u as f32 - 128.0) * 255.0 / 224.0;
+        let e = (v as f32 - 128.0) * 255.0 / 224.0;
+        let (rv, bu) = (2.0 * (1.0 - kr), 2.0 * (1.0 - kb));
+        [
+            (c + rv * e).round().clamp(0.0, 255.0) as u8,
+            (c - bu * kb / kg * d - rv * kr / kg * e).round().clamp(0.0, 255.0) as u8,
+            (c + bu * d).round().clamp(0.0, 255.0) as u8,
+        ]
+    }
 }
 
 impl LiveFrame {
+    /// Matrix to convert this frame's YUV with: the signalled one, or the usual one for its size.
+    pub fn yuv_matrix(&self) -> YuvMatrix {
+        self.colorspace.unwrap_or_else(|| YuvMatrix::default_for(self.width, self.height))
+    }
+
     pub fn get_size(&self) -> (u32, u32) {
         (self.width, self.height)
     }
@@ -82,11 +143,65 @@ impl LiveFrame {
     }
 }
 
+/// Where a reader's frame indices and timestamps start, to keep one global timeline across
+/// stream segments or reader restarts.
+///
+/// Frame indices come from the shared `FrameTimeline`, which the STMap worker, the map cache and
+/// `trim_before` all treat as strictly increasing: `start_frame_index` can skip ahead but never go back
+/// (a lower base is ignored). With `timestamp_origin_us`, the first frame of this reader gets that
+/// timestamp and later ones keep their spacing from the stream, so timestamps stay monotonic even
+/// when the restarted stream counts from zero again.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ReaderOrigin {
+    /// First frame index, `None` continues the timeline's numbering.
+    pub start_frame_index: Option<usize>,
+    /// Timestamp (µs) of the first frame, `None` uses the stream's own timestamps.
+    pub timestamp_origin_us: Option<i64>,
+}
+
+impl ReaderOrigin {
+    /// Continue right after the last frame of `timeline`, one `frame_period_us` later.
+    pub fn continue_timeline(timeline: &FrameTimeline, frame_period_us: i64) -> Self {
+        Self {
+            start_frame_index: Some(timeline.next_index()),
+            timestamp_origin_us: timeline.last_ts().map(|ts| ts + frame_period_us),
+        }
+    }
+}
+
+/// Maps stream timestamps to timeline timestamps and registers the frames, see `ReaderOrigin`.
+struct FrameStamper {
+    origin_us: Option<i64>,
+    first_stream_ts: Option<i64>,
+}
+
+impl FrameStamper {
+    fn new(origin: ReaderOrigin, timeline: &FrameTimeline) -> Self {
+        if let Some(idx) = origin.start_frame_index {
+            timeline.continue_from(idx);
+        }
+        Self { origin_us: origin.timestamp_origin_us, first_stream_ts: None }
+    }
+
+    /// Register a frame with stream timestamp `stream_ts_us`, returns its index and timeline timestamp.
+    fn stamp(&mut self, timeline: &FrameTimeline, stream_ts_us: i64) -> (usize, i64) {
+        let ts_us = match self.origin_us {
+            Some(origin) => origin + stream_ts_us - *self.first_stream_ts.get_or_insert(stream_ts_us),
+            None => stream_ts_us,
+        };
+        (timeline.register(ts_us), ts_us)
+    }
+}
+
 pub fn spawn_stream_reader(
     url: &str,
     out_tx: Sender<(usize, LiveFrame)>,
     target_pix_fmt: LivePixFmt,   // which format we want out: Rgb24 / Nv12 / Rgba32
     max_queue_warn: usize,        // for basic health logs
+    max_dimension: Option<u32>,   // downscale so neither side exceeds this, keeping the aspect ratio
+    timeline: Arc<FrameTimeline>, // assigns frame indices, shared with the render loop
+    origin: ReaderOrigin,         // first frame index / timestamp, for continuing a timeline
+    clock: SharedClock,           // the live clock, stamps the frames at ingest and follows their timestamps
     //st_live: Arc<StmapsLive>
 ) -> Result<std::thread::JoinHandle<()>> {
     ffmpeg::init().context("ffmpeg init failed")?;
@@ -95,7 +210,7 @@ pub fn spawn_stream_reader(
     let handle = std::thread::Builder::new()
         .name("stream_reader".into())
         .spawn(move || {
-            if let Err(e) = run_reader(&url_owned, &out_tx, target_pix_fmt, max_queue_warn /*, st_live.clone()*/) {
+            if let Err(e) = run_reader(&url_owned, &out_tx, target_pix_fmt, max_queue_warn, max_dimension, &timeline, origin, &*clock /*, st_live.clone()*/) {
                 eprintln!("[stream_reader] fatal error: {e:?}");
             }
         })?;
@@ -103,11 +218,171 @@ pub fn spawn_stream_reader(
     Ok(handle)
 }
 
+/// Frame rate of a stream from its average frame rate, or the base rate when the average is unknown.
+pub fn detected_fps(avg_frame_rate: Rational, base_frame_rate: Rational) -> Option<f64> {
+    [avg_frame_rate, base_frame_rate].into_iter()
+        .find(|r| r.numerator() > 0 && r.denominator() > 0)
+        .map(|r| r.numerator() as f64 / r.denominator() as f64)
+}
+
+/// Output size for a `w`x`h` source so that neither side exceeds `max_dimension`, keeping the aspect ratio.
+/// Sides are rounded to even numbers (NV12 needs that).
+pub fn limit_dimensions(w: u32, h: u32, max_dimension: Option<u32>) -> (u32, u32) {
+    match max_dimension {
+        Some(max) if max > 0 && w.max(h) > max => {
+            let scale = max as f64 / w.max(h) as f64;
+            let even = |v: u32| ((v as f64 * scale / 2.0).round() as u32 * 2).max(2);
+            (even(w), even(h))
+        }
+        _ => (w, h),
+    }
+}
+
+/// Y and interleaved UV plane sizes of a tightly packed `w`x`h` NV12 frame. `None` for odd
+/// dimensions: the chroma is subsampled by 2x2 blocks, an odd size doesn't divide into them.
+pub fn nv12_plane_sizes(w: usize, h: usize) -> Option<(usize, usize)> {
+    (w > 0 && h > 0 && w % 2 == 0 && h % 2 == 0).then(|| (w * h, w * h / 2))
+}
+
+/// Rows of a tightly packed `w`x`h` NV12 frame: each Y row with the UV row of its 2x2 blocks, `w` bytes
+/// of interleaved U and V, so pixel `x` has its chroma at `x & !1` and `x | 1`.
+/// `None` for odd dimensions or a buffer smaller than the frame.
+pub fn nv12_rows(src: &[u8], w: usize, h: usize) -> Option<impl Iterator<Item = (&[u8], &[u8])>> {
+    let (y_size, uv_size) = nv12_plane_sizes(w, h)?;
+    let (y_plane, uv_plane) = src.get(..y_size + uv_size)?.split_at(y_size);
+    Some(y_plane.chunks_exact(w).enumerate().map(move |(y, row)| (row, &uv_plane[y / 2 * w..][..w])))
+}
+
+/// Converts decoded frames to the target pixel format (and size), rebuilding the scaler when the input changes.
+struct FrameConverter {
+    target_fmt: Pixel,
+    max_dimension: Option<u32>,
+    scaler: Option<((u32, u32, Pixel), (u32, u32), Scaler)>,
+    skipped: u64,
+}
+
+impl FrameConverter {
+    fn new(target_fmt: Pixel, max_dimension: Option<u32>) -> Self {
+        Self { target_fmt, max_dimension, scaler: None, skipped: 0 }
+    }
+
+    fn skip(&mut self, reason: fmt::Arguments) {
+        self.skipped += 1;
+        if self.skipped == 1 || self.skipped % 100 == 0 {
+            log::warn!("stream_reader: skipping malformed frame ({reason}), {} skipped so far", self.skipped);
+        }
+    }
+
+    /// Scale / convert a decoded frame, `None` for frames that can't be converted safely
+    /// (zero size, no pixel format, rejected by the scaler, planes smaller than their dimensions).
+    fn convert(&mut self, frame: &frame::Video) -> Result<Option<(Vec<u8>, LivePixFmt, u32, u32)>> {
+        let target_fmt = self.target_fmt;
+
+        let src = (frame.width(), frame.height(), frame.format());
+        if src.0 == 0 || src.1 == 0 || src.2 == Pixel::None {
+            self.skip(format_args!("{}x{} {:?}", src.0, src.1, src.2));
+            return Ok(None);
+        }
+
+        // Lazily rebuild scaler if needed
+        if self.scaler.as_ref().map(|(s, _, _)| *s) != Some(src) {
+            let (mut ow, mut oh) = limit_dimensions(src.0, src.1, self.max_dimension);
+            if target_fmt == Pixel::NV12 {
+                // Odd sizes have no whole 2x2 chroma blocks, drop the last row / column instead
+                (ow, oh) = ((ow & !1).max(2), (oh & !1).max(2));
+            }
+            let sc = Scaler::get(src.2, src.0, src.1, target_fmt, ow, oh, Flags::BILINEAR)
+                .context("create scaler")?;
+            if (ow, oh) != (src.0, src.1) {
+                log::info!("stream_reader: source {}x{} downscaled to {ow}x{oh} (max dimension {:?})", src.0, src.1, self.max_dimension);
+            } else {
+                log::info!("stream_reader: processing at native {ow}x{oh}");
+            }
+            self.scaler = Some((src, (ow, oh), sc));
+        }
+
+        let (_, (w, h), sc) = self.scaler.as_mut().unwrap();
+        let (w, h) = (*w, *h);
+
+        // --- Convert to target pixel format ---
+        let mut out = frame::Video::empty();
+        out.set_format(target_fmt);
+        out.set_width(w);
+        out.set_height(h);
+        // A frame the scaler rejects is skipped like any other malformed one, the stream goes on
+        if let Err(e) = sc.run(frame, &mut out) {
+            self.skip(format_args!("scaler failed on {}x{} {:?}: {e}", src.0, src.1, src.2));
+            return Ok(None);
+        }
+
+        match extract_packed(&out, target_fmt, w, h) {
+            Some((bytes, pix_fmt)) => Ok(Some((bytes, pix_fmt, w, h))),
+            None => {
+                self.skip(format_args!("{w}x{h} {target_fmt:?}, stride {}", out.stride(0)));
+                Ok(None)
+            }
+        }
+    }
+}
+
+/// Whether `plane` of `out` holds `rows` rows of `row_bytes` each.
+fn plane_fits(out: &frame::Video, plane: usize, row_bytes: usize, rows: usize) -> bool {
+    if plane >= out.planes() { return false; }
+    let stride = out.stride(plane);
+    stride >= row_bytes && (rows == 0 || out.data(plane).len() >= (rows - 1) * stride + row_bytes)
+}
+
+/// Append `rows` rows of `row_bytes` from a plane with line size `stride` to `buf`.
+/// Planes without padding (`stride == row_bytes`, the common case) are copied in one go.
+fn pack_plane(buf: &mut Vec<u8>, data: &[u8], stride: usize, row_bytes: usize, rows: usize) {
+    if stride == row_bytes {
+        buf.extend_from_slice(&data[..row_bytes * rows]);
+        return;
+    }
+    for row in 0..rows {
+        let start = row * stride;
+        buf.extend_from_slice(&data[start..start + row_bytes]);
+    }
+}
+
+/// Tightly packed bytes of a converted frame, `None` when the planes are too small for `w`x`h`.
+fn extract_packed(out: &frame::Video, target_fmt: Pixel, w: u32, h: u32) -> Option<(Vec<u8>, LivePixFmt)> {
+    if w == 0 || h == 0 { return None; }
+    let (w, h) = (w as usize, h as usize);
+    let packed = |bpp: usize| -> Option<Vec<u8>> {
+        let row_bytes = w * bpp;
+        if !plane_fits(out, 0, row_bytes, h) { return None; }
+        let mut buf = Vec::with_capacity(row_bytes * h);
+        pack_plane(&mut buf, out.data(0), out.stride(0), row_bytes, h);
+        Some(buf)
+    };
+
+    match target_fmt {
+        Pixel::RGB24 => Some((packed(3)?, LivePixFmt::Rgb24)),
+        Pixel::RGBA => Some((packed(4)?, LivePixFmt::Rgba)),
+        Pixel::NV12 => {
+            nv12_plane_sizes(w, h)?;
+            if !plane_fits(out, 0, w, h) || !plane_fits(out, 1, w, h / 2) { return None; }
+            let mut buf = Vec::with_capacity(w * h * 3 / 2);
+            // Y plane, then interleaved UV at half height
+            pack_plane(&mut buf, out.data(0), out.stride(0), w, h);
+            pack_plane(&mut buf, out.data(1), out.stride(1), w, h / 2);
+            Some((buf, LivePixFmt::Nv12))
+        }
+
+        _ => panic!("Unsupported output pixel format"),
+    }
+}
+
 fn run_reader(
     url: &str,
     out_tx: &Sender<(usize, LiveFrame)>,
     target_pix_fmt: LivePixFmt,
     max_queue_warn: usize,
+    max_dimension: Option<u32>,
+    timeline: &FrameTimeline,
+    origin: ReaderOrigin,
+    clock: &dyn Clock,
 ) -> Result<()> 
 {
     println!("Starting stream reader for URL: {}", url);
@@ -141,7 +416,8 @@ fn run_reader(
         .context("open video decoder")?;
 
     let tb = v_stream.time_base();
-    let mut frame_index: usize = 0;
+    let source_fps = detected_fps(v_stream.avg_frame_rate(), v_stream.rate());
+    log::info!("stream_reader: source frame rate {source_fps:?}");
 
     // --- 3) Choose target pixel format ---
     let target_fmt = match target_pix_fmt {
@@ -150,112 +426,52 @@ fn run_reader(
         LivePixFmt::Rgba => Pixel::RGBA,
     };
 
-    let mut scaler: Option<(u32, u32, Pixel, Scaler)> = None;
+    let mut converter = FrameConverter::new(target_fmt, max_dimension);
+    let mut stamper = FrameStamper::new(origin, timeline);
 
     // --- 4) Demux/Decode loop ---
     for (stream, mut packet) in ictx.packets() {
         if stream.index() != v_stream_idx { continue; }
 
-        if decoder.send_packet(&packet).is_err() {
+        if let Err(e) = decoder.send_packet(&packet) {
+            LOG_THROTTLE.warn("stream_reader: send_packet", format_args!("[stream_reader] decoder send_packet err: {e}"));
             continue;
         }
 
         let mut frame = frame::Video::empty();
         while decoder.receive_frame(&mut frame).is_ok() {
+            let ingest_us = clock.now_us();
 
-            // Lazily rebuild scaler if needed
-            let (w, h, src_fmt) = (frame.width(), frame.height(), frame.format());
-            if scaler.as_ref().map(|(sw, sh, sf, _)| (*sw, *sh, *sf))
-                != Some((w, h, src_fmt)) 
-            {
-                let sc = Scaler::get(src_fmt, w, h, target_fmt, w, h, Flags::BILINEAR)
-                    .context("create scaler")?;
-                scaler = Some((w, h, src_fmt, sc));
-            }
-
-            let (_, _, _, sc) = scaler.as_mut().unwrap();
-
-            // --- 5) Convert to target pixel format ---
-            let mut out = frame::Video::empty();
-            out.set_format(target_fmt);
-            out.set_width(w);
-            out.set_height(h);
-            sc.run(&frame, &mut out).context("scale/run")?;
-
-            // --- 6) Extract tightly-packed bytes ---
-            let (bytes, pix_fmt) = match target_fmt {
-                Pixel::RGB24 => {
-                    let mut buf = Vec::with_capacity((w * h * 3) as usize);
-                    let ls = out.stride(0) as usize;
-                    let row_bytes = (w as usize) * 3;
-                    let data = out.data(0);
-
-                    for row in 0..h as usize {
-                        let start = row * ls;
-                        buf.extend_from_slice(&data[start..start + row_bytes]);
-                    }
-                    (buf, LivePixFmt::Rgb24)
-                }
-
-                Pixel::RGBA => {
-                    let mut buf = Vec::with_capacity((w * h * 4) as usize);
-                    let ls = out.stride(0) as usize;
-                    let row_bytes = (w as usize) * 4;
-                    let data = out.data(0);
-
-                    for row in 0..h as usize {
-                        let start = row * ls;
-                        buf.extend_from_slice(&data[start..start + row_bytes]);
-                    }
-                    (buf, LivePixFmt::Rgba)
-                }
-
-                Pixel::NV12 => {
-                    let mut buf = Vec::with_capacity((w * h * 3 / 2) as usize);
-
-                    let ls_y = out.stride(0) as usize;
-                    let ls_uv = out.stride(1) as usize;
-                    let data_y = out.data(0);
-                    let data_uv = out.data(1);
-
-                    // copy Y plane
-                    for row in 0..h as usize {
-                        let start = row * ls_y;
-                        buf.extend_from_slice(&data_y[start..start + w as usize]);
-                    }
-
-                    // copy UV plane
-                    for row in 0..(h as usize / 2) {
-                        let start = row * ls_uv;
-                        buf.extend_from_slice(&data_uv[start..start + w as usize]);
-                    }
-
-                    (buf, LivePixFmt::Nv12)
-                }
-
-                _ => panic!("Unsupported output pixel format"),
-            };
+            // --- 5) + 6) Scale / convert and extract tightly-packed bytes ---
+            let Some((bytes, pix_fmt, w, h)) = converter.convert(&frame)? else { continue; };
 
             // --- 7) Timestamp ---
-            let ts_us = frame.timestamp().unwrap_or_else(|| {
+            let stream_ts_us = frame.timestamp().unwrap_or_else(|| {
                 let pts = packet.pts().unwrap_or(0);
                 pts.rescale(tb, ffmpeg::util::rational::Rational(1, 1_000_000))
             });
 
             // --- 8) Send the frame to the consumer ---
+            let (frame_index, ts_us) = stamper.stamp(timeline, stream_ts_us);
+            clock.on_frame(ts_us);
             let msg = LiveFrame {
                 ts_us,
                 width: w,
                 height: h,
                 pix_fmt,
                 data: bytes,
+                colorspace: YuvMatrix::from_space(frame.color_space()),
+                source_fps,
+                ingest_us,
             };
 
+            if out_tx.len() >= max_queue_warn {
+                LOG_THROTTLE.warn("stream_reader: queue", format_args!("[stream_reader] {} frames queued, the consumer is falling behind", out_tx.len()));
+            }
             if let Err(err) = out_tx.send((frame_index, msg)) {
-                eprintln!("[stream_reader] channel send err: {}", err);
+                LOG_THROTTLE.warn("stream_reader: send", format_args!("[stream_reader] channel send err: {err}"));
+                timeline.mark_dropped(frame_index);
             }
-
-            frame_index += 1;
         }
     }
 
@@ -266,3 +482,195 @@ fn run_reader(
     Ok(())
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    #[test]
+    fn max_dimension_downscales_4k() {
+        ffmpeg::init().unwrap();
+        assert_eq!(limit_dimensions(3840, 2160, Some(1920)), (1920, 1080));
+        assert_eq!(limit_dimensions(2160, 3840, Some(1920)), (1080, 1920));
+        assert_eq!(limit_dimensions(1280, 720, Some(1920)), (1280, 720));
+        assert_eq!(limit_dimensions(3840, 2160, None), (3840, 2160));
+
+        let mut src = frame::Video::new(Pixel::RGBA, 3840, 2160);
+        src.data_mut(0).fill(128);
+
+        let mut converter = FrameConverter::new(Pixel::RGBA, Some(1920));
+        let (bytes, pix_fmt, w, h) = converter.convert(&src).unwrap().unwrap();
+        assert_eq!((w, h), (1920, 1080));
+        assert_eq!(pix_fmt, LivePixFmt::Rgba);
+        assert_eq!(bytes.len(), 1920 * 1080 * 4);
+        assert!(bytes.iter().all(|&b| b == 128));
+    }
+
+    #[test]
+    fn malformed_frames_are_skipped() {
+        ffmpeg::init().unwrap();
+        let mut converter = FrameConverter::new(Pixel::RGBA, None);
+
+        // 0x0 frame from a corrupt stream
+        assert!(converter.convert(&frame::Video::empty()).unwrap().is_none());
+        assert!(converter.convert(&frame::Video::new(Pixel::RGBA, 0, 720)).unwrap().is_none());
+
+        // Planes smaller than the frame claims to be
+        let small = frame::Video::new(Pixel::RGBA, 16, 4);
+        assert!(extract_packed(&small, Pixel::RGBA, 64, 4).is_none());
+        assert!(extract_packed(&small, Pixel::RGBA, 16, 40).is_none());
+        assert!(extract_packed(&small, Pixel::NV12, 16, 4).is_none());
+        assert_eq!(extract_packed(&small, Pixel::RGBA, 16, 4).unwrap().0.len(), 16 * 4 * 4);
+
+        // The reader keeps going with the next valid frame
+        let mut src = frame::Video::new(Pixel::RGBA, 64, 32);
+        src.data_mut(0).fill(7);
+        let (bytes, _, w, h) = converter.convert(&src).unwrap().expect("valid frame after a malformed one");
+        assert_eq!((w, h, bytes.len()), (64, 32, 64 * 32 * 4));
+        assert_eq!(converter.skipped, 2);
+    }
+
+    #[test]
+    fn restarted_reader_continues_timeline() {
+        let timeline = FrameTimeline::new();
+        let mut first = FrameStamper::new(ReaderOrigin::default(), &timeline);
+        for i in 0..10 {
+            assert_eq!(first.stamp(&timeline, 1_000 + i * 33_333), (i as usize, 1_000 + i * 33_333));
+        }
+        timeline.trim_before(8);
+
+        // The restarted stream counts from zero again
+        let origin = ReaderOrigin::continue_timeline(&timeline, 33_333);
+        assert_eq!(origin, ReaderOrigin { start_frame_index: Some(10), timestamp_origin_us: Some(1_000 + 10 * 33_333) });
+        let mut second = FrameStamper::new(origin, &timeline);
+        let mut last = (9, 1_000 + 9 * 33_333);
+        for i in 0..5 {
+            let (idx, ts) = second.stamp(&timeline, i * 40_000);
+            assert_eq!(idx, last.0 + 1);
+            assert!(ts > last.1, "timestamp went back: {ts} after {}", last.1);
+            assert_eq!(timeline.index_of(ts), Some(idx));
+            last = (idx, ts);
+        }
+        assert_eq!(last, (14, 1_000 + 10 * 33_333 + 4 * 40_000));
+
+        // A known base for the next segment can skip ahead, never go back
+        FrameStamper::new(ReaderOrigin { start_frame_index: Some(100), timestamp_origin_us: None }, &timeline);
+        assert_eq!(timeline.next_index(), 100);
+        FrameStamper::new(ReaderOrigin { start_frame_index: Some(50), timestamp_origin_us: None }, &timeline);
+        assert_eq!(timeline.next_index(), 100);
+    }
+
+    #[test]
+    fn padded_and_tight_planes_pack_the_same() {
+        let (w, h) = (6, 4);
+        let tight: Vec<u8> = (0..w * h).map(|i| i as u8).collect();
+        // Same rows with 2 bytes of padding each
+        let padded: Vec<u8> = tight.chunks(w).flat_map(|r| r.iter().copied().chain([0xEE, 0xEE])).collect();
+
+        let (mut a, mut b) = (Vec::new(), Vec::new());
+        pack_plane(&mut a, &tight, w, w, h);
+        pack_plane(&mut b, &padded, w + 2, w, h);
+        assert_eq!(a, tight);
+        assert_eq!(b, tight);
+
+        // Only the requested rows, even when the plane holds more
+        let mut uv = Vec::new();
+        pack_plane(&mut uv, &tight, w, w, h / 2);
+        assert_eq!(uv, &tight[..w * h / 2]);
+    }
+
+    #[test]
+    fn yuv_matrix_bt601_vs_bt709() {
+        let close = |a: [u8; 3], b: [u8; 3]| a.iter().zip(&b).all(|(a, b)| a.abs_diff(*b) <= 1);
+        // Pure red as encoded by each standard
+        let (red_601, red_709) = ((81, 90, 240), (63, 102, 240));
+        assert!(close(YuvMatrix::Bt601.to_rgb(red_601.0, red_601.1, red_601.2), [255, 0, 0]));
+        assert!(close(YuvMatrix::Bt709.to_rgb(red_709.0, red_709.1, red_709.2), [255, 0, 0]));
+        // Decoded with the wrong matrix, HD red comes out dull
+        let wrong = YuvMatrix::Bt601.to_rgb(red_709.0, red_709.1, red_709.2);
+        assert!(wrong[0] < 240, "{wrong:?}");
+        // Neutrals don't depend on the matrix
+        for m in [YuvMatrix::Bt601, YuvMatrix::Bt709, YuvMatrix::Bt2020] {
+            assert_eq!(m.to_rgb(235, 128, 128), [255, 255, 255]);
+            assert_eq!(m.to_rgb(16, 128, 128), [0, 0, 0]);
+        }
+
+        assert_eq!(YuvMatrix::default_for(1920, 1080), YuvMatrix::Bt709);
+        assert_eq!(YuvMatrix::default_for(720, 576), YuvMatrix::Bt601);
+        assert_eq!(YuvMatrix::from_space(ffmpeg::util::color::Space::BT2020NCL), Some(YuvMatrix::Bt2020));
+        assert_eq!(YuvMatrix::from_space(ffmpeg::util::color::Space::Unspecified), None);
+    }
+
+    #[test]
+    #[ignore = "benchmark, run with --ignored --nocapture"]
+    fn nv12_packing_throughput_4k() {
+        let (w, h) = (3840, 2160);
+        let n = 50;
+        for (name, stride) in [("tight", w), ("padded", w + 64)] {
+            let (y, uv) = (vec![16u8; stride * h], vec![128u8; stride * h / 2]);
+            let t = Instant::now();
+            for _ in 0..n {
+                let mut buf = Vec::with_capacity(w * h * 3 / 2);
+                pack_plane(&mut buf, &y, stride, w, h);
+                pack_plane(&mut buf, &uv, stride, w, h / 2);
+                std::hint::black_box(&buf);
+            }
+            println!("{name}: {:.3} ms/frame", t.elapsed().as_secs_f64() * 1000.0 / n as f64);
+        }
+        // Row by row on a tight plane, i.e. the path before the bulk copy
+        let (y, uv) = (vec![16u8; w * h], vec![128u8; w * h / 2]);
+        let t = Instant::now();
+        for _ in 0..n {
+            let mut buf = Vec::with_capacity(w * h * 3 / 2);
+            for row in 0..h { buf.extend_from_slice(&y[row * w..row * w + w]); }
+            for row in 0..h / 2 { buf.extend_from_slice(&uv[row * w..row * w + w]); }
+            std::hint::black_box(&buf);
+        }
+        println!("tight, row by row: {:.3} ms/frame", t.elapsed().as_secs_f64() * 1000.0 / n as f64);
+    }
+
+    #[test]
+    fn nv12_odd_dimensions_are_rejected() {
+        for (w, h) in [(1, 1), (3, 3), (3, 4), (4, 3), (0, 2)] {
+            assert_eq!(nv12_plane_sizes(w, h), None, "{w}x{h}");
+        }
+        assert_eq!(nv12_plane_sizes(6, 4), Some((24, 12)));
+        assert_eq!(nv12_plane_sizes(1920, 1080), Some((1920 * 1080, 1920 * 540)));
+
+        // The reader rounds odd sources down to even NV12 frames, and never packs odd ones
+        ffmpeg::init().unwrap();
+        let mut src = frame::Video::new(Pixel::RGBA, 5, 3);
+        src.data_mut(0).fill(60);
+        let mut converter = FrameConverter::new(Pixel::NV12, None);
+        let (bytes, pix_fmt, w, h) = converter.convert(&src).unwrap().unwrap();
+        assert_eq!((pix_fmt, w, h, bytes.len()), (LivePixFmt::Nv12, 4, 2, 4 * 2 * 3 / 2));
+        assert!(extract_packed(&frame::Video::new(Pixel::NV12, 4, 4), Pixel::NV12, 3, 3).is_none());
+    }
+
+    #[test]
+    fn nv12_rows_stay_in_their_planes() {
+        // Odd sizes never read, whatever the buffer holds
+        let big = vec![0u8; 64];
+        for (w, h) in [(1, 1), (3, 3)] {
+            assert!(nv12_rows(&big, w, h).is_none(), "{w}x{h}");
+        }
+
+        // 6x4: Y is the pixel index, U and V 100 + 10 * block index + 0 / 1
+        let (w, h) = (6, 4);
+        let mut frame: Vec<u8> = (0..(w * h) as u8).collect();
+        for block in 0..(w / 2) * (h / 2) {
+            frame.extend([100 + 10 * block as u8, 101 + 10 * block as u8]);
+        }
+        let rows: Vec<_> = nv12_rows(&frame, w, h).unwrap().collect();
+        assert_eq!(rows.len(), h);
+        for (y, (y_row, uv_row)) in rows.into_iter().enumerate() {
+            for x in 0..w {
+                let block = (y / 2) * (w / 2) + x / 2;
+                assert_eq!([y_row[x], uv_row[x & !1], uv_row[x | 1]], [(y * w + x) as u8, 100 + 10 * block as u8, 101 + 10 * block as u8], "({x}, {y})");
+            }
+        }
+        // A buffer short of the last UV pair is rejected instead of read past
+        assert!(nv12_rows(&frame[..frame.len() - 1], w, h).is_none());
+    }
+}
@@ -1,8 +1,16 @@
 
+mod cli;
 mod render_live;
 mod live_pix_fmt;
 mod fplay;
-//mod render_map_kind;
+mod imu_schema;
+mod selftest;
+mod deflicker;
+mod record_meta;
+mod replay;
+mod supersample;
+mod restream;
+mod shm_sink;
 
 use std::io::{BufRead, BufReader};
 use std::net::{TcpListener, TcpStream};
@@ -13,7 +21,7 @@ use std::time::Duration;
 
 
 
-use crossbeam_channel::{unbounded, Receiver, Sender};
+use crossbeam_channel::{bounded, unbounded, Receiver};
 use serde_json::json;
 use std::collections::BTreeMap;
 
@@ -21,89 +29,314 @@ use gyroflow_core::gyro_source::FileMetadata;
 use gyroflow_core::gyro_source::live::LiveImuSample;
 use gyroflow_core::stabilization_params::ReadoutDirection;
 use gyroflow_core::StabilizationManager;
+use gyroflow_core::stabilization::pixel_formats::RGBA8;
+use gyroflow_core::stmap::StMapBlocks;
 use gyroflow_core::stmap_live::{StmapsLive, LiveFrameJob};
+use gyroflow_core::live::{LivePipeline, StreamPtsClock, LiveImuMsg, ClockUnwrapper, LineChecksums, ComputeFallback, FrameTimeline, Heartbeat, LensProfileWatcher, MapRenderBackend, MemoryBudget, Watchdog, DEFAULT_INTEGRATE_PERIOD};
 
-use crate::render_live::{LiveRenderConfig, render_live_loop};
-use crate::live_pix_fmt::{LiveFrame, PixelFormat, spawn_stream_reader};
-use std::sync::OnceLock;
+use crate::cli::Args;
+use crate::imu_schema::{ImuSchema, imu_schema, set_imu_schema};
+use crate::render_live::{LiveRenderConfig, RenderExit, SinkFormat, render_live_loop};
+use crate::live_pix_fmt::{LiveFrame, PixelFormat, ReaderOrigin, limit_dimensions, spawn_stream_reader};
+use std::sync::{Mutex, RwLock};
 use std::path::Path;
 
 
-const IMU_ADDR: &str = "127.0.0.1:7007";
 // const FRAME_ADDR: &str = "127.0.0.1:7008"; // unused for now
 
 const MAX_QUEUE_WARN: usize = 50;
-const URL: &str = "C:\\git\\videos\\gyrovid.mp4"; // replace with your stream URL
-
-const FPS: f64 =  30.0;
-const WIDTH: usize = 2704;
-const HEIGHT: usize = 2028;
-const INTEGRATE_PERIOD_MS: u64 = 10;
-const load_file_path: &str = "C:\\git\\GyroFlowLive\\Materials\\parsing\\mountvid_everything.csv";
-const load_file: bool = false; //set to true to load from file instead of imu stream
 
 
 
 const G_SCALE: f64 = 1.0;
 const A_SCALE: f64 = 1.0;
-static TSCALE: OnceLock<f64> = OnceLock::new();
+static TSCALE: RwLock<Option<f64>> = RwLock::new(None);
 
+/// Set from the header; a header re-sent mid-stream may change it.
 pub fn set_tscale(val: f64) {
-    TSCALE.set(val).expect("TSCALE already set!");
+    let mut t = TSCALE.write().unwrap();
+    if t.is_some_and(|old| old != val) {
+        log::info!("tscale changed {} -> {val}", t.unwrap());
+    }
+    *t = Some(val);
 }
 
 pub fn get_tscale() -> f64 {
-    *TSCALE.get().expect("TSCALE not initialized yet!")
+    TSCALE.read().unwrap().expect("TSCALE not initialized yet!")
+}
+
+/// Width of the sensor clock from the header's `clock_bits`, `None` for a clock that doesn't wrap.
+static CLOCK_BITS: RwLock<Option<u32>> = RwLock::new(None);
+/// Created at the first sample, once `tscale` is known too.
+static SENSOR_CLOCK: Mutex<Option<ClockUnwrapper>> = Mutex::new(None);
+
+/// Set from the header. A re-sent header with the same width keeps the epoch offset.
+pub fn set_clock_bits(bits: u32) {
+    let mut b = CLOCK_BITS.write().unwrap();
+    if *b != Some(bits) {
+        log::info!("Sensor clock is {bits} bits wide, unwrapping its wraparounds");
+        *SENSOR_CLOCK.lock().unwrap() = None;
+    }
+    *b = Some(bits);
+}
+
+/// Sensor time on a continuous timeline, see `ClockUnwrapper`.
+fn unwrap_sensor_clock(ts_us: i64) -> i64 {
+    let Some(bits) = *CLOCK_BITS.read().unwrap() else { return ts_us };
+    let mut clock = SENSOR_CLOCK.lock().unwrap();
+    clock.get_or_insert_with(|| ClockUnwrapper::from_bits(bits, get_tscale() / 0.000001)).unwrap(ts_us)
+}
+
+/// Commands typed on stdin: `stmaps` writes the current STMaps to `stmap_dir`,
+/// any other line dumps the replay buffer to a new file in `replay_dir`.
+fn spawn_stdin_commands(replay_dir: Option<std::path::PathBuf>, stmap_dir: Option<std::path::PathBuf>, pipeline: Arc<LivePipeline>) {
+    if replay_dir.is_some() {
+        println!("Instant replay enabled, press Enter to save it");
+    }
+    if stmap_dir.is_some() {
+        println!("STMap dump enabled, type `stmaps` and Enter to save the maps of the current frame");
+    }
+    thread::spawn(move || {
+        for line in std::io::stdin().lock().lines() {
+            let Ok(line) = line else { break };
+            match (line.trim(), &replay_dir, &stmap_dir) {
+                ("stmaps", _, Some(dir)) => match pipeline.dump_current_stmaps(dir) {
+                    Ok((undist, dist)) => println!("STMaps written to {} and {}", undist.display(), dist.display()),
+                    Err(e) => log::error!("STMap dump to {} failed: {e:?}", dir.display()),
+                },
+                (_, Some(dir), _) => {
+                    let stamp = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default().as_millis();
+                    let path = dir.join(format!("replay-{stamp}.mp4"));
+                    if let Err(e) = replay::trigger_replay(&path) {
+                        log::error!("Instant replay to {} failed: {e:?}", path.display());
+                    }
+                }
+                _ => {}
+            }
+        }
+    });
 }
 
 fn main() {
-    
+    let args = Args::parse_and_validate();
+
+    let mut logger = env_logger::Builder::from_default_env();
+    if let Some(level) = args.log_level {
+        logger.filter_level(level);
+    }
+    logger.init();
+
+    if args.selftest {
+        let report = selftest::run(&args);
+        report.print();
+        std::process::exit(if report.passed() { 0 } else { 1 });
+    }
+
+    let cli_lens = match args.lens_profile() {
+        Ok(l) => l,
+        Err(e) => {
+            eprintln!("Failed to load lens profile: {e}");
+            return;
+        }
+    };
+    fplay::set_record_path(args.record.clone());
+    if let Some(path) = args.record.as_ref().filter(|_| args.record_metadata) {
+        if let Err(e) = record_meta::enable(path) {
+            eprintln!("Failed to create {}: {e}", record_meta::sidecar_path(path).display());
+            return;
+        }
+    }
+    if let Some(target) = args.stream_target() {
+        restream::enable(target);
+    }
+    if let Some(name) = args.shm_name.clone() {
+        shm_sink::enable(name, args.shm_slots);
+    }
 
-    env_logger::init();
     // Manager
     let stab_man = Arc::new(StabilizationManager::default());
-    let metadata: FileMetadata = FileMetadata::default();
     // Initialize from stream data (size + initial fps; can be overridden by header fps)
-    stab_man.init_from_stream_data(FPS, (WIDTH, HEIGHT));
+    stab_man.init_from_stream_data(args.fps, (args.width, args.height));
+    if let Some(path) = &args.param_log {
+        if let Err(e) = stab_man.start_live_param_log(path) {
+            eprintln!("Failed to create parameter log {}: {e}", path.display());
+            return;
+        }
+    }
+    stab_man.set_live_latency_profile(args.latency_profile);
+    if args.stream_clock {
+        stab_man.use_live_clock(Arc::new(StreamPtsClock::default()));
+    }
  
     // Stop flag
     let stop = Arc::new(AtomicBool::new(false));
 
+    // IMU ingestion: consumer + periodic integration live in the pipeline
+    let mut builder = LivePipeline::builder(Arc::clone(&stab_man))
+        .integrate_period(DEFAULT_INTEGRATE_PERIOD)
+        .compute_fallback(ComputeFallback::Cpu)
+        .auto_detect_column_swap(args.auto_detect_column_swap)
+        .no_imu_behavior(args.no_imu, Duration::from_millis(args.no_imu_timeout_ms));
+    if let Some(ms) = args.imu_reorder_ms {
+        builder = builder.imu_reorder_window(Duration::from_millis(ms));
+    }
+    if args.load_quats.is_some() {
+        builder = builder.without_integration();
+    }
+    // Decoded RGBA frames after the `max_dimension` downscale, the unit of the frame queue share
+    let (frame_w, frame_h) = limit_dimensions(args.width as u32, args.height as u32, args.max_dimension);
+    let frame_bytes = frame_w as usize * frame_h as usize * 4;
+    let memory_budget = args.memory_budget_mb.map(MemoryBudget::from_mb);
+    if let Some(budget) = memory_budget {
+        builder = builder.memory_budget(budget);
+    }
+    let max_queued_frames = memory_budget.map_or(0, |b| b.queued_frames(frame_bytes));
+    let frame_channel = move || if max_queued_frames > 0 { bounded::<(usize, LiveFrame)>(max_queued_frames) } else { unbounded::<(usize, LiveFrame)>() };
+    let pipeline = match builder.start() {
+        Ok(p) => Arc::new(p),
+        Err(e) => {
+            eprintln!("Failed to start live pipeline: {e}");
+            return;
+        }
+    };
+
     // Crossbeam channel (Sender, Receiver)
-    let (imu_tx, imu_rx) = unbounded::<LiveImuSample>();
-    let (frame_tx, frame_rx) = unbounded::<(usize, LiveFrame)>();
+    let (frame_tx, frame_rx) = frame_channel();
     let (meta_tx, meta_rx) = unbounded::<()>();
-    //create an stmap
-    //let st_live: Arc<StmapsLive> = Arc::new(StmapsLive::new(Arc::clone(&stab_man)));
 
-    let stream_reader_thread =  spawn_stream_reader(URL, frame_tx.clone(), PixelFormat::Rgba, MAX_QUEUE_WARN, /*Arc::clone(&st_live)*/)
+    // frame_index <-> ts_us, shared by the reader and the render loop
+    let timeline = Arc::new(FrameTimeline::new());
+
+    let stream_reader_thread =  spawn_stream_reader(&args.video_url, frame_tx, PixelFormat::Rgba, MAX_QUEUE_WARN, args.max_dimension, Arc::clone(&timeline), ReaderOrigin::default(), stab_man.live_clock() /*, Arc::clone(&st_live)*/)
         .expect("failed to spawn stream reader thread");
 
 
     
-    let cfg = LiveRenderConfig::new(FPS);
+    let mut cfg = LiveRenderConfig::new(args.present_fps);
+    if args.gpu_maps {
+        cfg.map_backend = MapRenderBackend::Gpu;
+    }
+    cfg.interpolate_missing_maps = args.interpolate_missing_maps;
+    cfg.compare_mode = args.compare;
+    cfg.compare_toggle_frames = args.compare_every as usize;
+    cfg.interpolation = args.interpolation;
+    cfg.deflicker_strength = args.deflicker_strength;
+    cfg.latency_sla_ms = args.latency_sla_ms;
+    cfg.max_queued_frames = max_queued_frames;
+    cfg.max_map_cache_bytes = memory_budget.map_or(0, |b| b.map_cache_bytes());
+    cfg.preview_stride = args.preview_stride;
+    cfg.output_fps_cap = args.output_fps_cap;
+    cfg.replay_buffer_secs = args.replay_buffer_secs;
+    cfg.supersample = args.supersample;
+    cfg.processing_order = args.processing_order;
+    cfg.resolution_transition = args.resolution_transition;
+    cfg.format_change = args.format_change;
+    let replay_dir = (args.replay_buffer_secs > 0.0).then(|| args.replay_dir.clone());
+    if replay_dir.is_some() || args.stmap_dump_dir.is_some() {
+        spawn_stdin_commands(replay_dir, args.stmap_dump_dir.clone(), Arc::clone(&pipeline));
+    }
+
+    // Stall detection of the render loop, its heartbeat is registered once it starts
+    let watchdog = Watchdog::new();
+    let watchdog_timeout = (args.watchdog_timeout_ms > 0).then(|| Duration::from_millis(args.watchdog_timeout_ms));
+    let _watchdog_thread = watchdog_timeout.map(|t| watchdog.spawn(t / 4));
+    let render_heartbeat = Heartbeat::new();
+    let render_watchdog = Arc::clone(&watchdog);
+
+    let lens_watcher = args.lens_file.as_ref().filter(|_| args.watch_lens).map(LensProfileWatcher::new);
+    let _lens_watch_thread = lens_watcher.as_ref().map(|w| w.spawn(Arc::clone(&stab_man), Duration::from_millis(500)));
 
     let value = Arc::clone(&stab_man);
+    let render_stop = Arc::clone(&stop);
+    let (video_url, max_dimension, max_restarts) = (args.video_url.clone(), args.max_dimension, args.reader_restarts);
+    let frame_period_us = (1_000_000.0 / args.fps).round() as i64;
+    let render_pipeline = args.prewarm.then(|| Arc::clone(&pipeline));
+    // The size the reader delivers the frames in, the render loop sets the kernel up for it again otherwise
+    let prewarm_size = (frame_w as usize, frame_h as usize);
+    let stmap_render = args.stmap_render;
+    let stmap_tile_size = args.stmap_tile_size;
     let render_thread = thread::spawn(move || {
         println!("waiting fosr metadata...");
         meta_rx.recv().expect("Failed to receive metadata-ready signal");
+        // The header set the lens up, the kernels built now are the ones the first frame needs
+        if let Some(pipeline) = render_pipeline {
+            value.set_live_interpolation(cfg.interpolation);
+            value.set_render_params(prewarm_size, supersample::render_size(prewarm_size, cfg.supersample));
+            log::info!("Pre-warming the stabilization for {}x{} frames (max dimension {max_dimension:?})", prewarm_size.0, prewarm_size.1);
+            if let Err(e) = pipeline.prewarm::<RGBA8>(prewarm_size.0, prewarm_size.1) {
+                log::warn!("Pre-warming the stabilization failed: {e}");
+            }
+        }
+        // The map worker takes the lens of the header too
+        let stmaps = stmap_render.then(|| StmapsLive::new(Arc::clone(&value)));
+        if let (Some(stmaps), Some(size)) = (&stmaps, stmap_tile_size) {
+            stmaps.set_blocks(StMapBlocks::Tiles { size });
+        }
         println!("Starting render live loop");
-        render_live_loop(frame_rx, Arc::clone(&value), cfg, PixelFormat::Rgba);
+        if let Some(timeout) = watchdog_timeout {
+            render_watchdog.watch("render loop", &render_heartbeat, timeout);
+            if let Some(stmaps) = &stmaps {
+                render_watchdog.watch("stmap worker", stmaps.heartbeat(), timeout);
+            }
+        }
+        // Supervisor: restart the reader when it dies, the render loop picks up where it left off
+        let mut frame_rx = frame_rx;
+        let mut restarts = 0;
+        loop {
+            match render_live_loop(frame_rx, Arc::clone(&value), Arc::clone(&timeline), stmaps.as_ref(), cfg, SinkFormat::Rgba, &render_stop, &render_heartbeat) {
+                RenderExit::ReaderDisconnected if restarts < max_restarts => {
+                    restarts += 1;
+                    log::warn!("Restarting stream reader ({restarts}/{max_restarts})");
+                    let (tx, rx) = frame_channel();
+                    // Keep indices and timestamps going from where the previous reader stopped
+                    let origin = ReaderOrigin::continue_timeline(&timeline, frame_period_us);
+                    if let Err(e) = spawn_stream_reader(&video_url, tx, PixelFormat::Rgba, MAX_QUEUE_WARN, max_dimension, Arc::clone(&timeline), origin, value.live_clock()) {
+                        log::error!("Failed to restart stream reader: {e:?}");
+                        break;
+                    }
+                    frame_rx = rx;
+                }
+                exit => {
+                    log::info!("Render loop finished: {exit:?}");
+                    break;
+                }
+            }
+        }
+        if let Some(stmaps) = &stmaps {
+            stmaps.stop();
+        }
     });
     
 
-       // Prepare a callback that will be called once per client when the full GCSV header is received
+       // Prepare a callback that will be called whenever a full GCSV header is received: the first one
+       // starts the stream, later ones (reconnect, lens change) update the running stream
     let stab_for_header = Arc::clone(&stab_man);
+    let (size, load_quats) = ((args.width, args.height), args.load_quats.clone());
+    let stream_started = Arc::new(AtomicBool::new(false));
+    let (no_imu_started, no_imu_meta_tx, no_imu_lens) = (Arc::clone(&stream_started), meta_tx.clone(), cli_lens.clone());
     let header_cb: Arc<dyn Fn(&str) + Send + Sync> = Arc::new(move |header: &str| {
         
         let meta_tx = meta_tx.clone();
         // Parse the header into FileMetadata
-        let metadata = parse_gyroflow_header(header);
+        let mut metadata = parse_gyroflow_header(header);
+        if metadata.lens_profile.is_none() {
+            metadata.lens_profile = cli_lens.clone();
+        }
         
         log::info!("Parsed GCSV header into FileMetadata: {:?}", metadata.detected_source);
         println!("Parsed GCSV header into FileMetadata: {:?}", metadata.frame_readout_direction);
+
+        if stream_started.swap(true, Ordering::Relaxed) {
+            match stab_for_header.update_live_metadata(metadata) {
+                Ok(()) => log::info!("Header re-sent mid-stream, live metadata updated"),
+                Err(e) => log::error!("Header re-sent mid-stream could not be applied: {e:?}"),
+            }
+            return;
+        }
+
         // Initialize live stream with this metadata
-        let _ = stab_for_header.start_single_stream(metadata, 3.0, 1.0, 0.0, (WIDTH, HEIGHT), (WIDTH, HEIGHT), Path::new(load_file_path), load_file);
+        let quats_path = load_quats.as_deref().unwrap_or(Path::new(""));
+        let _ = stab_for_header.start_single_stream(metadata, 3.0, 1.0, 0.0, size, size, quats_path, load_quats.is_some());
         
         println!("metadata loaded into stabilizer");
 
@@ -112,70 +345,122 @@ fn main() {
     });
 
     // Spawn server thread (binds and waits for generator to connect and write)
-    spawn_line_server::<LiveImuSample>(
+    spawn_line_server::<LiveImuMsg>(
         "imu server",
-        IMU_ADDR,
-        imu_tx,
+        args.imu_addr.clone(),
+        {
+            let pipeline = Arc::clone(&pipeline);
+            // Over the memory budget samples are dropped, a blocked reader would stall the client instead
+            Arc::new(move |(sample, video_us): LiveImuMsg| pipeline.push_imu(sample, video_us).is_ok())
+        },
         Arc::clone(&stop),
         Some(header_cb),
-        parse_imu_line,
+        {
+            let pipeline = Arc::clone(&pipeline);
+            Arc::new(move || pipeline.record_bad_checksum_line())
+        },
+        parse_imu_msg,
+        // Merging several clients is what the reorder window is for
+        args.imu_reorder_ms.is_some(),
     );
 
-
-    // Spawn consumer thread: pull samples from channel and push into GyroSource
-
-    {
-        let mut counter: i64 = 0;
-        let stab = Arc::clone(&stab_man);
-        thread::spawn(move || {
-            while let Ok(imu_sample) = imu_rx.recv() {
-                 
-                let LiveImuSample { ts_sensor_us, .. } = imu_sample;
-                // If you have a video clock, pass it; reusing sensor time for now
-                let now_video_us = ts_sensor_us;
-                //println!("Received IMU sample at ts_sensor_us={}", imu_sample);
-                //working :)
-                let mut g = stab.gyro.write();
-                g.push_live_imu(imu_sample, now_video_us);
-                if(counter%1000==0) {println!("IMU sample: {:?}", imu_sample);} 
-                counter+=1;
+    // No IMU client sent anything: start the stream without a header, `--no-imu` decides the output
+    match pipeline.wait_for_imu() {
+        Ok(true) => {}
+        Ok(false) => {
+            if !no_imu_started.swap(true, Ordering::Relaxed) {
+                let metadata = FileMetadata { lens_profile: no_imu_lens, ..Default::default() };
+                if let Err(e) = stab_man.start_single_stream(metadata, 3.0, 1.0, 0.0, size, size, Path::new(""), false) {
+                    log::error!("Failed to start the stream without IMU: {e:?}");
+                }
+                let _ = no_imu_meta_tx.send(());
             }
-        });
+        }
+        Err(e) => {
+            eprintln!("Failed to start live pipeline: {e}");
+            stop.store(true, Ordering::Relaxed);
+        }
     }
-    // Keep main alive; periodically integrate live data
-    if(!load_file){
-        loop {
-            stab_man.gyro.write().integrate_live_data();
-            if stop.load(Ordering::Relaxed) {
-                break;
-            }
-                    thread::sleep(Duration::from_millis(INTEGRATE_PERIOD_MS));
 
-        }   
-    }else{
-        loop{
-            thread::sleep(Duration::from_millis(1000));
+    // Keep main alive; the pipeline integrates live data in the background
+    let live_clock = stab_man.live_clock();
+    let mut last_stabilized = (live_clock.now_us(), 0u64);
+    while !stop.load(Ordering::Relaxed) {
+        thread::sleep(Duration::from_millis(1000));
+        for h in watchdog.ages() {
+            log::debug!("health: {} heartbeat {} ms ago{}", h.name, h.age.as_millis(), if h.stalled { " (stalled)" } else { "" });
+        }
+        let confidence = stab_man.live_confidence_stats.snapshot();
+        if confidence.frames > 0 {
+            log::debug!("health: confidence {:.2}, {} of {} frames under {}", confidence.last, confidence.low, confidence.frames, gyroflow_core::live::LOW_CONFIDENCE);
+        }
+        let session = stab_man.live_session_stats.snapshot();
+        if session.connections > 0 {
+            log::debug!("health: {} frames presented, {} dropped ({} and {} since the last of {} reconnects)",
+                session.session.presented, session.session.dropped, session.connection.presented, session.connection.dropped, session.reconnects());
+            let now_us = live_clock.now_us();
+            if now_us > last_stabilized.0 {
+                let preview_fps = (session.session.stabilized - last_stabilized.1) as f64 / ((now_us - last_stabilized.0) as f64 / 1e6);
+                log::debug!("health: effective preview rate {preview_fps:.1} fps");
+                last_stabilized = (now_us, session.session.stabilized);
+            }
+        }
+        let timing = stab_man.live_render_timing.snapshot();
+        if timing.frames > 0 {
+            let t = timing.average;
+            log::debug!("health: frame time {:.1} ms = map {:.1} + buffers {:.1} + process {:.1} + post {:.1} + sink {:.1}",
+                t.total_ms, t.map_wait_ms, t.buffer_setup_ms, t.process_ms, t.post_ms, t.sink_ms);
+        }
+        let e2e = stab_man.live_e2e_latency.snapshot();
+        if e2e.frames > 0 {
+            // Whatever the render stages don't account for was spent queued between the reader and the loop
+            log::debug!("health: ingest to sink p50 {:.1} / p95 {:.1} / p99 {:.1} ms, {:.1} ms of it queued",
+                e2e.p50_ms, e2e.p95_ms, e2e.p99_ms, (e2e.p50_ms - timing.average.total_ms).max(0.0));
+        }
+        let bad_lines = pipeline.ingest_stats().bad_checksum_lines;
+        if bad_lines > 0 {
+            log::debug!("health: {bad_lines} IMU lines dropped for a bad checksum");
+        }
+        let sla = stab_man.live_latency_stats.snapshot();
+        if sla.sla_ms > 0.0 {
+            log::debug!("health: latency {:.1} ms (SLA {:.0} ms), degradation level {}, {} late frames dropped", sla.latency_ms, sla.sla_ms, sla.level, sla.late_dropped);
+        }
+        if let Some(budget) = memory_budget {
+            let mem = pipeline.memory_usage();
+            let kb = |b: usize| b / 1024;
+            log::debug!("health: memory {} of {} KB (IMU channel {}, IMU ring {}, quaternions {}, maps {}, frames {})",
+                kb(mem.total()), kb(budget.total_bytes), kb(mem.imu_channel), kb(mem.imu_ring), kb(mem.quat_buffers), kb(mem.map_cache), kb(mem.frame_queue));
         }
     }
-    
+    watchdog.stop();
+    restream::shutdown();
+    shm_sink::shutdown();
+    record_meta::finish();
+    if let Some(w) = &lens_watcher {
+        w.stop();
+    }
+    pipeline.stop();
 }
 
 /// TCP line **server**: bind(addr) and accept() clients; for each client,
-/// read lines, parse with `parse_line`, and send to `tx`.
+/// read lines, parse with `parse_line`, and hand them to `deliver` (false once nobody receives them).
+/// Lines failing their checksum (see `LineChecksums`) are reported to `on_bad_checksum` instead.
 fn spawn_line_server<T: Send + 'static>(
     name: &'static str,
-    addr: &'static str,
-    tx: Sender<T>,
+    addr: String,
+    deliver: Arc<dyn Fn(T) -> bool + Send + Sync>,
     stop: Arc<AtomicBool>,
     on_header: Option<Arc<dyn Fn(&str) + Send + Sync>>,
+    on_bad_checksum: Arc<dyn Fn() + Send + Sync>,
     parse_line: fn(&str) -> Option<T>,
+    concurrent_clients: bool,
 ) {
  {
     thread::Builder::new()
         .name(format!("server_{name}"))
         .spawn(move || {
             // Bind once; if bind fails, crash early so the operator knows
-            let listener = match TcpListener::bind(addr) {
+            let listener = match TcpListener::bind(&addr) {
                 Ok(l) => {
                     eprintln!("[{name}] listening on {addr}");
                     l
@@ -186,21 +471,38 @@ fn spawn_line_server<T: Send + 'static>(
                 }
             };
 
-            // Accept-loop: handle one client at a time; when it disconnects, accept the next one
+            // Accept-loop: handle one client at a time; when it disconnects, accept the next one.
+            // With `concurrent_clients` every client gets a thread of its own instead
             listener
                 .set_nonblocking(false)
                 .ok(); // blocking accept is fine here
 
             while !stop.load(Ordering::Relaxed) {
                 match listener.accept() {
+                    Ok((stream, peer)) if concurrent_clients => {
+                        eprintln!("[{name}] client connected from {peer}");
+                        let (deliver, stop, on_header, on_bad_checksum) = (Arc::clone(&deliver), Arc::clone(&stop), on_header.clone(), Arc::clone(&on_bad_checksum));
+                        let spawned = thread::Builder::new()
+                            .name(format!("client_{name}"))
+                            .spawn(move || {
+                                if let Err(e) = handle_client(name, stream, &*deliver, &stop, on_header, &*on_bad_checksum, parse_line) {
+                                    eprintln!("[{name}] client {peer} handler error: {e}");
+                                }
+                                eprintln!("[{name}] client {peer} disconnected");
+                            });
+                        if let Err(e) = spawned {
+                            eprintln!("[{name}] failed to spawn a handler for {peer}: {e}");
+                        }
+                    }
                     Ok((stream, peer)) => {
                         eprintln!("[{name}] client connected from {peer}");
                         if let Err(e) = handle_client(
                             name,
                             stream.try_clone().unwrap(),
-                            &tx,
+                            &*deliver,
                             &stop,
                             on_header.clone(),
+                            &*on_bad_checksum,
                             parse_line,
                         ) {
                             eprintln!("[{name}] client handler error: {e}");
@@ -220,23 +522,24 @@ fn spawn_line_server<T: Send + 'static>(
  }
 }
 
-/// Handle a single connected client: read lines → parse → send
+/// Handle a single connected client: read lines → parse → deliver
 fn handle_client<T: Send>(
     name: &str,
     stream: TcpStream,
-    tx: &Sender<T>,
+    deliver: &(dyn Fn(T) -> bool + Send + Sync),
     stop: &Arc<AtomicBool>,
     on_header: Option<Arc<dyn Fn(&str) + Send + Sync>>,
+    on_bad_checksum: &(dyn Fn() + Send + Sync),
     parse_line: fn(&str) -> Option<T>,
 ) -> std::io::Result<()> {
        stream.set_read_timeout(Some(Duration::from_millis(500)))?;
     let reader = BufReader::new(stream);
 
-    // Header state: we collect lines until we hit the "t,..." line
-    let mut in_header = on_header.is_some();
-    let mut header_buf = String::new();
+    // Header state: we collect lines until we hit the "t,..." line, again whenever the client re-sends one
+    let mut header = on_header.as_ref().map(|_| HeaderAssembler::new());
+    let mut checksums = LineChecksums::default();
 
-    for maybe_line in reader.lines() {
+    for (line_no, maybe_line) in reader.lines().enumerate() {
         if stop.load(Ordering::Relaxed) {
             eprintln!("[{name}] stop requested");
             break;
@@ -244,32 +547,34 @@ fn handle_client<T: Send>(
         match maybe_line {
             Ok(l) => {
                 let line_trimmed = l.trim();
-                if in_header {
-                    // Accumulate header lines (including "GYROFLOW IMU LOG", version, etc.)
-                    header_buf.push_str(line_trimmed);
-                    header_buf.push('\n');
-
-                    // End of header is the column header line "t,..." (e.g. "t,gx,gy,gz,ax,ay,az")
-                    if line_trimmed.starts_with("t,") {
-                        in_header = false;
-
-                        if let Some(cb) = &on_header {
-                            // Remove trailing newline for cleanliness
-                            let hdr = header_buf.trim_end_matches('\n');
-                            cb(hdr);
+                if let Some(asm) = header.as_mut() {
+                    match asm.feed(line_trimmed) {
+                        HeaderLine::Complete(hdr) => {
+                            if let Some(cb) = &on_header {
+                                cb(&hdr);
+                            }
+                            continue;
                         }
+                        // Do NOT parse these lines as IMU samples
+                        HeaderLine::Partial => continue,
+                        HeaderLine::Record => {}
                     }
-
-                    // Do NOT parse these lines as IMU samples
-                    continue;
                 }
 
                 // After header: normal IMU data lines
-                if let Some(msg) = parse_line(line_trimmed) {
-                    if tx.send(msg).is_err() {
-                        eprintln!("[{name}] main loop dropped; exiting client handler");
-                        break;
+                let Some(record) = checksums.strip(line_trimmed) else {
+                    on_bad_checksum();
+                    continue;
+                };
+                match parse_line(record) {
+                    Some(msg) => {
+                        if !deliver(msg) {
+                            eprintln!("[{name}] main loop dropped; exiting client handler");
+                            break;
+                        }
                     }
+                    None if !line_trimmed.is_empty() => log::warn!("[{name}] line {}: malformed record, skipped: {line_trimmed}", line_no + 1),
+                    None => {}
                 }
             }
             Err(e) => {
@@ -288,50 +593,91 @@ fn handle_client<T: Send>(
     Ok(())
 }
 
-/// Simple parser that accepts "t,gx,gy,gz,ax,ay,az"
-/// - If `t` is large (>= 1e12), treat as nanoseconds and convert to microseconds
-/// - Otherwise treat `t` as a sample index and synthesize µs with a fixed sample period
+/// Header lines a logger may send before the header counts as broken.
+const MAX_HEADER_LINES: usize = 64;
+
+/// What a line of the IMU stream turned out to be.
+#[derive(Debug, PartialEq)]
+enum HeaderLine {
+    /// Last line of a header, with the whole header text.
+    Complete(String),
+    /// Part of a header that isn't finished yet.
+    Partial,
+    /// IMU record.
+    Record,
+}
+
+/// Collects header lines from the IMU stream. A header runs until the column line `t,...`; it is expected
+/// when a client connects and again whenever a `GYROFLOW IMU LOG` line shows up mid-stream.
+///
+/// A header that is interrupted by IMU records (or never ends) is discarded, so a half-received header
+/// can't replace the metadata of the running stream.
+struct HeaderAssembler {
+    buf: Option<Vec<String>>,
+}
+
+impl HeaderAssembler {
+    fn new() -> Self { Self { buf: Some(Vec::new()) } }
+
+    fn feed(&mut self, line: &str) -> HeaderLine {
+        if line.starts_with("GYROFLOW IMU LOG") {
+            if let Some(partial) = self.buf.as_ref().filter(|b| !b.is_empty()) {
+                log::warn!("IMU header restarted before it was complete, discarding {} lines", partial.len());
+            }
+            self.buf = Some(Vec::new());
+        }
+        let Some(buf) = self.buf.as_mut() else { return HeaderLine::Record; };
+
+        // Numeric first field: the header was cut off and records are flowing again
+        if !buf.is_empty() && line.split(',').next().is_some_and(|f| f.trim().parse::<f64>().is_ok()) {
+            log::warn!("IMU header interrupted by records after {} lines, ignoring it", buf.len());
+            self.buf = None;
+            return HeaderLine::Record;
+        }
+
+        buf.push(line.to_string());
+        // End of header is the column header line "t,..." (e.g. "t,gx,gy,gz,ax,ay,az")
+        if line.starts_with("t,") {
+            return HeaderLine::Complete(self.buf.take().unwrap_or_default().join("\n"));
+        }
+        if buf.len() > MAX_HEADER_LINES {
+            log::warn!("IMU header longer than {MAX_HEADER_LINES} lines, ignoring it");
+            self.buf = None;
+        }
+        HeaderLine::Partial
+    }
+}
+
+/// Parse an IMU line into a channel message for the live pipeline.
+fn parse_imu_msg(line: &str) -> Option<LiveImuMsg> {
+    // If you have a video clock, pass it; reusing sensor time for now
+    parse_imu_line(line).map(|s| (s, s.ts_sensor_us))
+}
+
+/// Parser for IMU records laid out as in the header's `t,...` column line (default "t,gx,gy,gz,ax,ay,az")
+/// - Columns are mapped by name, unknown columns (temperature, magnetometer, ...) are ignored
+/// - `t` is scaled to microseconds with the header's `tscale`
+/// - A trailing `*` checksum (CRC-32 of the record, see `live::append_checksum`) was already verified
+///   and stripped by `handle_client`
 fn parse_imu_line(line: &str) -> Option<LiveImuSample> {
     let l = line.trim();
     if l.is_empty() || l.starts_with("GYROFLOW") || l.starts_with("t,") {
         return None;
     }
 
-    let mut it = l.split(',');
-    let t_str = it.next()?.trim();
-
-    let gx = it.next()?.trim().parse::<f64>().ok()?;
-    let gy = it.next()?.trim().parse::<f64>().ok()?;
-    let gz = it.next()?.trim().parse::<f64>().ok()?;
-    let ax = it.next()?.trim().parse::<f64>().ok()?;
-    let ay = it.next()?.trim().parse::<f64>().ok()?;
-    let az = it.next()?.trim().parse::<f64>().ok()?;
-  
-    //println!("Parsed IMU line: t={} gx={} gy={} gz={} ax={} ay={} az={}", t_str, gx, gy, gz, ax, ay, az);
-
-    // auto-detect time column
-    // 1. Parse to f64 because we want to apply scaling
-    let raw_val = t_str.parse::<f64>().ok()?;
-
+    let rec = imu_schema().parse(l)?;
+    let [gx, gy, gz] = rec.gyro;
 
-    // 2. Apply tscale (global multiplier)
+    // Time column scaled to microseconds with tscale (seconds per unit), clamped into i64
     let us: f64 = 0.000001; // 1 microsecond in seconds
-    let scaler: f64 = get_tscale() / us;
-    let scaled = raw_val * scaler;
-
-    // 3. Clamp into i64 inte
-    let clamped = scaled
-        .clamp(i64::MIN as f64, i64::MAX as f64)
-        .round() as i64;
-
-    let ts_sensor_us = clamped;
+    let ts_sensor_us = unwrap_sensor_clock(rec.t.to_us(get_tscale() / us));
 
     // If your sender used scale factors (gscale/ascale), multiply here; for now = 1.0
     const GSCALE: f64 = G_SCALE;
     const ASCALE: f64 = A_SCALE;
 
     let gyro = [gx * GSCALE, gy * GSCALE, gz * GSCALE];
-    let accel = Some([ax * ASCALE, ay * ASCALE, az * ASCALE]);
+    let accel = rec.accel.map(|[ax, ay, az]| [ax * ASCALE, ay * ASCALE, az * ASCALE]);
 
     Some(LiveImuSample { ts_sensor_us, gyro, accel })
 }
@@ -361,7 +707,15 @@ pub fn parse_gyroflow_header(header: &str) -> FileMetadata {
     };
 
     for line in header.lines() {
-        if line.trim().is_empty() || line.starts_with("GYROFLOW") || line.starts_with("t,") {
+        if line.starts_with("t,") {
+            // Column header, IMU records are parsed by these names
+            match ImuSchema::from_header_line(line) {
+                Some(schema) => set_imu_schema(schema),
+                None => log::warn!("Unsupported IMU column header '{line}', keeping the previous layout"),
+            }
+            continue;
+        }
+        if line.trim().is_empty() || line.starts_with("GYROFLOW") {
             continue;
         }
         let mut parts = line.splitn(2, ',');
@@ -375,6 +729,13 @@ pub fn parse_gyroflow_header(header: &str) -> FileMetadata {
                 set_tscale(val);
                 }
             "vendor" => metadata.detected_source = Some(value.to_string()),
+            "clock_bits" => match value.parse::<u32>() {
+                Ok(bits @ 1..=62) => {
+                    set_clock_bits(bits);
+                    metadata.additional_data["clock_bits"] = json!(bits);
+                }
+                _ => log::warn!("Invalid clock_bits '{value}', the sensor clock is taken as not wrapping"),
+            },
             "frame_readout_time" => {
                 if let Ok(v) = value.parse::<f64>() {
                     metadata.frame_readout_time = Some(v);
@@ -390,7 +751,15 @@ pub fn parse_gyroflow_header(header: &str) -> FileMetadata {
                 };
             }
             "lensprofile" => {
-                metadata.lens_profile = Some(json!(value));
+                // Either a profile name/id from the lens DB, or a full profile as inline JSON
+                metadata.lens_profile = if value.starts_with('{') {
+                    serde_json::from_str::<serde_json::Value>(value).ok().or_else(|| Some(json!(value)))
+                } else {
+                    Some(json!(value))
+                };
+            }
+            "distortion_model" => {
+                metadata.additional_data["distortion_model"] = json!(value);
             }
             "frame_rate" | "fps" => {
                 if let Ok(v) = value.parse::<f64>() {
@@ -413,5 +782,124 @@ pub fn parse_gyroflow_header(header: &str) -> FileMetadata {
         }
     }
 
+    // Insta360 streams use their own model unless the header says otherwise
+    let is_insta360 = metadata.detected_source.as_deref().is_some_and(|v| v.to_ascii_lowercase().contains("insta360"));
+    if is_insta360 && metadata.additional_data.get("distortion_model").is_none() {
+        metadata.additional_data["distortion_model"] = json!("insta360");
+    }
+
     metadata
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LENS_A: &str = r#"{"camera_brand":"Test","lens_model":"A","calib_dimension":{"w":1920,"h":1080},"fisheye_params":{"camera_matrix":[[1000,0,960],[0,1000,540],[0,0,1]],"distortion_coeffs":[0,0,0,0]}}"#;
+    const LENS_B: &str = r#"{"camera_brand":"Test","lens_model":"B","calib_dimension":{"w":1920,"h":1080},"fisheye_params":{"camera_matrix":[[1400,0,960],[0,1400,540],[0,0,1]],"distortion_coeffs":[0,0,0,0]}}"#;
+
+    fn header_lines(fps: f64, lens: &str) -> Vec<String> {
+        ["GYROFLOW IMU LOG", "version,1.3", "tscale,0.000001", &format!("frame_rate,{fps}"), &format!("lensprofile,{lens}"), "t,gx,gy,gz,ax,ay,az"]
+            .iter().map(|l| l.to_string()).collect()
+    }
+
+    #[test]
+    fn mid_stream_header_updates_metadata() {
+        let stab = StabilizationManager::default();
+        stab.init_from_stream_data(30.0, (1920, 1080));
+
+        let mut asm = HeaderAssembler::new();
+        let mut headers = Vec::new();
+        let mut records = 0;
+        let records_block = ["0,0.1,0,0,0,0,1", "5000,0.1,0,0,0,0,1"];
+        let stream = header_lines(30.0, LENS_A).into_iter()
+            .chain(records_block.map(String::from))
+            // Partial header cut off by records must be ignored
+            .chain(["GYROFLOW IMU LOG".to_string(), "frame_rate,5".to_string()])
+            .chain(records_block.map(String::from))
+            .chain(header_lines(60.0, LENS_B))
+            .chain(records_block.map(String::from));
+        for line in stream {
+            match asm.feed(&line) {
+                HeaderLine::Complete(h) => headers.push(h),
+                HeaderLine::Partial => {}
+                HeaderLine::Record => records += 1,
+            }
+        }
+        assert_eq!((headers.len(), records), (2, 6));
+
+        let first = parse_gyroflow_header(&headers[0]);
+        stab.start_single_stream(first, 3.0, 1.0, 0.0, (1920, 1080), (1920, 1080), Path::new(""), false).unwrap();
+        assert_eq!(stab.params.read().fps, 30.0);
+        assert_eq!(stab.lens.read().lens_model, "A");
+
+        stab.update_live_metadata(parse_gyroflow_header(&headers[1])).unwrap();
+        assert_eq!(stab.params.read().fps, 60.0);
+        assert_eq!(stab.lens.read().lens_model, "B");
+        assert_eq!(stab.lens.read().fisheye_params.camera_matrix[0][0], 1400.0);
+        // The live session survives the update
+        assert!(stab.gyro.read().live.read().is_some());
+    }
+
+    #[test]
+    fn source_info_follows_the_header() {
+        let stab = Arc::new(StabilizationManager::default());
+        stab.init_from_stream_data(30.0, (1920, 1080));
+        let header = |fps: f64, fw: &str| {
+            let mut lines = header_lines(fps, LENS_A);
+            lines.insert(1, "vendor,PotatoCam Mark1".into());
+            lines.insert(2, format!("fwversion,{fw}"));
+            lines.join("\n")
+        };
+        stab.start_single_stream(parse_gyroflow_header(&header(30.0, "1.0.2")), 3.0, 1.0, 0.0, (1920, 1080), (1920, 1080), Path::new(""), false).unwrap();
+        let pipeline = LivePipeline::new(stab.clone(), None);
+
+        let info = pipeline.source_info();
+        assert_eq!(info.detected_source.as_deref(), Some("PotatoCam Mark1"));
+        assert_eq!(info.lens_profile.as_deref(), Some("A"));
+        assert_eq!(info.fwversion.as_deref(), Some("1.0.2"));
+        assert_eq!(info.frame_rate, Some(30.0));
+        assert_eq!(info.camera_identifier, None);
+
+        // Re-parsed mid-stream
+        stab.update_live_metadata(parse_gyroflow_header(&header(60.0, "1.1.0"))).unwrap();
+        let info = pipeline.source_info();
+        assert_eq!((info.fwversion.as_deref(), info.frame_rate), (Some("1.1.0"), Some(60.0)));
+    }
+
+    #[test]
+    fn imu_line_checksums() {
+        use gyroflow_core::live::append_checksum;
+        use std::io::Write;
+        use std::sync::Mutex;
+        use std::sync::atomic::AtomicU64;
+
+        set_tscale(0.000001);
+        let record = "1000,0.5,-0.25,0.125,0,9.8,0";
+        let checksummed = append_checksum(record);
+        let lines = [
+            // Loggers without checksums keep working
+            record.to_string(),
+            checksummed.clone(),
+            // A corrupted field no longer matches the checksum
+            checksummed.replacen("0.5", "0.9", 1),
+            format!("{record}*zz"),
+            // The `*` was corrupted away, the stream sends checksums so the line is rejected
+            checksummed.replacen('*', "", 1),
+            checksummed,
+        ];
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        client.write_all(lines.join("\n").as_bytes()).unwrap();
+        drop(client);
+
+        let (stream, _) = listener.accept().unwrap();
+        let delivered = Mutex::new(Vec::new());
+        let bad = AtomicU64::new(0);
+        handle_client("imu test", stream, &|s: LiveImuSample| { delivered.lock().unwrap().push(s.gyro); true },
+            &Arc::new(AtomicBool::new(false)), None, &|| { bad.fetch_add(1, Ordering::Relaxed); }, parse_imu_line).unwrap();
+        assert_eq!(*delivered.lock().unwrap(), vec![[0.5, -0.25, 0.125]; 3]);
+        assert_eq!(bad.load(Ordering::Relaxed), 3);
+    }
+}
@@ -0,0 +1,119 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use gyroflow_core::StabilizationManager;
+use gyroflow_core::gyro_source::GyroSource;
+use gyroflow_core::live::metadata;
+use gyroflow_core::live::trace::QuatTrace;
+
+/// Per-frame quaternions of the recording being written, see `enable`.
+static SIDECAR: Mutex<Option<(PathBuf, BufWriter<File>)>> = Mutex::new(None);
+
+/// `<recording>.quats.csv` next to the recording.
+pub fn sidecar_path(record: &Path) -> PathBuf {
+    let mut name = record.file_name().unwrap_or_default().to_os_string();
+    name.push(".quats.csv");
+    record.with_file_name(name)
+}
+
+/// Document the recording to `record`: per-frame quaternions go to a CSV sidecar (`QuatTrace` lines),
+/// the smoothing parameters and the lens into the global metadata of the file (see `global_metadata`).
+pub fn enable(record: &Path) -> std::io::Result<()> {
+    let path = sidecar_path(record);
+    let mut out = BufWriter::new(File::create(&path)?);
+    writeln!(out, "{}", QuatTrace::CSV_HEADER)?;
+    *SIDECAR.lock().unwrap() = Some((path, out));
+    Ok(())
+}
+
+pub fn is_enabled() -> bool { SIDECAR.lock().unwrap().is_some() }
+
+/// `live::metadata::recording_metadata` plus the name of the quaternion sidecar.
+pub fn global_metadata(stab: &StabilizationManager) -> Vec<(String, String)> {
+    let mut md: Vec<_> = metadata::recording_metadata(stab).into_iter().collect();
+    if let Some((path, _)) = SIDECAR.lock().unwrap().as_ref() {
+        let name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+        md.push((format!("{}quats", metadata::METADATA_PREFIX), name));
+    }
+    md
+}
+
+/// Append the quaternions of a stabilized frame, if enabled.
+pub fn record_frame(gyro: &GyroSource, frame_idx: usize, ts_us: i64) {
+    let mut sidecar = SIDECAR.lock().unwrap();
+    let Some((path, out)) = sidecar.as_mut() else { return };
+    if let Err(e) = writeln!(out, "{}", QuatTrace::at(gyro, frame_idx, ts_us)) {
+        log::error!("Writing {} failed, no more quaternions are recorded: {e:?}", path.display());
+        *sidecar = None;
+    }
+}
+
+/// Flush the sidecar at the end of the recording.
+pub fn finish() {
+    if let Some((path, mut out)) = SIDECAR.lock().unwrap().take() {
+        if let Err(e) = out.flush() {
+            log::error!("Writing {} failed: {e:?}", path.display());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+    use crate::fplay::{self, FProps};
+    use crate::live_pix_fmt::PixelFormat;
+
+    #[test]
+    fn sidecar_gets_a_line_per_frame() {
+        let record = std::env::temp_dir().join(format!("gyroflow_live_record_{}.mp4", std::process::id()));
+        assert_eq!(sidecar_path(&record).file_name().unwrap().to_string_lossy(), format!("gyroflow_live_record_{}.mp4.quats.csv", std::process::id()));
+
+        enable(&record).unwrap();
+        let gyro = GyroSource::new();
+        for i in 0..5 {
+            record_frame(&gyro, i, i as i64 * 33_333);
+        }
+        finish();
+        assert!(!is_enabled());
+
+        let csv = std::fs::read_to_string(sidecar_path(&record)).unwrap();
+        let lines: Vec<_> = csv.lines().collect();
+        assert_eq!(lines[0], QuatTrace::CSV_HEADER);
+        assert_eq!(lines.len(), 6);
+        assert!(lines[5].starts_with("4,133332,"));
+        let _ = std::fs::remove_file(sidecar_path(&record));
+    }
+
+    #[test]
+    #[ignore = "needs ffmpeg and ffprobe on PATH"]
+    fn metadata_is_read_back_from_the_recording() {
+        let path = std::env::temp_dir().join(format!("gyroflow_live_metadata_{}.mp4", std::process::id()));
+        fplay::set_record_metadata([
+            ("gyroflow_lens_checksum".to_string(), "0123456789abcdef".to_string()),
+            ("gyroflow_smoothing".to_string(), r#"{"algorithm":"Plain 3D"}"#.to_string()),
+        ]);
+        let props = FProps { width: 64, height: 48, fps: 30.0, pixel_format: PixelFormat::Rgb24 };
+        let mut child = fplay::spawn_encoder(props, &path).unwrap();
+        let mut stdin = child.stdin.take().unwrap();
+        for i in 0..10_u8 {
+            stdin.write_all(&vec![i * 20; 64 * 48 * 3]).unwrap();
+        }
+        drop(stdin);
+        assert!(child.wait().unwrap().success());
+        fplay::set_record_metadata([]);
+
+        let probe = Command::new("ffprobe")
+            .args(["-v", "error", "-show_entries", "format_tags", "-of", "json"])
+            .arg(&path)
+            .output()
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&probe.stdout).unwrap();
+        let tags = &json["format"]["tags"];
+        assert_eq!(tags["gyroflow_lens_checksum"], "0123456789abcdef");
+        assert_eq!(tags["gyroflow_smoothing"], r#"{"algorithm":"Plain 3D"}"#);
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -1,20 +1,75 @@
 use gyroflow_core::gpu::{BufferDescription, Buffers, BufferSource};
 use crossbeam_channel::{Receiver, RecvTimeoutError};
-use log::{debug, info, warn, trace};
+use log::{debug, error, info, trace};
 use std::time::{Duration, Instant};
 use once_cell::sync::OnceCell;
 use gyroflow_core::StabilizationManager;
-use crate::live_pix_fmt::{LiveFrame, PixelFormat};
-use gyroflow_core::stmap_live::StmapItem;
+use crate::live_pix_fmt::{nv12_rows, LiveFrame, PixelFormat};
+use gyroflow_core::stmap_live::{StmapBuildFailed, StmapResult};
+use gyroflow_core::live::{Degradation, FrameTimeline, Heartbeat, LatencySla, MapRenderBackend, MapRenderer, NoImu, RenderStage, LOG_THROTTLE, StageTimer, StreamClock};
+use gyroflow_core::stmap_live::StmapsLive;
+use gyroflow_core::stmap::{decode_stmap, is_valid_stmap_coord, STMAP_INVALID_COORD};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
 use crate::fplay;
+use crate::record_meta;
+use crate::replay;
+use crate::restream;
+use crate::shm_sink;
+use crate::deflicker::Deflicker;
+use crate::supersample;
 use crate::Arc;
+use gyroflow_core::stabilization::Interpolation;
+use gyroflow_core::stabilization::PixelType;
+use gyroflow_core::stabilization_params::BackgroundMode;
 use gyroflow_core::stabilization::pixel_formats::{RGB8, RGBA8};
 
+/// How often the render loop beats its heartbeat while waiting for frames.
+const IDLE_BEAT_PERIOD: Duration = Duration::from_millis(100);
+
 #[derive(Clone, Copy)]
 pub struct LiveRenderConfig {
+    /// How long a frame waits for its STMap when rendering through the map worker, see `render_live_loop`.
     pub wait_for_map_timeout: Duration,
     pub trim_before_idx: bool,
-    pub present_fps: f64,
+    pub present_fps: PresentRate,
+    /// Pass frames through unchanged (identity map) when no stabilized frame is available,
+    /// instead of skipping them.
+    pub identity_fallback: bool,
+    /// Where STMaps are applied to RGBA frames: the worker's maps (see `render_live_loop`) and the identity fallback.
+    pub map_backend: MapRenderBackend,
+    /// Blend the STMap of a frame the map worker dropped from its neighbours instead of reusing the last one.
+    pub interpolate_missing_maps: bool,
+    /// Show the raw input next to or alternating with the stabilized output.
+    pub compare_mode: CompareMode,
+    /// Frames shown before switching between raw and stabilized in `CompareMode::Toggle`.
+    pub compare_toggle_frames: usize,
+    /// Pixel interpolation of the stabilization kernel.
+    pub interpolation: Interpolation,
+    /// Per-frame brightness normalization of the output, 0 (off) to 1.
+    pub deflicker_strength: f64,
+    /// Frame-to-present latency target in ms, exceeding it degrades quality step by step (0 = no target).
+    pub latency_sla_ms: f64,
+    /// Decoded frames allowed to wait in the queue, the oldest are skipped beyond that (0 = unbounded).
+    pub max_queued_frames: usize,
+    /// Bytes of STMaps cached ahead of the frames, the oldest are evicted beyond it (0 = unbounded).
+    pub max_map_cache_bytes: usize,
+    /// Stabilize only 1 of every N frames, the preview repeats the last output for the others (1 = every frame).
+    pub preview_stride: u32,
+    /// Seconds of presented frames kept for `replay::trigger_replay` (0 = no replay buffer).
+    pub replay_buffer_secs: f64,
+    /// Render at this multiple of the output resolution and area-average down, against aliasing of
+    /// rotated edges (1.0 = off). The remap cost grows with its square.
+    pub supersample: f32,
+    /// Stabilize before or after converting to the sink's pixel format.
+    pub processing_order: ProcessingOrder,
+    /// Send at most this many frames per second of stream time to the sink, evenly spaced by their
+    /// timestamps; the others are skipped before stabilization (`None` = every frame).
+    pub output_fps_cap: Option<u32>,
+    /// What happens to frames of the previous size still in flight when the source changes resolution.
+    pub resolution_transition: TransitionPolicy,
+    /// What happens to frames of another pixel format than the previous ones.
+    pub format_change: FormatChangePolicy,
 }
 
 impl Default for LiveRenderConfig {
@@ -22,7 +77,24 @@ impl Default for LiveRenderConfig {
         Self {
             wait_for_map_timeout: Duration::from_millis(8),
             trim_before_idx: true,
-            present_fps: 30.0,
+            present_fps: PresentRate::Auto,
+            identity_fallback: true,
+            map_backend: MapRenderBackend::Cpu,
+            interpolate_missing_maps: false,
+            compare_mode: CompareMode::Off,
+            compare_toggle_frames: 30,
+            interpolation: Interpolation::Bilinear,
+            deflicker_strength: 0.0,
+            latency_sla_ms: 0.0,
+            max_queued_frames: 0,
+            max_map_cache_bytes: 0,
+            preview_stride: 1,
+            replay_buffer_secs: 0.0,
+            supersample: 1.0,
+            processing_order: ProcessingOrder::PreConversion,
+            output_fps_cap: None,
+            resolution_transition: TransitionPolicy::Drop,
+            format_change: FormatChangePolicy::Adapt,
         }
     }
 
@@ -31,64 +103,698 @@ impl Default for LiveRenderConfig {
 }
 
 impl LiveRenderConfig {
-    pub fn new(present_fps: f64) -> Self {
+    pub fn new(present_fps: PresentRate) -> Self {
         Self {
             wait_for_map_timeout: Duration::from_millis(8),
             trim_before_idx: true,
-            present_fps: present_fps as f64,
+            present_fps,
+            identity_fallback: true,
+            map_backend: MapRenderBackend::Cpu,
+            interpolate_missing_maps: false,
+            compare_mode: CompareMode::Off,
+            compare_toggle_frames: 30,
+            interpolation: Interpolation::Bilinear,
+            deflicker_strength: 0.0,
+            latency_sla_ms: 0.0,
+            max_queued_frames: 0,
+            max_map_cache_bytes: 0,
+            preview_stride: 1,
+            replay_buffer_secs: 0.0,
+            supersample: 1.0,
+            processing_order: ProcessingOrder::PreConversion,
+            output_fps_cap: None,
+            resolution_transition: TransitionPolicy::Drop,
+            format_change: FormatChangePolicy::Adapt,
         }
     }
 }
 
+/// Why `render_live_loop` returned.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RenderExit {
+    /// The stop flag was set, clean shutdown.
+    Stopped,
+    /// All frame senders were dropped while still running, the stream reader died or hit the end of the stream.
+    ReaderDisconnected,
+    /// The sink (ffplay) could not be started.
+    SinkFailed,
+}
+
+/// A/B view of raw and stabilized frames, for judging the stabilization live.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum CompareMode {
+    #[default]
+    Off,
+    /// Raw input on the left half, stabilized output on the right half.
+    #[value(name = "split")]
+    SplitScreen,
+    /// Alternate between raw and stabilized every `compare_toggle_frames` frames.
+    Toggle,
+}
+
+/// When the stabilization runs relative to the conversion to the sink's pixel format (the one the
+/// recording encoder gets), like `ProcessingOrder` of the offline renderer.
+///
+/// Both give the same pixels up to the rounding of the interpolation, the RGB formats are all 8-bit,
+/// so the choice is about speed:
+/// - `PreConversion` converts the stabilized output, at the output size. Cheaper when the input has
+///   fewer bytes per pixel than the sink (RGB24 in, RGBA out) or the output is smaller than the input.
+/// - `PostConversion` converts the input and stabilizes in the sink's format, so the kernel writes
+///   what the encoder takes. Cheaper when the sink has fewer bytes per pixel (RGBA in, RGB24 out).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ProcessingOrder {
+    /// Stabilize in the input format, then convert.
+    #[default]
+    #[value(name = "pre")]
+    PreConversion,
+    /// Convert to the sink format, then stabilize.
+    #[value(name = "post")]
+    PostConversion,
+}
+
+/// Frames of the previous size that arrive after the source switched resolution mid-stream (the
+/// reader rescales to whatever the decoder outputs). The stabilization is set up for the new size
+/// at its first frame either way; the older frames decoded before the switch can't go through it as is.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum TransitionPolicy {
+    /// Skip them, the maps built for the previous size are flushed with the switch.
+    #[default]
+    Drop,
+    /// Scale them into the new size, centered with black bars.
+    Letterbox,
+}
+
+/// Frames in another pixel format than the previous ones, when the source switches format mid-stream
+/// (the reader rebuilds its scaler for whatever the decoder outputs).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum FormatChangePolicy {
+    /// Switch to the new format and reset the state kept for the previous one. NV12 is converted to the sink format first.
+    #[default]
+    Adapt,
+    /// Keep the format of the first frame, skip the frames of any other.
+    Drop,
+}
+
+/// What the render loop did to a frame, see `trace_step`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Step {
+    /// Converted to this format, before or after the kernel.
+    Convert(PixelFormat),
+    /// Stabilized.
+    Kernel,
+}
+
+#[cfg(test)]
+thread_local! {
+    static STEPS: std::cell::RefCell<Vec<Step>> = const { std::cell::RefCell::new(Vec::new()) };
+}
+
+/// Record `_step` for the tests, which check the order of the conversions and the kernel. A no-op otherwise.
+fn trace_step(_step: Step) {
+    #[cfg(test)]
+    STEPS.with(|steps| steps.borrow_mut().push(_step));
+}
+
+/// Frame in the format the kernel runs in for `order`: the sink's with `PostConversion`, unchanged
+/// with `PreConversion` (the output gets converted after stabilizing instead). NV12 is never converted here.
+fn kernel_input(order: ProcessingOrder, mut frame: LiveFrame, sink: PixelFormat) -> LiveFrame {
+    if order == ProcessingOrder::PreConversion || frame.pix_fmt == sink {
+        return frame;
+    }
+    frame.data = match (frame.pix_fmt, sink) {
+        (PixelFormat::Rgb24, PixelFormat::Rgba) => frame.data.chunks_exact(3).flat_map(|px| [px[0], px[1], px[2], 255]).collect(),
+        (PixelFormat::Rgba, PixelFormat::Rgb24) => frame.data.chunks_exact(4).flat_map(|px| [px[0], px[1], px[2]]).collect(),
+        _ => return frame,
+    };
+    frame.pix_fmt = sink;
+    trace_step(Step::Convert(sink));
+    frame
+}
+
+/// `frame` (NV12) converted to packed `target` (RGB24 or RGBA) with its YUV matrix, for the kernel
+/// that only takes packed pixels. `None` for odd sizes or a short buffer.
+fn packed_from_nv12(frame: &LiveFrame, target: PixelFormat) -> Option<LiveFrame> {
+    let bpp = match target {
+        PixelFormat::Rgb24 => 3,
+        PixelFormat::Rgba => 4,
+        PixelFormat::Nv12 => return None,
+    };
+    let (w, h) = (frame.width as usize, frame.height as usize);
+    let rows = nv12_rows(&frame.data, w, h)?;
+    let matrix = frame.yuv_matrix();
+    let mut data = vec![255u8; w * h * bpp];
+    for ((y_row, uv_row), out) in rows.zip(data.chunks_exact_mut(w * bpp)) {
+        for (x, px) in out.chunks_exact_mut(bpp).enumerate() {
+            px[..3].copy_from_slice(&matrix.to_rgb(y_row[x], uv_row[x & !1], uv_row[x | 1]));
+        }
+    }
+    Some(LiveFrame { ts_us: frame.ts_us, width: frame.width, height: frame.height, pix_fmt: target, data, colorspace: frame.colorspace, source_fps: frame.source_fps, ingest_us: frame.ingest_us })
+}
+
+/// Frame rate the sink is opened with.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PresentRate {
+    /// The frame rate the reader detected in the stream, `DEFAULT_PRESENT_FPS` when it has none.
+    Auto,
+    Fixed(f64),
+}
+
+/// Present rate of `PresentRate::Auto` for streams that don't signal a frame rate.
+pub const DEFAULT_PRESENT_FPS: f64 = 30.0;
+
+impl PresentRate {
+    pub fn fps(self, source_fps: Option<f64>) -> f64 {
+        match self {
+            PresentRate::Fixed(fps) => fps,
+            PresentRate::Auto => source_fps.filter(|fps| *fps > 0.0 && fps.is_finite()).unwrap_or(DEFAULT_PRESENT_FPS),
+        }
+    }
+}
+
+/// What the sink (ffplay) receives.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SinkFormat {
+    Rgb24,
+    Rgba,
+    /// RGBA where alpha marks the valid region of the stabilized frame: opaque where real pixels exist,
+    /// transparent in the borders that would otherwise show the background. For keying in compositing tools.
+    RgbaMask,
+}
+
+impl SinkFormat {
+    pub fn pix_fmt(self) -> PixelFormat {
+        match self {
+            SinkFormat::Rgb24 => PixelFormat::Rgb24,
+            SinkFormat::Rgba | SinkFormat::RgbaMask => PixelFormat::Rgba,
+        }
+    }
+}
+
+/// Maps are shared `Arc`s: consecutive identical maps point at the same buffer.
+type MapPair = (Arc<Vec<u8>>, Arc<Vec<u8>>);
+
+/// Maps received ahead of their frame, keyed by frame index.
+/// Each entry remembers the frame timestamp it was built for, so a map can't be handed to a different frame.
 struct MapCache {
     start_idx: usize,
-    buf: Vec<Option<(Vec<u8>, Vec<u8>)>>,
+    buf: Vec<Option<(i64, MapPair)>>,
+    /// Blend a map the worker never delivered from its neighbours, see `interpolate`.
+    interpolate_missing_maps: bool,
+    /// Last map handed out, the previous neighbour for `interpolate`.
+    last: Option<(i64, MapPair)>,
+    /// Bound of the cached maps in bytes, the oldest are evicted beyond it (0 = unbounded).
+    max_bytes: usize,
+    /// Most recent map build the worker reported as failed.
+    last_failure: Option<StmapBuildFailed>,
 }
 
 impl MapCache {
-    fn new() -> Self { Self { start_idx: 0, buf: Vec::new() } }
-    fn insert(&mut self, idx: usize, dist: Vec<u8>, undist: Vec<u8>) {
+    fn new(interpolate_missing_maps: bool) -> Self { Self { start_idx: 0, buf: Vec::new(), interpolate_missing_maps, last: None, max_bytes: 0, last_failure: None } }
+    fn set_max_bytes(&mut self, max_bytes: usize) { self.max_bytes = max_bytes; }
+    fn insert(&mut self, idx: usize, ts_us: i64, dist: Arc<Vec<u8>>, undist: Arc<Vec<u8>>) {
         if idx < self.start_idx { return; }
         let pos = idx - self.start_idx;
         if pos >= self.buf.len() { self.buf.resize(pos + 1, None); }
-        self.buf[pos] = Some((dist, undist));
+        self.buf[pos] = Some((ts_us, (dist, undist)));
+        if self.max_bytes > 0 {
+            let mut bytes = self.bytes();
+            for slot in self.buf.iter_mut() {
+                if bytes <= self.max_bytes { break; }
+                if let Some((_, (dist, undist))) = slot.take() {
+                    bytes -= dist.len() + undist.len();
+                }
+            }
+        }
     }
-    fn take(&mut self, idx: usize) -> Option<(Vec<u8>, Vec<u8>)> {
+    /// Bytes of the cached maps.
+    fn bytes(&self) -> usize {
+        self.buf.iter().flatten().map(|(_, (dist, undist))| dist.len() + undist.len()).sum()
+    }
+    fn take(&mut self, idx: usize, ts_us: i64) -> Option<MapPair> {
         if idx < self.start_idx { return None; }
         let pos = idx - self.start_idx;
         if pos >= self.buf.len() { return None; }
-        self.buf[pos].take()
+        match self.buf[pos].take() {
+            Some((map_ts, maps)) if map_ts == ts_us => {
+                self.last = Some((map_ts, maps.clone()));
+                Some(maps)
+            }
+            Some((map_ts, _)) => {
+                LOG_THROTTLE.warn("render_live: stale map", format_args!("render_live: discarding stale map for frame {idx} (built for ts {map_ts}, frame ts {ts_us})"));
+                None
+            }
+            None => None,
+        }
+    }
+    /// Map for a frame whose map never arrived (the worker dropped it under load), blended linearly
+    /// by timestamp between the last map handed out and the next cached one.
+    /// Adjacent maps are nearly identical, so this hides the hitch. `None` when disabled or a neighbour is missing.
+    fn interpolate(&mut self, idx: usize, ts_us: i64) -> Option<MapPair> {
+        if !self.interpolate_missing_maps || idx < self.start_idx { return None; }
+        let (prev_ts, prev) = self.last.clone()?;
+        let (next_ts, next) = self.buf.iter().skip(idx - self.start_idx + 1).flatten().next().cloned()?;
+        if !(prev_ts < ts_us && ts_us < next_ts) { return None; }
+        let t = (ts_us - prev_ts) as f32 / (next_ts - prev_ts) as f32;
+        let maps = (blend_maps(&prev.0, &next.0, t)?, blend_maps(&prev.1, &next.1, t)?);
+        debug!("render_live: interpolated missing map for frame {idx} ({t:.2} between ts {prev_ts} and {next_ts})");
+        self.last = Some((ts_us, maps.clone()));
+        Some(maps)
+    }
+    /// Drop every cached map, keeping the frame index the cache starts at.
+    fn clear(&mut self) {
+        self.buf.clear();
+        self.last = None;
     }
     fn trim_before(&mut self, keep_from: usize) {
         if keep_from <= self.start_idx { return; }
         let to_drop = (keep_from - self.start_idx).min(self.buf.len());
-        if to_drop > 0 {
-            self.buf.drain(0..to_drop);
-            self.start_idx = keep_from;
+        self.buf.drain(0..to_drop);
+        self.start_idx = keep_from;
+    }
+}
+
+/// Linear blend of two STMaps of the same size, `t` 0 is `a`. Pixels invalid in either map stay invalid.
+fn blend_maps(a: &Arc<Vec<u8>>, b: &Arc<Vec<u8>>, t: f32) -> Option<Arc<Vec<u8>>> {
+    if Arc::ptr_eq(a, b) { return Some(a.clone()); }
+    let (a, b) = (decode_stmap(a)?, decode_stmap(b)?);
+    if (a.width, a.height) != (b.width, b.height) { return None; }
+    let coords: Vec<f32> = a.coords.iter().zip(&b.coords).map(|(&a, &b)| {
+        if is_valid_stmap_coord(a) && is_valid_stmap_coord(b) { a + (b - a) * t } else { STMAP_INVALID_COORD }
+    }).collect();
+    Some(Arc::new(StmapsLive::encode_exr(a.width, a.height, &coords).ok()?))
+}
+
+/// Identity STMap (coords = pixel position) for one resolution.
+struct IdentityMap {
+    size: (usize, usize),
+    coords: Vec<f32>,
+    /// `None` when the EXR encoder failed, the coordinates are still good for a passthrough.
+    maps: Option<MapPair>,
+}
+
+/// Only depends on the resolution, so the last one is kept around.
+static IDENTITY_MAP: Mutex<Option<Arc<IdentityMap>>> = Mutex::new(None);
+
+fn identity_map(w: usize, h: usize) -> Arc<IdentityMap> {
+    let mut slot = IDENTITY_MAP.lock().unwrap();
+    if let Some(m) = slot.as_ref().filter(|m| m.size == (w, h)) {
+        return m.clone();
+    }
+    debug!("render_live: building identity map for {w}x{h}");
+    let coords: Vec<f32> = (0..h).flat_map(|y| (0..w).flat_map(move |x| [x as f32, y as f32])).collect();
+    // Same map both ways: undistorting and distorting an identity is still an identity
+    let maps = match StmapsLive::encode_exr(w, h, &coords) {
+        Ok(exr) => {
+            let exr = Arc::new(exr);
+            Some((exr.clone(), exr))
+        }
+        Err(e) => {
+            error!("render_live: failed to encode the identity map for {w}x{h}: {e}");
+            None
         }
+    };
+    let map = Arc::new(IdentityMap { size: (w, h), coords, maps });
+    *slot = Some(map.clone());
+    map
+}
+
+fn identity_map_fallback(w: u32, h: u32) -> Option<MapPair> {
+    identity_map(w as usize, h as usize).maps.clone()
+}
+
+/// Nearest-neighbour remap of `input` through pixel `coords` of the output; out-of-range coords are clamped.
+fn apply_coords(coords: &[f32], input: &[u8], in_size: (usize, usize), output: &mut [u8], out_size: (usize, usize), bpp: usize) {
+    for (i, px) in output.chunks_exact_mut(bpp).take(out_size.0 * out_size.1).enumerate() {
+        let sx = (coords[i * 2].round().max(0.0) as usize).min(in_size.0 - 1);
+        let sy = (coords[i * 2 + 1].round().max(0.0) as usize).min(in_size.1 - 1);
+        let src = (sy * in_size.0 + sx) * bpp;
+        px.copy_from_slice(&input[src..src + bpp]);
     }
 }
 
-fn identity_map_fallback(_w: u32, _h: u32) -> Option<(Vec<u8>, Vec<u8>)> { None }
+/// Unstabilized frame in the output buffer, used when stabilization fails (warm-up, map errors).
+/// RGBA frames go through `renderer`, so the map is applied on the GPU when that backend is selected.
+fn passthrough_frame(renderer: &mut MapRenderer, input: &[u8], in_size: (usize, usize), output: &mut [u8], out_size: (usize, usize), bpp: usize) {
+    let map = identity_map(out_size.0, out_size.1);
+    if bpp == 4 {
+        renderer.render(input, in_size, &map.coords, output, out_size);
+    } else {
+        apply_coords(&map.coords, input, in_size, output, out_size, bpp);
+    }
+}
+
+/// How the render loop stabilizes a frame.
+enum FrameMapping {
+    /// The stabilization kernel (`process_pixels`).
+    Kernel,
+    /// The STMap pair of the frame from the map worker, `None` when none arrived in time.
+    Map(Option<MapPair>),
+}
 
+/// Input pixel coordinates of every output pixel from an undistortion STMap, nearest map pixel per
+/// output pixel, and whether that map pixel is inside the lens FOV (the map's coverage mask when it
+/// has one). STMaps are normalized to the frame, so this holds at any map resolution.
+fn map_coords(undist: &[u8], in_size: (usize, usize), out_size: (usize, usize)) -> Option<(Vec<f32>, Vec<bool>)> {
+    let map = decode_stmap(undist)?;
+    if map.width == 0 || map.height == 0 || out_size.0 == 0 || out_size.1 == 0 {
+        return None;
+    }
+    let (sx, sy) = (in_size.0 as f32 / map.width as f32, in_size.1 as f32 / map.height as f32);
+    let mut coords = vec![0.0f32; out_size.0 * out_size.1 * 2];
+    let mut covered = vec![true; out_size.0 * out_size.1];
+    for (y, (row, row_covered)) in coords.chunks_exact_mut(out_size.0 * 2).zip(covered.chunks_exact_mut(out_size.0)).enumerate() {
+        let map_y = y * map.height / out_size.1;
+        for (x, (px, covered)) in row.chunks_exact_mut(2).zip(row_covered).enumerate() {
+            let i = map_y * map.width + x * map.width / out_size.0;
+            px[0] = map.coords[i * 2] * sx;
+            px[1] = map.coords[i * 2 + 1] * sy;
+            *covered = map.is_valid(i);
+        }
+    }
+    Some((coords, covered))
+}
+
+/// Background of the output pixels outside the lens FOV, like the kernel draws it: the solid color
+/// (transparent with the alpha mask). `None` for the other modes, the clamped remap repeats the edge instead.
+fn map_background(stab_man: &StabilizationManager) -> Option<[u8; 4]> {
+    let params = stab_man.params.read();
+    let bg = params.background;
+    matches!(params.background_mode, BackgroundMode::SolidColor).then(|| [bg[0], bg[1], bg[2], bg[3]].map(|c| (c * 255.0).round().clamp(0.0, 255.0) as u8))
+}
+
+/// Stabilize the frame in `buffers` as `mapping` says. STMaps go through `renderer` for RGBA (so the
+/// GPU backend applies them when selected), RGB24 is remapped on the CPU.
+fn stabilize<T: PixelType>(stab_man: &StabilizationManager, mapping: &FrameMapping, renderer: &mut MapRenderer, ts_us: i64, buffers: &mut Buffers) -> anyhow::Result<()> {
+    trace_step(Step::Kernel);
+    let (_dist, undist) = match mapping {
+        FrameMapping::Kernel => return stab_man.process_pixels::<T>(ts_us, None, buffers).map(|_| ()).map_err(Into::into),
+        FrameMapping::Map(maps) => maps.as_ref().ok_or_else(|| anyhow::anyhow!("no STMap for this frame yet"))?,
+    };
+    let (BufferSource::Cpu { buffer: input }, BufferSource::Cpu { buffer: output }) = (&buffers.input.data, &mut buffers.output.data) else {
+        anyhow::bail!("STMaps are only applied to CPU buffers");
+    };
+    let in_size = (buffers.input.size.0, buffers.input.size.1);
+    let out_size = (buffers.output.size.0, buffers.output.size.1);
+    let bpp = buffers.input.size.2 / in_size.0.max(1);
+    let (coords, covered) = map_coords(undist, in_size, out_size).ok_or_else(|| anyhow::anyhow!("can't decode the STMap"))?;
+    if bpp == 4 {
+        renderer.render(input, in_size, &coords, output, out_size);
+    } else {
+        apply_coords(&coords, input, in_size, output, out_size, bpp);
+    }
+    if let Some(background) = map_background(stab_man) {
+        for (px, _) in output.chunks_exact_mut(bpp).zip(&covered).filter(|(_, covered)| !**covered) {
+            px.copy_from_slice(&background[..bpp]);
+        }
+    }
+    Ok(())
+}
+
+/// Frames are copied instead of stabilized: no IMU sample arrived yet and `NoImu::Passthrough` is configured.
+fn no_imu_passthrough(stab_man: &StabilizationManager) -> bool {
+    stab_man.live_no_imu_action() == Some(NoImu::Passthrough)
+}
+
+/// Whether frame `frame_idx` shows (part of) the raw input in `cfg.compare_mode`.
+fn compare_shows_raw(cfg: &LiveRenderConfig, frame_idx: usize) -> bool {
+    match cfg.compare_mode {
+        CompareMode::Off => false,
+        CompareMode::SplitScreen => true,
+        CompareMode::Toggle => (frame_idx / cfg.compare_toggle_frames.max(1)) % 2 == 1,
+    }
+}
+
+/// Overlay the raw `input` on the stabilized `output`: its left half for `SplitScreen`, the whole frame otherwise.
+/// The raw frame goes through the identity map first, so it has the output geometry.
+fn apply_compare(mode: CompareMode, renderer: &mut MapRenderer, input: &[u8], in_size: (usize, usize), output: &mut [u8], out_size: (usize, usize), bpp: usize) {
+    let mut raw = vec![0u8; out_size.0 * out_size.1 * bpp];
+    passthrough_frame(renderer, input, in_size, &mut raw, out_size, bpp);
+    if mode == CompareMode::SplitScreen {
+        let stride = out_size.0 * bpp;
+        let half = (out_size.0 / 2) * bpp;
+        for (dst, src) in output.chunks_exact_mut(stride).zip(raw.chunks_exact(stride)) {
+            dst[..half].copy_from_slice(&src[..half]);
+        }
+    } else {
+        output[..raw.len()].copy_from_slice(&raw);
+    }
+}
+
+/// Map for frame `wanted_idx`, from the cache or by draining the worker channel until `deadline`.
+/// Maps of dropped or already forgotten frames are discarded instead of being cached.
+/// If the map still didn't arrive by then, it's interpolated from its neighbours when the cache allows it.
+/// A build failure reported for the frame ends the wait right away.
 fn drain_maps_until(
-    maps_rx: &Receiver<StmapItem>,
+    maps_rx: &Receiver<StmapResult>,
     cache: &mut MapCache,
+    timeline: &FrameTimeline,
     wanted_idx: usize,
     deadline: Instant,
-) -> Option<(Vec<u8>, Vec<u8>)> {
+) -> Option<MapPair> {
+    let wanted_ts = timeline.ts_of(wanted_idx)?;
+    if let Some(maps) = cache.take(wanted_idx, wanted_ts) { return Some(maps); }
     loop {
-        if Instant::now() >= deadline { return None; }
+        if Instant::now() >= deadline { break; }
         let left = deadline.saturating_duration_since(Instant::now());
         match maps_rx.recv_timeout(left) {
-            Ok((_fname, idx, dist, undist)) => {
-                if idx == wanted_idx { return Some((dist, undist)); }
-                cache.insert(idx, dist, undist);
+            Ok(Err(failed)) => {
+                LOG_THROTTLE.warn("render_live: map build", format_args!("render_live: {failed}"));
+                let idx = failed.frame_index;
+                cache.last_failure = Some(failed);
+                if idx == wanted_idx { break; }
             }
-            Err(RecvTimeoutError::Timeout) => return None,
-            Err(RecvTimeoutError::Disconnected) => return None,
+            Ok(Ok((_fname, idx, dist, undist))) => {
+                let Some(ts_us) = timeline.ts_of(idx) else {
+                    trace!("render_live: dropping map for frame {idx} (frame dropped or already presented)");
+                    continue;
+                };
+                if idx == wanted_idx {
+                    cache.last = Some((ts_us, (dist.clone(), undist.clone())));
+                    return Some((dist, undist));
+                }
+                cache.insert(idx, ts_us, dist, undist);
+            }
+            Err(RecvTimeoutError::Timeout) => break,
+            Err(RecvTimeoutError::Disconnected) => break,
         }
     }
+    cache.interpolate(wanted_idx, wanted_ts)
+}
+
+/// Steps of the latency SLA this loop can take, in order.
+const LATENCY_STEPS: [Degradation; 3] = [Degradation::DropLateFrames, Degradation::ReducedMapResolution, Degradation::FastInterpolation];
+
+/// Resolution of the STMaps relative to the frame under `Degradation::ReducedMapResolution`.
+const REDUCED_MAP_SCALE: f64 = 0.5;
+
+/// `LATENCY_STEPS` of a loop rendering through the map worker (`stmaps`) or not. The kernel builds
+/// no STMaps, so there is no map resolution to reduce.
+fn latency_steps(stmaps: bool) -> Vec<Degradation> {
+    LATENCY_STEPS.into_iter().filter(|&step| stmaps || step != Degradation::ReducedMapResolution).collect()
+}
+
+/// Picks the frames that go through stabilization when only every `stride`th one is previewed.
+#[derive(Debug)]
+struct PreviewStride {
+    stride: u64,
+    next: u64,
+}
+
+impl PreviewStride {
+    fn new(stride: u32) -> Self { Self { stride: stride.max(1) as u64, next: 0 } }
+
+    /// Whether the next frame is stabilized, the others repeat the last output.
+    fn stabilize_next(&mut self) -> bool {
+        let stabilize = self.next % self.stride == 0;
+        self.next += 1;
+        stabilize
+    }
+}
+
+/// Picks the frames sent to the sink under `LiveRenderConfig::output_fps_cap`.
+///
+/// Output slots are laid out every `1 / cap` s of stream time from the first frame; the first frame
+/// at (or a quarter slot before, against timestamp jitter) each slot is emitted. Unlike `present_fps`
+/// this depends on the timestamps only, so the same input always gives the same output frames.
+#[derive(Debug)]
+struct OutputRateCap {
+    period_us: Option<f64>,
+    next_us: Option<f64>,
+}
+
+impl OutputRateCap {
+    fn new(fps_cap: Option<u32>) -> Self {
+        Self { period_us: fps_cap.filter(|&fps| fps > 0).map(|fps| 1_000_000.0 / fps as f64), next_us: None }
+    }
+
+    /// Whether the frame at `ts_us` goes to the sink.
+    fn emit(&mut self, ts_us: i64) -> bool {
+        let Some(period) = self.period_us else { return true; };
+        let ts = ts_us as f64;
+        let tolerance = period / 4.0;
+        let next = match self.next_us {
+            // A timeline that jumped back (new stream) starts the slots over
+            Some(next) if ts >= next - 2.0 * period => next,
+            _ => ts,
+        };
+        if ts < next - tolerance {
+            self.next_us = Some(next);
+            return false;
+        }
+        // Slots a gap in the stream skipped over stay empty
+        let mut next = next + period;
+        while next - tolerance <= ts { next += period; }
+        self.next_us = Some(next);
+        true
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum SizeCheck {
+    /// The size the stabilization is set up for.
+    Current,
+    /// A new size from this frame on.
+    Changed { from: (u32, u32) },
+    /// The previous size, on a frame older than the switch: decoded before it, still in flight.
+    Stale,
+}
+
+/// How far before the last resolution switch a frame still counts as in flight, further back the
+/// stream restarted its timestamps.
+const IN_FLIGHT_WINDOW_US: i64 = 1_000_000;
+
+/// Follows the input resolution of the render loop. A frame of another size switches to it, unless
+/// it's timestamped before the last switch; those are leftovers of the old size, see `TransitionPolicy`.
+/// A frame more than `IN_FLIGHT_WINDOW_US` before the switch starts a new timeline instead.
+#[derive(Debug, Default)]
+struct ResolutionTracker {
+    size: Option<(u32, u32)>,
+    switched_at_us: i64,
+}
+
+impl ResolutionTracker {
+    fn check(&mut self, size: (u32, u32), ts_us: i64) -> SizeCheck {
+        let Some(current) = self.size else {
+            self.size = Some(size);
+            self.switched_at_us = ts_us;
+            return SizeCheck::Current;
+        };
+        if ts_us < self.switched_at_us - IN_FLIGHT_WINDOW_US {
+            self.switched_at_us = ts_us;
+        }
+        if size == current {
+            return SizeCheck::Current;
+        }
+        if ts_us < self.switched_at_us {
+            return SizeCheck::Stale;
+        }
+        self.size = Some(size);
+        self.switched_at_us = ts_us;
+        SizeCheck::Changed { from: current }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum FormatCheck {
+    /// The format of the previous frames.
+    Current,
+    /// A new format from this frame on.
+    Changed { from: PixelFormat },
+    /// Another format than the first frame's, under `FormatChangePolicy::Drop`.
+    Skip,
+}
+
+/// Follows the input pixel format of the render loop, see `FormatChangePolicy`.
+#[derive(Debug, Default)]
+struct FormatTracker {
+    pix_fmt: Option<PixelFormat>,
+}
+
+impl FormatTracker {
+    fn check(&mut self, pix_fmt: PixelFormat, policy: FormatChangePolicy) -> FormatCheck {
+        let current = *self.pix_fmt.get_or_insert(pix_fmt);
+        if pix_fmt == current {
+            return FormatCheck::Current;
+        }
+        if policy == FormatChangePolicy::Drop {
+            return FormatCheck::Skip;
+        }
+        self.pix_fmt = Some(pix_fmt);
+        FormatCheck::Changed { from: current }
+    }
+}
+
+/// Set the stabilization up for a new input size mid-stream. The output (and so the sink) keeps its
+/// size; the cached kernel parameters and the STMaps, built for the old size, are dropped.
+fn switch_input_size(stab_man: &StabilizationManager, map_cache: &mut MapCache, size: (usize, usize), render_size: (usize, usize)) {
+    stab_man.set_render_params(size, render_size);
+    stab_man.live_latest_stmap.clear();
+    map_cache.clear();
+}
+
+/// `frame` scaled (nearest neighbour) to fit `size`, centered on black. `None` for NV12.
+fn letterbox_frame(frame: &LiveFrame, size: (u32, u32)) -> Option<LiveFrame> {
+    let bpp = match frame.pix_fmt {
+        PixelFormat::Rgb24 => 3,
+        PixelFormat::Rgba => 4,
+        PixelFormat::Nv12 => return None,
+    };
+    let (iw, ih) = (frame.width as usize, frame.height as usize);
+    let (ow, oh) = (size.0 as usize, size.1 as usize);
+    if iw == 0 || ih == 0 || frame.data.len() < iw * ih * bpp {
+        return None;
+    }
+    let scale = (ow as f64 / iw as f64).min(oh as f64 / ih as f64);
+    let (sw, sh) = (((iw as f64 * scale).round() as usize).clamp(1, ow), ((ih as f64 * scale).round() as usize).clamp(1, oh));
+    let (x0, y0) = ((ow - sw) / 2, (oh - sh) / 2);
+    let mut data = vec![0u8; ow * oh * bpp];
+    if bpp == 4 {
+        data.chunks_exact_mut(4).for_each(|px| px[3] = 255);
+    }
+    for y in 0..sh {
+        let sy = (y * ih / sh).min(ih - 1);
+        let row = &mut data[((y0 + y) * ow + x0) * bpp..((y0 + y) * ow + x0 + sw) * bpp];
+        for (x, px) in row.chunks_exact_mut(bpp).enumerate() {
+            let sx = (x * iw / sw).min(iw - 1);
+            px.copy_from_slice(&frame.data[(sy * iw + sx) * bpp..(sy * iw + sx + 1) * bpp]);
+        }
+    }
+    Some(LiveFrame { ts_us: frame.ts_us, width: size.0, height: size.1, pix_fmt: frame.pix_fmt, data, colorspace: frame.colorspace, source_fps: frame.source_fps, ingest_us: frame.ingest_us })
+}
+
+/// Send a finished frame to the sink and feed its latency to the SLA policy.
+/// `last` keeps a copy of the frame for repeating it, see `LiveRenderConfig::preview_stride`.
+/// `stabilized` is the index and stage timer of a stabilized frame: the timer's post-processing and
+/// sink stages are closed and its breakdown recorded, and the frame's quaternions go to the metadata
+/// sidecar once it is in the sink, so the sidecar describes the frames of the recording.
+fn present(buf: &[u8], (ts_us, ingest_us): (i64, i64), clock: &mut StreamClock, sla: &mut LatencySla, stab_man: &StabilizationManager, last: Option<&mut Vec<u8>>, mut stabilized: Option<(usize, &mut StageTimer)>) -> anyhow::Result<()> {
+    if let Some((_, timer)) = stabilized.as_mut() {
+        timer.lap(RenderStage::Post);
+    }
+    if let Some(last) = last {
+        last.clear();
+        last.extend_from_slice(buf);
+    }
+    let res = fplay::push_frame(buf);
+    if let Some(props) = fplay::props() {
+        stab_man.live_latest_frame.publish(ts_us, (props.width as usize, props.height as usize), props.pixel_format.bytes_per_pixel(), buf);
+    }
+    replay::push(ts_us, buf);
+    restream::push(buf);
+    shm_sink::push(ts_us, buf);
+    sla.record(clock.latency_ms(ts_us));
+    if let Some((frame_idx, timer)) = stabilized {
+        timer.lap(RenderStage::Sink);
+        stab_man.live_render_timing.record(timer.finish());
+        if res.is_ok() {
+            record_meta::record_frame(&stab_man.gyro.read(), frame_idx, ts_us);
+        }
+    }
+    if res.is_ok() {
+        stab_man.live_session_stats.record_presented();
+        stab_man.live_e2e_latency.record(Duration::from_micros((clock.now_us() - ingest_us).max(0) as u64));
+    }
+    res
 }
 
 fn checksum(buf: &[u8]) -> u64 {
@@ -98,98 +804,308 @@ fn checksum(buf: &[u8]) -> u64 {
     h.finish()
 }
 
+/// Stabilize and present the frames of `frames_rx` until the stop flag is set or the reader goes away.
+/// With `maps`, frames are rendered through the STMaps of that worker instead of the kernel; those
+/// only correct the lens (and rolling shutter), like the STMaps exported offline.
 pub fn render_live_loop(
     frames_rx: Receiver<(usize, LiveFrame)>,
     stab_man: Arc<StabilizationManager>,
+    timeline: Arc<FrameTimeline>,
+    maps: Option<&StmapsLive>,
     cfg: LiveRenderConfig,
-    display_pix_fmt: PixelFormat, // <--- new: choose output format (Rgb24 / Rgba)
-) {
+    sink_fmt: SinkFormat,
+    stop: &AtomicBool,
+    heartbeat: &Heartbeat,
+) -> RenderExit {
     println!("render_live: start");
     let mut initialized = false;
+    let mut out_size = (0usize, 0usize); // presented size, may be a crop of the stabilized output
+    let mut render_size = (0usize, 0usize); // stabilized buffer size, `out_size` times `cfg.supersample`
+    let mut renderer = MapRenderer::new(cfg.map_backend);
+    let mut deflicker = Deflicker::new(cfg.deflicker_strength);
+    let mut sla = LatencySla::new(cfg.latency_sla_ms, &latency_steps(maps.is_some()), stab_man.live_latency_stats.clone());
+    let mut clock = StreamClock::new(stab_man.live_clock());
+    let mut fast_interpolation = false;
+    let mut reduced_maps = false;
+    // Every call is a new connection of the stream reader, the session totals carry on
+    let session = &stab_man.live_session_stats;
+    session.begin_connection();
+    let mut preview_stride = PreviewStride::new(cfg.preview_stride);
+    let mut output_cap = OutputRateCap::new(cfg.output_fps_cap);
+    let mut resolution = ResolutionTracker::default();
+    let mut format = FormatTracker::default();
+    let mut last_output = (cfg.preview_stride > 1).then(Vec::new);
+    let maps_rx = maps.map(|m| m.rx());
+    let mut map_cache = MapCache::new(cfg.interpolate_missing_maps);
+    map_cache.set_max_bytes(cfg.max_map_cache_bytes);
+    if cfg.replay_buffer_secs > 0.0 {
+        replay::enable(cfg.replay_buffer_secs);
+    }
 
-    while let Ok((_frame_idx, frame)) = frames_rx.recv() {
+    let exit = loop {
+        heartbeat.beat();
+        let profile = stab_man.live_latency_profile.get();
+        // Waiting for the next frame is not a stall, keep beating while idle
+        let (_frame_idx, mut frame) = match profile.recv_timeout(&frames_rx, IDLE_BEAT_PERIOD) {
+            Ok(f) => f,
+            Err(RecvTimeoutError::Timeout) if stop.load(Ordering::Relaxed) => break RenderExit::Stopped,
+            Err(RecvTimeoutError::Timeout) => continue,
+            Err(_) if stop.load(Ordering::Relaxed) => break RenderExit::Stopped,
+            Err(_) => {
+                error!("render_live: frame channel disconnected, the stream reader is gone");
+                break RenderExit::ReaderDisconnected;
+            }
+        };
+        if stop.load(Ordering::Relaxed) {
+            break RenderExit::Stopped;
+        }
+        session.record_received();
+
+        if timeline.is_dropped(_frame_idx) {
+            trace!("render_live: skipping dropped frame {_frame_idx}");
+            session.record_dropped();
+            continue;
+        }
+        let queued = frames_rx.len();
+        stab_man.live_memory_gauges.set_frame_queue(queued * frame.data.len());
+        if cfg.max_queued_frames > 0 && queued >= cfg.max_queued_frames {
+            // Over the memory budget: the oldest frame goes first, newer ones are waiting
+            trace!("render_live: skipping frame {_frame_idx}, {queued} frames queued");
+            timeline.mark_dropped(_frame_idx);
+            session.record_dropped();
+            continue;
+        }
+        if cfg.trim_before_idx {
+            timeline.trim_before(_frame_idx);
+        }
 
-        
-        let (w, h) = frame.get_size();
         let ts_us = frame.ts_us();
+        let ingest_us = frame.ingest_us;
+        match format.check(frame.pix_fmt, cfg.format_change) {
+            FormatCheck::Current => {}
+            FormatCheck::Changed { from } => {
+                log::info!("render_live: input pixel format changed from {from} to {} at frame {_frame_idx}", frame.pix_fmt);
+                // The brightness history of the old format would take the switch for flicker
+                deflicker = Deflicker::new(cfg.deflicker_strength);
+            }
+            FormatCheck::Skip => {
+                trace!("render_live: dropping frame {_frame_idx} in {}, the stream started in {}", frame.pix_fmt, format.pix_fmt.unwrap());
+                session.record_dropped();
+                continue;
+            }
+        }
+        if frame.pix_fmt == PixelFormat::Nv12 {
+            let Some(packed) = packed_from_nv12(&frame, sink_fmt.pix_fmt()) else {
+                LOG_THROTTLE.warn("render_live: nv12", format_args!("render_live: can't convert NV12 frame {_frame_idx} ({}x{}, {} bytes)", frame.width, frame.height, frame.data.len()));
+                session.record_dropped();
+                continue;
+            };
+            trace_step(Step::Convert(packed.pix_fmt));
+            frame = packed;
+        }
+        match resolution.check(frame.get_size(), ts_us) {
+            SizeCheck::Current => {}
+            SizeCheck::Changed { from } if initialized => {
+                let (w, h) = frame.get_size();
+                log::info!("render_live: input resolution changed from {}x{} to {w}x{h} at frame {_frame_idx}, output stays {}x{}", from.0, from.1, out_size.0, out_size.1);
+                switch_input_size(&stab_man, &mut map_cache, (w as usize, h as usize), render_size);
+            }
+            SizeCheck::Changed { .. } => {}
+            SizeCheck::Stale => {
+                let boxed = match cfg.resolution_transition {
+                    TransitionPolicy::Letterbox => resolution.size.and_then(|size| letterbox_frame(&frame, size)),
+                    TransitionPolicy::Drop => None,
+                };
+                let Some(boxed) = boxed else {
+                    trace!("render_live: dropping frame {_frame_idx} of the previous resolution");
+                    session.record_dropped();
+                    continue;
+                };
+                frame = boxed;
+            }
+        }
+        let (w, h) = frame.get_size();
+        if initialized && sla.should_drop(clock.latency_ms(ts_us)) {
+            trace!("render_live: dropping frame {_frame_idx}, already over the latency SLA");
+            stab_man.live_latency_stats.record_late_drop();
+            session.record_dropped();
+            continue;
+        }
+        if initialized && sla.is_active(Degradation::FastInterpolation) != fast_interpolation {
+            fast_interpolation = !fast_interpolation;
+            stab_man.set_live_interpolation(if fast_interpolation { Interpolation::Bilinear } else { cfg.interpolation });
+        }
+        if let Some(maps) = maps.filter(|_| initialized && sla.is_active(Degradation::ReducedMapResolution) != reduced_maps) {
+            reduced_maps = !reduced_maps;
+            maps.set_map_scale(if reduced_maps { REDUCED_MAP_SCALE } else { 1.0 });
+        }
         let ts_ms = ts_us as f64 / 1000.0;
+        let mut timer = StageTimer::start();
         stab_man.live_on_new_frame(_frame_idx, ts_ms, 1);
-        
+        let mapping = match (maps, maps_rx.as_ref()) {
+            (Some(maps), Some(maps_rx)) => {
+                maps.submit_frame(_frame_idx, ts_us);
+                let deadline = Instant::now() + cfg.wait_for_map_timeout;
+                let frame_maps = drain_maps_until(maps_rx, &mut map_cache, &timeline, _frame_idx, deadline);
+                if let Some(failed) = map_cache.last_failure.take().filter(|f| frame_maps.is_none() && f.frame_index == _frame_idx) {
+                    debug!("render_live: frame {_frame_idx} goes out with the previous maps, {failed}");
+                }
+                // The maps hold no rotation, so the last one stands in well for a late one
+                let frame_maps = frame_maps.or_else(|| map_cache.last.as_ref().map(|(_, maps)| maps.clone()));
+                if cfg.trim_before_idx {
+                    map_cache.trim_before(_frame_idx + 1);
+                }
+                stab_man.live_memory_gauges.set_map_cache(map_cache.bytes());
+                FrameMapping::Map(frame_maps)
+            }
+            _ => FrameMapping::Kernel,
+        };
+        timer.lap(RenderStage::MapWait);
+
         // Initialize stab + ffplay once we know the actual frame size
         if !initialized {
-            
-            stab_man.set_render_params((w as usize, h as usize), (w as usize, h as usize));
-            log::info!("Live stabilization initialized for {}x{}", w, h);
+            // Out-of-FOV pixels get the background color, a transparent background turns that into the mask
+            stab_man.set_live_alpha_mask(sink_fmt == SinkFormat::RgbaMask);
+            stab_man.set_live_interpolation(cfg.interpolation);
+            stab_man.set_render_params((w as usize, h as usize), supersample::render_size((w as usize, h as usize), cfg.supersample));
+            render_size = stab_man.live_output_buffer_size();
+            out_size = supersample::output_size(render_size, cfg.supersample);
+            log::info!("Live stabilization initialized for {}x{}, output {}x{}", w, h, out_size.0, out_size.1);
+            if render_size != out_size {
+                log::info!("Supersampling {}x: rendering at {}x{}", cfg.supersample, render_size.0, render_size.1);
+            }
 
             // init ffplay with the chosen display format (Rgb24 or Rgba)
-            if let Err(e) = fplay::init_ffplay(w, h, cfg.present_fps, display_pix_fmt) {
+            // The sink timestamps the frames by its frame rate, so that is the capped one
+            let present_fps = cfg.present_fps.fps(frame.source_fps);
+            let present_fps = cfg.output_fps_cap.map_or(present_fps, |cap| present_fps.min(cap as f64));
+            log::info!("Presenting at {present_fps} fps ({:?}, source {:?} fps)", cfg.present_fps, frame.source_fps);
+            if record_meta::is_enabled() {
+                fplay::set_record_metadata(record_meta::global_metadata(&stab_man));
+            }
+            if let Err(e) = fplay::init_ffplay(out_size.0 as u32, out_size.1 as u32, present_fps, sink_fmt.pix_fmt()) {
                 eprintln!("Failed to init ffplay: {e:?}");
-                return;
+                return RenderExit::SinkFailed;
             }
 
             initialized = true;
+            // Starting the sink is the first frame's sink time
+            timer.lap(RenderStage::Sink);
+        }
+
+        if !output_cap.emit(ts_us) {
+            trace!("render_live: frame {_frame_idx} skipped by the output frame rate cap");
+            continue;
+        }
+        if !preview_stride.stabilize_next() {
+            if let Some(last) = last_output.as_ref().filter(|b| !b.is_empty()) {
+                if let Err(e) = present(last, (ts_us, ingest_us), &mut clock, &mut sla, &stab_man, None, None) {
+                    LOG_THROTTLE.warn("render_live: push_frame", format_args!("fplay::push_frame failed (repeated preview): {e:?}"));
+                }
+            }
+            continue;
         }
+        session.record_stabilized();
 
+        // With `PostConversion` the output conversions below are no-ops, the frame is already in the sink format
+        let frame = kernel_input(cfg.processing_order, frame, sink_fmt.pix_fmt());
+        let passthrough = no_imu_passthrough(&stab_man);
         match frame.pix_fmt {
             PixelFormat::Rgb24 => {
                 // -------- RGB24 input path --------
                 let input_rgb = frame.as_rgb24();
                 if input_rgb.len() != (w as usize) * (h as usize) * 3 {
-                    eprintln!(
+                    LOG_THROTTLE.warn("render_live: buffer size", format_args!(
                         "render_live: bad RGB24 buffer size: got {}, expected {}",
                         input_rgb.len(),
                         (w as usize) * (h as usize) * 3
-                    );
+                    ));
+                    continue;
+                }
+
+                if sink_fmt == SinkFormat::RgbaMask {
+                    // The mask needs an alpha channel through the kernel, so expand to opaque RGBA first
+                    let mut input_rgba_vec = vec![255u8; (w as usize) * (h as usize) * 4];
+                    for (dst, src) in input_rgba_vec.chunks_exact_mut(4).zip(input_rgb.chunks_exact(3)) {
+                        dst[..3].copy_from_slice(src);
+                    }
+                    trace_step(Step::Convert(PixelFormat::Rgba));
+                    let mut output_rgba = vec![0u8; render_size.0 * render_size.1 * 4];
+                    let mut buffers = cpu_buffers(&mut input_rgba_vec, (w as usize, h as usize), &mut output_rgba, render_size, 4);
+                    timer.lap(RenderStage::BufferSetup);
+                    if passthrough {
+                        drop(buffers);
+                        passthrough_frame(&mut renderer, &input_rgba_vec, (w as usize, h as usize), &mut output_rgba, render_size, 4);
+                    } else if let Err(e) = stabilize::<RGBA8>(&stab_man, &mapping, &mut renderer, ts_us, &mut buffers) {
+                        LOG_THROTTLE.warn("render_live: process_pixels", format_args!("Stabilization failed at ts_us={ts_us} (RGB24->RGBA mask): {e:?}"));
+                        if !cfg.identity_fallback { continue; }
+                        drop(buffers);
+                        passthrough_frame(&mut renderer, &input_rgba_vec, (w as usize, h as usize), &mut output_rgba, render_size, 4);
+                    }
+                    timer.lap(RenderStage::Process);
+                    let mut output_rgba = supersample::downsample(output_rgba, render_size, out_size, 4);
+                    deflicker.apply(&mut output_rgba, 4);
+                    if compare_shows_raw(&cfg, _frame_idx) {
+                        apply_compare(cfg.compare_mode, &mut renderer, &input_rgba_vec, (w as usize, h as usize), &mut output_rgba, out_size, 4);
+                    }
+                    if let Err(e) = present(&output_rgba, (ts_us, ingest_us), &mut clock, &mut sla, &stab_man, last_output.as_mut(), Some((_frame_idx, &mut timer))) {
+                        LOG_THROTTLE.warn("render_live: push_frame", format_args!("fplay::push_frame failed (RGB24->RGBA mask): {e:?}"));
+                    }
                     continue;
                 }
 
                 let mut input_rgb_vec = input_rgb.to_vec();
-                let mut output_rgb = vec![0u8; (w as usize) * (h as usize) * 3];
+                let mut output_rgb = vec![0u8; render_size.0 * render_size.1 * 3];
 
                 let _in_before  = checksum(&input_rgb_vec);
                 let _out_before = checksum(&output_rgb);
 
-                let mut buffers = buffers_from_live_frame_rgb24(&frame, input_rgb_vec.as_mut_slice(), &mut output_rgb);
-
-                match stab_man.process_pixels::<RGB8>(ts_us, None, &mut buffers) {
-                    Ok(info) => {
-                        let _out_after = checksum(&output_rgb);
-                        
-
-                        // Decide how to send, based on display_pix_fmt
-                        match display_pix_fmt {
-                            PixelFormat::Rgb24 => {
-                                if let Err(e) = fplay::push_frame(&output_rgb) {
-                                    eprintln!("fplay::push_frame failed (RGB24->RGB24): {e:?}");
-                                }
-                            }
-                            PixelFormat::Rgba => {
-                                // Convert RGB24 -> RGBA for display
-                                let w_usize = w as usize;
-                                let h_usize = h as usize;
-                                let mut output_rgba = vec![0u8; w_usize * h_usize * 4];
-
-                                for i in 0..(w_usize * h_usize) {
-                                    let src = i * 3;
-                                    let dst = i * 4;
-                                    output_rgba[dst    ] = output_rgb[src    ];
-                                    output_rgba[dst + 1] = output_rgb[src + 1];
-                                    output_rgba[dst + 2] = output_rgb[src + 2];
-                                    output_rgba[dst + 3] = 255;
-                                }
-
-                                if let Err(e) = fplay::push_frame(&output_rgba) {
-                                    eprintln!("fplay::push_frame failed (RGB24->RGBA): {e:?}");
-                                }
-                            }
-                            PixelFormat::Nv12 => {
-                                eprintln!("render_live: display_pix_fmt=NV12 is not supported for ffplay");
-                            }
+                let mut buffers = buffers_from_live_frame_rgb24(&frame, input_rgb_vec.as_mut_slice(), &mut output_rgb, render_size);
+
+                timer.lap(RenderStage::BufferSetup);
+                if passthrough {
+                    drop(buffers);
+                    passthrough_frame(&mut renderer, &input_rgb_vec, (w as usize, h as usize), &mut output_rgb, render_size, 3);
+                } else if let Err(e) = stabilize::<RGB8>(&stab_man, &mapping, &mut renderer, ts_us, &mut buffers) {
+                    LOG_THROTTLE.warn("render_live: process_pixels", format_args!("Stabilization failed at ts_us={ts_us} (RGB24): {e:?}"));
+                    if !cfg.identity_fallback { continue; }
+                    drop(buffers);
+                    passthrough_frame(&mut renderer, &input_rgb_vec, (w as usize, h as usize), &mut output_rgb, render_size, 3);
+                }
+                timer.lap(RenderStage::Process);
+                let mut output_rgb = supersample::downsample(output_rgb, render_size, out_size, 3);
+                deflicker.apply(&mut output_rgb, 3);
+                if compare_shows_raw(&cfg, _frame_idx) {
+                    apply_compare(cfg.compare_mode, &mut renderer, &input_rgb_vec, (w as usize, h as usize), &mut output_rgb, out_size, 3);
+                }
+
+                let _out_after = checksum(&output_rgb);
+
+                // Decide how to send, based on sink_fmt
+                match sink_fmt {
+                    SinkFormat::Rgb24 => {
+                        if let Err(e) = present(&output_rgb, (ts_us, ingest_us), &mut clock, &mut sla, &stab_man, last_output.as_mut(), Some((_frame_idx, &mut timer))) {
+                            LOG_THROTTLE.warn("render_live: push_frame", format_args!("fplay::push_frame failed (RGB24->RGB24): {e:?}"));
                         }
                     }
-                    Err(e) => {
-                        eprintln!("Stabilization failed at ts_us={ts_us} (RGB24): {e:?}");
-                        continue;
+                    SinkFormat::Rgba | SinkFormat::RgbaMask => {
+                        // Convert RGB24 -> RGBA for display
+                        let (w_usize, h_usize) = out_size;
+                        let mut output_rgba = vec![0u8; w_usize * h_usize * 4];
+
+                        for i in 0..(w_usize * h_usize) {
+                            let src = i * 3;
+                            let dst = i * 4;
+                            output_rgba[dst    ] = output_rgb[src    ];
+                            output_rgba[dst + 1] = output_rgb[src + 1];
+                            output_rgba[dst + 2] = output_rgb[src + 2];
+                            output_rgba[dst + 3] = 255;
+                        }
+                        trace_step(Step::Convert(PixelFormat::Rgba));
+
+                        if let Err(e) = present(&output_rgba, (ts_us, ingest_us), &mut clock, &mut sla, &stab_man, last_output.as_mut(), Some((_frame_idx, &mut timer))) {
+                            LOG_THROTTLE.warn("render_live: push_frame", format_args!("fplay::push_frame failed (RGB24->RGBA): {e:?}"));
+                        }
                     }
                 }
             }
@@ -199,81 +1115,126 @@ pub fn render_live_loop(
                 
                 let input_rgba = frame.as_rgba();
                 if input_rgba.len() != (w as usize) * (h as usize) * 4 {
-                    eprintln!(
+                    LOG_THROTTLE.warn("render_live: buffer size", format_args!(
                         "render_live: bad RGBA buffer size: got {}, expected {}",
                         input_rgba.len(),
                         (w as usize) * (h as usize) * 4
-                    );
+                    ));
                     continue;
                 }
 
                 let mut input_rgba_vec = input_rgba.to_vec();
-                let mut output_rgba = vec![0u8; (w as usize) * (h as usize) * 4];
-
-                let mut buffers = buffers_from_live_frame_rgba(&frame, input_rgba_vec.as_mut_slice(), &mut output_rgba);
-
-                match stab_man.process_pixels::<RGBA8>(ts_us, None, &mut buffers) {
-                    Ok(info) => {
-                        
-
-                        match display_pix_fmt {
-                            PixelFormat::Rgba => {
-                                // Already RGBA, send directly
-                                if let Err(e) = fplay::push_frame(&output_rgba) {
-                                    eprintln!("fplay::push_frame failed (RGBA->RGBA): {e:?}");
-                                }
-                            }
-                            PixelFormat::Rgb24 => {
-                                // Convert RGBA -> RGB24 (drop alpha)
-                                let w_usize = w as usize;
-                                let h_usize = h as usize;
-                                let mut output_rgb = vec![0u8; w_usize * h_usize * 3];
-
-                                for i in 0..(w_usize * h_usize) {
-                                    let src = i * 4;
-                                    let dst = i * 3;
-                                    output_rgb[dst    ] = output_rgba[src    ];
-                                    output_rgb[dst + 1] = output_rgba[src + 1];
-                                    output_rgb[dst + 2] = output_rgba[src + 2];
-                                }
-
-                                if let Err(e) = fplay::push_frame(&output_rgb) {
-                                    eprintln!("fplay::push_frame failed (RGBA->RGB24): {e:?}");
-                                }
-                            }
-                            PixelFormat::Nv12 => {
-                                eprintln!("render_live: display_pix_fmt=NV12 is not supported for ffplay");
-                            }
+                let mut output_rgba = vec![0u8; render_size.0 * render_size.1 * 4];
+
+                let mut buffers = buffers_from_live_frame_rgba(&frame, input_rgba_vec.as_mut_slice(), &mut output_rgba, render_size);
+                if sink_fmt == SinkFormat::RgbaMask {
+                    // Input alpha must be opaque, otherwise it leaks into the mask
+                    if let BufferSource::Cpu { buffer } = &mut buffers.input.data {
+                        buffer.chunks_exact_mut(4).for_each(|px| px[3] = 255);
+                    }
+                }
+
+                timer.lap(RenderStage::BufferSetup);
+                if passthrough {
+                    drop(buffers);
+                    passthrough_frame(&mut renderer, &input_rgba_vec, (w as usize, h as usize), &mut output_rgba, render_size, 4);
+                } else if let Err(e) = stabilize::<RGBA8>(&stab_man, &mapping, &mut renderer, ts_us, &mut buffers) {
+                    LOG_THROTTLE.warn("render_live: process_pixels", format_args!("Stabilization failed at ts_us={ts_us} (RGBA): {e:?}"));
+                    if !cfg.identity_fallback { continue; }
+                    drop(buffers);
+                    passthrough_frame(&mut renderer, &input_rgba_vec, (w as usize, h as usize), &mut output_rgba, render_size, 4);
+                }
+                timer.lap(RenderStage::Process);
+                let mut output_rgba = supersample::downsample(output_rgba, render_size, out_size, 4);
+                deflicker.apply(&mut output_rgba, 4);
+                if compare_shows_raw(&cfg, _frame_idx) {
+                    apply_compare(cfg.compare_mode, &mut renderer, &input_rgba_vec, (w as usize, h as usize), &mut output_rgba, out_size, 4);
+                }
+
+                match sink_fmt {
+                    SinkFormat::Rgba | SinkFormat::RgbaMask => {
+                        // Already RGBA, send directly
+                        if let Err(e) = present(&output_rgba, (ts_us, ingest_us), &mut clock, &mut sla, &stab_man, last_output.as_mut(), Some((_frame_idx, &mut timer))) {
+                            LOG_THROTTLE.warn("render_live: push_frame", format_args!("fplay::push_frame failed (RGBA->RGBA): {e:?}"));
                         }
                     }
-                    Err(e) => {
-                        eprintln!("Stabilization failed at ts_us={ts_us} (RGBA): {e:?}");
-                        continue;
+                    SinkFormat::Rgb24 => {
+                        // Convert RGBA -> RGB24 (drop alpha)
+                        let (w_usize, h_usize) = out_size;
+                        let mut output_rgb = vec![0u8; w_usize * h_usize * 3];
+
+                        for i in 0..(w_usize * h_usize) {
+                            let src = i * 4;
+                            let dst = i * 3;
+                            output_rgb[dst    ] = output_rgba[src    ];
+                            output_rgb[dst + 1] = output_rgba[src + 1];
+                            output_rgb[dst + 2] = output_rgba[src + 2];
+                        }
+                        trace_step(Step::Convert(PixelFormat::Rgb24));
+
+                        if let Err(e) = present(&output_rgb, (ts_us, ingest_us), &mut clock, &mut sla, &stab_man, last_output.as_mut(), Some((_frame_idx, &mut timer))) {
+                            LOG_THROTTLE.warn("render_live: push_frame", format_args!("fplay::push_frame failed (RGBA->RGB24): {e:?}"));
+                        }
                     }
                 }
             }
 
-            PixelFormat::Nv12 => {
-                eprintln!(
-                    "render_live: received NV12 frame ({}x{}), but NV12 is not yet handled in render_live_loop. \
-                     Choose Rgb24 or Rgba as stream target format if you want stabilization.",
-                    w, h
-                );
-                continue;
-            }
+            // Converted to the sink format when it arrived
+            PixelFormat::Nv12 => continue,
         }
-    }
+    };
 
-    log::info!("render_live: exit");
+    // The SLA of the next connection starts at full quality
+    if let Some(maps) = maps.filter(|_| reduced_maps) {
+        maps.set_map_scale(1.0);
+    }
+    if let Some(pool) = renderer.pool_stats() {
+        log::info!("render_live: GPU map buffers reused {} times, allocated {} times, {} freed", pool.hits, pool.misses, pool.evicted);
+    }
+    // Problems of this connection still held back by the throttle
+    LOG_THROTTLE.flush();
+    // A reconnect publishes again with its first frame
+    stab_man.live_latest_frame.clear();
+    log::info!("render_live: exit ({exit:?})");
     //fplay::shutdown_ffplay();
+    exit
 }
 
 // ------------------------ buffer helpers ------------------------
+// Input and output must be separate allocations, `process_pixels` rejects overlapping CPU buffers
+// (see `Buffers`); processing in place needs a copy of the input first.
+
+pub(crate) fn cpu_buffers<'a>(
+    input: &'a mut [u8],
+    in_size: (usize, usize),
+    output: &'a mut [u8],
+    out_size: (usize, usize),
+    bytes_per_pixel: usize,
+) -> Buffers<'a> {
+    let input_desc = BufferDescription {
+        size: (in_size.0, in_size.1, in_size.0 * bytes_per_pixel),
+        rect: None,
+        rotation: None,
+        data: BufferSource::Cpu { buffer: input },
+        texture_copy: false,
+    };
+
+    let output_desc = BufferDescription {
+        size: (out_size.0, out_size.1, out_size.0 * bytes_per_pixel),
+        rect: None,
+        rotation: None,
+        data: BufferSource::Cpu { buffer: output },
+        texture_copy: false,
+    };
+
+    Buffers { input: input_desc, output: output_desc }
+}
 
 fn buffers_from_live_frame_rgb24<'a>(
     frame: &'a LiveFrame,
     input_rgb: &'a mut [u8],
     output_rgb: &'a mut [u8],
+    out_size: (usize, usize),
 ) -> Buffers<'a> {
     let (w, h) = frame.get_size();
     let w_usize = w as usize;
@@ -292,7 +1253,7 @@ fn buffers_from_live_frame_rgb24<'a>(
     };
 
     let output_desc = BufferDescription {
-        size: (w_usize, h_usize, stride),
+        size: (out_size.0, out_size.1, out_size.0 * 3),
         rect: None,
         rotation: None,
         data: BufferSource::Cpu { buffer: output_rgb },
@@ -306,6 +1267,7 @@ fn buffers_from_live_frame_rgba<'a>(
     frame: &'a LiveFrame,
     input_rgba: &'a mut [u8],
     output_rgba: &'a mut [u8],
+    out_size: (usize, usize),
 ) -> Buffers<'a> {
     let (w, h) = frame.get_size();
     let w_usize = w as usize;
@@ -324,7 +1286,7 @@ fn buffers_from_live_frame_rgba<'a>(
     };
 
     let output_desc = BufferDescription {
-        size: (w_usize, h_usize, stride),
+        size: (out_size.0, out_size.1, out_size.0 * 4),
         rect: None,
         rotation: None,
         data: BufferSource::Cpu { buffer: output_rgba },
@@ -333,3 +1295,575 @@ fn buffers_from_live_frame_rgba<'a>(
 
     Buffers { input: input_desc, output: output_desc }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossbeam_channel::unbounded;
+
+    fn map(idx: usize) -> Arc<Vec<u8>> { Arc::new(vec![idx as u8]) }
+
+    #[test]
+    fn dropped_frame_does_not_desync_map_cache() {
+        let timeline = FrameTimeline::new();
+        for i in 0..10 {
+            timeline.register(i * 33_333);
+        }
+        timeline.mark_dropped(5);
+
+        // The worker still finishes a map for frame 5, and results arrive out of order
+        let (tx, rx) = unbounded::<StmapResult>();
+        for idx in [6, 5, 8, 7, 9] {
+            tx.send(Ok((String::new(), idx, map(idx), map(idx)))).unwrap();
+        }
+
+        let mut cache = MapCache::new(false);
+        let deadline = Instant::now() + Duration::from_millis(50);
+        assert!(drain_maps_until(&rx, &mut cache, &timeline, 5, deadline).is_none());
+        for idx in 6..10 {
+            let (dist, undist) = drain_maps_until(&rx, &mut cache, &timeline, idx, deadline).expect("map missing");
+            assert_eq!((dist[0], undist[0]), (idx as u8, idx as u8), "wrong map for frame {idx}");
+            cache.trim_before(idx + 1);
+        }
+        assert_eq!(timeline.index_of(6 * 33_333), Some(6));
+    }
+
+    #[test]
+    fn reader_crash_is_reported() {
+        let stab = Arc::new(StabilizationManager::default());
+        let cfg = LiveRenderConfig::default();
+
+        // Sender dropped while running: the reader died
+        let (tx, rx) = unbounded::<(usize, LiveFrame)>();
+        drop(tx);
+        let stop = AtomicBool::new(false);
+        let exit = render_live_loop(rx, stab.clone(), Arc::new(FrameTimeline::new()), None, cfg, SinkFormat::Rgba, &stop, &Heartbeat::new());
+        assert_eq!(exit, RenderExit::ReaderDisconnected);
+
+        // Same disconnect after a stop request is a clean shutdown
+        let (tx, rx) = unbounded::<(usize, LiveFrame)>();
+        drop(tx);
+        stop.store(true, Ordering::Relaxed);
+        let exit = render_live_loop(rx, stab, Arc::new(FrameTimeline::new()), None, cfg, SinkFormat::Rgba, &stop, &Heartbeat::new());
+        assert_eq!(exit, RenderExit::Stopped);
+    }
+
+    #[test]
+    fn preview_stride_stabilizes_every_nth_frame() {
+        let mut stride = PreviewStride::new(3);
+        let stabilized: Vec<usize> = (0..10).filter(|_| stride.stabilize_next()).collect();
+        assert_eq!(stabilized, vec![0, 3, 6, 9]);
+
+        let mut every = PreviewStride::new(1);
+        assert!((0..10).all(|_| every.stabilize_next()));
+        // 0 is treated as 1
+        let mut zero = PreviewStride::new(0);
+        assert!((0..10).all(|_| zero.stabilize_next()));
+    }
+
+    #[test]
+    fn output_fps_cap_emits_evenly_spaced_frames() {
+        // 2 s at 60 fps with ±1 µs of rounding jitter
+        let ts: Vec<i64> = (0..120_i64).map(|i| (i as f64 * 1_000_000.0 / 60.0).round() as i64 + (i % 3 - 1)).collect();
+        let mut cap = OutputRateCap::new(Some(30));
+        let emitted: Vec<usize> = (0..ts.len()).filter(|&i| cap.emit(ts[i])).collect();
+        assert_eq!(emitted.len(), 60);
+        assert!(emitted.iter().enumerate().all(|(n, &i)| i == n * 2), "{emitted:?}");
+        // 30 of the first second's 60 frames
+        assert_eq!(emitted.iter().filter(|&&i| i < 60).count(), 30);
+
+        // Deterministic: the same timestamps give the same frames
+        let mut again = OutputRateCap::new(Some(30));
+        assert_eq!((0..ts.len()).filter(|&i| again.emit(ts[i])).collect::<Vec<_>>(), emitted);
+
+        // At or above the source rate nothing is skipped
+        let mut uncapped = OutputRateCap::new(Some(60));
+        assert!(ts.iter().all(|&t| uncapped.emit(t)));
+        let mut none = OutputRateCap::new(None);
+        assert!(ts.iter().all(|&t| none.emit(t)));
+    }
+
+    #[test]
+    fn session_stats_survive_a_reconnect() {
+        let stab = Arc::new(StabilizationManager::default());
+        let timeline = Arc::new(FrameTimeline::new());
+        let stop = AtomicBool::new(false);
+        // One connection of the reader: `frames` frames the reader itself dropped, then it dies
+        let connection = |frames: usize| {
+            let (tx, rx) = unbounded::<(usize, LiveFrame)>();
+            for _ in 0..frames {
+                let ts_us = timeline.next_index() as i64 * 33_333;
+                let idx = timeline.register(ts_us);
+                timeline.mark_dropped(idx);
+                let frame = LiveFrame { ts_us, width: 2, height: 2, pix_fmt: PixelFormat::Rgba, data: vec![0; 16], colorspace: None, source_fps: None, ingest_us: 0 };
+                tx.send((idx, frame)).unwrap();
+            }
+            drop(tx);
+            render_live_loop(rx, stab.clone(), timeline.clone(), None, LiveRenderConfig::default(), SinkFormat::Rgba, &stop, &Heartbeat::new())
+        };
+
+        assert_eq!(connection(3), RenderExit::ReaderDisconnected);
+        let first = stab.live_session_stats.snapshot();
+        assert_eq!((first.session.received, first.connection.received, first.connections), (3, 3, 1));
+
+        assert_eq!(connection(2), RenderExit::ReaderDisconnected);
+        let after = stab.live_session_stats.snapshot();
+        assert_eq!(after.session.received, 5);
+        assert_eq!(after.session.dropped, 5);
+        assert_eq!(after.connection.received, 2);
+        assert_eq!(after.connection.dropped, 2);
+        assert_eq!(after.reconnects(), 1);
+    }
+
+    #[test]
+    fn split_screen_shows_raw_and_stabilized_halves() {
+        let (w, h) = (33, 8);
+        let input: Vec<u8> = (0..w * h * 4).map(|i| (i * 7 % 251) as u8).collect();
+        let stabilized = vec![0xAAu8; w * h * 4];
+        let cfg = LiveRenderConfig { compare_mode: CompareMode::SplitScreen, ..Default::default() };
+        let mut renderer = MapRenderer::new(MapRenderBackend::Cpu);
+
+        assert!(compare_shows_raw(&cfg, 0));
+        let mut output = stabilized.clone();
+        apply_compare(cfg.compare_mode, &mut renderer, &input, (w, h), &mut output, (w, h), 4);
+        let (stride, half) = (w * 4, (w / 2) * 4);
+        for y in 0..h {
+            let row = &output[y * stride..(y + 1) * stride];
+            assert_eq!(&row[..half], &input[y * stride..y * stride + half], "row {y}: left half is not the raw input");
+            assert!(row[half..].iter().all(|&v| v == 0xAA), "row {y}: right half is not the stabilized output");
+        }
+
+        // Toggle alternates whole frames
+        let cfg = LiveRenderConfig { compare_mode: CompareMode::Toggle, compare_toggle_frames: 10, ..Default::default() };
+        assert!(!compare_shows_raw(&cfg, 5));
+        assert!(compare_shows_raw(&cfg, 15));
+        let mut output = stabilized.clone();
+        apply_compare(cfg.compare_mode, &mut renderer, &input, (w, h), &mut output, (w, h), 4);
+        assert_eq!(output, input);
+        assert!(!compare_shows_raw(&LiveRenderConfig::default(), 15));
+    }
+
+    #[test]
+    fn identity_map_reproduces_input() {
+        let (w, h) = (37, 21);
+        let input: Vec<u8> = (0..w * h * 4).map(|i| (i * 7 % 251) as u8).collect();
+        let mut output = vec![0u8; input.len()];
+        passthrough_frame(&mut MapRenderer::new(MapRenderBackend::Cpu), &input, (w, h), &mut output, (w, h), 4);
+        assert_eq!(output, input);
+
+        // Built once per resolution
+        let a = identity_map_fallback(w as u32, h as u32).unwrap();
+        let b = identity_map_fallback(w as u32, h as u32).unwrap();
+        assert!(Arc::ptr_eq(&a.0, &b.0));
+        assert!(!a.0.is_empty());
+    }
+
+    #[test]
+    fn missing_map_is_interpolated_from_neighbours() {
+        let (w, h) = (8, 4);
+        let timeline = FrameTimeline::new();
+        for i in 0..3 {
+            timeline.register(i * 33_333);
+        }
+        // Coordinates shifted by a different amount per frame
+        let shifted = |dx: f32| -> Vec<f32> { (0..h).flat_map(|y| (0..w).flat_map(move |x| [x as f32 + dx, y as f32])).collect() };
+        let exr = |dx: f32| Arc::new(StmapsLive::encode_exr(w, h, &shifted(dx)).unwrap());
+        let (before, after) = (shifted(0.0), shifted(4.0));
+
+        let (tx, rx) = unbounded::<StmapResult>();
+        let deadline = || Instant::now() + Duration::from_millis(10);
+        for interpolate in [false, true] {
+            // The worker dropped the map of frame 1
+            tx.send(Ok((String::new(), 0, exr(0.0), exr(0.0)))).unwrap();
+            tx.send(Ok((String::new(), 2, exr(4.0), exr(4.0)))).unwrap();
+            let mut cache = MapCache::new(interpolate);
+            assert!(drain_maps_until(&rx, &mut cache, &timeline, 0, deadline()).is_some());
+            let maps = drain_maps_until(&rx, &mut cache, &timeline, 1, deadline());
+            if !interpolate {
+                assert!(maps.is_none());
+                continue;
+            }
+            let (dist, undist) = maps.expect("map not interpolated");
+            for map in [dist, undist] {
+                let coords = decode_stmap(&map).unwrap().coords;
+                for (i, c) in coords.iter().enumerate() {
+                    let (lo, hi) = (before[i].min(after[i]), before[i].max(after[i]));
+                    assert!(*c >= lo - 1e-3 && *c <= hi + 1e-3, "coord {i}: {c} not within {lo}..{hi}");
+                }
+                assert!((coords[0] - 2.0).abs() < 1e-3, "x of the first pixel: {}", coords[0]);
+            }
+            // The next frame still gets its real map
+            let (dist, _) = drain_maps_until(&rx, &mut cache, &timeline, 2, deadline()).unwrap();
+            assert!(Arc::ptr_eq(&dist, &cache.last.as_ref().unwrap().1.0));
+        }
+    }
+
+    #[test]
+    fn frames_are_rendered_through_their_stmap() {
+        let stab = StabilizationManager::default();
+        let (w, h) = (8, 4);
+        // Half the resolution of the frame, shifted by one frame pixel to the right
+        let (mw, mh) = (w / 2, h / 2);
+        let coords: Vec<f32> = (0..mh).flat_map(|y| (0..mw).flat_map(move |x| [x as f32 + 0.5, y as f32])).collect();
+        let exr = Arc::new(StmapsLive::encode_exr(mw, mh, &coords).unwrap());
+        let mut renderer = MapRenderer::new(MapRenderBackend::Cpu);
+
+        for bpp in [3, 4] {
+            let mut input: Vec<u8> = (0..w * h).flat_map(|i| vec![(i % w) as u8 * 10; bpp]).collect();
+            let mut output = vec![0u8; input.len()];
+            let mapping = FrameMapping::Map(Some((exr.clone(), exr.clone())));
+            let mut buffers = cpu_buffers(&mut input, (w, h), &mut output, (w, h), bpp);
+            stabilize::<RGBA8>(&stab, &mapping, &mut renderer, 0, &mut buffers).unwrap();
+            drop(buffers);
+            // Each output pixel comes from the map pixel covering it, one frame pixel to its right
+            for x in 0..w {
+                assert_eq!(output[x * bpp] as usize, (x / 2 * 2 + 1) * 10, "{bpp} bpp, pixel {x}");
+            }
+
+            // No map yet: an error, so the identity fallback applies
+            let mut buffers = cpu_buffers(&mut input, (w, h), &mut output, (w, h), bpp);
+            assert!(stabilize::<RGBA8>(&stab, &FrameMapping::Map(None), &mut renderer, 0, &mut buffers).is_err());
+        }
+    }
+
+    #[test]
+    fn pixels_outside_the_map_coverage_get_the_background() {
+        use gyroflow_core::stmap::{encode_stmap, StMapBlocks, StMapChannels};
+        let stab = StabilizationManager::default();
+        let (w, h) = (8, 4);
+        // Identity at half the resolution, the left map column is outside the lens FOV
+        let (mw, mh) = (w / 2, h / 2);
+        let coords: Vec<f32> = (0..mh).flat_map(|y| (0..mw).flat_map(move |x| [x as f32, y as f32])).collect();
+        let covered: Vec<bool> = (0..mw * mh).map(|i| i % mw != 0).collect();
+        let exr = Arc::new(encode_stmap(mw, mh, &coords, &covered, StMapChannels::RgMask, StMapBlocks::Scanline).unwrap());
+        let mapping = FrameMapping::Map(Some((exr.clone(), exr)));
+        let mut renderer = MapRenderer::new(MapRenderBackend::Cpu);
+
+        for (mode, edge) in [(BackgroundMode::SolidColor, 0), (BackgroundMode::RepeatPixels, 10)] {
+            stab.params.write().background_mode = mode;
+            for bpp in [3, 4] {
+                let mut input: Vec<u8> = (0..w * h).flat_map(|i| vec![(i % w) as u8 * 10 + 10; bpp]).collect();
+                let mut output = vec![0u8; input.len()];
+                let mut buffers = cpu_buffers(&mut input, (w, h), &mut output, (w, h), bpp);
+                stabilize::<RGBA8>(&stab, &mapping, &mut renderer, 0, &mut buffers).unwrap();
+                drop(buffers);
+                for x in 0..w {
+                    // The default background is transparent black, the edge modes keep the clamped input
+                    let expected = if x < 2 { edge } else { (x / 2 * 2) * 10 + 10 };
+                    assert_eq!(output[(w + x) * bpp] as usize, expected, "{mode:?}, {bpp} bpp, pixel {x}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn auto_present_rate_follows_the_source() {
+        use crate::live_pix_fmt::detected_fps;
+        use ffmpeg_next::util::rational::Rational;
+
+        // 60 fps stream (average rate unknown, base rate 60) -> sink opened at 60 fps
+        let source_fps = detected_fps(Rational(0, 1), Rational(60, 1));
+        assert_eq!(source_fps, Some(60.0));
+        let cfg = LiveRenderConfig::default();
+        assert_eq!(cfg.present_fps.fps(source_fps), 60.0);
+        assert_eq!(PresentRate::Auto.fps(detected_fps(Rational(24000, 1001), Rational(24, 1))), 24000.0 / 1001.0);
+
+        // The override wins, streams without a rate fall back to the default
+        assert_eq!(LiveRenderConfig::new(PresentRate::Fixed(25.0)).present_fps.fps(source_fps), 25.0);
+        assert_eq!(PresentRate::Auto.fps(detected_fps(Rational(0, 1), Rational(0, 0))), DEFAULT_PRESENT_FPS);
+    }
+
+    #[test]
+    fn map_build_failure_reaches_the_render_loop() {
+        use gyroflow_core::stmap::StMapError;
+
+        let timeline = FrameTimeline::new();
+        for i in 0..3 {
+            timeline.register(i * 33_333);
+        }
+        // The worker's encoder failed on frame 1, even after its retry
+        let (tx, rx) = unbounded::<StmapResult>();
+        tx.send(Ok((String::new(), 0, map(0), map(0)))).unwrap();
+        let encode_error = StMapError::Encode(exr::error::Error::Invalid("simulated encoder failure".into()));
+        tx.send(Err(StmapBuildFailed { frame_index: 1, error: encode_error.into() })).unwrap();
+        tx.send(Ok((String::new(), 2, map(2), map(2)))).unwrap();
+
+        let mut cache = MapCache::new(false);
+        let deadline = Instant::now() + Duration::from_secs(5);
+        assert!(drain_maps_until(&rx, &mut cache, &timeline, 0, deadline).is_some());
+        // No empty map for frame 1, and no waiting for one until the deadline
+        let start = Instant::now();
+        assert!(drain_maps_until(&rx, &mut cache, &timeline, 1, deadline).is_none());
+        assert!(start.elapsed() < Duration::from_secs(1));
+        let failed = cache.last_failure.as_ref().expect("build failure not reported");
+        assert_eq!(failed.frame_index, 1);
+        assert!(matches!(failed.encoder_error(), Some(StMapError::Encode(_))));
+        // The frames after it get their maps
+        assert_eq!(drain_maps_until(&rx, &mut cache, &timeline, 2, deadline).unwrap().0[0], 2);
+    }
+
+    #[test]
+    fn map_cache_evicts_oldest_maps_over_its_budget() {
+        let map = |idx: usize| Arc::new(vec![idx as u8; 100]);
+        let mut cache = MapCache::new(false);
+        cache.set_max_bytes(3 * 200);
+        for idx in 0..10 {
+            cache.insert(idx, idx as i64, map(idx), map(idx));
+            assert!(cache.bytes() <= 3 * 200, "{} bytes after frame {idx}", cache.bytes());
+        }
+        assert!(cache.take(6, 6).is_none());
+        for idx in 7..10 {
+            assert_eq!(cache.take(idx, idx as i64).unwrap().0[0], idx as u8);
+        }
+    }
+
+    #[test]
+    fn processing_order_converts_before_or_after_the_kernel() {
+        let rgb = || LiveFrame { ts_us: 0, width: 2, height: 1, pix_fmt: PixelFormat::Rgb24, data: vec![1, 2, 3, 4, 5, 6], colorspace: None, source_fps: None, ingest_us: 0 };
+
+        // Pre: the kernel gets the input as is
+        let pre = kernel_input(ProcessingOrder::PreConversion, rgb(), PixelFormat::Rgba);
+        assert_eq!((pre.pix_fmt, pre.data), (PixelFormat::Rgb24, rgb().data));
+
+        // Post: converted to the sink format first
+        let post = kernel_input(ProcessingOrder::PostConversion, rgb(), PixelFormat::Rgba);
+        assert_eq!((post.pix_fmt, &post.data), (PixelFormat::Rgba, &vec![1, 2, 3, 255, 4, 5, 6, 255]));
+        let back = kernel_input(ProcessingOrder::PostConversion, post, PixelFormat::Rgb24);
+        assert_eq!((back.pix_fmt, back.data), (PixelFormat::Rgb24, rgb().data));
+    }
+
+    /// Serializes the tests that drive the render loop into the sink, `fplay` has a single global player.
+    static SINK: Mutex<()> = Mutex::new(());
+
+    /// `frames` through the render loop into a `sink_fmt` sink: the bytes the sink got and the steps traced on the way.
+    fn render_to_sink(stab: Arc<StabilizationManager>, frames: Vec<LiveFrame>, cfg: LiveRenderConfig, sink_fmt: SinkFormat) -> (Vec<u8>, Vec<Step>) {
+        use std::io::Read;
+        use std::net::{TcpListener, TcpStream};
+
+        let _sink = SINK.lock().unwrap_or_else(|e| e.into_inner());
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        fplay::connect_next_to(TcpStream::connect(listener.local_addr().unwrap()).unwrap());
+        let (mut received, _) = listener.accept().unwrap();
+
+        let timeline = Arc::new(FrameTimeline::new());
+        let (tx, rx) = unbounded::<(usize, LiveFrame)>();
+        for frame in frames {
+            tx.send((timeline.register(frame.ts_us), frame)).unwrap();
+        }
+        drop(tx);
+        STEPS.with(|steps| steps.borrow_mut().clear());
+        let exit = render_live_loop(rx, stab, timeline, None, cfg, sink_fmt, &AtomicBool::new(false), &Heartbeat::new());
+        assert_eq!(exit, RenderExit::ReaderDisconnected);
+        fplay::shutdown_ffplay();
+        let mut bytes = Vec::new();
+        received.read_to_end(&mut bytes).unwrap();
+        (bytes, STEPS.with(|steps| steps.take()))
+    }
+
+    #[test]
+    fn render_loop_converts_in_the_processing_order() {
+        let rgb = |i: i64| LiveFrame { ts_us: i * 33_333, width: 4, height: 2, pix_fmt: PixelFormat::Rgb24, data: vec![100; 4 * 2 * 3], colorspace: None, source_fps: None, ingest_us: 0 };
+        for (order, expected) in [
+            (ProcessingOrder::PreConversion, [Step::Kernel, Step::Convert(PixelFormat::Rgba)]),
+            (ProcessingOrder::PostConversion, [Step::Convert(PixelFormat::Rgba), Step::Kernel]),
+        ] {
+            let stab = Arc::new(StabilizationManager::default());
+            stab.init_from_stream_data(30.0, (4, 2));
+            let cfg = LiveRenderConfig { processing_order: order, ..Default::default() };
+            let (bytes, steps) = render_to_sink(stab, (0..3).map(rgb).collect(), cfg, SinkFormat::Rgba);
+            // Every frame reaches the sink as RGBA, converted on the side of the kernel the order says
+            assert_eq!(bytes.len(), 3 * 4 * 2 * 4, "{order:?}");
+            assert_eq!(steps, expected.repeat(3), "{order:?}");
+        }
+    }
+
+    #[test]
+    fn end_to_end_latency_runs_from_the_ingest_stamp_to_the_sink() {
+        use gyroflow_core::live::ManualClock;
+
+        // The sink pushes every frame at `SINK_US`, the reader took them 100..=1 ms before
+        const SINK_US: i64 = 1_000_000;
+        let stab = Arc::new(StabilizationManager::default());
+        stab.init_from_stream_data(30.0, (4, 2));
+        stab.use_live_clock(Arc::new(ManualClock::new(SINK_US)));
+        let frame = |i: i64| LiveFrame {
+            ts_us: i * 33_333, width: 4, height: 2, pix_fmt: PixelFormat::Rgba, data: vec![100; 4 * 2 * 4], colorspace: None, source_fps: None,
+            ingest_us: SINK_US - (100 - i) * 1000,
+        };
+        let (bytes, _) = render_to_sink(stab.clone(), (0..100).map(frame).collect(), LiveRenderConfig::default(), SinkFormat::Rgba);
+        assert_eq!(bytes.len(), 100 * 4 * 2 * 4);
+        let s = stab.live_e2e_latency.snapshot();
+        assert_eq!((s.frames, s.p50_ms, s.p95_ms, s.p99_ms, s.max_ms), (100, 50.0, 95.0, 99.0, 100.0));
+    }
+
+    #[test]
+    fn map_resolution_is_a_step_only_when_rendering_through_maps() {
+        assert_eq!(latency_steps(true), LATENCY_STEPS);
+        assert_eq!(latency_steps(false), [Degradation::DropLateFrames, Degradation::FastInterpolation]);
+    }
+
+    #[test]
+    fn no_imu_passthrough_lasts_until_the_first_sample() {
+        use gyroflow_core::gyro_source::live::LiveImuSample;
+
+        let stab = StabilizationManager::default();
+        stab.init_from_stream_data(30.0, (64, 48));
+        stab.gyro.read().enable_live(3.0, 1.0, 0.0, 30.0);
+        // Video without IMU: the default lens-corrects, passthrough copies
+        assert!(!no_imu_passthrough(&stab));
+        stab.set_live_no_imu_behavior(NoImu::Passthrough);
+        assert!(no_imu_passthrough(&stab));
+
+        stab.gyro.read().push_live_imu_batch(&[(LiveImuSample { ts_sensor_us: 0, gyro: [0.0; 3], accel: Some([0.0, 0.0, 1.0]) }, 0)]);
+        assert!(!no_imu_passthrough(&stab));
+    }
+
+    #[test]
+    fn resolution_switch_720p_to_1080p_with_frames_in_flight() {
+        let stab = Arc::new(StabilizationManager::default());
+        stab.init_from_stream_data(30.0, (1280, 720));
+        stab.gyro.read().enable_live(3.0, 1.0, 0.0, 30.0);
+        stab.set_device(-1);
+        stab.set_render_params((1280, 720), (1280, 720));
+        let render_size = stab.live_output_buffer_size();
+        let frame = |ts_us: i64, (w, h): (u32, u32)| LiveFrame {
+            ts_us, width: w, height: h, pix_fmt: PixelFormat::Rgba, data: vec![200; w as usize * h as usize * 4], colorspace: None, source_fps: None, ingest_us: 0,
+        };
+        let stabilize = |frame: &LiveFrame| {
+            let mut input = frame.data.clone();
+            let mut output = vec![0u8; render_size.0 * render_size.1 * 4];
+            let mut buffers = cpu_buffers(&mut input, (frame.width as usize, frame.height as usize), &mut output, render_size, 4);
+            stab.process_pixels::<RGBA8>(frame.ts_us, None, &mut buffers).map(|_| ())
+        };
+
+        let mut resolution = ResolutionTracker::default();
+        let hd = frame(0, (1280, 720));
+        assert_eq!(resolution.check(hd.get_size(), hd.ts_us), SizeCheck::Current);
+        stabilize(&hd).expect("720p frame");
+        stab.live_latest_stmap.publish(&(String::new(), 0, Arc::new(vec![1]), Arc::new(vec![1])));
+        let mut map_cache = MapCache::new(false);
+        map_cache.insert(1, 33_333, Arc::new(vec![1]), Arc::new(vec![1]));
+        map_cache.last = Some((0, (Arc::new(vec![1]), Arc::new(vec![1]))));
+
+        // The source switches to 1080p at frame 2; frame 1 was decoded before the switch but arrives after it
+        let full_hd = frame(66_667, (1920, 1080));
+        assert_eq!(resolution.check(full_hd.get_size(), full_hd.ts_us), SizeCheck::Changed { from: (1280, 720) });
+        switch_input_size(&stab, &mut map_cache, (1920, 1080), render_size);
+        assert_eq!(stab.stabilization.read().size, (1920, 1080));
+        assert_eq!(stab.live_output_buffer_size(), render_size, "the sink keeps its size");
+        assert!(stab.live_latest_stmap.get().is_none(), "the 720p map was not flushed");
+        assert!(map_cache.bytes() == 0 && map_cache.last.is_none(), "the 720p maps stayed cached");
+        stabilize(&full_hd).expect("1080p frame after the switch");
+
+        let in_flight = frame(33_333, (1280, 720));
+        assert_eq!(resolution.check(in_flight.get_size(), in_flight.ts_us), SizeCheck::Stale);
+        let boxed = letterbox_frame(&in_flight, resolution.size.unwrap()).unwrap();
+        assert_eq!((boxed.get_size(), boxed.data.len(), boxed.ts_us), ((1920, 1080), 1920 * 1080 * 4, 33_333));
+        stabilize(&boxed).expect("letterboxed 720p frame");
+
+        // Later frames of the new size are current, and a later 720p frame is a switch back
+        assert_eq!(resolution.check((1920, 1080), 100_000), SizeCheck::Current);
+        assert_eq!(resolution.check((1280, 720), 133_333), SizeCheck::Changed { from: (1920, 1080) });
+
+        // The timestamps restart along with a resize: a new timeline, not a frame in flight
+        assert_eq!(resolution.check((1920, 1080), 0), SizeCheck::Changed { from: (1280, 720) });
+        assert_eq!(resolution.check((1920, 1080), 33_333), SizeCheck::Current);
+
+        // Another aspect ratio gets black bars: 4:3 into 16:9 is 1440 wide, centered
+        let sd = LiveFrame { data: vec![90; 640 * 480 * 3], pix_fmt: PixelFormat::Rgb24, ..frame(0, (640, 480)) };
+        let boxed = letterbox_frame(&sd, (1920, 1080)).unwrap();
+        let px = |x: usize, y: usize| &boxed.data[(y * 1920 + x) * 3..(y * 1920 + x + 1) * 3];
+        assert_eq!((px(239, 540), px(240, 540), px(1679, 540), px(1680, 540)), (&[0, 0, 0][..], &[90, 90, 90][..], &[90, 90, 90][..], &[0, 0, 0][..]));
+    }
+
+    #[test]
+    fn nv12_frames_are_converted_with_their_matrix() {
+        use crate::live_pix_fmt::YuvMatrix;
+        // Saturated red in limited range BT.601, which reads as a different red in BT.709
+        let (y, u, v) = (81u8, 90u8, 240u8);
+        let frame = |(w, h): (u32, u32), colorspace: Option<YuvMatrix>| {
+            let mut data = vec![y; (w * h) as usize];
+            data.extend((0..w * h / 4).flat_map(|_| [u, v]));
+            LiveFrame { ts_us: 0, width: w, height: h, pix_fmt: PixelFormat::Nv12, data, colorspace, source_fps: None, ingest_us: 0 }
+        };
+        assert_ne!(YuvMatrix::Bt601.to_rgb(y, u, v), YuvMatrix::Bt709.to_rgb(y, u, v));
+        for (size, colorspace, matrix) in [
+            ((64, 48), Some(YuvMatrix::Bt601), YuvMatrix::Bt601),
+            ((64, 48), Some(YuvMatrix::Bt709), YuvMatrix::Bt709),
+            ((64, 48), Some(YuvMatrix::Bt2020), YuvMatrix::Bt2020),
+            // Unsignalled: BT.601 for SD, BT.709 for HD
+            ((64, 48), None, YuvMatrix::Bt601),
+            ((1280, 720), None, YuvMatrix::Bt709),
+        ] {
+            let packed = packed_from_nv12(&frame(size, colorspace), PixelFormat::Rgb24).unwrap();
+            assert!(packed.data.chunks_exact(3).all(|px| px == matrix.to_rgb(y, u, v)), "{size:?} {colorspace:?}");
+        }
+    }
+
+    #[test]
+    fn pixel_format_switch_rgb24_to_nv12() {
+        let (w, h) = (64u32, 48u32);
+        let stab = StabilizationManager::default();
+        stab.init_from_stream_data(30.0, (w as usize, h as usize));
+        stab.gyro.read().enable_live(3.0, 1.0, 0.0, 30.0);
+        stab.set_device(-1);
+        stab.set_render_params((w as usize, h as usize), (w as usize, h as usize));
+        let render_size = stab.live_output_buffer_size();
+        let stabilize = |frame: &LiveFrame| {
+            let mut input = frame.data.clone();
+            let mut output = vec![0u8; render_size.0 * render_size.1 * 3];
+            let mut buffers = cpu_buffers(&mut input, (frame.width as usize, frame.height as usize), &mut output, render_size, 3);
+            stab.process_pixels::<RGB8>(frame.ts_us, None, &mut buffers).map(|_| ())
+        };
+        let frame = |ts_us: i64, pix_fmt: PixelFormat, data: Vec<u8>| LiveFrame {
+            ts_us, width: w, height: h, pix_fmt, data, colorspace: None, source_fps: None, ingest_us: 0,
+        };
+        // NV12 white on the left half, black on the right; limited range, neutral chroma
+        let nv12 = || {
+            let mut data: Vec<u8> = (0..w * h).map(|i| if i % w < w / 2 { 235 } else { 16 }).collect();
+            data.resize((w * h * 3 / 2) as usize, 128);
+            data
+        };
+
+        let mut format = FormatTracker::default();
+        let rgb = frame(0, PixelFormat::Rgb24, vec![90; (w * h * 3) as usize]);
+        assert_eq!(format.check(rgb.pix_fmt, FormatChangePolicy::Adapt), FormatCheck::Current);
+        stabilize(&rgb).expect("RGB24 frame");
+
+        // The source switches to NV12: the loop follows and converts to the sink format before the kernel
+        let yuv = frame(33_333, PixelFormat::Nv12, nv12());
+        assert_eq!(format.check(yuv.pix_fmt, FormatChangePolicy::Adapt), FormatCheck::Changed { from: PixelFormat::Rgb24 });
+        let packed = packed_from_nv12(&yuv, PixelFormat::Rgb24).unwrap();
+        assert_eq!((packed.pix_fmt, packed.data.len(), packed.ts_us), (PixelFormat::Rgb24, (w * h * 3) as usize, 33_333));
+        let px = |f: &LiveFrame, x: u32, bpp: u32| f.data[(x * bpp) as usize..((x + 1) * bpp) as usize].to_vec();
+        assert_eq!((px(&packed, 0, 3), px(&packed, w - 1, 3)), (vec![255; 3], vec![0; 3]));
+        stabilize(&packed).expect("NV12 frame after the switch");
+        assert_eq!(format.check(PixelFormat::Nv12, FormatChangePolicy::Adapt), FormatCheck::Current);
+
+        // An RGBA sink gets opaque pixels; odd sizes and short buffers can't be converted
+        assert_eq!(px(&packed_from_nv12(&yuv, PixelFormat::Rgba).unwrap(), 0, 4), vec![255; 4]);
+        assert!(packed_from_nv12(&LiveFrame { width: w - 1, ..frame(0, PixelFormat::Nv12, nv12()) }, PixelFormat::Rgb24).is_none());
+        assert!(packed_from_nv12(&frame(0, PixelFormat::Nv12, vec![16; 10]), PixelFormat::Rgb24).is_none());
+
+        // `Drop` keeps the first format
+        let mut first_only = FormatTracker::default();
+        assert_eq!(first_only.check(PixelFormat::Rgb24, FormatChangePolicy::Drop), FormatCheck::Current);
+        assert_eq!(first_only.check(PixelFormat::Nv12, FormatChangePolicy::Drop), FormatCheck::Skip);
+        assert_eq!(first_only.check(PixelFormat::Rgb24, FormatChangePolicy::Drop), FormatCheck::Current);
+
+        // Through the render loop: the NV12 frames after the RGB24 one are converted on the way, the sink gets them all
+        let stab = Arc::new(StabilizationManager::default());
+        stab.init_from_stream_data(30.0, (w as usize, h as usize));
+        stab.gyro.read().enable_live(3.0, 1.0, 0.0, 30.0);
+        stab.set_live_no_imu_behavior(NoImu::Passthrough);
+        let frames = vec![rgb, frame(33_333, PixelFormat::Nv12, nv12()), frame(66_666, PixelFormat::Nv12, nv12())];
+        let (bytes, steps) = render_to_sink(stab, frames, LiveRenderConfig::default(), SinkFormat::Rgb24);
+        let frame_len = (w * h * 3) as usize;
+        assert_eq!(bytes.len(), 3 * frame_len);
+        assert_eq!(steps.iter().filter(|s| **s == Step::Convert(PixelFormat::Rgb24)).count(), 2, "{steps:?}");
+        assert!(bytes[..frame_len].iter().all(|&b| b == 90));
+        for out in bytes.chunks_exact(frame_len).skip(1) {
+            assert_eq!((&out[..3], &out[frame_len - 3..]), (&[255; 3][..], &[0; 3][..]));
+        }
+    }
+}
@@ -0,0 +1,110 @@
+use anyhow::{bail, Result};
+use std::collections::VecDeque;
+use std::io::Write;
+use std::path::Path;
+use std::sync::{Arc, Mutex, OnceLock};
+
+use crate::fplay::{self, FProps};
+use crate::live_pix_fmt::PixelFormat;
+
+/// The last seconds of presented frames, as RGB24, for instant replays.
+///
+/// Frames are appended as they are presented and the ones older than the window are dropped.
+/// `trigger_replay` takes a snapshot (the frames are shared, not copied) and encodes it with
+/// the same settings as `--record`, while new frames keep coming in.
+pub struct ReplayBuffer {
+    window_us: i64,
+    frames: Mutex<VecDeque<(i64, Arc<Vec<u8>>)>>,
+}
+
+impl ReplayBuffer {
+    pub fn new(secs: f64) -> Self {
+        Self { window_us: (secs * 1_000_000.0).round() as i64, frames: Mutex::new(VecDeque::new()) }
+    }
+
+    /// Append a presented frame in `pixel_format` (RGB24 or RGBA).
+    pub fn push(&self, ts_us: i64, buf: &[u8], pixel_format: PixelFormat) {
+        let rgb = match pixel_format {
+            PixelFormat::Rgb24 => buf.to_vec(),
+            PixelFormat::Rgba => buf.chunks_exact(4).flat_map(|px| [px[0], px[1], px[2]]).collect(),
+            PixelFormat::Nv12 => return,
+        };
+        let mut frames = self.frames.lock().unwrap();
+        frames.push_back((ts_us, Arc::new(rgb)));
+        while frames.front().is_some_and(|(ts, _)| ts_us - ts >= self.window_us) {
+            frames.pop_front();
+        }
+    }
+
+    pub fn len(&self) -> usize { self.frames.lock().unwrap().len() }
+
+    /// Write the buffered frames, oldest first, as raw RGB24. Returns the number of frames written.
+    pub fn dump_to(&self, out: &mut impl Write) -> std::io::Result<usize> {
+        let snapshot: Vec<_> = self.frames.lock().unwrap().iter().map(|(_, f)| Arc::clone(f)).collect();
+        for frame in &snapshot {
+            out.write_all(frame)?;
+        }
+        Ok(snapshot.len())
+    }
+
+    /// Encode the buffered frames into `path`, `props` being the sink's. Blocks until ffmpeg is done.
+    pub fn trigger_replay(&self, path: &Path, props: FProps) -> Result<usize> {
+        let mut child = fplay::spawn_encoder(FProps { pixel_format: PixelFormat::Rgb24, ..props }, path)?;
+        let written = child.stdin.take().map(|mut stdin| self.dump_to(&mut stdin));
+        let status = child.wait()?;
+        let frames = written.transpose()?.unwrap_or_default();
+        if !status.success() {
+            bail!("ffmpeg exited with {status} while writing the replay");
+        }
+        Ok(frames)
+    }
+}
+
+static REPLAY: OnceLock<ReplayBuffer> = OnceLock::new();
+
+/// Keep the last `secs` of presented frames from now on. The first call sets the window,
+/// the buffer then lives for the whole session (reconnects included).
+pub fn enable(secs: f64) {
+    REPLAY.get_or_init(|| ReplayBuffer::new(secs));
+}
+
+/// Append a presented frame, in the format of the sink, if replays are enabled.
+pub fn push(ts_us: i64, buf: &[u8]) {
+    if let (Some(replay), Some(props)) = (REPLAY.get(), fplay::props()) {
+        replay.push(ts_us, buf, props.pixel_format);
+    }
+}
+
+/// Dump the replay buffer to a video file, returns the number of frames written.
+pub fn trigger_replay(path: &Path) -> Result<usize> {
+    let Some(replay) = REPLAY.get() else { bail!("the replay buffer is disabled") };
+    let Some(props) = fplay::props() else { bail!("nothing presented yet") };
+    let frames = replay.trigger_replay(path, props)?;
+    log::info!("Instant replay: {frames} frames written to {}", path.display());
+    Ok(frames)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dump_holds_the_last_seconds_of_frames() {
+        let replay = ReplayBuffer::new(2.0);
+        // 3 s at 30 fps of 4x2 RGBA frames, every frame filled with its index
+        for i in 0..90_u8 {
+            let mut frame = vec![i; 4 * 2 * 4];
+            frame.chunks_exact_mut(4).for_each(|px| px[3] = 255);
+            replay.push(i as i64 * 1_000_000 / 30, &frame, PixelFormat::Rgba);
+        }
+        assert_eq!(replay.len(), 60);
+
+        let mut out = Vec::new();
+        assert_eq!(replay.dump_to(&mut out).unwrap(), 60);
+        assert_eq!(out.len(), 60 * 4 * 2 * 3);
+        // Oldest first, alpha dropped
+        let frames: Vec<_> = out.chunks_exact(4 * 2 * 3).collect();
+        assert!(frames[0].iter().all(|&b| b == 30));
+        assert!(frames[59].iter().all(|&b| b == 89));
+    }
+}
@@ -0,0 +1,255 @@
+use anyhow::{bail, Result};
+use std::io::Write;
+use std::process::{Child, Command, Stdio};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use crossbeam_channel::{bounded, Receiver, RecvTimeoutError, Sender, TrySendError};
+
+use crate::fplay::{self, FProps};
+
+/// Frames waiting for the encoder. Beyond that the stream is behind and new frames are dropped,
+/// the render loop never waits for the network.
+const QUEUE_FRAMES: usize = 8;
+
+/// Wait before reconnecting after the connection failed, doubled on every failure up to `MAX_BACKOFF`.
+const MIN_BACKOFF: Duration = Duration::from_millis(500);
+const MAX_BACKOFF: Duration = Duration::from_secs(10);
+
+/// RTMP or RTSP endpoint the stabilized output is re-encoded and pushed to.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StreamTarget {
+    pub url: String,
+    /// ffmpeg video encoder, e.g. `libx264` or `h264_nvenc`.
+    pub codec: String,
+    pub bitrate_kbps: u32,
+}
+
+impl StreamTarget {
+    /// Container ffmpeg muxes into for the scheme of `url`.
+    pub fn container(&self) -> Result<&'static str> {
+        let scheme = self.url.split_once("://").map(|(s, _)| s.to_ascii_lowercase()).unwrap_or_default();
+        Ok(match scheme.as_str() {
+            "rtmp" | "rtmps" => "flv",
+            "rtsp" | "rtsps" => "rtsp",
+            _ => bail!("unsupported stream url '{}', expected rtmp:// or rtsp://", self.url),
+        })
+    }
+
+    /// ffmpeg reading raw `props` frames from stdin and pushing them to the target.
+    fn spawn(&self, props: FProps) -> Result<Child> {
+        let container = self.container()?;
+        let mut cmd = Command::new("ffmpeg");
+        cmd.args(["-loglevel", "error"])
+            .args(["-f", "rawvideo", "-pixel_format", props.pixel_format.ffmpeg_name()])
+            .args(["-video_size", &format!("{}x{}", props.width, props.height), "-framerate", &props.fps.to_string()])
+            .args(["-i", "-"])
+            .args(["-c:v", &self.codec, "-pix_fmt", "yuv420p", "-b:v", &format!("{}k", self.bitrate_kbps)])
+            // A keyframe every 2 s, so viewers joining or a reconnect start quickly
+            .args(["-g", &((props.fps * 2.0).round().max(1.0) as u32).to_string()]);
+        if self.codec == "libx264" {
+            cmd.args(["-preset", "veryfast", "-tune", "zerolatency"]);
+        }
+        if container == "rtsp" {
+            cmd.args(["-rtsp_transport", "tcp"]);
+        }
+        let child = cmd.args(["-f", container]).arg(&self.url)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::inherit())
+            .spawn()?;
+        Ok(child)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct RestreamStats {
+    /// Frames written to the encoder.
+    pub sent: AtomicU64,
+    /// Frames dropped because the encoder or the network fell behind, or while reconnecting.
+    pub dropped: AtomicU64,
+    /// Connections made after the first one.
+    pub reconnects: AtomicU64,
+}
+
+/// Pushes the stabilized output to an RTMP/RTSP server through an ffmpeg process.
+///
+/// Frames are handed to a writer thread over a short queue, so a slow network or server only
+/// drops frames of the stream and never stalls the render loop. When ffmpeg exits (server gone,
+/// connection reset) the writer reconnects with a growing backoff, independently of the stream reader.
+pub struct Restreamer {
+    tx: Sender<Arc<Vec<u8>>>,
+    stats: Arc<RestreamStats>,
+    worker: thread::JoinHandle<()>,
+}
+
+impl Restreamer {
+    pub fn start(target: StreamTarget, props: FProps) -> Result<Self> {
+        target.container()?;
+        if props.pixel_format.bytes_per_pixel() == 0 {
+            bail!("restream: pixel format {:?} is not supported here", props.pixel_format);
+        }
+        let (tx, rx) = bounded(QUEUE_FRAMES);
+        let stats = Arc::new(RestreamStats::default());
+        let worker_stats = Arc::clone(&stats);
+        let worker = thread::Builder::new()
+            .name("restream".into())
+            .spawn(move || Self::run(target, props, rx, &worker_stats))?;
+        Ok(Self { tx, stats, worker })
+    }
+
+    pub fn stats(&self) -> &RestreamStats { &self.stats }
+
+    /// Queue a frame in the format of `props`, dropped if the stream is behind.
+    pub fn push(&self, buf: &[u8]) {
+        match self.tx.try_send(Arc::new(buf.to_vec())) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) | Err(TrySendError::Disconnected(_)) => {
+                self.stats.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Send the queued frames, close the stream and wait for ffmpeg to finish.
+    pub fn finish(self) {
+        drop(self.tx);
+        let _ = self.worker.join();
+    }
+
+    fn run(target: StreamTarget, props: FProps, rx: Receiver<Arc<Vec<u8>>>, stats: &RestreamStats) {
+        let mut backoff = MIN_BACKOFF;
+        let mut connected_once = false;
+        loop {
+            match target.spawn(props) {
+                Ok(mut child) => {
+                    if connected_once {
+                        stats.reconnects.fetch_add(1, Ordering::Relaxed);
+                    }
+                    connected_once = true;
+                    log::info!("Streaming stabilized output to {}", target.url);
+                    let mut stdin = child.stdin.take();
+                    let (mut written, mut lost) = (false, false);
+                    for frame in rx.iter() {
+                        if stdin.as_mut().is_none_or(|s| s.write_all(&frame).is_err()) {
+                            stats.dropped.fetch_add(1, Ordering::Relaxed);
+                            lost = true;
+                            break;
+                        }
+                        stats.sent.fetch_add(1, Ordering::Relaxed);
+                        written = true;
+                    }
+                    // Closing stdin makes ffmpeg flush and end the stream
+                    drop(stdin);
+                    let status = child.wait();
+                    if !lost {
+                        log::info!("Stream to {} closed ({status:?})", target.url);
+                        return;
+                    }
+                    log::warn!("Stream to {} lost ({status:?}), reconnecting in {} ms", target.url, backoff.as_millis());
+                    if written { backoff = MIN_BACKOFF; }
+                }
+                Err(e) => log::error!("Failed to start streaming to {}: {e:?}", target.url),
+            }
+            // Frames arriving meanwhile are stale by the time we're back, drop them
+            let until = std::time::Instant::now() + backoff;
+            loop {
+                match rx.recv_timeout(until.saturating_duration_since(std::time::Instant::now())) {
+                    Ok(_) => { stats.dropped.fetch_add(1, Ordering::Relaxed); }
+                    Err(RecvTimeoutError::Timeout) => break,
+                    Err(RecvTimeoutError::Disconnected) => return,
+                }
+            }
+            backoff = (backoff * 2).min(MAX_BACKOFF);
+        }
+    }
+}
+
+static TARGET: OnceLock<StreamTarget> = OnceLock::new();
+static RESTREAMER: Mutex<Option<Restreamer>> = Mutex::new(None);
+
+/// Stream the presented frames to `target` from the first one on. Must be set before rendering starts.
+pub fn enable(target: StreamTarget) {
+    let _ = TARGET.set(target);
+}
+
+/// Queue a presented frame, in the format of the sink, if streaming is enabled.
+/// The stream starts with the first frame, once the sink properties are known.
+pub fn push(buf: &[u8]) {
+    let Some(target) = TARGET.get() else { return };
+    let mut guard = RESTREAMER.lock().unwrap();
+    if guard.is_none() {
+        let Some(props) = fplay::props() else { return };
+        match Restreamer::start(target.clone(), props) {
+            Ok(r) => *guard = Some(r),
+            Err(e) => {
+                log::error!("Streaming disabled: {e:?}");
+                return;
+            }
+        }
+    }
+    if let Some(r) = guard.as_ref() {
+        r.push(buf);
+    }
+}
+
+/// Flush and close the stream at the end of the session.
+pub fn shutdown() {
+    if let Some(r) = RESTREAMER.lock().unwrap().take() {
+        let stats = r.stats();
+        log::info!("Stream finished: {} frames sent, {} dropped, {} reconnects",
+            stats.sent.load(Ordering::Relaxed), stats.dropped.load(Ordering::Relaxed), stats.reconnects.load(Ordering::Relaxed));
+        r.finish();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader};
+    use crate::live_pix_fmt::PixelFormat;
+
+    fn target(url: &str) -> StreamTarget {
+        StreamTarget { url: url.into(), codec: "libx264".into(), bitrate_kbps: 1000 }
+    }
+
+    #[test]
+    fn container_follows_the_url_scheme() {
+        assert_eq!(target("rtmp://127.0.0.1/live/key").container().unwrap(), "flv");
+        assert_eq!(target("RTSP://cam.local:8554/out").container().unwrap(), "rtsp");
+        assert!(target("udp://127.0.0.1:1234").container().is_err());
+        assert!(target("out.mp4").container().is_err());
+    }
+
+    #[test]
+    #[ignore = "needs ffmpeg on PATH"]
+    fn frames_arrive_at_a_local_rtmp_listener() {
+        let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let url = format!("rtmp://127.0.0.1:{port}/live/test");
+        // ffmpeg as the RTMP server, printing a checksum line per received frame
+        let mut listener = Command::new("ffmpeg")
+            .args(["-loglevel", "error", "-listen", "1", "-f", "flv", "-i", &url, "-f", "framecrc", "-"])
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        thread::sleep(Duration::from_millis(500));
+
+        let props = FProps { width: 64, height: 48, fps: 30.0, pixel_format: PixelFormat::Rgb24 };
+        let streamer = Restreamer::start(target(&url), props).unwrap();
+        for i in 0..60_u8 {
+            streamer.push(&vec![i; 64 * 48 * 3]);
+            thread::sleep(Duration::from_millis(33));
+        }
+        let sent = streamer.stats().sent.load(Ordering::Relaxed);
+        streamer.finish();
+
+        let received = BufReader::new(listener.stdout.take().unwrap()).lines()
+            .map_while(|l| l.ok())
+            .filter(|l| !l.starts_with('#'))
+            .count();
+        listener.wait().unwrap();
+        assert!(sent > 0);
+        assert!(received > 0, "no frames arrived ({sent} sent)");
+    }
+}
@@ -0,0 +1,220 @@
+// live/selftest.rs
+// `--selftest`: drive the whole live path with a synthetic IMU stream and synthetic frames,
+// so an operator can check the binary on their hardware before going live.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use gyroflow_core::StabilizationManager;
+use gyroflow_core::gyro_source::live::LiveImuSample;
+use gyroflow_core::live::{ComputeFallback, LivePipeline, DEFAULT_INTEGRATE_PERIOD};
+use gyroflow_core::stabilization::pixel_formats::RGBA8;
+
+use crate::cli::Args;
+use crate::parse_gyroflow_header;
+use crate::render_live::cpu_buffers;
+
+/// Length of the synthetic stream.
+const SELFTEST_SECS: f64 = 3.0;
+/// IMU sample rate of the synthetic generator.
+const IMU_RATE_HZ: f64 = 200.0;
+/// Latency allowed on top of the smoothing look-ahead.
+const LATENCY_MARGIN_MS: f64 = 250.0;
+/// Mean absolute pixel difference for a frame to count as stabilized.
+const MIN_MEAN_DIFF: f64 = 0.5;
+
+#[derive(Debug, Clone, Default)]
+pub struct SelftestReport {
+    pub backend: String,
+    pub input_size: (usize, usize),
+    pub output_size: (usize, usize),
+    pub frames: usize,
+    pub rendered: usize,
+    pub changed: usize,
+    pub latency_avg_ms: f64,
+    pub latency_max_ms: f64,
+    pub latency_limit_ms: f64,
+    pub error: Option<String>,
+}
+
+impl SelftestReport {
+    pub fn passed(&self) -> bool {
+        self.error.is_none()
+            && self.rendered * 10 >= self.frames * 8
+            && self.changed * 2 >= self.rendered.max(1)
+            && self.latency_max_ms <= self.latency_limit_ms
+    }
+
+    pub fn print(&self) {
+        println!("selftest: {}", if self.passed() { "PASS" } else { "FAIL" });
+        println!("  backend:    {}", self.backend);
+        println!("  resolution: {}x{} -> {}x{}", self.input_size.0, self.input_size.1, self.output_size.0, self.output_size.1);
+        println!("  frames:     {} rendered of {}, {} changed by stabilization", self.rendered, self.frames, self.changed);
+        println!("  latency:    avg {:.1} ms, max {:.1} ms (limit {:.0} ms)", self.latency_avg_ms, self.latency_max_ms, self.latency_limit_ms);
+        if let Some(e) = &self.error {
+            println!("  error:      {e}");
+        }
+    }
+}
+
+/// Handheld-like shake: a few degrees at a few Hz on every axis, in deg/s like a logger sends it.
+fn synthetic_gyro(t_s: f64) -> [f64; 3] {
+    let tau = std::f64::consts::TAU;
+    [
+        20.0 * (tau * 2.0 * t_s).sin(),
+        15.0 * (tau * 3.1 * t_s + 1.0).sin(),
+         8.0 * (tau * 1.3 * t_s + 2.0).sin(),
+    ]
+}
+
+/// Checkerboard with a gradient, so any rotation changes most pixels.
+fn synthetic_frame(w: usize, h: usize, k: usize) -> Vec<u8> {
+    let mut buf = vec![255u8; w * h * 4];
+    for (i, px) in buf.chunks_exact_mut(4).enumerate() {
+        let (x, y) = (i % w, i / w);
+        let check = if ((x / 32) + (y / 32)) % 2 == 0 { 200 } else { 40 };
+        px[0] = check;
+        px[1] = (x * 255 / w.max(1)) as u8;
+        px[2] = ((y * 255 / h.max(1)) as u8).wrapping_add(k as u8);
+    }
+    buf
+}
+
+fn synthetic_header(fps: f64, size: (usize, usize)) -> String {
+    let (w, h) = size;
+    let f = w.max(h) as f64 * 0.8;
+    let lens = serde_json::json!({
+        "camera_brand": "Selftest",
+        "lens_model": "Synthetic",
+        "calib_dimension": { "w": w, "h": h },
+        "fisheye_params": {
+            "camera_matrix": [[f, 0.0, w as f64 / 2.0], [0.0, f, h as f64 / 2.0], [0.0, 0.0, 1.0]],
+            "distortion_coeffs": [0.0, 0.0, 0.0, 0.0]
+        }
+    });
+    format!("GYROFLOW IMU LOG\nversion,1.3\nid,selftest\ntscale,0.000001\nframe_rate,{fps}\nlensprofile,{lens}\nt,gx,gy,gz,ax,ay,az")
+}
+
+/// Run the self-test and return its report.
+pub fn run(args: &Args) -> SelftestReport {
+    let size = (args.width, args.height);
+    let fps = args.fps;
+    let mut report = SelftestReport { input_size: size, frames: (SELFTEST_SECS * fps) as usize, ..Default::default() };
+
+    let stab = Arc::new(StabilizationManager::default());
+    stab.init_from_stream_data(fps, size);
+    let metadata = parse_gyroflow_header(&synthetic_header(fps, size));
+    if let Err(e) = stab.start_single_stream(metadata, 3.0, 1.0, 0.0, size, size, std::path::Path::new(""), false) {
+        report.error = Some(format!("failed to start the stream: {e:?}"));
+        return report;
+    }
+    let pipeline = match LivePipeline::builder(Arc::clone(&stab)).integrate_period(DEFAULT_INTEGRATE_PERIOD).compute_fallback(ComputeFallback::Cpu).start() {
+        Ok(p) => p,
+        Err(e) => {
+            report.error = Some(format!("failed to start the live pipeline: {e}"));
+            return report;
+        }
+    };
+
+    let post_ms = stab.gyro.read().live_smoothing_window().unwrap_or_default().post_ms;
+    report.latency_limit_ms = post_ms + LATENCY_MARGIN_MS;
+
+    // IMU generator, in real time like a connected logger
+    let start = Instant::now();
+    let done = Arc::new(AtomicBool::new(false));
+    let imu = {
+        let (tx, done) = (pipeline.imu_sender(), Arc::clone(&done));
+        let imu_secs = SELFTEST_SECS + (post_ms + LATENCY_MARGIN_MS) / 1000.0 + 0.5;
+        thread::spawn(move || {
+            let period = 1.0 / IMU_RATE_HZ;
+            let mut i = 0u64;
+            while !done.load(Ordering::Relaxed) && i as f64 * period <= imu_secs {
+                let t_s = i as f64 * period;
+                let ahead = Duration::from_secs_f64(t_s).saturating_sub(start.elapsed());
+                thread::sleep(ahead);
+                let ts_us = (t_s * 1_000_000.0).round() as i64;
+                let sample = LiveImuSample { ts_sensor_us: ts_us, gyro: synthetic_gyro(t_s), accel: Some([0.0, 0.0, 1.0]) };
+                if tx.send((sample, ts_us)).is_err() { break; }
+                i += 1;
+            }
+        })
+    };
+
+    stab.set_render_params(size, size);
+    let out_size = stab.live_output_buffer_size();
+    report.output_size = out_size;
+
+    let deadline_ms = report.latency_limit_ms * 2.0;
+    let mut latencies = Vec::with_capacity(report.frames);
+    for k in 0..report.frames {
+        let ts_us = (k as f64 / fps * 1_000_000.0).round() as i64;
+        let captured = start + Duration::from_micros(ts_us as u64);
+        thread::sleep(captured.saturating_duration_since(Instant::now()));
+
+        // Wait until the smoothed buffers reach past this frame
+        let ready = loop {
+            let reached = stab.gyro.read().live.read().as_ref()
+                .and_then(|st| st.quat_buffer_store_smoothed.get_latest_buffer())
+                .is_some_and(|b| b.last_us >= ts_us + (post_ms * 1000.0) as i64);
+            if reached { break true; }
+            if captured.elapsed().as_secs_f64() * 1000.0 > deadline_ms { break false; }
+            thread::sleep(Duration::from_millis(2));
+        };
+        if !ready { continue; }
+
+        stab.live_on_new_frame(k, ts_us as f64 / 1000.0, 1);
+        let mut input = synthetic_frame(size.0, size.1, k);
+        let original = input.clone();
+        let mut output = vec![0u8; out_size.0 * out_size.1 * 4];
+        let mut buffers = cpu_buffers(&mut input, size, &mut output, out_size, 4);
+        match stab.process_pixels::<RGBA8>(ts_us, None, &mut buffers) {
+            Ok(info) => report.backend = info.backend.to_string(),
+            Err(e) => {
+                log::warn!("selftest: frame {k} failed: {e:?}");
+                continue;
+            }
+        }
+        latencies.push(captured.elapsed().as_secs_f64() * 1000.0);
+        report.rendered += 1;
+
+        if out_size == size {
+            let diff: u64 = output.iter().zip(&original).map(|(a, b)| a.abs_diff(*b) as u64).sum();
+            if diff as f64 / output.len() as f64 >= MIN_MEAN_DIFF {
+                report.changed += 1;
+            }
+        } else {
+            // Different output size, the frame was necessarily resampled
+            report.changed += 1;
+        }
+    }
+
+    done.store(true, Ordering::Relaxed);
+    let _ = imu.join();
+    pipeline.stop();
+
+    if !latencies.is_empty() {
+        report.latency_avg_ms = latencies.iter().sum::<f64>() / latencies.len() as f64;
+        report.latency_max_ms = latencies.iter().cloned().fold(0.0, f64::max);
+    }
+    if report.backend.is_empty() {
+        report.backend = "none (no frame rendered)".into();
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_pass_criteria() {
+        let ok = SelftestReport { frames: 90, rendered: 88, changed: 80, latency_max_ms: 600.0, latency_limit_ms: 750.0, ..Default::default() };
+        assert!(ok.passed());
+        assert!(!SelftestReport { changed: 10, ..ok.clone() }.passed());
+        assert!(!SelftestReport { latency_max_ms: 900.0, ..ok.clone() }.passed());
+        assert!(!SelftestReport { rendered: 30, changed: 30, ..ok.clone() }.passed());
+        assert!(!SelftestReport { error: Some("no lens".into()), ..ok }.passed());
+    }
+}
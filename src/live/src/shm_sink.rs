@@ -0,0 +1,290 @@
+use anyhow::{bail, Context, Result};
+use std::ptr;
+use std::sync::atomic::{fence, AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+
+use shared_memory::{Shmem, ShmemConf};
+
+use crate::fplay;
+use crate::live_pix_fmt::PixelFormat;
+
+/// "GFSR" little endian, first word of the region.
+const MAGIC: u32 = 0x5253_4647;
+const VERSION: u32 = 1;
+
+/// Ring header: magic u32 @0, version u32 @4, slots u32 @8, slot_bytes u64 @16, write_index u64 @24.
+/// `write_index` counts the frames written, the latest one is in slot `(write_index - 1) % slots`.
+const RING_HEADER: usize = 64;
+/// Slot header: seq u64 @0, index u64 @8, ts_us i64 @16, width u32 @24, height u32 @28, format u32 @32, len u64 @40.
+/// `seq` is odd while the slot is being written, a reader retries when it changed during its copy.
+const SLOT_HEADER: usize = 64;
+
+/// Default number of slots: the consumer can read one frame while the next two are written.
+pub const DEFAULT_SLOTS: usize = 3;
+
+/// How often `ShmRingReader::latest` retries a slot overwritten during the copy before giving up.
+const READ_ATTEMPTS: usize = 8;
+
+fn format_code(format: PixelFormat) -> u32 {
+    match format {
+        PixelFormat::Rgb24 => 0,
+        PixelFormat::Nv12  => 1,
+        PixelFormat::Rgba  => 2,
+    }
+}
+
+fn format_from_code(code: u32) -> Option<PixelFormat> {
+    match code {
+        0 => Some(PixelFormat::Rgb24),
+        1 => Some(PixelFormat::Nv12),
+        2 => Some(PixelFormat::Rgba),
+        _ => None,
+    }
+}
+
+/// Name of the mapping for the OS: POSIX wants a leading slash, Windows takes it as is.
+fn os_id(name: &str) -> String {
+    if cfg!(unix) && !name.starts_with('/') { format!("/{name}") } else { name.to_string() }
+}
+
+/// Bytes of a slot, header included, kept a multiple of 64 so every slot header is aligned.
+fn slot_stride(slot_bytes: usize) -> usize {
+    SLOT_HEADER + slot_bytes.div_ceil(64) * 64
+}
+
+/// Typed view of a mapped region laid out as described at `RING_HEADER` and `SLOT_HEADER`.
+struct Region {
+    shmem: Shmem,
+}
+
+// SAFETY: the mapping stays valid wherever the owner lives, all shared state in it is accessed
+// through atomics or guarded by the slot sequence numbers.
+unsafe impl Send for Region {}
+
+impl Region {
+    fn base(&self) -> *mut u8 { self.shmem.as_ptr() }
+
+    fn u32_at(&self, offset: usize) -> &AtomicU32 {
+        // SAFETY: in bounds of the mapping (checked on create / open) and 4-byte aligned, the mapping is page aligned
+        unsafe { &*(self.base().add(offset) as *const AtomicU32) }
+    }
+
+    fn u64_at(&self, offset: usize) -> &AtomicU64 {
+        // SAFETY: as `u32_at`, 8-byte aligned
+        unsafe { &*(self.base().add(offset) as *const AtomicU64) }
+    }
+
+    fn slots(&self) -> usize { self.u32_at(8).load(Ordering::Relaxed) as usize }
+    fn slot_bytes(&self) -> usize { self.u64_at(16).load(Ordering::Relaxed) as usize }
+    fn write_index(&self) -> &AtomicU64 { self.u64_at(24) }
+    fn slot_offset(&self, slot: usize) -> usize { RING_HEADER + slot * slot_stride(self.slot_bytes()) }
+}
+
+/// One frame read from the ring.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ShmFrame {
+    /// Frames written before this one.
+    pub index: u64,
+    pub ts_us: i64,
+    pub width: u32,
+    pub height: u32,
+    pub pixel_format: PixelFormat,
+    pub data: Vec<u8>,
+}
+
+/// Writes frames into a named shared memory ring, for a co-located consumer (e.g. a compositor)
+/// that maps the same region and takes the latest frame without a socket in between.
+///
+/// The region starts with a header (slot count and size, write index), followed by `slots` slots of a
+/// small header (sequence number, frame index, timestamp, size, format) and the pixels. The writer never
+/// waits for the consumer: a slot is simply overwritten `slots` frames later, and its sequence number
+/// tells a reader that copied it meanwhile to read again. The region is removed when the ring is dropped.
+pub struct ShmRing {
+    region: Region,
+}
+
+impl ShmRing {
+    /// Create the region `name` with `slots` slots of `slot_bytes` pixel bytes each.
+    pub fn create(name: &str, slots: usize, slot_bytes: usize) -> Result<Self> {
+        if slots == 0 || slot_bytes == 0 {
+            bail!("shared memory ring needs at least one slot of at least one byte");
+        }
+        let size = RING_HEADER + slots * slot_stride(slot_bytes);
+        let shmem = ShmemConf::new().size(size).os_id(os_id(name)).create()
+            .with_context(|| format!("creating shared memory '{name}' of {size} bytes (a stale one of a crashed session must be removed first)"))?;
+        let region = Region { shmem };
+        // SAFETY: freshly created, nobody reads yet
+        unsafe { ptr::write_bytes(region.base(), 0, size) };
+        region.u32_at(8).store(slots as u32, Ordering::Relaxed);
+        region.u64_at(16).store(slot_bytes as u64, Ordering::Relaxed);
+        region.u32_at(4).store(VERSION, Ordering::Relaxed);
+        // Readers check the magic first, it goes in last
+        region.u32_at(0).store(MAGIC, Ordering::Release);
+        Ok(Self { region })
+    }
+
+    /// Frames written so far.
+    pub fn written(&self) -> u64 { self.region.write_index().load(Ordering::Relaxed) }
+
+    /// Write a frame of `size` in `format` into the next slot, returns its index.
+    pub fn write(&mut self, ts_us: i64, size: (u32, u32), format: PixelFormat, buf: &[u8]) -> Result<u64> {
+        let region = &self.region;
+        if buf.len() > region.slot_bytes() {
+            bail!("frame of {} bytes doesn't fit the {} byte slots of the shared memory ring", buf.len(), region.slot_bytes());
+        }
+        let index = region.write_index().load(Ordering::Relaxed);
+        let offset = region.slot_offset((index % region.slots() as u64) as usize);
+        let seq = region.u64_at(offset);
+        let s = seq.load(Ordering::Relaxed);
+        seq.store(s + 1, Ordering::Relaxed);
+        fence(Ordering::Release);
+        region.u64_at(offset + 8).store(index, Ordering::Relaxed);
+        region.u64_at(offset + 16).store(ts_us as u64, Ordering::Relaxed);
+        region.u32_at(offset + 24).store(size.0, Ordering::Relaxed);
+        region.u32_at(offset + 28).store(size.1, Ordering::Relaxed);
+        region.u32_at(offset + 32).store(format_code(format), Ordering::Relaxed);
+        region.u64_at(offset + 40).store(buf.len() as u64, Ordering::Relaxed);
+        // SAFETY: `buf` fits the slot (checked above), readers detect the overwrite through `seq`
+        unsafe { ptr::copy_nonoverlapping(buf.as_ptr(), region.base().add(offset + SLOT_HEADER), buf.len()) };
+        seq.store(s + 2, Ordering::Release);
+        region.write_index().store(index + 1, Ordering::Release);
+        Ok(index)
+    }
+}
+
+/// Consumer side of a `ShmRing`, in this or another process.
+pub struct ShmRingReader {
+    region: Region,
+}
+
+impl ShmRingReader {
+    /// Map the existing ring `name`.
+    pub fn open(name: &str) -> Result<Self> {
+        let shmem = ShmemConf::new().os_id(os_id(name)).open().with_context(|| format!("opening shared memory '{name}'"))?;
+        if shmem.len() < RING_HEADER {
+            bail!("shared memory '{name}' is too small for a frame ring");
+        }
+        let region = Region { shmem };
+        if region.u32_at(0).load(Ordering::Acquire) != MAGIC || region.u32_at(4).load(Ordering::Relaxed) != VERSION {
+            bail!("shared memory '{name}' is not a version {VERSION} frame ring");
+        }
+        let size = RING_HEADER + region.slots() * slot_stride(region.slot_bytes());
+        if region.shmem.len() < size {
+            bail!("shared memory '{name}' is {} bytes, its header describes {size}", region.shmem.len());
+        }
+        Ok(Self { region })
+    }
+
+    /// The most recent frame, `None` before the first one or when the writer kept overwriting it while copying.
+    pub fn latest(&self) -> Option<ShmFrame> {
+        let region = &self.region;
+        for _ in 0..READ_ATTEMPTS {
+            let written = region.write_index().load(Ordering::Acquire);
+            if written == 0 { return None; }
+            let offset = region.slot_offset(((written - 1) % region.slots() as u64) as usize);
+            let seq = region.u64_at(offset);
+            let s = seq.load(Ordering::Acquire);
+            if s % 2 == 1 { continue; }
+            let index = region.u64_at(offset + 8).load(Ordering::Relaxed);
+            let ts_us = region.u64_at(offset + 16).load(Ordering::Relaxed) as i64;
+            let width = region.u32_at(offset + 24).load(Ordering::Relaxed);
+            let height = region.u32_at(offset + 28).load(Ordering::Relaxed);
+            let format = region.u32_at(offset + 32).load(Ordering::Relaxed);
+            let len = (region.u64_at(offset + 40).load(Ordering::Relaxed) as usize).min(region.slot_bytes());
+            let mut data = vec![0u8; len];
+            // SAFETY: within the slot, a concurrent write is detected below and the copy discarded
+            unsafe { ptr::copy_nonoverlapping(region.base().add(offset + SLOT_HEADER), data.as_mut_ptr(), len) };
+            fence(Ordering::Acquire);
+            if seq.load(Ordering::Relaxed) != s { continue; }
+            return Some(ShmFrame { index, ts_us, width, height, pixel_format: format_from_code(format)?, data });
+        }
+        None
+    }
+}
+
+static TARGET: OnceLock<(String, usize)> = OnceLock::new();
+static RING: Mutex<Option<ShmRing>> = Mutex::new(None);
+static FAILED: AtomicBool = AtomicBool::new(false);
+
+/// Write the presented frames to the shared memory ring `name` of `slots` slots. Must be set before rendering starts.
+pub fn enable(name: String, slots: usize) {
+    let _ = TARGET.set((name, slots.max(1)));
+}
+
+/// Write a presented frame, in the format of the sink, if the ring is enabled.
+/// The ring is created with the first frame, once the sink properties are known.
+pub fn push(ts_us: i64, buf: &[u8]) {
+    let Some((name, slots)) = TARGET.get() else { return };
+    if FAILED.load(Ordering::Relaxed) { return; }
+    let Some(props) = fplay::props() else { return };
+    let mut guard = RING.lock().unwrap();
+    if guard.is_none() {
+        let slot_bytes = props.width as usize * props.height as usize * props.pixel_format.bytes_per_pixel();
+        match ShmRing::create(name, *slots, slot_bytes) {
+            Ok(ring) => {
+                log::info!("Writing stabilized frames to shared memory '{name}' ({slots} slots of {slot_bytes} bytes)");
+                *guard = Some(ring);
+            }
+            Err(e) => {
+                log::error!("Shared memory output disabled: {e:?}");
+                FAILED.store(true, Ordering::Relaxed);
+                return;
+            }
+        }
+    }
+    if let Some(ring) = guard.as_mut() {
+        if let Err(e) = ring.write(ts_us, (props.width, props.height), props.pixel_format, buf) {
+            gyroflow_core::live::LOG_THROTTLE.warn("shm_sink: write", format_args!("shm_sink: {e}"));
+        }
+    }
+}
+
+/// Remove the ring at the end of the session.
+pub fn shutdown() {
+    if let Some(ring) = RING.lock().unwrap().take() {
+        log::info!("Shared memory output finished: {} frames written", ring.written());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn reader_thread_sees_whole_frames() {
+        let name = format!("gyroflow_live_shm_test_{}", std::process::id());
+        let (w, h) = (64usize, 32usize);
+        let mut ring = ShmRing::create(&name, DEFAULT_SLOTS, w * h * 4).unwrap();
+        let reader = ShmRingReader::open(&name).unwrap();
+        assert!(reader.latest().is_none());
+        assert!(ShmRingReader::open("gyroflow_live_shm_test_missing").is_err());
+
+        const FRAMES: u64 = 300;
+        let consumer = thread::spawn(move || {
+            let deadline = Instant::now() + Duration::from_secs(10);
+            let mut seen = 0;
+            while Instant::now() < deadline {
+                let Some(frame) = reader.latest() else { continue };
+                // Every byte and the header come from the same frame, or the sequence number caught it
+                assert!(frame.data.iter().all(|&b| b == frame.index as u8), "torn frame {}", frame.index);
+                assert_eq!(frame.ts_us, frame.index as i64 * 33_333);
+                assert_eq!((frame.width, frame.height, frame.pixel_format), (w as u32, h as u32, PixelFormat::Rgba));
+                seen += 1;
+                if frame.index == FRAMES - 1 { return seen; }
+            }
+            panic!("last frame never read ({seen} reads)");
+        });
+        for i in 0..FRAMES {
+            ring.write(i as i64 * 33_333, (w as u32, h as u32), PixelFormat::Rgba, &vec![i as u8; w * h * 4]).unwrap();
+            if i % 50 == 0 { thread::sleep(Duration::from_millis(1)); }
+        }
+        assert!(consumer.join().unwrap() > 0);
+        assert_eq!(ring.written(), FRAMES);
+
+        // Frames bigger than a slot are refused, the ring stays as it was
+        assert!(ring.write(0, (w as u32, h as u32 * 2), PixelFormat::Rgba, &vec![0; w * h * 8]).is_err());
+        assert_eq!(ring.written(), FRAMES);
+    }
+}
@@ -103,7 +103,8 @@ pub fn render(mdkplayer: &MDKPlayerWrapper, timestamp: f64, frame: usize, width:
                     fov: itm.fov,
                     minimal_fov: itm.minimal_fov,
                     focal_length: itm.focal_length,
-                    backend: "Qt RHI"
+                    backend: "Qt RHI",
+                    confidence: None,
                 });
             }
         }
@@ -182,9 +182,9 @@ void main() {
     ///////////////////////////////////////////////////////////////////
     // Add lens distortion back
     if (params.lens_correction_amount < 1.0) {
-        float factor = max(1.0 - params.lens_correction_amount, 0.001); // FIXME: this is close but wrong
+        // Same focal length as the output camera, see `Stabilization::add_lens_distortion`
         vec2 out_c = vec2(params.output_width / 2.0, params.output_height / 2.0);
-        vec2 out_f = (params.f / params.fov) / factor;
+        vec2 out_f = params.f / params.fov;
 
         vec2 new_out_pos = texPos;
 
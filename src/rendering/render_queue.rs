@@ -980,7 +980,11 @@ impl RenderQueue {
                     let total = if per_frame { stab.params.read().frame_count } else { 1 };
                     let mut processed = 0;
                     progress((0.0, processed, total, false, false));
-                    for (fname_base, frame, dist, undist) in core::stmap::generate_stmaps(&stab, per_frame) {
+                    for maps in core::stmap::generate_stmaps(&stab, per_frame) {
+                        let (fname_base, frame, dist, undist) = match maps {
+                            Ok(maps) => maps,
+                            Err(e) => return err((e.to_string(), String::new())),
+                        };
                         if let Err(e) = filesystem::write(&filesystem::get_file_url(&folder_url, &format!("{fname_base}-undistort-{frame}.exr"), true), &undist) {
                             return err((e.to_string(), String::new()));
                         }